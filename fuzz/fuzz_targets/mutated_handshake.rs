@@ -0,0 +1,68 @@
+//! Run a handshake between two parties, truncating, extending or flipping
+//! bits of the messages in between.
+//!
+//! A changed message must be rejected if its payload is encrypted, and with
+//! an error that a tampered message may cause, never with a panic.
+
+#![no_main]
+
+use arbitrary::{Arbitrary, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use noise_fuzz::{handshake, is_message_error};
+use noise_protocol::patterns::HandshakePattern;
+
+fn mutate(u: &mut Unstructured, message: &mut Vec<u8>) -> arbitrary::Result<()> {
+    match u.int_in_range(0..=3)? {
+        0 => {}
+        1 => message.truncate(u.int_in_range(0..=message.len())?),
+        2 => message.extend_from_slice(<&[u8]>::arbitrary(u)?),
+        _ => {
+            if !message.is_empty() {
+                let i = u.choose_index(message.len())?;
+                message[i] ^= 1 << u.int_in_range(0..=7)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let Ok(pattern) = HandshakePattern::arbitrary(&mut u) else {
+        return;
+    };
+    let (Some(mut i), Some(mut r)) = (handshake(pattern, true), handshake(pattern, false)) else {
+        return;
+    };
+    for n in 0..pattern.get_message_patterns_len() {
+        let (send, recv) = if n % 2 == 0 {
+            (&mut i, &mut r)
+        } else {
+            (&mut r, &mut i)
+        };
+        // Fails if a key received in a changed message is invalid.
+        let message = match send.write_message_vec(b"payload") {
+            Ok(message) => message,
+            Err(e) => {
+                assert!(is_message_error(e.kind()), "{:?}", e);
+                return;
+            }
+        };
+        let mut mutated = message.clone();
+        if mutate(&mut u, &mut mutated).is_err() {
+            return;
+        }
+        match recv.read_message_vec(&mutated) {
+            Ok(payload) => {
+                assert!(mutated == message || !pattern.payload_protection(n).encrypted);
+                if mutated == message {
+                    assert_eq!(payload, b"payload");
+                }
+            }
+            Err(e) => {
+                assert!(is_message_error(e.kind()), "{:?}", e);
+                return;
+            }
+        }
+    }
+});
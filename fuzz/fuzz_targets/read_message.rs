@@ -0,0 +1,39 @@
+//! Read arbitrary messages, writing valid ones on our turns, with any
+//! predefined pattern, as either party.
+
+#![no_main]
+
+use arbitrary::{Arbitrary, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use noise_fuzz::{handshake, is_message_error};
+use noise_protocol::patterns::HandshakePattern;
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let (Ok(pattern), Ok(initiator)) = (
+        HandshakePattern::arbitrary(&mut u),
+        bool::arbitrary(&mut u),
+    ) else {
+        return;
+    };
+    let Some(mut hs) = handshake(pattern, initiator) else {
+        return;
+    };
+    while !hs.completed() {
+        if hs.is_write_turn() {
+            // Fails if a key received before is invalid.
+            if let Err(e) = hs.write_message_vec(b"") {
+                assert!(is_message_error(e.kind()), "{:?}", e);
+                return;
+            }
+        } else {
+            let Ok(message) = <&[u8]>::arbitrary(&mut u) else {
+                return;
+            };
+            if let Err(e) = hs.read_message_vec(message) {
+                assert!(is_message_error(e.kind()), "{:?}", e);
+                return;
+            }
+        }
+    }
+});
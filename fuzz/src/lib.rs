@@ -0,0 +1,49 @@
+//! Shared setup of the fuzz targets.
+//!
+//! Run a target with `cargo fuzz run read_message` in this directory.
+
+use noise_protocol::patterns::{HandshakePattern, Token};
+use noise_protocol::{DhKeyPair, ErrorKind, HandshakeStateBuilder, U8Array, DH};
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+pub type HS = noise_protocol::HandshakeState<X25519, ChaCha20Poly1305, Blake2s>;
+
+fn key(b: u8) -> DhKeyPair<<X25519 as DH>::Key, <X25519 as DH>::Pubkey> {
+    DhKeyPair::from_private::<X25519>(U8Array::from_slice(&[b; 32]))
+}
+
+/// A handshake state for `pattern`, with fixed static keys and PSKs, so that
+/// both parties of a pair agree on them.
+///
+/// Returns [`None`] for patterns with ephemeral keys in pre-messages.
+pub fn handshake(pattern: HandshakePattern, initiator: bool) -> Option<HS> {
+    let (s, rs) = if initiator { (1, 2) } else { (2, 1) };
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(pattern)
+        .set_is_initiator(initiator)
+        .set_prologue(b"fuzz")
+        .set_s(key(s))
+        .set_rs(key(rs).public);
+    let psks = (0..pattern.get_message_patterns_len())
+        .flat_map(|m| pattern.get_message_pattern(m))
+        .filter(|t| matches!(t, Token::PSK))
+        .count();
+    for i in 0..psks {
+        b.push_psk(&[i as u8; 32]);
+    }
+    b.try_build_handshake_state().ok()
+}
+
+/// Whether `kind` is an error that a malformed or tampered message may cause.
+///
+/// [`StaticKeyMismatch`](ErrorKind::StaticKeyMismatch) is one, as the static
+/// keys of the peers are known in advance.
+pub fn is_message_error(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::TooShort
+            | ErrorKind::Decryption
+            | ErrorKind::InvalidPublicKey
+            | ErrorKind::StaticKeyMismatch
+    )
+}
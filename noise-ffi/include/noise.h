@@ -0,0 +1,219 @@
+#ifndef NOISE_H
+#define NOISE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Success.
+ */
+#define NOISE_OK 0
+
+/**
+ * A pointer is null, a string is not UTF-8, or a length is invalid.
+ */
+#define NOISE_ERROR_INVALID_ARGUMENT -1
+
+/**
+ * The protocol name is malformed, or its pattern or primitives are not
+ * supported.
+ */
+#define NOISE_ERROR_UNKNOWN_PROTOCOL -2
+
+/**
+ * A key is missing, has the wrong length or is invalid.
+ */
+#define NOISE_ERROR_INVALID_KEY -3
+
+/**
+ * The output buffer is too small; the required length has been written.
+ */
+#define NOISE_ERROR_BUFFER_TOO_SMALL -4
+
+/**
+ * The operation is not possible in the current state, e.g., writing a
+ * message when it is the turn of the peer, or encrypting before
+ * `noise_split`.
+ */
+#define NOISE_ERROR_INVALID_STATE -5
+
+/**
+ * A message could not be decrypted.
+ */
+#define NOISE_ERROR_DECRYPTION -6
+
+/**
+ * Any other handshake failure, e.g., a missing PSK.
+ */
+#define NOISE_ERROR_HANDSHAKE -7
+
+/**
+ * An unexpected internal error, e.g., the nonce is exhausted.
+ */
+#define NOISE_ERROR_INTERNAL -8
+
+/**
+ * A handshake, or after [`noise_split`], a transport session.
+ */
+typedef struct NoiseState NoiseState;
+
+/**
+ * Keys and settings of [`noise_handshake_new`].
+ *
+ * Pointers may be null if the corresponding length is 0. `psks` holds the
+ * 32-byte PSKs, concatenated in the order they are used.
+ */
+typedef struct {
+  bool is_initiator;
+  bool is_elligator_encoded;
+  const uint8_t *prologue;
+  size_t prologue_len;
+  /**
+   * Local static private key.
+   */
+  const uint8_t *s;
+  size_t s_len;
+  /**
+   * Remote static public key.
+   */
+  const uint8_t *rs;
+  size_t rs_len;
+  const uint8_t *psks;
+  size_t psks_len;
+} NoiseHandshakeParams;
+
+/**
+ * Generate a key pair for the DH function `dh`, e.g., `25519`.
+ *
+ * # Safety
+ *
+ * `dh` must be a NUL-terminated string, and the key buffers must be valid
+ * for their lengths.
+ */
+int noise_generate_keypair(const char *dh,
+                           bool elligator,
+                           uint8_t *private_key,
+                           size_t *private_key_len,
+                           uint8_t *public_key,
+                           size_t *public_key_len);
+
+/**
+ * Create a handshake for `protocol_name`, e.g.,
+ * `Noise_XX_25519_ChaChaPoly_BLAKE2s`, and store it in `*state`.
+ *
+ * # Safety
+ *
+ * `protocol_name` must be a NUL-terminated string, the buffers of `params`
+ * must be valid for their lengths, and `state` must be valid for writes.
+ */
+int noise_handshake_new(const char *protocol_name,
+                        const NoiseHandshakeParams *params,
+                        NoiseState **state);
+
+/**
+ * Write the next handshake message, with `payload`, into `out`.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and the buffers must be
+ * valid for their lengths.
+ */
+int noise_write_message(NoiseState *state,
+                        const uint8_t *payload,
+                        size_t payload_len,
+                        uint8_t *out,
+                        size_t *out_len);
+
+/**
+ * Read the next handshake message, writing its payload into `out`.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and the buffers must be
+ * valid for their lengths.
+ */
+int noise_read_message(NoiseState *state,
+                       const uint8_t *message,
+                       size_t message_len,
+                       uint8_t *out,
+                       size_t *out_len);
+
+/**
+ * Whether the handshake is completed, and [`noise_split`] can be called.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`].
+ */
+bool noise_handshake_completed(const NoiseState *state);
+
+/**
+ * Get the handshake hash, for channel binding.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and `out` must be valid
+ * for `*out_len` bytes.
+ */
+int noise_handshake_hash(NoiseState *state, uint8_t *out, size_t *out_len);
+
+/**
+ * Get the static public key of the peer, failing with
+ * [`NOISE_ERROR_INVALID_STATE`] if it is not known yet.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and `out` must be valid
+ * for `*out_len` bytes.
+ */
+int noise_handshake_remote_static(NoiseState *state, uint8_t *out, size_t *out_len);
+
+/**
+ * Switch a completed handshake to transport mode.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`].
+ */
+int noise_split(NoiseState *state);
+
+/**
+ * Encrypt a transport message.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and the buffers must be
+ * valid for their lengths.
+ */
+int noise_session_encrypt(NoiseState *state,
+                          const uint8_t *plaintext,
+                          size_t plaintext_len,
+                          uint8_t *out,
+                          size_t *out_len);
+
+/**
+ * Decrypt a transport message.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and the buffers must be
+ * valid for their lengths.
+ */
+int noise_session_decrypt(NoiseState *state,
+                          const uint8_t *ciphertext,
+                          size_t ciphertext_len,
+                          uint8_t *out,
+                          size_t *out_len);
+
+/**
+ * Free a state. Null is ignored.
+ *
+ * # Safety
+ *
+ * `state` must come from [`noise_handshake_new`], and not be used
+ * afterwards.
+ */
+void noise_free(NoiseState *state);
+
+#endif  /* NOISE_H */
@@ -0,0 +1,448 @@
+//! C API for `noise-protocol`, cf. `include/noise.h`.
+//!
+//! A `NoiseState` is created with [`noise_handshake_new`], driven with
+//! [`noise_write_message`] and [`noise_read_message`] until the handshake is
+//! completed, switched to transport mode with [`noise_split`], used with
+//! [`noise_session_encrypt`] and [`noise_session_decrypt`], and released with
+//! [`noise_free`].
+//!
+//! All buffers are owned by the caller. Output buffers are passed as a
+//! pointer and a pointer to a length: the length is the capacity of the
+//! buffer on input, and the number of bytes written on output. If the buffer
+//! is too small, [`NOISE_ERROR_BUFFER_TOO_SMALL`] is returned, the required
+//! length is written instead, and the state is left unchanged.
+//!
+//! Functions return [`NOISE_OK`] or a negative error code.
+//!
+//! With the `python` feature, the crate is also a Python extension module,
+//! cf. the [`python`] module.
+
+use noise_protocol::registry::{DynCipherState, DynHandshakeState, HandshakeParams, RegistryError};
+use noise_protocol::{Error, ErrorKind};
+use noise_rust_crypto::registry::registry;
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+/// Success.
+pub const NOISE_OK: c_int = 0;
+/// A pointer is null, a string is not UTF-8, or a length is invalid.
+pub const NOISE_ERROR_INVALID_ARGUMENT: c_int = -1;
+/// The protocol name is malformed, or its pattern or primitives are not
+/// supported.
+pub const NOISE_ERROR_UNKNOWN_PROTOCOL: c_int = -2;
+/// A key is missing, has the wrong length or is invalid.
+pub const NOISE_ERROR_INVALID_KEY: c_int = -3;
+/// The output buffer is too small; the required length has been written.
+pub const NOISE_ERROR_BUFFER_TOO_SMALL: c_int = -4;
+/// The operation is not possible in the current state, e.g., writing a
+/// message when it is the turn of the peer, or encrypting before
+/// `noise_split`.
+pub const NOISE_ERROR_INVALID_STATE: c_int = -5;
+/// A message could not be decrypted.
+pub const NOISE_ERROR_DECRYPTION: c_int = -6;
+/// Any other handshake failure, e.g., a missing PSK.
+pub const NOISE_ERROR_HANDSHAKE: c_int = -7;
+/// An unexpected internal error, e.g., the nonce is exhausted.
+pub const NOISE_ERROR_INTERNAL: c_int = -8;
+
+const TAG_LEN: usize = 16;
+
+/// Keys and settings of [`noise_handshake_new`].
+///
+/// Pointers may be null if the corresponding length is 0. `psks` holds the
+/// 32-byte PSKs, concatenated in the order they are used.
+#[repr(C)]
+pub struct NoiseHandshakeParams {
+    pub is_initiator: bool,
+    pub is_elligator_encoded: bool,
+    pub prologue: *const u8,
+    pub prologue_len: usize,
+    /// Local static private key.
+    pub s: *const u8,
+    pub s_len: usize,
+    /// Remote static public key.
+    pub rs: *const u8,
+    pub rs_len: usize,
+    pub psks: *const u8,
+    pub psks_len: usize,
+}
+
+/// A handshake, or after [`noise_split`], a transport session.
+pub struct NoiseState {
+    is_initiator: bool,
+    inner: Inner,
+}
+
+enum Inner {
+    Handshake(Box<dyn DynHandshakeState>),
+    Transport {
+        send: Box<dyn DynCipherState>,
+        recv: Box<dyn DynCipherState>,
+    },
+}
+
+fn registry_error(e: RegistryError) -> c_int {
+    match e {
+        RegistryError::InvalidKey | RegistryError::MissingKey => NOISE_ERROR_INVALID_KEY,
+        _ => NOISE_ERROR_UNKNOWN_PROTOCOL,
+    }
+}
+
+fn error(e: Error) -> c_int {
+    match e.kind() {
+        ErrorKind::Decryption | ErrorKind::TooShort => NOISE_ERROR_DECRYPTION,
+        ErrorKind::BufferTooSmall => NOISE_ERROR_BUFFER_TOO_SMALL,
+        ErrorKind::WrongTurn | ErrorKind::HandshakeComplete | ErrorKind::PatternViolation => {
+            NOISE_ERROR_INVALID_STATE
+        }
+        ErrorKind::MissingKey
+        | ErrorKind::InvalidPublicKey
+        | ErrorKind::StaticKeyMismatch
+        | ErrorKind::AuthenticationRejected => NOISE_ERROR_INVALID_KEY,
+        ErrorKind::NonceExhausted => NOISE_ERROR_INTERNAL,
+        ErrorKind::MessageTooLong | ErrorKind::MissingParameter => NOISE_ERROR_INVALID_ARGUMENT,
+        _ => NOISE_ERROR_HANDSHAKE,
+    }
+}
+
+/// Run `f`, turning a panic into [`NOISE_ERROR_INTERNAL`], as unwinding
+/// into C is undefined behavior.
+fn guard(f: impl FnOnce() -> Result<(), c_int>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => NOISE_OK,
+        Ok(Err(code)) => code,
+        Err(_) => NOISE_ERROR_INTERNAL,
+    }
+}
+
+unsafe fn input<'a>(p: *const u8, len: usize) -> Result<&'a [u8], c_int> {
+    if len == 0 {
+        Ok(&[])
+    } else if p.is_null() {
+        Err(NOISE_ERROR_INVALID_ARGUMENT)
+    } else {
+        Ok(slice::from_raw_parts(p, len))
+    }
+}
+
+unsafe fn optional_input<'a>(p: *const u8, len: usize) -> Result<Option<&'a [u8]>, c_int> {
+    if len == 0 {
+        Ok(None)
+    } else {
+        input(p, len).map(Some)
+    }
+}
+
+/// Check that `required` bytes fit into the output buffer `(out, out_len)`,
+/// or else report the required length.
+unsafe fn check_output(out: *mut u8, out_len: *mut usize, required: usize) -> Result<(), c_int> {
+    if out_len.is_null() {
+        return Err(NOISE_ERROR_INVALID_ARGUMENT);
+    }
+    if *out_len < required {
+        *out_len = required;
+        return Err(NOISE_ERROR_BUFFER_TOO_SMALL);
+    }
+    if out.is_null() && required > 0 {
+        return Err(NOISE_ERROR_INVALID_ARGUMENT);
+    }
+    Ok(())
+}
+
+unsafe fn output(out: *mut u8, out_len: *mut usize, data: &[u8]) -> Result<(), c_int> {
+    check_output(out, out_len, data.len())?;
+    ptr::copy_nonoverlapping(data.as_ptr(), out, data.len());
+    *out_len = data.len();
+    Ok(())
+}
+
+unsafe fn state<'a>(state: *mut NoiseState) -> Result<&'a mut NoiseState, c_int> {
+    state.as_mut().ok_or(NOISE_ERROR_INVALID_ARGUMENT)
+}
+
+unsafe fn handshake<'a>(
+    state: *mut NoiseState,
+) -> Result<&'a mut Box<dyn DynHandshakeState>, c_int> {
+    match &mut self::state(state)?.inner {
+        Inner::Handshake(hs) => Ok(hs),
+        Inner::Transport { .. } => Err(NOISE_ERROR_INVALID_STATE),
+    }
+}
+
+/// Generate a key pair for the DH function `dh`, e.g., `25519`.
+///
+/// # Safety
+///
+/// `dh` must be a NUL-terminated string, and the key buffers must be valid
+/// for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn noise_generate_keypair(
+    dh: *const c_char,
+    elligator: bool,
+    private_key: *mut u8,
+    private_key_len: *mut usize,
+    public_key: *mut u8,
+    public_key_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        if dh.is_null() {
+            return Err(NOISE_ERROR_INVALID_ARGUMENT);
+        }
+        let dh = CStr::from_ptr(dh)
+            .to_str()
+            .map_err(|_| NOISE_ERROR_INVALID_ARGUMENT)?;
+        let (private, public) = registry()
+            .generate_keypair(dh, elligator)
+            .map_err(registry_error)?;
+        check_output(private_key, private_key_len, private.len())?;
+        check_output(public_key, public_key_len, public.len())?;
+        output(private_key, private_key_len, &private)?;
+        output(public_key, public_key_len, &public)
+    })
+}
+
+/// Create a handshake for `protocol_name`, e.g.,
+/// `Noise_XX_25519_ChaChaPoly_BLAKE2s`, and store it in `*state`.
+///
+/// # Safety
+///
+/// `protocol_name` must be a NUL-terminated string, the buffers of `params`
+/// must be valid for their lengths, and `state` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn noise_handshake_new(
+    protocol_name: *const c_char,
+    params: *const NoiseHandshakeParams,
+    state: *mut *mut NoiseState,
+) -> c_int {
+    guard(|| {
+        if protocol_name.is_null() || state.is_null() {
+            return Err(NOISE_ERROR_INVALID_ARGUMENT);
+        }
+        let params = params.as_ref().ok_or(NOISE_ERROR_INVALID_ARGUMENT)?;
+        let protocol_name = CStr::from_ptr(protocol_name)
+            .to_str()
+            .map_err(|_| NOISE_ERROR_INVALID_ARGUMENT)?;
+
+        let psks = input(params.psks, params.psks_len)?;
+        if psks.len() % 32 != 0 {
+            return Err(NOISE_ERROR_INVALID_ARGUMENT);
+        }
+        let psks: Vec<[u8; 32]> = psks.chunks(32).map(|p| p.try_into().unwrap()).collect();
+
+        let hs = registry()
+            .new_handshake(
+                protocol_name,
+                &HandshakeParams {
+                    is_initiator: params.is_initiator,
+                    is_elligator_encoded: params.is_elligator_encoded,
+                    prologue: input(params.prologue, params.prologue_len)?,
+                    s: optional_input(params.s, params.s_len)?,
+                    rs: optional_input(params.rs, params.rs_len)?,
+                    psks: &psks,
+                },
+            )
+            .map_err(registry_error)?;
+        *state = Box::into_raw(Box::new(NoiseState {
+            is_initiator: params.is_initiator,
+            inner: Inner::Handshake(hs),
+        }));
+        Ok(())
+    })
+}
+
+/// Write the next handshake message, with `payload`, into `out`.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and the buffers must be
+/// valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn noise_write_message(
+    state: *mut NoiseState,
+    payload: *const u8,
+    payload_len: usize,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let hs = handshake(state)?;
+        if hs.completed() || !hs.is_write_turn() {
+            return Err(NOISE_ERROR_INVALID_STATE);
+        }
+        let payload = input(payload, payload_len)?;
+        check_output(out, out_len, payload.len() + hs.get_next_message_overhead())?;
+        let message = hs.write_message_vec(payload).map_err(error)?;
+        output(out, out_len, &message)
+    })
+}
+
+/// Read the next handshake message, writing its payload into `out`.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and the buffers must be
+/// valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn noise_read_message(
+    state: *mut NoiseState,
+    message: *const u8,
+    message_len: usize,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let hs = handshake(state)?;
+        if hs.completed() || hs.is_write_turn() {
+            return Err(NOISE_ERROR_INVALID_STATE);
+        }
+        let message = input(message, message_len)?;
+        let overhead = hs.get_next_message_overhead();
+        check_output(out, out_len, message.len().saturating_sub(overhead))?;
+        let payload = hs.read_message_vec(message).map_err(error)?;
+        output(out, out_len, &payload)
+    })
+}
+
+/// Whether the handshake is completed, and [`noise_split`] can be called.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`].
+#[no_mangle]
+pub unsafe extern "C" fn noise_handshake_completed(state: *const NoiseState) -> bool {
+    match state.as_ref().map(|s| &s.inner) {
+        Some(Inner::Handshake(hs)) => hs.completed(),
+        _ => false,
+    }
+}
+
+/// Get the handshake hash, for channel binding.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and `out` must be valid
+/// for `*out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn noise_handshake_hash(
+    state: *mut NoiseState,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let hs = handshake(state)?;
+        output(out, out_len, hs.get_hash())
+    })
+}
+
+/// Get the static public key of the peer, failing with
+/// [`NOISE_ERROR_INVALID_STATE`] if it is not known yet.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and `out` must be valid
+/// for `*out_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn noise_handshake_remote_static(
+    state: *mut NoiseState,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let rs = handshake(state)?
+            .get_rs()
+            .ok_or(NOISE_ERROR_INVALID_STATE)?;
+        output(out, out_len, &rs)
+    })
+}
+
+/// Switch a completed handshake to transport mode.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`].
+#[no_mangle]
+pub unsafe extern "C" fn noise_split(state: *mut NoiseState) -> c_int {
+    guard(|| {
+        let state = self::state(state)?;
+        let (c1, c2) = match &state.inner {
+            Inner::Handshake(hs) if hs.completed() => hs.get_ciphers(),
+            _ => return Err(NOISE_ERROR_INVALID_STATE),
+        };
+        let (send, recv) = if state.is_initiator {
+            (c1, c2)
+        } else {
+            (c2, c1)
+        };
+        state.inner = Inner::Transport { send, recv };
+        Ok(())
+    })
+}
+
+/// Encrypt a transport message.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and the buffers must be
+/// valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn noise_session_encrypt(
+    state: *mut NoiseState,
+    plaintext: *const u8,
+    plaintext_len: usize,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let send = match &mut self::state(state)?.inner {
+            Inner::Transport { send, .. } => send,
+            Inner::Handshake(_) => return Err(NOISE_ERROR_INVALID_STATE),
+        };
+        let plaintext = input(plaintext, plaintext_len)?;
+        check_output(out, out_len, plaintext.len() + TAG_LEN)?;
+        output(out, out_len, &send.encrypt_vec(plaintext))
+    })
+}
+
+/// Decrypt a transport message.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and the buffers must be
+/// valid for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn noise_session_decrypt(
+    state: *mut NoiseState,
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    out: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(|| {
+        let recv = match &mut self::state(state)?.inner {
+            Inner::Transport { recv, .. } => recv,
+            Inner::Handshake(_) => return Err(NOISE_ERROR_INVALID_STATE),
+        };
+        let ciphertext = input(ciphertext, ciphertext_len)?;
+        check_output(out, out_len, ciphertext.len().saturating_sub(TAG_LEN))?;
+        let plaintext = recv.decrypt_vec(ciphertext).map_err(error)?;
+        output(out, out_len, &plaintext)
+    })
+}
+
+/// Free a state. Null is ignored.
+///
+/// # Safety
+///
+/// `state` must come from [`noise_handshake_new`], and not be used
+/// afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn noise_free(state: *mut NoiseState) {
+    if !state.is_null() {
+        drop(Box::from_raw(state));
+    }
+}
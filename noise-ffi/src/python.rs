@@ -0,0 +1,179 @@
+//! Python bindings, built with the `python` feature, cf. `pyproject.toml`.
+//!
+//! ```python
+//! import noise_rust
+//!
+//! i = noise_rust.HandshakeState("Noise_NN_25519_ChaChaPoly_BLAKE2s", True, elligator=True)
+//! r = noise_rust.HandshakeState("Noise_NN_25519_ChaChaPoly_BLAKE2s", False, elligator=True)
+//! r.read_message(i.write_message(b""))
+//! i.read_message(r.write_message(b""))
+//! i, r = i.split(), r.split()
+//! assert r.decrypt(i.encrypt(b"hello")) == b"hello"
+//! ```
+
+use crate::registry;
+use noise_protocol::registry::{DynCipherState, DynHandshakeState, HandshakeParams};
+use noise_protocol::Error;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(
+    noise_rust,
+    NoiseError,
+    PyException,
+    "A handshake or transport message failed."
+);
+
+fn noise_error(e: Error) -> PyErr {
+    NoiseError::new_err(e.description())
+}
+
+/// A handshake, created from a protocol name, e.g.,
+/// `Noise_XX_25519_ChaChaPoly_BLAKE2s`.
+#[pyclass(name = "HandshakeState", unsendable)]
+pub struct PyHandshakeState {
+    hs: Box<dyn DynHandshakeState>,
+    is_initiator: bool,
+}
+
+#[pymethods]
+impl PyHandshakeState {
+    #[new]
+    #[pyo3(signature = (protocol_name, is_initiator, *, elligator = false, prologue = None, s = None, rs = None, psks = Vec::new()))]
+    fn new(
+        protocol_name: &str,
+        is_initiator: bool,
+        elligator: bool,
+        prologue: Option<&[u8]>,
+        s: Option<&[u8]>,
+        rs: Option<&[u8]>,
+        psks: Vec<Bound<'_, PyBytes>>,
+    ) -> PyResult<Self> {
+        let psks = psks
+            .iter()
+            .map(|p| p.as_bytes().try_into())
+            .collect::<Result<Vec<[u8; 32]>, _>>()
+            .map_err(|_| PyValueError::new_err("PSKs must be 32 bytes"))?;
+        let hs = registry()
+            .new_handshake(
+                protocol_name,
+                &HandshakeParams {
+                    is_initiator,
+                    is_elligator_encoded: elligator,
+                    prologue: prologue.unwrap_or_default(),
+                    s,
+                    rs,
+                    psks: &psks,
+                },
+            )
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyHandshakeState { hs, is_initiator })
+    }
+
+    /// Write the next message, returning it.
+    #[pyo3(signature = (payload = None))]
+    fn write_message<'p>(
+        &mut self,
+        py: Python<'p>,
+        payload: Option<&[u8]>,
+    ) -> PyResult<Bound<'p, PyBytes>> {
+        let message = self
+            .hs
+            .write_message_vec(payload.unwrap_or_default())
+            .map_err(noise_error)?;
+        Ok(PyBytes::new(py, &message))
+    }
+
+    /// Read the next message, returning its payload.
+    fn read_message<'p>(&mut self, py: Python<'p>, data: &[u8]) -> PyResult<Bound<'p, PyBytes>> {
+        let payload = self.hs.read_message_vec(data).map_err(noise_error)?;
+        Ok(PyBytes::new(py, &payload))
+    }
+
+    fn completed(&self) -> bool {
+        self.hs.completed()
+    }
+
+    fn is_write_turn(&self) -> bool {
+        self.hs.is_write_turn()
+    }
+
+    /// The handshake hash.
+    fn get_hash<'p>(&self, py: Python<'p>) -> Bound<'p, PyBytes> {
+        PyBytes::new(py, self.hs.get_hash())
+    }
+
+    /// The static public key of the peer, if known.
+    fn get_rs<'p>(&self, py: Python<'p>) -> Option<Bound<'p, PyBytes>> {
+        self.hs.get_rs().map(|rs| PyBytes::new(py, &rs))
+    }
+
+    /// Get the transport state of a completed handshake.
+    fn split(&self) -> PyResult<PyTransportState> {
+        if !self.hs.completed() {
+            return Err(NoiseError::new_err("Handshake is not completed"));
+        }
+        let (c1, c2) = self.hs.get_ciphers();
+        let (send, recv) = if self.is_initiator {
+            (c1, c2)
+        } else {
+            (c2, c1)
+        };
+        Ok(PyTransportState { send, recv })
+    }
+}
+
+/// The cipher states of a completed handshake.
+#[pyclass(name = "TransportState", unsendable)]
+pub struct PyTransportState {
+    send: Box<dyn DynCipherState>,
+    recv: Box<dyn DynCipherState>,
+}
+
+#[pymethods]
+impl PyTransportState {
+    fn encrypt<'p>(&mut self, py: Python<'p>, plaintext: &[u8]) -> Bound<'p, PyBytes> {
+        PyBytes::new(py, &self.send.encrypt_vec(plaintext))
+    }
+
+    fn decrypt<'p>(&mut self, py: Python<'p>, ciphertext: &[u8]) -> PyResult<Bound<'p, PyBytes>> {
+        let plaintext = self.recv.decrypt_vec(ciphertext).map_err(noise_error)?;
+        Ok(PyBytes::new(py, &plaintext))
+    }
+
+    fn rekey_send(&mut self) {
+        self.send.rekey()
+    }
+
+    fn rekey_recv(&mut self) {
+        self.recv.rekey()
+    }
+}
+
+/// Generate a key pair for the DH function `dh`, returned as
+/// `(private, public)`.
+#[pyfunction]
+#[pyo3(signature = (dh = "25519", elligator = false))]
+fn generate_keypair<'p>(
+    py: Python<'p>,
+    dh: &str,
+    elligator: bool,
+) -> PyResult<(Bound<'p, PyBytes>, Bound<'p, PyBytes>)> {
+    let (private, public) = registry()
+        .generate_keypair(dh, elligator)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok((PyBytes::new(py, &private), PyBytes::new(py, &public)))
+}
+
+/// The `noise_rust` module, e.g., to embed it in a Rust program with
+/// [`append_to_inittab!`](pyo3::append_to_inittab).
+#[pymodule]
+pub fn noise_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyHandshakeState>()?;
+    m.add_class::<PyTransportState>()?;
+    m.add_function(wrap_pyfunction!(generate_keypair, m)?)?;
+    m.add("NoiseError", m.py().get_type::<NoiseError>())?;
+    Ok(())
+}
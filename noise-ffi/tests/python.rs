@@ -0,0 +1,35 @@
+// Runs test_python.py against the bindings embedded in the test binary, with
+// the `python` feature, e.g., `cargo test --features python --test python`.
+
+#![cfg(feature = "python")]
+
+use noise_ffi::python::noise_rust;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::ffi::CString;
+
+#[test]
+fn python_bindings() {
+    pyo3::append_to_inittab!(noise_rust);
+    pyo3::prepare_freethreaded_python();
+
+    Python::with_gil(|py| {
+        let globals = PyDict::new(py);
+        let code = CString::new(include_str!("test_python.py")).unwrap();
+        py.run(&code, Some(&globals), None).unwrap();
+        let code = CString::new(
+            "import unittest\n\
+             suite = unittest.defaultTestLoader.loadTestsFromTestCase(TestNoise)\n\
+             result = unittest.TextTestRunner(verbosity=2).run(suite)",
+        )
+        .unwrap();
+        py.run(&code, Some(&globals), None).unwrap();
+        let result = globals.get_item("result").unwrap().unwrap();
+        let successful: bool = result
+            .call_method0("wasSuccessful")
+            .unwrap()
+            .extract()
+            .unwrap();
+        assert!(successful);
+    });
+}
@@ -0,0 +1,49 @@
+import unittest
+
+import noise_rust
+
+
+def handshake(i, r):
+    while not (i.completed() and r.completed()):
+        if i.is_write_turn():
+            r.read_message(i.write_message())
+        else:
+            i.read_message(r.write_message())
+        i, r = r, i
+
+
+class TestNoise(unittest.TestCase):
+    def test_xx(self):
+        name = "Noise_XX_25519_ChaChaPoly_BLAKE2s"
+        i_s, i_pub = noise_rust.generate_keypair()
+        r_s, _ = noise_rust.generate_keypair()
+        i = noise_rust.HandshakeState(name, True, elligator=True, prologue=b"p", s=i_s)
+        r = noise_rust.HandshakeState(name, False, elligator=True, prologue=b"p", s=r_s)
+        self.assertEqual(r.read_message(i.write_message(b"hi")), b"hi")
+        with self.assertRaises(noise_rust.NoiseError):
+            i.split()
+        handshake(i, r)
+        self.assertEqual(i.get_hash(), r.get_hash())
+        self.assertEqual(r.get_rs(), i_pub)
+
+        i, r = i.split(), r.split()
+        self.assertEqual(r.decrypt(i.encrypt(b"hello")), b"hello")
+        self.assertEqual(i.decrypt(r.encrypt(b"world")), b"world")
+        with self.assertRaises(noise_rust.NoiseError):
+            r.decrypt(b"\0" * 17)
+
+    def test_psk(self):
+        name = "Noise_NNpsk0_25519_AESGCM_SHA256"
+        i = noise_rust.HandshakeState(name, True, psks=[b"\1" * 32])
+        r = noise_rust.HandshakeState(name, False, psks=[b"\1" * 32])
+        handshake(i, r)
+        with self.assertRaises(ValueError):
+            noise_rust.HandshakeState(name, True, psks=[b"short"])
+
+    def test_unknown_protocol(self):
+        with self.assertRaises(ValueError):
+            noise_rust.HandshakeState("Noise_XX_448_ChaChaPoly_BLAKE2s", True)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,37 @@
+use std::collections::{HashSet, VecDeque};
+
+/// A set of byte strings remembering only the `capacity` most recently
+/// inserted ones.
+pub struct BoundedSet {
+    keys: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl BoundedSet {
+    pub fn new(capacity: usize) -> Self {
+        BoundedSet {
+            keys: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Insert `key`, evicting the oldest key if full.
+    ///
+    /// Returns whether `key` was not present.
+    pub fn insert(&mut self, key: &[u8]) -> bool {
+        if self.keys.contains(key) {
+            return false;
+        }
+        if self.capacity > 0 {
+            if self.order.len() == self.capacity {
+                let oldest = self.order.pop_front().unwrap();
+                self.keys.remove(&oldest);
+            }
+            self.keys.insert(key.to_vec());
+            self.order.push_back(key.to_vec());
+        }
+        true
+    }
+}
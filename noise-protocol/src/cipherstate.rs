@@ -1,4 +1,6 @@
-use crate::traits::{Cipher, U8Array};
+use crate::error::Error;
+use crate::instrument;
+use crate::traits::{Cipher, Hash, U8Array};
 
 #[cfg(feature = "use_alloc")]
 use alloc::vec::Vec;
@@ -9,7 +11,9 @@ use alloc::vec::Vec;
 ///
 /// # Panics
 ///
-/// Encryption and decryption methods will panic if nonce reaches maximum u64, i.e., 2 ^ 64 - 1.
+/// Encryption methods will panic if nonce reaches maximum u64, i.e., 2 ^ 64 - 1,
+/// except [`try_encrypt_ad`](CipherState::try_encrypt_ad) and
+/// [`try_encrypt`](CipherState::try_encrypt). Decryption methods fail then.
 pub struct CipherState<C: Cipher> {
     key: C::Key,
     n: u64,
@@ -44,24 +48,107 @@ where
         }
     }
 
-    /// Rekey. Set our key to `REKEY(old key)`.
+    /// Create a `CipherState` with nonce 0 from an externally supplied key,
+    /// e.g., one provisioned by a KMS, to use the transport machinery without
+    /// a handshake.
+    ///
+    /// Unlike [`new`](CipherState::new), returns [`None`] if `key` is not
+    /// [`C::key_len()`](Cipher::key_len) bytes long.
+    pub fn from_key(key: &[u8]) -> Option<Self> {
+        if key.len() != C::key_len() {
+            return None;
+        }
+        Some(CipherState::new(key, 0))
+    }
+
+    /// Derive a pair of `CipherState`s from a shared secret instead of a
+    /// handshake, e.g., a secret exported from another protocol.
+    ///
+    /// Like `Split()`, the keys are `HKDF(HASH(label), secret)`. The first
+    /// `CipherState` is for initiator to responder, and the second for
+    /// responder to initiator. `label` should name the protocol and the
+    /// purpose, so that the same secret gives different keys elsewhere. The
+    /// secret must be uniformly random, or at least have enough entropy.
+    pub fn pair_from_secret<H: Hash>(secret: &[u8], label: &[u8]) -> (Self, Self) {
+        let (k1, k2) = H::hkdf(H::hash(label).as_slice(), secret);
+        (
+            CipherState::new(&k1.as_slice()[..C::key_len()], 0),
+            CipherState::new(&k2.as_slice()[..C::key_len()], 0),
+        )
+    }
+
+    /// Rekey. Set our key to `REKEY(old key)`, cf. [`Cipher::rekey`].
+    ///
+    /// The nonce is not changed. Both peers must rekey at the same point of
+    /// the stream, e.g., every 2 ^ 20 messages, for long-lived sessions to
+    /// refresh their keys without a new handshake.
     pub fn rekey(&mut self) {
         self.key = C::rekey(&self.key);
+        instrument::rekey();
+    }
+
+    // Nonce 2 ^ 64 - 1 is reserved, complying to the spec.
+    fn assert_not_exhausted(&self) {
+        assert!(!self.is_exhausted(), "nonce exhausted");
     }
 
     /// AEAD encryption.
     pub fn encrypt_ad(&mut self, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) {
+        self.encrypt_ad_impl(authtext, plaintext, out);
+        instrument::bytes_sent(plaintext.len());
+    }
+
+    /// Like [`encrypt_ad`](CipherState::encrypt_ad), but not counted as
+    /// transport traffic. Used for handshake messages.
+    pub(crate) fn encrypt_ad_impl(&mut self, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) {
+        self.assert_not_exhausted();
         C::encrypt(&self.key, self.n, authtext, plaintext, out);
         #[cfg(feature = "use_std")]
         if option_env!("NOISE_RUST_TEST_IN_PLACE").is_some() {
             let mut inout = plaintext.to_vec();
-            inout.extend_from_slice(&[0; 16]);
+            inout.resize(plaintext.len() + C::tag_len(), 0);
             let l = C::encrypt_in_place(&self.key, self.n, authtext, &mut inout, plaintext.len());
             assert_eq!(inout, out);
             assert_eq!(l, out.len());
         }
-        // This will fail when n == 2 ^ 64 - 1, complying to the spec.
-        self.n = self.n.checked_add(1).unwrap();
+        self.n += 1;
+    }
+
+    /// Like [`encrypt_ad`](CipherState::encrypt_ad), but fails with
+    /// [NonceExhausted](crate::ErrorKind::NonceExhausted) instead of
+    /// panicking when the nonce has reached 2 ^ 64 - 1.
+    pub fn try_encrypt_ad(
+        &mut self,
+        authtext: &[u8],
+        plaintext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        if self.is_exhausted() {
+            return Err(Error::nonce_exhausted());
+        }
+        self.encrypt_ad(authtext, plaintext, out);
+        Ok(())
+    }
+
+    /// AEAD encryption of the concatenation of `bufs`, cf.
+    /// [`Cipher::encrypt_vectored`].
+    pub fn encrypt_ad_vectored(&mut self, authtext: &[u8], bufs: &[&[u8]], out: &mut [u8]) {
+        self.encrypt_ad_vectored_impl(authtext, bufs, out);
+        instrument::bytes_sent(out.len() - C::tag_len());
+    }
+
+    /// Like [`encrypt_ad_vectored`](CipherState::encrypt_ad_vectored), but
+    /// not counted as transport traffic, for callers that count the payload
+    /// themselves.
+    pub(crate) fn encrypt_ad_vectored_impl(
+        &mut self,
+        authtext: &[u8],
+        bufs: &[&[u8]],
+        out: &mut [u8],
+    ) {
+        self.assert_not_exhausted();
+        C::encrypt_vectored(&self.key, self.n, authtext, bufs, out);
+        self.n += 1;
     }
 
     /// AEAD encryption in place.
@@ -71,51 +158,114 @@ where
         in_out: &mut [u8],
         plaintext_len: usize,
     ) -> usize {
+        self.assert_not_exhausted();
         let size = C::encrypt_in_place(&self.key, self.n, authtext, in_out, plaintext_len);
-        // This will fail when n == 2 ^ 64 - 1, complying to the spec.
-        self.n = self.n.checked_add(1).unwrap();
+        self.n += 1;
+        instrument::bytes_sent(plaintext_len);
         size
     }
 
     /// AEAD decryption.
+    ///
+    /// Fails with [Decryption](crate::ErrorKind::Decryption) if the
+    /// ciphertext is not authentic, and with
+    /// [NonceExhausted](crate::ErrorKind::NonceExhausted) if the nonce has
+    /// reached 2 ^ 64 - 1.
     pub fn decrypt_ad(
         &mut self,
         authtext: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()> {
+    ) -> Result<(), Error> {
+        self.decrypt_ad_impl(authtext, ciphertext, out)?;
+        instrument::bytes_received(out.len());
+        Ok(())
+    }
+
+    /// Like [`decrypt_ad`](CipherState::decrypt_ad), but not counted as
+    /// transport traffic. Used for handshake messages.
+    pub(crate) fn decrypt_ad_impl(
+        &mut self,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        if self.is_exhausted() {
+            return Err(Error::nonce_exhausted());
+        }
         let r = C::decrypt(&self.key, self.n, authtext, ciphertext, out);
         #[cfg(feature = "use_std")]
         if option_env!("NOISE_RUST_TEST_IN_PLACE").is_some() {
             let mut inout = ciphertext.to_vec();
             let r2 = C::decrypt_in_place(&self.key, self.n, authtext, &mut inout, ciphertext.len());
-            assert_eq!(r.map(|_| out.len()), r2);
+            assert_eq!(r.as_ref().map(|_| out.len()).ok(), r2.ok());
             if r.is_ok() {
                 assert_eq!(&inout[..out.len()], out);
             }
         }
         r?;
-        self.n = self.n.checked_add(1).unwrap();
+        self.n += 1;
         Ok(())
     }
 
     /// AEAD decryption in place.
+    ///
+    /// Fails like [`decrypt_ad`](CipherState::decrypt_ad).
     pub fn decrypt_ad_in_place(
         &mut self,
         authtext: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
+        if self.is_exhausted() {
+            return Err(Error::nonce_exhausted());
+        }
         let size = C::decrypt_in_place(&self.key, self.n, authtext, in_out, ciphertext_len)?;
-        self.n = self.n.checked_add(1).unwrap();
+        self.n += 1;
+        instrument::bytes_received(size);
         Ok(size)
     }
 
+    /// AEAD decryption with an explicit nonce `n`, e.g., one sent along with
+    /// a datagram that may arrive out of order.
+    ///
+    /// The nonce of this `CipherState` is not used or changed, so a
+    /// [`ReplayFilter`](crate::ReplayFilter) or the like is needed to reject
+    /// replayed messages. Fails with
+    /// [NonceExhausted](crate::ErrorKind::NonceExhausted) if `n` is
+    /// 2 ^ 64 - 1.
+    pub fn decrypt_with_nonce(
+        &self,
+        n: u64,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        if n == u64::MAX {
+            return Err(Error::nonce_exhausted());
+        }
+        C::decrypt(&self.key, n, authtext, ciphertext, out)?;
+        instrument::bytes_received(out.len());
+        Ok(())
+    }
+
     /// Encryption.
     pub fn encrypt(&mut self, plaintext: &[u8], out: &mut [u8]) {
         self.encrypt_ad(&[0u8; 0], plaintext, out)
     }
 
+    /// Like [`encrypt`](CipherState::encrypt), but fails with
+    /// [NonceExhausted](crate::ErrorKind::NonceExhausted) instead of
+    /// panicking when the nonce has reached 2 ^ 64 - 1.
+    pub fn try_encrypt(&mut self, plaintext: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        self.try_encrypt_ad(&[0u8; 0], plaintext, out)
+    }
+
+    /// Encryption of the concatenation of `bufs`.
+    pub fn encrypt_vectored(&mut self, bufs: &[&[u8]], out: &mut [u8]) {
+        self.encrypt_ad_vectored(&[0u8; 0], bufs, out)
+    }
+
     /// Encryption in place.
     pub fn encrypt_in_place(&mut self, in_out: &mut [u8], plaintext_len: usize) -> usize {
         self.encrypt_ad_in_place(&[0u8; 0], in_out, plaintext_len)
@@ -124,13 +274,13 @@ where
     /// Encryption, returns ciphertext as `Vec<u8>`.
     #[cfg(any(feature = "use_std", feature = "use_alloc"))]
     pub fn encrypt_vec(&mut self, plaintext: &[u8]) -> Vec<u8> {
-        let mut out = vec![0u8; plaintext.len() + 16];
+        let mut out = vec![0u8; plaintext.len() + C::tag_len()];
         self.encrypt(plaintext, &mut out);
         out
     }
 
     /// Decryption.
-    pub fn decrypt(&mut self, ciphertext: &[u8], out: &mut [u8]) -> Result<(), ()> {
+    pub fn decrypt(&mut self, ciphertext: &[u8], out: &mut [u8]) -> Result<(), Error> {
         self.decrypt_ad(&[0u8; 0], ciphertext, out)
     }
 
@@ -139,17 +289,17 @@ where
         &mut self,
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
         self.decrypt_ad_in_place(&[0u8; 0], in_out, ciphertext_len)
     }
 
     /// Decryption, returns plaintext as `Vec<u8>`.
     #[cfg(any(feature = "use_std", feature = "use_alloc"))]
-    pub fn decrypt_vec(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, ()> {
-        if ciphertext.len() < 16 {
-            return Err(());
+    pub fn decrypt_vec(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if ciphertext.len() < C::tag_len() {
+            return Err(Error::too_short());
         }
-        let mut out = vec![0u8; ciphertext.len() - 16];
+        let mut out = vec![0u8; ciphertext.len() - C::tag_len()];
         self.decrypt(ciphertext, &mut out)?;
         Ok(out)
     }
@@ -159,6 +309,36 @@ where
         self.n
     }
 
+    /// Set the next value of `n`, e.g., to resynchronize the receiving side
+    /// of a datagram transport that sends a truncated counter with each
+    /// message, after reconstructing the full nonce from it.
+    ///
+    /// Never set the nonce of a `CipherState` that encrypts to a value that
+    /// has been used before: encrypting two messages with the same nonce
+    /// breaks confidentiality and authenticity. When decrypting, rewinding
+    /// the nonce lets a message be accepted again, so reject replays, e.g.,
+    /// with a [`ReplayFilter`](crate::ReplayFilter), or only move the nonce
+    /// forward. Cf. [`decrypt_with_nonce`](CipherState::decrypt_with_nonce)
+    /// to decrypt with an explicit nonce without changing it.
+    pub fn set_nonce(&mut self, n: u64) {
+        self.n = n;
+    }
+
+    /// Number of messages that can still be encrypted or decrypted, i.e.,
+    /// 2 ^ 64 - 1 minus the next nonce.
+    ///
+    /// [`rekey`](CipherState::rekey) does not change it, so applications that
+    /// rekey periodically can use it to schedule a new handshake instead.
+    pub fn remaining(&self) -> u64 {
+        u64::MAX - self.n
+    }
+
+    /// Whether the nonce has reached 2 ^ 64 - 1, so that no more messages can
+    /// be encrypted or decrypted.
+    pub fn is_exhausted(&self) -> bool {
+        self.n == u64::MAX
+    }
+
     /// Get underlying cipher and nonce.
     ///
     /// This is useful for e.g. WireGuard. Because packets may be lost or arrive out of order,
@@ -166,4 +346,15 @@ where
     pub fn extract(self) -> (C::Key, u64) {
         (self.key, self.n)
     }
+
+    /// Restore a `CipherState` from a key and a nonce returned by
+    /// [`extract`](CipherState::extract), e.g., to resume a session after a
+    /// restart.
+    ///
+    /// The key must be kept in secure storage meanwhile. Never restore the
+    /// same, or an older, checkpoint twice: encrypting again with a nonce
+    /// already used breaks confidentiality.
+    pub fn restore(key: C::Key, n: u64) -> Self {
+        CipherState { key, n }
+    }
 }
@@ -0,0 +1,125 @@
+//! A [`tokio_util::codec`] codec for Noise transport messages.
+//!
+//! Each message is framed with a 2-byte big endian length prefix, the usual
+//! framing for Noise over a stream:
+//!
+//! ```ignore
+//! let codec = NoiseCodec::from_handshake(&hs);
+//! let mut framed = Framed::new(tcp_stream, codec);
+//! framed.send(&b"hello"[..]).await?;
+//! let reply = framed.next().await.unwrap()?;
+//! ```
+
+use crate::cipherstate::CipherState;
+use crate::error::Error;
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, DH};
+use bytes::{Buf, BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Maximum length of a Noise message.
+pub const MAX_MESSAGE_LEN: usize = 65535;
+
+/// Maximum length of a plaintext that fits in one message, with a 16-byte
+/// tag. Ciphers with longer tags fit less, cf. [`Cipher::tag_len`].
+pub const MAX_PAYLOAD_LEN: usize = MAX_MESSAGE_LEN - 16;
+
+/// Encrypts and frames outgoing messages, and decrypts incoming ones.
+///
+/// Encoding a plaintext that does not fit in one message fails with
+/// [`InvalidInput`](io::ErrorKind::InvalidInput). A message that fails to
+/// decrypt fails with [`InvalidData`](io::ErrorKind::InvalidData), after
+/// which the stream must be dropped. Encoding after the sending nonce is
+/// exhausted fails with [`Other`](io::ErrorKind::Other).
+pub struct NoiseCodec<C: Cipher> {
+    send: CipherState<C>,
+    recv: CipherState<C>,
+}
+
+impl<C: Cipher> NoiseCodec<C> {
+    /// Create a codec from the sending and receiving cipher states.
+    pub fn new(send: CipherState<C>, recv: CipherState<C>) -> Self {
+        NoiseCodec { send, recv }
+    }
+
+    /// Create a codec from a completed handshake.
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has not completed.
+    pub fn from_handshake<D: DH, H: Hash>(hs: &HandshakeState<D, C, H>) -> Self {
+        assert!(hs.completed());
+        let (c1, c2) = hs.get_ciphers();
+        if hs.get_is_initiator() {
+            NoiseCodec::new(c1, c2)
+        } else {
+            NoiseCodec::new(c2, c1)
+        }
+    }
+
+    /// The sending cipher state, e.g., to rekey.
+    pub fn send_cipher(&mut self) -> &mut CipherState<C> {
+        &mut self.send
+    }
+
+    /// The receiving cipher state, e.g., to rekey.
+    pub fn recv_cipher(&mut self) -> &mut CipherState<C> {
+        &mut self.recv
+    }
+}
+
+impl<C: Cipher, T: AsRef<[u8]>> Encoder<T> for NoiseCodec<C> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let plaintext = item.as_ref();
+        if plaintext.len() > MAX_MESSAGE_LEN - C::tag_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Noise message too long",
+            ));
+        }
+        if self.send.is_exhausted() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                Error::nonce_exhausted(),
+            ));
+        }
+        let len = plaintext.len() + C::tag_len();
+        dst.reserve(2 + len);
+        dst.put_u16(len as u16);
+        let start = dst.len();
+        dst.resize(start + len, 0);
+        self.send.encrypt(plaintext, &mut dst[start..]);
+        Ok(())
+    }
+}
+
+impl<C: Cipher> Decoder for NoiseCodec<C> {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, io::Error> {
+        if src.len() < 2 {
+            return Ok(None);
+        }
+        let len = u16::from_be_bytes([src[0], src[1]]) as usize;
+        if src.len() < 2 + len {
+            src.reserve(2 + len - src.len());
+            return Ok(None);
+        }
+        src.advance(2);
+        let ciphertext = src.split_to(len);
+
+        let decryption_failed = || io::Error::new(io::ErrorKind::InvalidData, Error::decryption());
+        if len < C::tag_len() {
+            return Err(decryption_failed());
+        }
+        let mut plaintext = BytesMut::zeroed(len - C::tag_len());
+        self.recv
+            .decrypt(&ciphertext, &mut plaintext)
+            .map_err(|_| decryption_failed())?;
+        Ok(Some(plaintext))
+    }
+}
@@ -0,0 +1,339 @@
+//! Demultiplexing of Noise sessions sharing one UDP socket.
+//!
+//! Like in WireGuard, each peer assigns a random 32-bit index to every
+//! session, and tells the other peer during the handshake. Every datagram
+//! then starts with a small unencrypted header carrying the index of the
+//! receiver:
+//!
+//! ```text
+//! handshake initiation: 1 | sender index | handshake message
+//! handshake response:   2 | sender index | receiver index | handshake message
+//! transport:            3 | receiver index | transport message
+//! ```
+//!
+//! Indices are big endian. [`Demux`] assigns local indices and routes
+//! incoming datagrams to the session they belong to. It does not do any I/O
+//! or cryptography itself, so sessions can be any type, e.g., a
+//! [`HandshakeState`](crate::HandshakeState) during the handshake and a pair
+//! of [`CipherState`](crate::CipherState)s after it.
+//!
+//! Transport messages over UDP may be lost or reordered, so they usually
+//! carry an explicit nonce in the message, which is up to the application.
+//!
+//! Handshakes with more than two messages send further handshake messages as
+//! transport datagrams, after the index of the peer is known.
+
+use crate::ratelimit::{Admission, HandshakePolicy};
+use std::collections::hash_map::{Entry, HashMap, RandomState};
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+const TYPE_INIT: u8 = 1;
+const TYPE_RESPONSE: u8 = 2;
+const TYPE_TRANSPORT: u8 = 3;
+
+/// A parsed datagram.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Packet<'a> {
+    /// First handshake message.
+    HandshakeInit {
+        /// Index of the session at the initiator.
+        sender: u32,
+        /// The handshake message.
+        message: &'a [u8],
+    },
+    /// Second handshake message.
+    HandshakeResponse {
+        /// Index of the session at the responder.
+        sender: u32,
+        /// Index of the session at the initiator.
+        receiver: u32,
+        /// The handshake message.
+        message: &'a [u8],
+    },
+    /// Any later message.
+    Transport {
+        /// Index of the session at the receiver.
+        receiver: u32,
+        /// The message.
+        message: &'a [u8],
+    },
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+impl<'a> Packet<'a> {
+    /// Parse a datagram. Returns [`None`] if it is malformed.
+    pub fn parse(datagram: &'a [u8]) -> Option<Self> {
+        match *datagram.first()? {
+            TYPE_INIT => Some(Packet::HandshakeInit {
+                sender: read_u32(datagram, 1)?,
+                message: &datagram[5..],
+            }),
+            TYPE_RESPONSE => Some(Packet::HandshakeResponse {
+                sender: read_u32(datagram, 1)?,
+                receiver: read_u32(datagram, 5)?,
+                message: &datagram[9..],
+            }),
+            TYPE_TRANSPORT => Some(Packet::Transport {
+                receiver: read_u32(datagram, 1)?,
+                message: &datagram[5..],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Encode the datagram.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match *self {
+            Packet::HandshakeInit { sender, message } => {
+                out.push(TYPE_INIT);
+                out.extend_from_slice(&sender.to_be_bytes());
+                out.extend_from_slice(message);
+            }
+            Packet::HandshakeResponse {
+                sender,
+                receiver,
+                message,
+            } => {
+                out.push(TYPE_RESPONSE);
+                out.extend_from_slice(&sender.to_be_bytes());
+                out.extend_from_slice(&receiver.to_be_bytes());
+                out.extend_from_slice(message);
+            }
+            Packet::Transport { receiver, message } => {
+                out.push(TYPE_TRANSPORT);
+                out.extend_from_slice(&receiver.to_be_bytes());
+                out.extend_from_slice(message);
+            }
+        }
+        out
+    }
+}
+
+/// Where a datagram should go, cf. [`Demux::route`].
+#[derive(Debug)]
+pub enum Route<'a, S> {
+    /// A new handshake from a peer. Start a responder, [`insert`](Demux::insert)
+    /// it and reply with the returned index as `sender` and this `sender` as
+    /// `receiver`.
+    New {
+        /// Index of the session at the initiator.
+        sender: u32,
+        /// The handshake message.
+        message: &'a [u8],
+    },
+    /// A new handshake that should be answered with a cookie challenge, cf.
+    /// [`Admission::Challenge`].
+    Challenge {
+        /// Index of the session at the initiator.
+        sender: u32,
+    },
+    /// A new handshake that should be processed after a delay, cf.
+    /// [`Admission::Delay`].
+    Delay {
+        /// Index of the session at the initiator.
+        sender: u32,
+        /// The handshake message.
+        message: &'a [u8],
+        /// The delay.
+        delay: Duration,
+    },
+    /// A datagram of an existing session.
+    Session {
+        /// Local index of the session.
+        index: u32,
+        /// Index of the session at the peer, if this is a handshake
+        /// response. Record it with
+        /// [`set_remote_index`](Demux::set_remote_index) once the response
+        /// has been read successfully.
+        sender: Option<u32>,
+        /// The session.
+        session: &'a mut S,
+        /// The handshake or transport message.
+        message: &'a [u8],
+    },
+}
+
+struct Slot<S> {
+    session: S,
+    remote_index: Option<u32>,
+    last_seen: Instant,
+}
+
+/// Assigns local session indices and routes datagrams to sessions.
+///
+/// Local indices are random, so they do not reveal how many sessions there
+/// are, and unused ones are picked on collision. They are not secret, and
+/// datagrams are only authenticated by the session itself, so a session
+/// must not trust the routing, e.g., when updating the peer address.
+pub struct Demux<S> {
+    slots: HashMap<u32, Slot<S>>,
+    timeout: Duration,
+    random: RandomState,
+    counter: u64,
+}
+
+impl<S> Demux<S> {
+    /// Create a demultiplexer expiring sessions that have been idle for
+    /// `timeout`, cf. [`expire`](Demux::expire).
+    pub fn new(timeout: Duration) -> Self {
+        Demux {
+            slots: HashMap::new(),
+            timeout,
+            random: RandomState::new(),
+            counter: 0,
+        }
+    }
+
+    fn random_index(&mut self) -> u32 {
+        let mut h = self.random.build_hasher();
+        h.write_u64(self.counter);
+        self.counter += 1;
+        h.finish() as u32
+    }
+
+    /// Add a session, returning its new local index.
+    ///
+    /// `remote_index` is the index of the session at the peer, if already
+    /// known, i.e., for responders.
+    pub fn insert(&mut self, session: S, remote_index: Option<u32>) -> u32 {
+        let slot = Slot {
+            session,
+            remote_index,
+            last_seen: Instant::now(),
+        };
+        loop {
+            let index = self.random_index();
+            if let Entry::Vacant(e) = self.slots.entry(index) {
+                e.insert(slot);
+                return index;
+            }
+        }
+    }
+
+    /// Remove a session.
+    pub fn remove(&mut self, index: u32) -> Option<S> {
+        self.slots.remove(&index).map(|s| s.session)
+    }
+
+    /// Get a session.
+    pub fn get_mut(&mut self, index: u32) -> Option<&mut S> {
+        self.slots.get_mut(&index).map(|s| &mut s.session)
+    }
+
+    /// Record the index of a session at the peer.
+    ///
+    /// Returns `false` if there is no such session.
+    pub fn set_remote_index(&mut self, index: u32, remote_index: u32) -> bool {
+        match self.slots.get_mut(&index) {
+            Some(slot) => {
+                slot.remote_index = Some(remote_index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Index of a session at the peer, to use as `receiver` in datagrams to
+    /// it, if known.
+    pub fn remote_index(&self, index: u32) -> Option<u32> {
+        self.slots.get(&index)?.remote_index
+    }
+
+    /// Number of sessions.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Whether there are no sessions.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Route a datagram.
+    ///
+    /// Returns [`None`] if the datagram is malformed or for an unknown
+    /// session.
+    ///
+    /// The datagram is not authenticated yet, so the session must still
+    /// check it, e.g., by decrypting it, and then call
+    /// [`touch`](Demux::touch).
+    pub fn route<'a>(&'a mut self, datagram: &'a [u8]) -> Option<Route<'a, S>> {
+        let (index, sender, message) = match Packet::parse(datagram)? {
+            Packet::HandshakeInit { sender, message } => {
+                return Some(Route::New { sender, message })
+            }
+            Packet::HandshakeResponse {
+                sender,
+                receiver,
+                message,
+            } => (receiver, Some(sender), message),
+            Packet::Transport { receiver, message } => (receiver, None, message),
+        };
+        let slot = self.slots.get_mut(&index)?;
+        Some(Route::Session {
+            index,
+            sender,
+            session: &mut slot.session,
+            message,
+        })
+    }
+
+    /// Like [`route`](Demux::route), but new handshakes from `source` are
+    /// first checked with `policy`.
+    ///
+    /// `has_cookie` is whether the datagram comes with a valid answer to a
+    /// cookie challenge, as checked by the application. Dropped handshakes
+    /// return [`None`].
+    pub fn route_from<'a, A>(
+        &'a mut self,
+        datagram: &'a [u8],
+        source: &A,
+        has_cookie: bool,
+        policy: &dyn HandshakePolicy<A>,
+    ) -> Option<Route<'a, S>> {
+        match self.route(datagram)? {
+            Route::New { sender, message } => match policy.admit(source, has_cookie) {
+                Admission::Accept => Some(Route::New { sender, message }),
+                Admission::Challenge => Some(Route::Challenge { sender }),
+                Admission::Drop => None,
+                Admission::Delay(delay) => Some(Route::Delay {
+                    sender,
+                    message,
+                    delay,
+                }),
+            },
+            r => Some(r),
+        }
+    }
+
+    /// Mark a session as active, after it has received an authentic
+    /// datagram.
+    pub fn touch(&mut self, index: u32) {
+        if let Some(slot) = self.slots.get_mut(&index) {
+            slot.last_seen = Instant::now();
+        }
+    }
+
+    /// Remove sessions that have not been [`touch`](Demux::touch)ed, or
+    /// inserted, for longer than the timeout, returning them with their
+    /// indices.
+    pub fn expire(&mut self) -> Vec<(u32, S)> {
+        let now = Instant::now();
+        let expired: Vec<u32> = self
+            .slots
+            .iter()
+            .filter(|(_, s)| now.duration_since(s.last_seen) > self.timeout)
+            .map(|(&i, _)| i)
+            .collect();
+        expired
+            .into_iter()
+            .map(|i| (i, self.slots.remove(&i).unwrap().session))
+            .collect()
+    }
+}
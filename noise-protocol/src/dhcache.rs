@@ -0,0 +1,149 @@
+use crate::traits::{U8Array, DH};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// A cache of static-static DH results, i.e., the `ss` token.
+///
+/// Responders with many frequently reconnecting peers, e.g., in `IK` or
+/// `KK`, can skip one scalar multiplication per handshake by caching `ss`,
+/// keyed by the local and remote static public keys. Other DH tokens involve
+/// an ephemeral key and cannot be cached.
+///
+/// Set a cache with
+/// [`HandshakeState::set_static_dh_cache`](crate::HandshakeState::set_static_dh_cache).
+/// As entries are keyed by the local public key too, entries for an old key
+/// are never used after a rotation, call
+/// [`invalidate_local`](StaticDhCache::invalidate_local) to drop them.
+///
+/// The cached values are as sensitive as the static private key. If a key
+/// may have been compromised, [`clear`](StaticDhCache::clear) the cache.
+pub trait StaticDhCache<D: DH>: Send + Sync {
+    /// Get the cached `DH(local, remote)`, if any.
+    fn get(&self, local: &D::Pubkey, remote: &D::Pubkey) -> Option<D::Output>;
+    /// Cache `DH(local, remote)`.
+    fn insert(&self, local: &D::Pubkey, remote: &D::Pubkey, output: &D::Output);
+    /// Drop all entries of a local static key, e.g., after rotating it.
+    fn invalidate_local(&self, local: &D::Pubkey);
+    /// Drop all entries of a remote static key, e.g., when the peer rotates
+    /// it or is removed.
+    fn invalidate_remote(&self, remote: &D::Pubkey);
+    /// Drop all entries.
+    fn clear(&self);
+}
+
+/// A [`StaticDhCache`] holding up to a fixed number of entries in memory,
+/// evicting the least recently used first.
+///
+/// Entries are moved, never copied, inside the cache, so outputs that zero
+/// themselves on drop, e.g., `Sensitive` outputs of `noise-rust-crypto`, are
+/// zeroed when evicted or invalidated.
+pub struct MemoryStaticDhCache<D: DH> {
+    inner: Mutex<Entries<D>>,
+}
+
+/// Local and remote public keys.
+type Key = (Vec<u8>, Vec<u8>);
+
+struct Entries<D: DH> {
+    /// Entries and the time they were last used.
+    map: HashMap<Key, (D::Output, u64)>,
+    /// Keys by the time they were last used.
+    order: BTreeMap<u64, Key>,
+    clock: u64,
+    capacity: usize,
+}
+
+impl<D: DH> MemoryStaticDhCache<D> {
+    /// Create a cache holding up to `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        MemoryStaticDhCache {
+            inner: Mutex::new(Entries {
+                map: HashMap::with_capacity(capacity),
+                order: BTreeMap::new(),
+                clock: 0,
+                capacity,
+            }),
+        }
+    }
+
+    /// Number of cached entries.
+    pub fn len(&self) -> usize {
+        self.with(|e| e.map.len())
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Entries<D>) -> T) -> T {
+        match self.inner.lock() {
+            Ok(mut e) => f(&mut e),
+            Err(poisoned) => f(&mut poisoned.into_inner()),
+        }
+    }
+}
+
+fn key<D: DH>(local: &D::Pubkey, remote: &D::Pubkey) -> Key {
+    (local.as_slice().to_vec(), remote.as_slice().to_vec())
+}
+
+impl<D: DH> StaticDhCache<D> for MemoryStaticDhCache<D>
+where
+    D::Output: Send,
+{
+    fn get(&self, local: &D::Pubkey, remote: &D::Pubkey) -> Option<D::Output> {
+        self.with(|e| {
+            let k = key::<D>(local, remote);
+            let (output, used) = e.map.get_mut(&k)?;
+            e.clock += 1;
+            let k = e.order.remove(&*used).unwrap();
+            *used = e.clock;
+            e.order.insert(e.clock, k);
+            Some(U8Array::clone(output))
+        })
+    }
+
+    fn insert(&self, local: &D::Pubkey, remote: &D::Pubkey, output: &D::Output) {
+        self.with(|e| {
+            if e.capacity == 0 {
+                return;
+            }
+            let k = key::<D>(local, remote);
+            e.clock += 1;
+            let value = (U8Array::clone(output), e.clock);
+            match e.map.insert(k.clone(), value) {
+                Some((_, used)) => {
+                    e.order.remove(&used);
+                }
+                None if e.map.len() > e.capacity => {
+                    let (_, oldest) = e.order.pop_first().unwrap();
+                    e.map.remove(&oldest);
+                }
+                None => {}
+            }
+            e.order.insert(e.clock, k);
+        })
+    }
+
+    fn invalidate_local(&self, local: &D::Pubkey) {
+        self.with(|e| {
+            e.map.retain(|(l, _), _| l != local.as_slice());
+            e.order.retain(|_, (l, _)| l != local.as_slice());
+        })
+    }
+
+    fn invalidate_remote(&self, remote: &D::Pubkey) {
+        self.with(|e| {
+            e.map.retain(|(_, r), _| r != remote.as_slice());
+            e.order.retain(|_, (_, r)| r != remote.as_slice());
+        })
+    }
+
+    fn clear(&self) {
+        self.with(|e| {
+            e.map.clear();
+            e.order.clear();
+        })
+    }
+}
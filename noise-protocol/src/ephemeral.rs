@@ -0,0 +1,153 @@
+use crate::boundedset::BoundedSet;
+use crate::traits::{DhKeyPair, DH};
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+/// Where an ephemeral key was seen.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum EphemeralOrigin {
+    /// Generated locally.
+    Local,
+    /// Received from the peer.
+    Remote,
+}
+
+/// A hook called with every ephemeral public key a [`HandshakeState`]
+/// generates or receives.
+///
+/// Set one with
+/// [`HandshakeState::set_ephemeral_observer`](crate::HandshakeState::set_ephemeral_observer).
+///
+/// [`HandshakeState`]: crate::HandshakeState
+pub trait EphemeralObserver: Send + Sync {
+    /// Observe an ephemeral public key.
+    ///
+    /// `peer` is the remote static public key, if it is already known.
+    fn observe(&self, origin: EphemeralOrigin, e: &[u8], peer: Option<&[u8]>);
+}
+
+/// An ephemeral key that has been seen before.
+#[derive(Debug)]
+pub struct EphemeralReuse<'a> {
+    /// Where the key was seen this time.
+    pub origin: EphemeralOrigin,
+    /// The ephemeral public key.
+    pub e: &'a [u8],
+    /// The remote static public key, if known.
+    pub peer: Option<&'a [u8]>,
+}
+
+/// An [`EphemeralObserver`] that remembers recent ephemeral keys and reports
+/// any key seen twice.
+///
+/// Ephemeral keys should never repeat, so a reused key, local or remote,
+/// indicates a broken RNG somewhere. This only reports, it never fails a
+/// handshake. Note that reading the same message with several handshake
+/// states, e.g., with [`KeyRing::read_message`](crate::KeyRing::read_message),
+/// also counts as reuse, as does sharing one detector between both peers of
+/// a handshake.
+pub struct EphemeralReuseDetector<F> {
+    seen: Mutex<BoundedSet>,
+    on_reuse: F,
+}
+
+impl<F> EphemeralReuseDetector<F>
+where
+    F: Fn(&EphemeralReuse) + Send + Sync,
+{
+    /// Create a detector that remembers the last `capacity` keys and calls
+    /// `on_reuse` when a key is seen again.
+    pub fn new(capacity: usize, on_reuse: F) -> Self {
+        EphemeralReuseDetector {
+            seen: Mutex::new(BoundedSet::new(capacity)),
+            on_reuse,
+        }
+    }
+}
+
+impl<F> EphemeralObserver for EphemeralReuseDetector<F>
+where
+    F: Fn(&EphemeralReuse) + Send + Sync,
+{
+    fn observe(&self, origin: EphemeralOrigin, e: &[u8], peer: Option<&[u8]>) {
+        let reused = match self.seen.lock() {
+            Ok(mut seen) => !seen.insert(e),
+            Err(poisoned) => !poisoned.into_inner().insert(e),
+        };
+
+        if reused {
+            (self.on_reuse)(&EphemeralReuse { origin, e, peer });
+        }
+    }
+}
+
+/// Ephemeral key pairs generated ahead of time.
+///
+/// Generating an Elligator key pair takes about two tries on average, and
+/// sometimes many more, which shows as latency spikes on busy servers. Call
+/// [`refill`](EphemeralPool::refill) from a background thread, and let
+/// handshakes take their ephemeral keys from the pool with
+/// [`HandshakeStateBuilder::set_ephemeral_pool`](crate::HandshakeStateBuilder::set_ephemeral_pool).
+///
+/// Each key pair is taken at most once. If the pool is empty, handshakes
+/// generate their keys as usual.
+pub struct EphemeralPool<D: DH> {
+    keys: Mutex<VecDeque<DhKeyPair<D::Key, D::Pubkey>>>,
+    elligator: bool,
+    capacity: usize,
+}
+
+impl<D: DH> EphemeralPool<D> {
+    /// Create an empty pool for at most `capacity` key pairs, with Elligator
+    /// representatives as public keys if `elligator` is set, cf.
+    /// [`DH::genkey`].
+    pub fn new(elligator: bool, capacity: usize) -> Self {
+        EphemeralPool {
+            keys: Mutex::new(VecDeque::with_capacity(capacity)),
+            elligator,
+            capacity,
+        }
+    }
+
+    /// Whether the public keys are Elligator representatives.
+    pub fn is_elligator(&self) -> bool {
+        self.elligator
+    }
+
+    /// The number of key pairs in the pool.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Generate up to `n` key pairs, without exceeding the capacity, and
+    /// return how many were added.
+    ///
+    /// The pool is not locked while generating, so handshakes can take keys
+    /// meanwhile.
+    pub fn refill(&self, n: usize) -> usize {
+        let n = n.min(self.capacity.saturating_sub(self.len()));
+        let generated: Vec<_> = (0..n).map(|_| D::genkey(self.elligator)).collect();
+        let mut keys = self.lock();
+        let added = generated
+            .len()
+            .min(self.capacity.saturating_sub(keys.len()));
+        keys.extend(generated.into_iter().take(added));
+        added
+    }
+
+    /// Take a key pair, if there is one.
+    pub fn take(&self) -> Option<DhKeyPair<D::Key, D::Pubkey>> {
+        self.lock().pop_front()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<DhKeyPair<D::Key, D::Pubkey>>> {
+        self.keys
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,179 @@
+use core::fmt::{Display, Error as FmtError, Formatter};
+
+/// Error of handshakes, cipher states and crypto primitives.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+}
+
+/// Error kind.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ErrorKind {
+    /// A DH operation has failed.
+    DH,
+    /// A PSK is needed, but none is available.
+    NeedPSK,
+    /// Decryption failed.
+    Decryption,
+    /// The message is too short, and impossible to read.
+    TooShort,
+    /// The message is longer than the maximum message length, 65535 bytes
+    /// by default, cf.
+    /// [`HandshakeStateBuilder::set_max_message_len`](crate::HandshakeStateBuilder::set_max_message_len).
+    MessageTooLong,
+    /// The first message has been seen before, cf.
+    /// [`ReplayCache`](crate::ReplayCache), or a transport nonce, cf.
+    /// [`ReplayFilter`](crate::ReplayFilter).
+    Replay,
+    /// A saved state is malformed or does not match the protocol.
+    InvalidState,
+    /// A key required by the pattern has not been set, cf.
+    /// [`HandshakeStateBuilder::try_build_handshake_state`](crate::HandshakeStateBuilder::try_build_handshake_state).
+    MissingKey,
+    /// A required setting of a builder, e.g., the pattern, has not been set,
+    /// cf.
+    /// [`HandshakeStateBuilder::try_build_handshake_state`](crate::HandshakeStateBuilder::try_build_handshake_state).
+    MissingParameter,
+    /// A KEM operation has failed.
+    Kem,
+    /// The output buffer is too small for the message.
+    BufferTooSmall,
+    /// The nonce has reached 2 ^ 64 - 1, and the cipher state can not be
+    /// used any more, cf. [`CipherState::remaining`](crate::CipherState::remaining).
+    NonceExhausted,
+    /// A public key is invalid, e.g., not on the curve, or not supported,
+    /// e.g., an Elligator representative for a curve without Elligator.
+    InvalidPublicKey,
+    /// It is not our turn to read or write this message, cf.
+    /// [`HandshakeState::is_my_turn`](crate::HandshakeState::is_my_turn).
+    WrongTurn,
+    /// The handshake has already completed, and there is no more message to
+    /// read or write.
+    HandshakeComplete,
+    /// The operation does not fit the handshake pattern.
+    PatternViolation,
+    /// The static key of the peer has been rejected, cf.
+    /// [`RemoteStaticVerifier`](crate::RemoteStaticVerifier).
+    AuthenticationRejected,
+    /// The static key received from the peer differs from the one set with
+    /// [`HandshakeStateBuilder::set_rs`](crate::HandshakeStateBuilder::set_rs).
+    StaticKeyMismatch,
+    /// A signature of the `sig` token is invalid.
+    Signature,
+}
+
+impl Error {
+    pub(crate) fn need_psk() -> Error {
+        ErrorKind::NeedPSK.into()
+    }
+
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn decryption() -> Error {
+        ErrorKind::Decryption.into()
+    }
+
+    pub(crate) fn buffer_too_small() -> Error {
+        ErrorKind::BufferTooSmall.into()
+    }
+
+    pub(crate) fn nonce_exhausted() -> Error {
+        ErrorKind::NonceExhausted.into()
+    }
+
+    pub(crate) fn too_short() -> Error {
+        ErrorKind::TooShort.into()
+    }
+
+    pub(crate) fn message_too_long() -> Error {
+        ErrorKind::MessageTooLong.into()
+    }
+
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn invalid_state() -> Error {
+        ErrorKind::InvalidState.into()
+    }
+
+    pub(crate) fn missing_key() -> Error {
+        ErrorKind::MissingKey.into()
+    }
+
+    pub(crate) fn missing_parameter() -> Error {
+        ErrorKind::MissingParameter.into()
+    }
+
+    pub(crate) fn invalid_public_key() -> Error {
+        ErrorKind::InvalidPublicKey.into()
+    }
+
+    pub(crate) fn wrong_turn() -> Error {
+        ErrorKind::WrongTurn.into()
+    }
+
+    pub(crate) fn handshake_complete() -> Error {
+        ErrorKind::HandshakeComplete.into()
+    }
+
+    pub(crate) fn pattern_violation() -> Error {
+        ErrorKind::PatternViolation.into()
+    }
+
+    #[cfg(feature = "use_std")]
+    pub(crate) fn authentication_rejected() -> Error {
+        ErrorKind::AuthenticationRejected.into()
+    }
+
+    pub(crate) fn static_key_mismatch() -> Error {
+        ErrorKind::StaticKeyMismatch.into()
+    }
+
+    pub(crate) fn replay() -> Error {
+        ErrorKind::Replay.into()
+    }
+
+    /// Error kind.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// A static description of the error.
+    pub fn description(&self) -> &'static str {
+        match self.kind {
+            ErrorKind::DH => "DH error",
+            ErrorKind::NeedPSK => "Need PSK",
+            ErrorKind::Decryption => "Decryption failed",
+            ErrorKind::TooShort => "Message is too short",
+            ErrorKind::MessageTooLong => "Message is too long",
+            ErrorKind::Replay => "Replayed message",
+            ErrorKind::InvalidState => "Invalid saved state",
+            ErrorKind::MissingKey => "Missing key",
+            ErrorKind::MissingParameter => "Missing parameter",
+            ErrorKind::Kem => "KEM error",
+            ErrorKind::BufferTooSmall => "Buffer is too small",
+            ErrorKind::NonceExhausted => "Nonce exhausted",
+            ErrorKind::InvalidPublicKey => "Invalid public key",
+            ErrorKind::WrongTurn => "Wrong turn",
+            ErrorKind::HandshakeComplete => "Handshake is complete",
+            ErrorKind::PatternViolation => "Pattern violation",
+            ErrorKind::AuthenticationRejected => "Authentication rejected",
+            ErrorKind::StaticKeyMismatch => "Static key mismatch",
+            ErrorKind::Signature => "Invalid signature",
+        }
+    }
+}
+
+/// For implementations of the crypto primitives, e.g., to fail
+/// [`DH::dh`](crate::DH::dh) with [`ErrorKind::InvalidPublicKey`].
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error { kind }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        fmt.write_str(self.description())
+    }
+}
+
+#[cfg(feature = "use_std")]
+impl ::std::error::Error for Error {}
@@ -0,0 +1,420 @@
+//! Encryption of files and other large streams to a recipient's static key.
+//!
+//! A one-way handshake pattern, e.g., `N` or `X`, is used to derive a key,
+//! which then encrypts the data in chunks. The format is:
+//!
+//! ```text
+//! header: handshake message, whose payload is the chunk size (u32, BE)
+//! chunks: AEAD(flag || data), flag = 1 for the last chunk, 0 otherwise
+//! ```
+//!
+//! Every chunk except the last one contains exactly `chunk_size` bytes of
+//! data, and the last one contains at most `chunk_size` bytes, possibly
+//! zero. Chunks are encrypted with consecutive nonces, so they can not be
+//! reordered, and the flag lets the recipient detect truncation at a chunk
+//! boundary.
+//!
+//! [`FileEncryptor`] and [`FileDecryptor`] do no I/O and can be driven from
+//! async code. With `use_std`, [`encrypt`] and [`decrypt`] process a whole
+//! [`Read`](std::io::Read) into a [`Write`](std::io::Write).
+//!
+//! The chunks alone are a [`StreamEncryptor`] and a [`StreamDecryptor`],
+//! which can also be keyed by an interactive session, cf.
+//! [`TransportState::into_stream_encryptor`](crate::TransportState::into_stream_encryptor).
+//!
+//! Decrypted chunks are authenticated, but the stream as a whole is only
+//! known to be complete once [`FileDecryptor::finish`] succeeds. Do not act
+//! on the data before that.
+
+use crate::cipherstate::CipherState;
+use crate::error::Error;
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, DH};
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+/// Default chunk size, 64 KiB.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum chunk size accepted by [`FileDecryptor`], 16 MiB.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+const FLAG_MORE: u8 = 0;
+const FLAG_LAST: u8 = 1;
+
+fn check_one_way<D: DH, C: Cipher, H: Hash>(
+    hs: &HandshakeState<D, C, H>,
+    is_initiator: bool,
+) -> Result<(), Error> {
+    if !hs.get_pattern().is_one_way()
+        || hs.get_is_initiator() != is_initiator
+        || hs.completed()
+        || hs.is_write_turn() != is_initiator
+    {
+        return Err(Error::invalid_state());
+    }
+    Ok(())
+}
+
+/// Encrypts chunks of a stream with a cipher state.
+pub struct StreamEncryptor<C: Cipher> {
+    cipher: CipherState<C>,
+    chunk_size: usize,
+    finished: bool,
+}
+
+impl<C: Cipher> StreamEncryptor<C> {
+    /// Panics if `chunk_size` is zero or larger than `MAX_CHUNK_SIZE`.
+    pub(crate) fn new(cipher: CipherState<C>, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0 && chunk_size <= MAX_CHUNK_SIZE);
+        StreamEncryptor {
+            cipher,
+            chunk_size,
+            finished: false,
+        }
+    }
+
+    /// The chunk size.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Whether the last chunk has been encrypted.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Encrypt a chunk.
+    ///
+    /// # Panics
+    ///
+    /// * If the last chunk has already been encrypted.
+    ///
+    /// * If `data` is longer than the chunk size, or if it is not the last
+    ///   chunk and shorter than the chunk size.
+    pub fn encrypt_chunk(&mut self, data: &[u8], last: bool) -> Vec<u8> {
+        assert!(!self.finished, "last chunk already encrypted");
+        if last {
+            assert!(data.len() <= self.chunk_size);
+        } else {
+            assert_eq!(data.len(), self.chunk_size);
+        }
+
+        let mut plaintext = Vec::with_capacity(data.len() + 1);
+        plaintext.push(if last { FLAG_LAST } else { FLAG_MORE });
+        plaintext.extend_from_slice(data);
+        self.finished = last;
+        self.cipher.encrypt_vec(&plaintext)
+    }
+}
+
+/// Decrypts chunks of a stream encrypted with a [`StreamEncryptor`].
+///
+/// Decrypted chunks are authenticated, but the stream as a whole is only
+/// known to be complete once [`finish`](StreamDecryptor::finish) succeeds.
+pub struct StreamDecryptor<C: Cipher> {
+    cipher: CipherState<C>,
+    chunk_size: usize,
+    finished: bool,
+}
+
+impl<C: Cipher> StreamDecryptor<C> {
+    /// Panics if `chunk_size` is zero or larger than `MAX_CHUNK_SIZE`.
+    pub(crate) fn new(cipher: CipherState<C>, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0 && chunk_size <= MAX_CHUNK_SIZE);
+        StreamDecryptor {
+            cipher,
+            chunk_size,
+            finished: false,
+        }
+    }
+
+    /// The chunk size.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Length of an encrypted chunk. Only the last chunk may be shorter.
+    pub fn encrypted_chunk_len(&self) -> usize {
+        self.chunk_size + 1 + C::tag_len()
+    }
+
+    /// Whether the last chunk has been decrypted.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Decrypt a chunk.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [Decryption](crate::ErrorKind::Decryption): the chunk is corrupted
+    ///   or out of order.
+    /// - [TooShort](crate::ErrorKind::TooShort): the chunk is shorter than
+    ///   [`encrypted_chunk_len`](StreamDecryptor::encrypted_chunk_len) but
+    ///   not the last one, i.e., the stream is truncated.
+    /// - [InvalidState](crate::ErrorKind::InvalidState): the last chunk has
+    ///   already been decrypted.
+    pub fn decrypt_chunk(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.finished {
+            return Err(Error::invalid_state());
+        }
+        if data.len() > self.encrypted_chunk_len() || data.len() < 1 + C::tag_len() {
+            return Err(Error::decryption());
+        }
+
+        let mut plaintext = self
+            .cipher
+            .decrypt_vec(data)
+            .map_err(|_| Error::decryption())?;
+        match plaintext[0] {
+            FLAG_LAST => self.finished = true,
+            FLAG_MORE if data.len() == self.encrypted_chunk_len() => (),
+            FLAG_MORE => return Err(Error::too_short()),
+            _ => return Err(Error::decryption()),
+        }
+        plaintext.remove(0);
+        Ok(plaintext)
+    }
+
+    /// Check that the whole stream has been decrypted.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [TooShort](crate::ErrorKind::TooShort): the last chunk has not been
+    ///   decrypted, i.e., the stream is truncated.
+    pub fn finish(self) -> Result<(), Error> {
+        if self.finished {
+            Ok(())
+        } else {
+            Err(Error::too_short())
+        }
+    }
+}
+
+/// Encrypts a stream, chunk by chunk.
+pub struct FileEncryptor<C: Cipher> {
+    stream: StreamEncryptor<C>,
+}
+
+impl<C: Cipher> FileEncryptor<C> {
+    /// Start encrypting with a new initiator handshake state of a one-way
+    /// pattern.
+    ///
+    /// Returns the encryptor and the header, to be written before the chunks.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [InvalidState](crate::ErrorKind::InvalidState): `hs` is not a new
+    ///   initiator handshake state of a one-way pattern.
+    /// - Errors of [`write_message`](HandshakeState::write_message).
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is zero or larger than [`MAX_CHUNK_SIZE`].
+    pub fn new<D: DH, H: Hash>(
+        mut hs: HandshakeState<D, C, H>,
+        chunk_size: usize,
+    ) -> Result<(Self, Vec<u8>), Error> {
+        assert!(chunk_size > 0 && chunk_size <= MAX_CHUNK_SIZE);
+        check_one_way(&hs, true)?;
+
+        let header = hs.write_message_vec(&(chunk_size as u32).to_be_bytes())?;
+        let enc = FileEncryptor {
+            stream: StreamEncryptor::new(hs.get_ciphers().0, chunk_size),
+        };
+        Ok((enc, header))
+    }
+
+    /// The chunk size.
+    pub fn chunk_size(&self) -> usize {
+        self.stream.chunk_size()
+    }
+
+    /// Whether the last chunk has been encrypted.
+    pub fn is_finished(&self) -> bool {
+        self.stream.is_finished()
+    }
+
+    /// Encrypt a chunk, cf. [`StreamEncryptor::encrypt_chunk`].
+    ///
+    /// # Panics
+    ///
+    /// Like [`StreamEncryptor::encrypt_chunk`].
+    pub fn encrypt_chunk(&mut self, data: &[u8], last: bool) -> Vec<u8> {
+        self.stream.encrypt_chunk(data, last)
+    }
+}
+
+/// Decrypts a stream encrypted with [`FileEncryptor`], chunk by chunk.
+pub struct FileDecryptor<C: Cipher> {
+    stream: StreamDecryptor<C>,
+}
+
+impl<C: Cipher> FileDecryptor<C> {
+    /// Length of the header for a new responder handshake state.
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has already completed.
+    pub fn header_len<D: DH, H: Hash>(hs: &HandshakeState<D, C, H>) -> usize {
+        hs.get_next_message_overhead() + 4
+    }
+
+    /// Start decrypting with a new responder handshake state of a one-way
+    /// pattern and the header.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [InvalidState](crate::ErrorKind::InvalidState): `hs` is not a new
+    ///   responder handshake state of a one-way pattern, or the chunk size in
+    ///   the header is invalid.
+    /// - Errors of [`read_message`](HandshakeState::read_message).
+    pub fn new<D: DH, H: Hash>(
+        mut hs: HandshakeState<D, C, H>,
+        header: &[u8],
+    ) -> Result<Self, Error> {
+        check_one_way(&hs, false)?;
+
+        let payload = hs.read_message_vec(header)?;
+        let chunk_size: [u8; 4] = payload
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::invalid_state())?;
+        let chunk_size = u32::from_be_bytes(chunk_size) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+            return Err(Error::invalid_state());
+        }
+
+        Ok(FileDecryptor {
+            stream: StreamDecryptor::new(hs.get_ciphers().0, chunk_size),
+        })
+    }
+
+    /// The chunk size.
+    pub fn chunk_size(&self) -> usize {
+        self.stream.chunk_size()
+    }
+
+    /// Length of an encrypted chunk. Only the last chunk may be shorter.
+    pub fn encrypted_chunk_len(&self) -> usize {
+        self.stream.encrypted_chunk_len()
+    }
+
+    /// Whether the last chunk has been decrypted.
+    pub fn is_finished(&self) -> bool {
+        self.stream.is_finished()
+    }
+
+    /// Decrypt a chunk, cf. [`StreamDecryptor::decrypt_chunk`].
+    pub fn decrypt_chunk(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.stream.decrypt_chunk(data)
+    }
+
+    /// Check that the whole stream has been decrypted, cf.
+    /// [`StreamDecryptor::finish`].
+    pub fn finish(self) -> Result<(), Error> {
+        self.stream.finish()
+    }
+}
+
+#[cfg(feature = "use_std")]
+fn read_full<R: std::io::Read>(r: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(feature = "use_std")]
+fn invalid_data(e: Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
+/// Encrypt everything from `input` to `output`.
+///
+/// `hs` is a new initiator handshake state of a one-way pattern. Returns the
+/// number of bytes of plaintext.
+#[cfg(feature = "use_std")]
+pub fn encrypt<D, C, H, R, W>(
+    hs: HandshakeState<D, C, H>,
+    chunk_size: usize,
+    mut input: R,
+    mut output: W,
+) -> std::io::Result<u64>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+    R: std::io::Read,
+    W: std::io::Write,
+{
+    let (mut enc, header) = FileEncryptor::new(hs, chunk_size).map_err(invalid_data)?;
+    output.write_all(&header)?;
+
+    let mut buf = vec![0u8; chunk_size];
+    let mut total = 0;
+    loop {
+        let n = read_full(&mut input, &mut buf)?;
+        let last = n < chunk_size;
+        output.write_all(&enc.encrypt_chunk(&buf[..n], last))?;
+        total += n as u64;
+        if last {
+            break;
+        }
+    }
+    output.flush()?;
+    Ok(total)
+}
+
+/// Decrypt everything from `input` to `output`.
+///
+/// `hs` is a new responder handshake state of a one-way pattern. Returns the
+/// number of bytes of plaintext.
+///
+/// On error, `output` may already contain some authenticated but incomplete
+/// data, which should be discarded.
+#[cfg(feature = "use_std")]
+pub fn decrypt<D, C, H, R, W>(
+    hs: HandshakeState<D, C, H>,
+    mut input: R,
+    mut output: W,
+) -> std::io::Result<u64>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+    R: std::io::Read,
+    W: std::io::Write,
+{
+    let mut header = vec![0u8; FileDecryptor::header_len(&hs)];
+    if read_full(&mut input, &mut header)? < header.len() {
+        return Err(invalid_data(Error::too_short()));
+    }
+    let mut dec = FileDecryptor::new(hs, &header).map_err(invalid_data)?;
+
+    let mut buf = vec![0u8; dec.encrypted_chunk_len()];
+    let mut total = 0;
+    while !dec.is_finished() {
+        let n = read_full(&mut input, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let data = dec.decrypt_chunk(&buf[..n]).map_err(invalid_data)?;
+        output.write_all(&data)?;
+        total += data.len() as u64;
+    }
+    dec.finish().map_err(invalid_data)?;
+    if read_full(&mut input, &mut [0u8; 1])? != 0 {
+        return Err(invalid_data(Error::invalid_state()));
+    }
+    output.flush()?;
+    Ok(total)
+}
@@ -0,0 +1,194 @@
+//! Fingerprints of public keys, for comparing identities out-of-band.
+//!
+//! Two formats are supported:
+//!
+//! * [`ZBase32`]: the whole key in [z-base-32], e.g., for configuration files
+//!   or QR codes. Parse it back with [`parse_zbase32`].
+//! * [`ShortFingerprint`]: the first 16 bytes of the key in hex with a
+//!   checksum, e.g., `3a7f:09c2:…:e1d4-5b20`, for reading aloud or
+//!   comparing by eye.
+//!
+//! The same formats work for Elligator representatives, but the fingerprint of
+//! a representative differs from that of the corresponding public key, so
+//! always compare like with like.
+//!
+//! [z-base-32]: https://philzimmermann.com/docs/human-oriented-base-32-encoding.txt
+
+use crate::traits::U8Array;
+use core::fmt::{Display, Error as FmtError, Formatter, Write};
+use core::str::FromStr;
+
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// Formats a key in z-base-32, without padding.
+pub struct ZBase32<'a>(pub &'a [u8]);
+
+impl Display for ZBase32<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        let mut acc: u16 = 0;
+        let mut bits = 0;
+        for &b in self.0 {
+            acc = (acc << 8) | b as u16;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                fmt.write_char(ZBASE32_ALPHABET[(acc >> bits) as usize & 31] as char)?;
+            }
+        }
+        if bits > 0 {
+            fmt.write_char(ZBASE32_ALPHABET[(acc << (5 - bits)) as usize & 31] as char)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a key formatted with [`ZBase32`].
+///
+/// Upper case letters are accepted. Returns [`None`] if `s` is not the
+/// encoding of exactly `A::len()` bytes.
+pub fn parse_zbase32<A: U8Array>(s: &str) -> Option<A> {
+    if s.len() != (A::len() * 8 + 4) / 5 {
+        return None;
+    }
+
+    let mut out = A::new();
+    let mut acc: u16 = 0;
+    let mut bits = 0;
+    let mut i = 0;
+    for c in s.bytes() {
+        let c = c.to_ascii_lowercase();
+        let v = ZBASE32_ALPHABET.iter().position(|&a| a == c)? as u16;
+        acc = (acc << 5) | v;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.as_mut()[i] = (acc >> bits) as u8;
+            i += 1;
+        }
+    }
+    // Leftover bits must be zero padding.
+    if acc & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Number of key bytes in a [`ShortFingerprint`].
+pub const SHORT_FINGERPRINT_LEN: usize = 16;
+
+/// A truncated fingerprint of a key: its first 16 bytes, formatted as hex in
+/// groups of four digits, followed by a 16-bit checksum that catches typos.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ShortFingerprint([u8; SHORT_FINGERPRINT_LEN]);
+
+impl ShortFingerprint {
+    /// Fingerprint of a key.
+    ///
+    /// # Panics
+    ///
+    /// If `key` is shorter than 16 bytes.
+    pub fn of(key: &[u8]) -> Self {
+        let mut f = [0u8; SHORT_FINGERPRINT_LEN];
+        f.copy_from_slice(&key[..SHORT_FINGERPRINT_LEN]);
+        ShortFingerprint(f)
+    }
+
+    /// Whether this is the fingerprint of `key`.
+    pub fn matches(&self, key: &[u8]) -> bool {
+        key.len() >= SHORT_FINGERPRINT_LEN && key[..SHORT_FINGERPRINT_LEN] == self.0
+    }
+
+    /// As bytes.
+    pub fn as_bytes(&self) -> &[u8; SHORT_FINGERPRINT_LEN] {
+        &self.0
+    }
+
+    fn checksum(&self) -> u16 {
+        crc16(&self.0)
+    }
+}
+
+impl Display for ShortFingerprint {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        for (i, pair) in self.0.chunks(2).enumerate() {
+            if i > 0 {
+                fmt.write_char(':')?;
+            }
+            write!(fmt, "{:02x}{:02x}", pair[0], pair[1])?;
+        }
+        write!(fmt, "-{:04x}", self.checksum())
+    }
+}
+
+/// Error parsing a [`ShortFingerprint`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ParseFingerprintError {
+    /// The string is not in the right format.
+    Malformed,
+    /// The checksum does not match, i.e., there is a typo.
+    Checksum,
+}
+
+impl Display for ParseFingerprintError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        fmt.write_str(match self {
+            ParseFingerprintError::Malformed => "Malformed fingerprint",
+            ParseFingerprintError::Checksum => "Fingerprint checksum mismatch",
+        })
+    }
+}
+
+#[cfg(feature = "use_std")]
+impl ::std::error::Error for ParseFingerprintError {}
+
+impl FromStr for ShortFingerprint {
+    type Err = ParseFingerprintError;
+
+    /// Parse a fingerprint. Upper case hex digits are accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (groups, checksum) = s.split_once('-').ok_or(ParseFingerprintError::Malformed)?;
+
+        let mut f = [0u8; SHORT_FINGERPRINT_LEN];
+        let mut n = 0;
+        for group in groups.split(':') {
+            if n == SHORT_FINGERPRINT_LEN / 2 {
+                return Err(ParseFingerprintError::Malformed);
+            }
+            let v = parse_hex16(group)?;
+            f[2 * n..2 * n + 2].copy_from_slice(&v.to_be_bytes());
+            n += 1;
+        }
+        if n != SHORT_FINGERPRINT_LEN / 2 {
+            return Err(ParseFingerprintError::Malformed);
+        }
+
+        let f = ShortFingerprint(f);
+        if parse_hex16(checksum)? != f.checksum() {
+            return Err(ParseFingerprintError::Checksum);
+        }
+        Ok(f)
+    }
+}
+
+fn parse_hex16(s: &str) -> Result<u16, ParseFingerprintError> {
+    if s.len() != 4 || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseFingerprintError::Malformed);
+    }
+    u16::from_str_radix(s, 16).map_err(|_| ParseFingerprintError::Malformed)
+}
+
+/// CRC-16/CCITT-FALSE.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -1,7 +1,5 @@
 //! Handshake patterns.
 
-use arrayvec::ArrayVec;
-
 /// A token in noise message patterns.
 #[allow(missing_docs)]
 #[derive(Copy, Clone)]
@@ -13,16 +11,50 @@ pub enum Token {
     SE,
     SS,
     PSK,
+    E1,
+    EKEM1,
+    SIG,
 }
 
 use self::Token::*;
 
+/// Security properties of the payload of a handshake message.
+///
+/// These follow from the DH and PSK tokens up to and including the message,
+/// cf. section 7.7 of the noise spec, and hold only if the handshake
+/// eventually completes successfully.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct PayloadProtection {
+    /// The payload is encrypted.
+    pub encrypted: bool,
+    /// The payload is authenticated by the sender's static key.
+    ///
+    /// If only through `ss`, this is vulnerable to key-compromise
+    /// impersonation.
+    pub sender_authenticated: bool,
+    /// The payload can only be decrypted by the owner of the recipient's
+    /// static key.
+    pub recipient_authenticated: bool,
+    /// The payload is encrypted with the ephemeral-ephemeral DH, so it stays
+    /// secret if static keys are compromised later.
+    pub forward_secret: bool,
+}
+
 /// Noise handshake pattern.
-#[derive(Clone)]
+///
+/// Patterns only reference static token sequences, so they are cheap to copy
+/// and can be defined in const contexts:
+///
+/// ```
+/// use noise_protocol::patterns::{HandshakePattern, Token::*};
+///
+/// const NOISE_NN: HandshakePattern = HandshakePattern::new(&[], &[], &[&[E], &[E, EE]], "NN");
+/// ```
+#[derive(Copy, Clone)]
 pub struct HandshakePattern {
-    pre_i: ArrayVec<Token, 4>,
-    pre_r: ArrayVec<Token, 4>,
-    msg_patterns: ArrayVec<ArrayVec<Token, 8>, 8>,
+    pre_i: &'static [Token],
+    pre_r: &'static [Token],
+    msg_patterns: &'static [&'static [Token]],
     name: &'static str,
 }
 
@@ -32,42 +64,33 @@ impl HandshakePattern {
     /// # Pattern validity
     ///
     /// It is the caller's responlity to ensure that the pattern is *valid*.
-    ///
-    /// # Panics
-    ///
-    /// If any of the patterns are too long (longer than 8 tokens).
-    ///
-    /// Or if the number of patterns are too large (larger than 8).
-    pub fn new(
-        pre_i: &[Token],
-        pre_r: &[Token],
-        msg_patterns: &[&[Token]],
+    pub const fn new(
+        pre_i: &'static [Token],
+        pre_r: &'static [Token],
+        msg_patterns: &'static [&'static [Token]],
         name: &'static str,
     ) -> Self {
         HandshakePattern {
-            pre_i: pre_i.iter().cloned().collect(),
-            pre_r: pre_r.iter().cloned().collect(),
-            msg_patterns: msg_patterns
-                .iter()
-                .map(|p| p.iter().cloned().collect())
-                .collect(),
+            pre_i,
+            pre_r,
+            msg_patterns,
             name,
         }
     }
 
     /// Get initiator pre-messages.
-    pub fn get_pre_i(&self) -> &[Token] {
-        &self.pre_i
+    pub fn get_pre_i(&self) -> &'static [Token] {
+        self.pre_i
     }
 
     /// Get responder pre-messages.
-    pub fn get_pre_r(&self) -> &[Token] {
-        &self.pre_r
+    pub fn get_pre_r(&self) -> &'static [Token] {
+        self.pre_r
     }
 
     /// Get message patterns.
-    pub fn get_message_pattern(&self, i: usize) -> &[Token] {
-        &self.msg_patterns[i]
+    pub fn get_message_pattern(&self, i: usize) -> &'static [Token] {
+        self.msg_patterns[i]
     }
 
     /// Get number of message patterns.
@@ -90,374 +113,764 @@ impl HandshakePattern {
         })
     }
 
-    /// Whether the pattern is a one-way pattern.
-    pub fn is_one_way(&self) -> bool {
-        self.msg_patterns.len() == 1
-    }
-
-    fn with_psks(&self, poses: &[usize], new_name: &'static str) -> HandshakePattern {
-        let mut new_msg_patterns = self.msg_patterns.clone();
-        for pos in poses {
-            if *pos == 0usize {
-                new_msg_patterns[0].insert(0, PSK);
-            } else {
-                new_msg_patterns[pos - 1].push(PSK);
+    /// Get the security properties of the payload of message `i`.
+    ///
+    /// E.g., in `XX` the payload of message 1 is already encrypted, forward
+    /// secret and authenticated by the responder, so the responder can send
+    /// application data in it without waiting for the third message
+    /// (“half-RTT” data).
+    ///
+    /// # Panics
+    ///
+    /// If `i` is not less than [`get_message_patterns_len`](HandshakePattern::get_message_patterns_len).
+    pub fn payload_protection(&self, i: usize) -> PayloadProtection {
+        let (mut ee, mut es, mut se, mut ss, mut psk) = (false, false, false, false, false);
+        // Whether the sender of message `i` has signed.
+        let mut signed = false;
+        for (j, m) in self.msg_patterns[..=i].iter().enumerate() {
+            for t in m.iter() {
+                match t {
+                    EE | EKEM1 => ee = true,
+                    ES => es = true,
+                    SE => se = true,
+                    SS => ss = true,
+                    PSK => psk = true,
+                    SIG => signed |= j % 2 == i % 2,
+                    E | S | E1 => {}
+                }
             }
         }
-        HandshakePattern {
-            pre_i: self.pre_i.clone(),
-            pre_r: self.pre_r.clone(),
-            msg_patterns: new_msg_patterns,
-            name: new_name,
+
+        // `es` authenticates the responder and `se` the initiator.
+        let (sender, recipient) = if i % 2 == 1 { (es, se) } else { (se, es) };
+        PayloadProtection {
+            encrypted: ee || es || se || ss || psk,
+            sender_authenticated: sender || ss || signed,
+            recipient_authenticated: recipient || ss,
+            forward_secret: ee,
         }
     }
-}
 
-macro_rules! vec {
-    () => {
-        ArrayVec::new()
-    };
-    ( $( $x:expr ),* ) => {
-        {
-            let mut temp_vec = ArrayVec::new();
-            $(
-                temp_vec.push($x);
-            )*
-            temp_vec
-        }
-    };
+    /// Whether the pattern is a one-way pattern.
+    pub fn is_one_way(&self) -> bool {
+        self.msg_patterns.len() == 1
+    }
 }
 
 /// The `Noise_N` pattern.
-pub fn noise_n() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES]],
-        name: "N",
-    }
+pub const fn noise_n() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES]], "N")
 }
 
 /// The `Noise_K` pattern.
-pub fn noise_k() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![S],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES, SS]],
-        name: "K",
-    }
+pub const fn noise_k() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E, ES, SS]], "K")
 }
 
 /// The `Noise_X` pattern.
-pub fn noise_x() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES, S, SS]],
-        name: "X",
-    }
+pub const fn noise_x() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S, SS]], "X")
 }
 
 /// The `Noise_NN` pattern.
-pub fn noise_nn() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE]],
-        name: "NN",
-    }
+pub const fn noise_nn() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE]], "NN")
 }
 
 /// The `Noise_NK` pattern.
-pub fn noise_nk() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES], vec![E, EE]],
-        name: "NK",
-    }
+pub const fn noise_nk() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES], &[E, EE]], "NK")
 }
 
 /// The `Noise_NX` pattern.
-pub fn noise_nx() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE, S, ES]],
-        name: "NX",
-    }
+pub const fn noise_nx() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S, ES]], "NX")
 }
 
 /// The `Noise_XN` pattern.
-pub fn noise_xn() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE], vec![S, SE]],
-        name: "XN",
-    }
+pub const fn noise_xn() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE], &[S, SE]], "XN")
 }
 
 /// The `Noise_XK` pattern.
-pub fn noise_xk() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES], vec![E, EE], vec![S, SE]],
-        name: "XK",
-    }
+pub const fn noise_xk() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES], &[E, EE], &[S, SE]], "XK")
 }
 
 /// The `Noise_XX` pattern.
-pub fn noise_xx() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE, S, ES], vec![S, SE]],
-        name: "XX",
-    }
+pub const fn noise_xx() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S, ES], &[S, SE]], "XX")
 }
 
 /// The `Noise_KN` pattern.
-pub fn noise_kn() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![S],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE, SE]],
-        name: "KN",
-    }
+pub const fn noise_kn() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, SE]], "KN")
 }
 
 /// The `Noise_KK` pattern.
-pub fn noise_kk() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![S],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES, SS], vec![E, EE, SE]],
-        name: "KK",
-    }
+pub const fn noise_kk() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E, ES, SS], &[E, EE, SE]], "KK")
 }
 
 /// The `Noise_KX` pattern.
-pub fn noise_kx() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![S],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E], vec![E, EE, SE, S, ES]],
-        name: "KX",
-    }
+pub const fn noise_kx() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, SE, S, ES]], "KX")
 }
 
 /// The `Noise_IN` pattern.
-pub fn noise_in() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E, S], vec![E, EE, SE]],
-        name: "IN",
-    }
+pub const fn noise_in() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, SE]], "IN")
 }
 
 /// The `Noise_IK` pattern.
-pub fn noise_ik() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![S],
-        msg_patterns: vec![vec![E, ES, S, SS], vec![E, EE, SE]],
-        name: "IK",
-    }
+pub const fn noise_ik() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S, SS], &[E, EE, SE]], "IK")
 }
 
 /// The `Noise_IX` pattern.
-pub fn noise_ix() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![],
-        msg_patterns: vec![vec![E, S], vec![E, EE, SE, S, ES]],
-        name: "IX",
-    }
+pub const fn noise_ix() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, SE, S, ES]], "IX")
 }
 
 /// The `Noise_XXfallback` pattern.
 ///
 /// Something that is used in noise pipes.
-pub fn noise_xx_fallback() -> HandshakePattern {
-    HandshakePattern {
-        pre_i: vec![],
-        pre_r: vec![E],
-        msg_patterns: vec![vec![E, EE, S, SE], vec![S, ES]],
-        name: "XXfallback",
-    }
+pub const fn noise_xx_fallback() -> HandshakePattern {
+    HandshakePattern::new(&[], &[E], &[&[E, EE, S, SE], &[S, ES]], "XXfallback")
+}
+
+// Deferred patterns.
+
+/// The `Noise_NK1` pattern.
+pub const fn noise_nk1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E], &[E, EE, ES]], "NK1")
+}
+
+/// The `Noise_NX1` pattern.
+pub const fn noise_nx1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S], &[ES]], "NX1")
+}
+
+/// The `Noise_X1N` pattern.
+pub const fn noise_x1n() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE], &[S], &[SE]], "X1N")
+}
+
+/// The `Noise_X1K` pattern.
+pub const fn noise_x1k() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES], &[E, EE], &[S], &[SE]], "X1K")
+}
+
+/// The `Noise_XK1` pattern.
+pub const fn noise_xk1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E], &[E, EE, ES], &[S, SE]], "XK1")
+}
+
+/// The `Noise_X1K1` pattern.
+pub const fn noise_x1k1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E], &[E, EE, ES], &[S], &[SE]], "X1K1")
+}
+
+/// The `Noise_X1X` pattern.
+pub const fn noise_x1x() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S, ES], &[S], &[SE]], "X1X")
+}
+
+/// The `Noise_XX1` pattern.
+pub const fn noise_xx1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S], &[ES, S, SE]], "XX1")
+}
+
+/// The `Noise_X1X1` pattern.
+pub const fn noise_x1x1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S], &[ES, S], &[SE]], "X1X1")
+}
+
+/// The `Noise_K1N` pattern.
+pub const fn noise_k1n() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE], &[SE]], "K1N")
+}
+
+/// The `Noise_K1K` pattern.
+pub const fn noise_k1k() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E, ES], &[E, EE], &[SE]], "K1K")
+}
+
+/// The `Noise_KK1` pattern.
+pub const fn noise_kk1() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E], &[E, EE, SE, ES]], "KK1")
+}
+
+/// The `Noise_K1K1` pattern.
+pub const fn noise_k1k1() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E], &[E, EE, ES], &[SE]], "K1K1")
+}
+
+/// The `Noise_K1X` pattern.
+pub const fn noise_k1x() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, S, ES], &[SE]], "K1X")
+}
+
+/// The `Noise_KX1` pattern.
+pub const fn noise_kx1() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, SE, S], &[ES]], "KX1")
+}
+
+/// The `Noise_K1X1` pattern.
+pub const fn noise_k1x1() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, S], &[SE, ES]], "K1X1")
+}
+
+/// The `Noise_I1N` pattern.
+pub const fn noise_i1n() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE], &[SE]], "I1N")
+}
+
+/// The `Noise_I1K` pattern.
+pub const fn noise_i1k() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S], &[E, EE], &[SE]], "I1K")
+}
+
+/// The `Noise_IK1` pattern.
+pub const fn noise_ik1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, S], &[E, EE, SE, ES]], "IK1")
+}
+
+/// The `Noise_I1K1` pattern.
+pub const fn noise_i1k1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, S], &[E, EE, ES], &[SE]], "I1K1")
+}
+
+/// The `Noise_I1X` pattern.
+pub const fn noise_i1x() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, S, ES], &[SE]], "I1X")
+}
+
+/// The `Noise_IX1` pattern.
+pub const fn noise_ix1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, SE, S], &[ES]], "IX1")
+}
+
+/// The `Noise_I1X1` pattern.
+pub const fn noise_i1x1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, S], &[SE, ES]], "I1X1")
+}
+
+// Hybrid forward secrecy patterns, cf. [`Kem`](crate::Kem).
+//
+// These need a KEM, so they are not among the predefined patterns.
+
+/// The `Noise_NNhfs` pattern.
+pub const fn noise_nn_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, E1], &[E, EE, EKEM1]], "NNhfs")
+}
+
+/// The `Noise_NKhfs` pattern.
+pub const fn noise_nk_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, E1], &[E, EE, EKEM1]], "NKhfs")
+}
+
+/// The `Noise_NXhfs` pattern.
+pub const fn noise_nx_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, E1], &[E, EE, EKEM1, S, ES]], "NXhfs")
+}
+
+/// The `Noise_XNhfs` pattern.
+pub const fn noise_xn_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, E1], &[E, EE, EKEM1], &[S, SE]], "XNhfs")
+}
+
+/// The `Noise_XKhfs` pattern.
+pub const fn noise_xk_hfs() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[S],
+        &[&[E, ES, E1], &[E, EE, EKEM1], &[S, SE]],
+        "XKhfs",
+    )
+}
+
+/// The `Noise_XXhfs` pattern.
+pub const fn noise_xx_hfs() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[E, E1], &[E, EE, EKEM1, S, ES], &[S, SE]],
+        "XXhfs",
+    )
+}
+
+/// The `Noise_KNhfs` pattern.
+pub const fn noise_kn_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E, E1], &[E, EE, EKEM1, SE]], "KNhfs")
+}
+
+/// The `Noise_KKhfs` pattern.
+pub const fn noise_kk_hfs() -> HandshakePattern {
+    HandshakePattern::new(
+        &[S],
+        &[S],
+        &[&[E, ES, SS, E1], &[E, EE, EKEM1, SE]],
+        "KKhfs",
+    )
+}
+
+/// The `Noise_KXhfs` pattern.
+pub const fn noise_kx_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E, E1], &[E, EE, EKEM1, SE, S, ES]], "KXhfs")
+}
+
+/// The `Noise_INhfs` pattern.
+pub const fn noise_in_hfs() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, E1, S], &[E, EE, EKEM1, SE]], "INhfs")
+}
+
+/// The `Noise_IKhfs` pattern.
+pub const fn noise_ik_hfs() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[S],
+        &[&[E, ES, E1, S, SS], &[E, EE, EKEM1, SE]],
+        "IKhfs",
+    )
+}
+
+/// The `Noise_IXhfs` pattern.
+pub const fn noise_ix_hfs() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[E, E1, S], &[E, EE, EKEM1, SE, S, ES]],
+        "IXhfs",
+    )
+}
+
+// Signature patterns, where the `sig` token authenticates the sender with a
+// signature of the handshake hash instead of a static DH. They need a
+// signature scheme, so they are not among the predefined patterns either.
+
+/// The `Noise_NXsig` pattern.
+pub const fn noise_nx_sig() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, SIG]], "NXsig")
+}
+
+/// The `Noise_XNsig` pattern.
+pub const fn noise_xn_sig() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE], &[SIG]], "XNsig")
+}
+
+/// The `Noise_XXsig` pattern.
+pub const fn noise_xx_sig() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, SIG], &[SIG]], "XXsig")
 }
 
 // PSK Patterns.
 
 /// The `Noise_Npsk0` pattern.
-pub fn noise_n_psk0() -> HandshakePattern {
-    noise_n().with_psks(&[0], "Npsk0")
+pub const fn noise_n_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[PSK, E, ES]], "Npsk0")
 }
 
 /// The `Noise_Kpsk0` pattern.
-pub fn noise_k_psk0() -> HandshakePattern {
-    noise_k().with_psks(&[0], "Kpsk0")
+pub const fn noise_k_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[PSK, E, ES, SS]], "Kpsk0")
 }
 
 /// The `Noise_Xpsk1` pattern.
-pub fn noise_x_psk1() -> HandshakePattern {
-    noise_x().with_psks(&[1], "Xpsk1")
+pub const fn noise_x_psk1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S, SS, PSK]], "Xpsk1")
 }
 
 /// The `Noise_NNpsk0` pattern.
-pub fn noise_nn_psk0() -> HandshakePattern {
-    noise_nn().with_psks(&[0], "NNpsk0")
+pub const fn noise_nn_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[PSK, E], &[E, EE]], "NNpsk0")
 }
 
 /// The `Noise_NNpsk2` pattern.
-pub fn noise_nn_psk2() -> HandshakePattern {
-    noise_nn().with_psks(&[2], "NNpsk2")
+pub const fn noise_nn_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, PSK]], "NNpsk2")
 }
 
 /// The `Noise_NKpsk0` pattern.
-pub fn noise_nk_psk0() -> HandshakePattern {
-    noise_nk().with_psks(&[0], "NKpsk0")
+pub const fn noise_nk_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[PSK, E, ES], &[E, EE]], "NKpsk0")
 }
 
 /// The `Noise_NKpsk2` pattern.
-pub fn noise_nk_psk2() -> HandshakePattern {
-    noise_nk().with_psks(&[2], "NKpsk2")
+pub const fn noise_nk_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES], &[E, EE, PSK]], "NKpsk2")
 }
 
 /// The `Noise_NXpsk2` pattern.
-pub fn noise_nx_psk2() -> HandshakePattern {
-    noise_nx().with_psks(&[2], "NXpsk2")
+pub const fn noise_nx_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S, ES, PSK]], "NXpsk2")
 }
 
 /// The `Noise_XNpsk3` pattern.
-pub fn noise_xn_psk3() -> HandshakePattern {
-    noise_xn().with_psks(&[3], "XNpsk3")
+pub const fn noise_xn_psk3() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE], &[S, SE, PSK]], "XNpsk3")
 }
 
 /// The `Noise_XKpsk3` pattern.
-pub fn noise_xk_psk3() -> HandshakePattern {
-    noise_xk().with_psks(&[3], "XKpsk3")
+pub const fn noise_xk_psk3() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES], &[E, EE], &[S, SE, PSK]], "XKpsk3")
 }
 
 /// The `Noise_XXpsk3` pattern.
-pub fn noise_xx_psk3() -> HandshakePattern {
-    noise_xx().with_psks(&[3], "XXpsk3")
+pub const fn noise_xx_psk3() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E], &[E, EE, S, ES], &[S, SE, PSK]], "XXpsk3")
 }
 
 /// The `Noise_KNpsk0` pattern.
-pub fn noise_kn_psk0() -> HandshakePattern {
-    noise_kn().with_psks(&[0], "KNpsk0")
+pub const fn noise_kn_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[PSK, E], &[E, EE, SE]], "KNpsk0")
 }
 
 /// The `Noise_KNpsk2` pattern.
-pub fn noise_kn_psk2() -> HandshakePattern {
-    noise_kn().with_psks(&[2], "KNpsk2")
+pub const fn noise_kn_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, SE, PSK]], "KNpsk2")
 }
 
 /// The `Noise_KKpsk0` pattern.
-pub fn noise_kk_psk0() -> HandshakePattern {
-    noise_kk().with_psks(&[0], "KKpsk0")
+pub const fn noise_kk_psk0() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[PSK, E, ES, SS], &[E, EE, SE]], "KKpsk0")
 }
 
 /// The `Noise_KKpsk2` pattern.
-pub fn noise_kk_psk2() -> HandshakePattern {
-    noise_kk().with_psks(&[2], "KKpsk2")
+pub const fn noise_kk_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[S], &[&[E, ES, SS], &[E, EE, SE, PSK]], "KKpsk2")
 }
 
 /// The `Noise_KXpsk2` pattern.
-pub fn noise_kx_psk2() -> HandshakePattern {
-    noise_kx().with_psks(&[2], "KXpsk2")
+pub const fn noise_kx_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E], &[E, EE, SE, S, ES, PSK]], "KXpsk2")
 }
 
 /// The `Noise_INpsk1` pattern.
-pub fn noise_in_psk1() -> HandshakePattern {
-    noise_in().with_psks(&[1], "INpsk1")
+pub const fn noise_in_psk1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S, PSK], &[E, EE, SE]], "INpsk1")
 }
 
 /// The `Noise_INpsk2` pattern.
-pub fn noise_in_psk2() -> HandshakePattern {
-    noise_in().with_psks(&[2], "INpsk2")
+pub const fn noise_in_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, SE, PSK]], "INpsk2")
 }
 
 /// The `Noise_IKpsk1` pattern.
-pub fn noise_ik_psk1() -> HandshakePattern {
-    noise_ik().with_psks(&[1], "IKpsk1")
+pub const fn noise_ik_psk1() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S, SS, PSK], &[E, EE, SE]], "IKpsk1")
 }
 
 /// The `Noise_IKpsk2` pattern.
-pub fn noise_ik_psk2() -> HandshakePattern {
-    noise_ik().with_psks(&[2], "IKpsk2")
+pub const fn noise_ik_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[S], &[&[E, ES, S, SS], &[E, EE, SE, PSK]], "IKpsk2")
 }
 
 /// The `Noise_IXpsk2` pattern.
-pub fn noise_ix_psk2() -> HandshakePattern {
-    noise_ix().with_psks(&[2], "IXpsk2")
+pub const fn noise_ix_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S], &[E, EE, SE, S, ES, PSK]], "IXpsk2")
 }
 
 /// The `Noise_NNpsk0+psk2` pattern.
-pub fn noise_nn_psk0_psk2() -> HandshakePattern {
-    noise_nn().with_psks(&[0, 2], "NNpsk0+psk2")
+pub const fn noise_nn_psk0_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[PSK, E], &[E, EE, PSK]], "NNpsk0+psk2")
 }
 
 /// The `Noise_NXpsk0+psk1+psk2` pattern.
-pub fn noise_nx_psk0_psk1_psk2() -> HandshakePattern {
-    noise_nx().with_psks(&[0, 1, 2], "NXpsk0+psk1+psk2")
+pub const fn noise_nx_psk0_psk1_psk2() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E, PSK], &[E, EE, S, ES, PSK]],
+        "NXpsk0+psk1+psk2",
+    )
 }
 
 /// The `Noise_XNpsk1+psk3` pattern.
-pub fn noise_xn_psk1_psk3() -> HandshakePattern {
-    noise_xn().with_psks(&[1, 3], "XNpsk1+psk3")
+pub const fn noise_xn_psk1_psk3() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[E, PSK], &[E, EE], &[S, SE, PSK]],
+        "XNpsk1+psk3",
+    )
 }
 
 /// The `Noise_XKpsk0+psk3` pattern.
-pub fn noise_xk_psk0_psk3() -> HandshakePattern {
-    noise_xk().with_psks(&[0, 3], "XKpsk0+psk3")
+pub const fn noise_xk_psk0_psk3() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[S],
+        &[&[PSK, E, ES], &[E, EE], &[S, SE, PSK]],
+        "XKpsk0+psk3",
+    )
 }
 
 /// The `Noise_KNpsk1+psk2` pattern.
-pub fn noise_kn_psk1_psk2() -> HandshakePattern {
-    noise_kn().with_psks(&[1, 2], "KNpsk1+psk2")
+pub const fn noise_kn_psk1_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[S], &[], &[&[E, PSK], &[E, EE, SE, PSK]], "KNpsk1+psk2")
 }
 
 /// The `Noise_KKpsk0+psk2` pattern
-pub fn noise_kk_psk0_psk2() -> HandshakePattern {
-    noise_kk().with_psks(&[0, 2], "KKpsk0+psk2")
+pub const fn noise_kk_psk0_psk2() -> HandshakePattern {
+    HandshakePattern::new(
+        &[S],
+        &[S],
+        &[&[PSK, E, ES, SS], &[E, EE, SE, PSK]],
+        "KKpsk0+psk2",
+    )
 }
 
 /// The `Noise_INpsk1+psk2` pattern.
-pub fn noise_in_psk1_psk2() -> HandshakePattern {
-    noise_in().with_psks(&[1, 2], "INpsk1+psk2")
+pub const fn noise_in_psk1_psk2() -> HandshakePattern {
+    HandshakePattern::new(&[], &[], &[&[E, S, PSK], &[E, EE, SE, PSK]], "INpsk1+psk2")
 }
 
 /// The `Noise_IKpsk0+psk2` pattern.
-pub fn noise_ik_psk0_psk2() -> HandshakePattern {
-    noise_ik().with_psks(&[0, 2], "IKpsk0+psk2")
+pub const fn noise_ik_psk0_psk2() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[S],
+        &[&[PSK, E, ES, S, SS], &[E, EE, SE, PSK]],
+        "IKpsk0+psk2",
+    )
 }
 
 /// The `Noise_IXpsk0+psk2` pattern.
-pub fn noise_ix_psk0_psk2() -> HandshakePattern {
-    noise_ix().with_psks(&[0, 2], "IXpsk0+psk2")
+pub const fn noise_ix_psk0_psk2() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E, S], &[E, EE, SE, S, ES, PSK]],
+        "IXpsk0+psk2",
+    )
 }
 
 /// The `Noise_XXpsk0+psk1` pattern.
-pub fn noise_xx_psk0_psk1() -> HandshakePattern {
-    noise_xx().with_psks(&[0, 1], "XXpsk0+psk1")
+pub const fn noise_xx_psk0_psk1() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E, PSK], &[E, EE, S, ES], &[S, SE]],
+        "XXpsk0+psk1",
+    )
 }
 
 /// The `Noise_XXpsk0+psk2` pattern.
-pub fn noise_xx_psk0_psk2() -> HandshakePattern {
-    noise_xx().with_psks(&[0, 2], "XXpsk0+psk2")
+pub const fn noise_xx_psk0_psk2() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E], &[E, EE, S, ES, PSK], &[S, SE]],
+        "XXpsk0+psk2",
+    )
 }
 
 /// The `Noise_XXpsk0+psk3` pattern.
-pub fn noise_xx_psk0_psk3() -> HandshakePattern {
-    noise_xx().with_psks(&[0, 3], "XXpsk0+psk3")
+pub const fn noise_xx_psk0_psk3() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E], &[E, EE, S, ES], &[S, SE, PSK]],
+        "XXpsk0+psk3",
+    )
 }
 
 /// The `Noise_XXpsk0+psk1+psk2+psk3` pattern.
-pub fn noise_xx_psk0_psk1_psk2_psk3() -> HandshakePattern {
-    noise_xx().with_psks(&[0, 1, 2, 3], "XXpsk0+psk1+psk2+psk3")
+pub const fn noise_xx_psk0_psk1_psk2_psk3() -> HandshakePattern {
+    HandshakePattern::new(
+        &[],
+        &[],
+        &[&[PSK, E, PSK], &[E, EE, S, ES, PSK], &[S, SE, PSK]],
+        "XXpsk0+psk1+psk2+psk3",
+    )
+}
+
+/// All predefined patterns in this module, except the hybrid ones.
+const PREDEFINED: &[HandshakePattern] = &[
+    noise_n(),
+    noise_k(),
+    noise_x(),
+    noise_nn(),
+    noise_nk(),
+    noise_nx(),
+    noise_xn(),
+    noise_xk(),
+    noise_xx(),
+    noise_kn(),
+    noise_kk(),
+    noise_kx(),
+    noise_in(),
+    noise_ik(),
+    noise_ix(),
+    noise_xx_fallback(),
+    noise_nk1(),
+    noise_nx1(),
+    noise_x1n(),
+    noise_x1k(),
+    noise_xk1(),
+    noise_x1k1(),
+    noise_x1x(),
+    noise_xx1(),
+    noise_x1x1(),
+    noise_k1n(),
+    noise_k1k(),
+    noise_kk1(),
+    noise_k1k1(),
+    noise_k1x(),
+    noise_kx1(),
+    noise_k1x1(),
+    noise_i1n(),
+    noise_i1k(),
+    noise_ik1(),
+    noise_i1k1(),
+    noise_i1x(),
+    noise_ix1(),
+    noise_i1x1(),
+    noise_n_psk0(),
+    noise_k_psk0(),
+    noise_x_psk1(),
+    noise_nn_psk0(),
+    noise_nn_psk2(),
+    noise_nk_psk0(),
+    noise_nk_psk2(),
+    noise_nx_psk2(),
+    noise_xn_psk3(),
+    noise_xk_psk3(),
+    noise_xx_psk3(),
+    noise_kn_psk0(),
+    noise_kn_psk2(),
+    noise_kk_psk0(),
+    noise_kk_psk2(),
+    noise_kx_psk2(),
+    noise_in_psk1(),
+    noise_in_psk2(),
+    noise_ik_psk1(),
+    noise_ik_psk2(),
+    noise_ix_psk2(),
+    noise_nn_psk0_psk2(),
+    noise_nx_psk0_psk1_psk2(),
+    noise_xn_psk1_psk3(),
+    noise_xk_psk0_psk3(),
+    noise_kn_psk1_psk2(),
+    noise_kk_psk0_psk2(),
+    noise_in_psk1_psk2(),
+    noise_ik_psk0_psk2(),
+    noise_ix_psk0_psk2(),
+    noise_xx_psk0_psk1(),
+    noise_xx_psk0_psk2(),
+    noise_xx_psk0_psk3(),
+    noise_xx_psk0_psk1_psk2_psk3(),
+];
+
+/// Iterate over all predefined patterns.
+pub fn predefined() -> impl Iterator<Item = HandshakePattern> {
+    PREDEFINED.iter().copied()
+}
+
+const HYBRID: &[fn() -> HandshakePattern] = &[
+    noise_nn_hfs,
+    noise_nk_hfs,
+    noise_nx_hfs,
+    noise_xn_hfs,
+    noise_xk_hfs,
+    noise_xx_hfs,
+    noise_kn_hfs,
+    noise_kk_hfs,
+    noise_kx_hfs,
+    noise_in_hfs,
+    noise_ik_hfs,
+    noise_ix_hfs,
+];
+
+/// Iterate over all hybrid forward secrecy patterns, e.g., “XXhfs”.
+pub fn hybrid() -> impl Iterator<Item = HandshakePattern> {
+    HYBRID.iter().map(|p| p())
+}
+
+const SIGNED: &[fn() -> HandshakePattern] = &[noise_nx_sig, noise_xn_sig, noise_xx_sig];
+
+/// Iterate over all signature patterns, e.g., “XXsig”.
+pub fn signed() -> impl Iterator<Item = HandshakePattern> {
+    SIGNED.iter().map(|p| p())
+}
+
+/// Get a predefined pattern by its name, e.g., “XXpsk3”.
+pub fn by_name(name: &str) -> Option<HandshakePattern> {
+    predefined().find(|p| p.get_name() == name)
+}
+
+/// Like [`by_name`], but usable in constants, e.g., to fail compilation on a
+/// typo in a pattern name.
+pub const fn by_name_const(name: &str) -> Option<HandshakePattern> {
+    let mut i = 0;
+    while i < PREDEFINED.len() {
+        if str_eq(PREDEFINED[i].name, name) {
+            return Some(PREDEFINED[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Token {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[E, S, EE, ES, SE, SS, PSK, E1, EKEM1, SIG])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+/// Picks one of the [predefined](predefined), [hybrid](hybrid) or
+/// [signature](signed) patterns.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for HandshakePattern {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let i = u.int_in_range(0..=PREDEFINED.len() + HYBRID.len() + SIGNED.len() - 1)?;
+        Ok(if i < PREDEFINED.len() {
+            PREDEFINED[i]
+        } else if i < PREDEFINED.len() + HYBRID.len() {
+            HYBRID[i - PREDEFINED.len()]()
+        } else {
+            SIGNED[i - PREDEFINED.len() - HYBRID.len()]()
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(4))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PayloadProtection {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(PayloadProtection {
+            encrypted: u.arbitrary()?,
+            sender_authenticated: u.arbitrary()?,
+            recipient_authenticated: u.arbitrary()?,
+            forward_secret: u.arbitrary()?,
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (4, Some(4))
+    }
 }
@@ -1,69 +1,242 @@
 use crate::cipherstate::CipherState;
-use crate::handshakepattern::{HandshakePattern, Token};
-use crate::symmetricstate::SymmetricState;
-use crate::traits::{Cipher, Hash, U8Array, DH, DhKeyPair};
+#[cfg(feature = "use_std")]
+use crate::dhcache::StaticDhCache;
+#[cfg(feature = "use_std")]
+use crate::ephemeral::{EphemeralObserver, EphemeralOrigin, EphemeralPool};
+use crate::error::Error;
+use crate::handshakepattern::{HandshakePattern, PayloadProtection, Token};
+use crate::instrument::{self, Instant};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::obfuscation::ObfuscationConfig;
+#[cfg(feature = "use_std")]
+use crate::opaquedh::OpaqueDh;
+#[cfg(feature = "use_std")]
+use crate::psk::PskProvider;
+#[cfg(feature = "use_std")]
+use crate::remotestatic::RemoteStaticVerifier;
+#[cfg(feature = "use_std")]
+use crate::replay::ReplayCache;
+use crate::sessionid::SessionId;
+#[cfg(feature = "use_std")]
+use crate::sharedkey::{PrecomputedStatic, SharedStaticKey};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::stateio::{put_array, put_option, Reader};
+use crate::symmetricstate::{expand, SymmetricState};
+use crate::traits::{Cipher, DhKeyPair, Hash, Kem, NoKem, NoSignature, Signature, U8Array, DH};
+use crate::transportstate::{TransportState, MAX_MESSAGE_LEN};
+use crate::util::ct_eq_pubkey;
 use arrayvec::{ArrayString, ArrayVec};
-use core::fmt::{Display, Error as FmtError, Formatter, Write};
+use core::fmt::Write;
+use rand_core::{CryptoRng, RngCore};
+use zeroize::Zeroizing;
 
 #[cfg(feature = "use_alloc")]
 use alloc::vec::Vec;
+#[cfg(feature = "use_std")]
+use std::sync::Arc;
+
+/// Our static key pair, either owned by one handshake state, shared, or
+/// held outside of memory.
+enum StaticKey<D: DH> {
+    Owned(DhKeyPair<D::Key, D::Pubkey>),
+    #[cfg(feature = "use_std")]
+    Shared(SharedStaticKey<D>),
+    #[cfg(feature = "use_std")]
+    Opaque(Arc<dyn OpaqueDh<D>>, D::Pubkey),
+}
+
+impl<D: DH> Clone for StaticKey<D> {
+    fn clone(&self) -> Self {
+        match self {
+            StaticKey::Owned(k) => StaticKey::Owned(k.clone()),
+            #[cfg(feature = "use_std")]
+            StaticKey::Shared(k) => StaticKey::Shared(k.clone()),
+            #[cfg(feature = "use_std")]
+            StaticKey::Opaque(k, public) => StaticKey::Opaque(k.clone(), public.clone()),
+        }
+    }
+}
+
+impl<D: DH> StaticKey<D> {
+    fn public(&self) -> &D::Pubkey {
+        match self {
+            StaticKey::Owned(k) => &k.public,
+            #[cfg(feature = "use_std")]
+            StaticKey::Shared(k) => k.public(),
+            #[cfg(feature = "use_std")]
+            StaticKey::Opaque(_, public) => public,
+        }
+    }
+
+    fn dh(&self, remote: &D::Pubkey, is_elligator_encoded: bool) -> Result<D::Output, Error> {
+        match self {
+            StaticKey::Owned(k) => D::dh(&k.private, remote, is_elligator_encoded),
+            #[cfg(feature = "use_std")]
+            StaticKey::Shared(k) => D::dh(&k.keypair().private, remote, is_elligator_encoded),
+            #[cfg(feature = "use_std")]
+            StaticKey::Opaque(k, _) => k.dh(remote, is_elligator_encoded),
+        }
+    }
+}
 
 /// Noise handshake state.
-pub struct HandshakeState<D: DH, C: Cipher, H: Hash> {
+///
+/// `K` is the KEM of the `e1` and `ekem1` tokens of hybrid patterns, e.g.,
+/// [`noise_xx_hfs`](crate::patterns::noise_xx_hfs), and [`NoKem`] for other
+/// patterns.
+///
+/// `S` is the signature scheme of the `sig` token of signature patterns,
+/// e.g., [`noise_xx_sig`](crate::patterns::noise_xx_sig), and
+/// [`NoSignature`] for other patterns.
+pub struct HandshakeState<D: DH, C: Cipher, H: Hash, K: Kem = NoKem, S: Signature = NoSignature> {
     symmetric: SymmetricState<C, H>,
-    s: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    s: Option<StaticKey<D>>,
     e: Option<DhKeyPair<D::Key, D::Pubkey>>,
     rs: Option<D::Pubkey>,
     re: Option<D::Pubkey>,
+    e1: Option<(K::Key, K::Pubkey)>,
+    re1: Option<K::Pubkey>,
+    sig_key: Option<S::Key>,
+    rsig: Option<S::Pubkey>,
     is_initiator: bool,
     is_elligator_encoded: bool,
+    is_static_elligator_encoded: bool,
+    // Whether all-zero DH outputs, i.e., from low order public keys, are
+    // accepted.
+    allow_low_order: bool,
+    // Whether the public keys of the peer are checked with
+    // `DH::check_pubkey`.
+    strict_pubkey_validation: bool,
+    // Longest message read or written.
+    max_message_len: usize,
     pattern: HandshakePattern,
     message_index: usize,
     pattern_has_psk: bool,
-    psks: ArrayVec<[u8; 32], 4>,
+    psks: ArrayVec<Zeroizing<[u8; 32]>, 4>,
+    started: Instant,
+    // Whether this continues another handshake, after `fallback`, so that
+    // the metrics count them as one.
+    is_fallback: bool,
+    #[cfg(feature = "keylog")]
+    key_log: Option<Arc<dyn crate::keylog::KeyLog>>,
+    #[cfg(feature = "use_std")]
+    ephemeral_observer: Option<Arc<dyn EphemeralObserver>>,
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    prologue: Vec<u8>,
+    // Whether written messages are kept in `last_sent`.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    keep_last_sent: bool,
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    last_sent: Option<Vec<u8>>,
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    obfuscation: Option<ObfuscationConfig>,
+    // Padding length of the next message to write, with obfuscation.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    next_padding: usize,
+    #[cfg(feature = "use_std")]
+    replay_cache: Option<Arc<dyn ReplayCache>>,
+    #[cfg(feature = "use_std")]
+    static_dh_cache: Option<Arc<dyn StaticDhCache<D>>>,
+    #[cfg(feature = "use_std")]
+    psk_provider: Option<Arc<dyn PskProvider<D>>>,
+    #[cfg(feature = "use_std")]
+    remote_static_verifier: Option<Arc<dyn RemoteStaticVerifier>>,
+    #[cfg(feature = "use_std")]
+    remote_signing_key_verifier: Option<Arc<dyn RemoteStaticVerifier>>,
+}
+
+// The state changed by reading or writing a message.
+struct Checkpoint<D: DH, C: Cipher, H: Hash, K: Kem, S: Signature> {
+    symmetric: SymmetricState<C, H>,
+    e: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    rs: Option<D::Pubkey>,
+    re: Option<D::Pubkey>,
+    e1: Option<(K::Key, K::Pubkey)>,
+    re1: Option<K::Pubkey>,
+    rsig: Option<S::Pubkey>,
+    psks: ArrayVec<Zeroizing<[u8; 32]>, 4>,
 }
 
-impl<D, C, H> Clone for HandshakeState<D, C, H>
+impl<D, C, H, K, S> Clone for HandshakeState<D, C, H, K, S>
 where
     D: DH,
     C: Cipher,
     H: Hash,
+    K: Kem,
+    S: Signature,
 {
     fn clone(&self) -> Self {
         Self {
             symmetric: self.symmetric.clone(),
-            s: self.s.as_ref().map(Clone::clone),
+            s: self.s.clone(),
             e: self.e.as_ref().map(Clone::clone),
             rs: self.rs.as_ref().map(U8Array::clone),
             re: self.re.as_ref().map(U8Array::clone),
+            e1: self.e1.as_ref().map(|(k, pk)| (k.clone(), pk.clone())),
+            re1: self.re1.as_ref().map(U8Array::clone),
+            sig_key: self.sig_key.as_ref().map(U8Array::clone),
+            rsig: self.rsig.as_ref().map(U8Array::clone),
             is_initiator: self.is_initiator,
             is_elligator_encoded: self.is_elligator_encoded,
-            pattern: self.pattern.clone(),
+            is_static_elligator_encoded: self.is_static_elligator_encoded,
+            allow_low_order: self.allow_low_order,
+            strict_pubkey_validation: self.strict_pubkey_validation,
+            max_message_len: self.max_message_len,
+            pattern: self.pattern,
             message_index: self.message_index,
             pattern_has_psk: self.pattern_has_psk,
             psks: self.psks.clone(),
+            started: self.started,
+            is_fallback: self.is_fallback,
+            #[cfg(feature = "keylog")]
+            key_log: self.key_log.clone(),
+            #[cfg(feature = "use_std")]
+            ephemeral_observer: self.ephemeral_observer.clone(),
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            prologue: self.prologue.clone(),
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            keep_last_sent: self.keep_last_sent,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            last_sent: self.last_sent.clone(),
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            obfuscation: self.obfuscation,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            next_padding: self.next_padding,
+            #[cfg(feature = "use_std")]
+            replay_cache: self.replay_cache.clone(),
+            #[cfg(feature = "use_std")]
+            static_dh_cache: self.static_dh_cache.clone(),
+            #[cfg(feature = "use_std")]
+            psk_provider: self.psk_provider.clone(),
+            #[cfg(feature = "use_std")]
+            remote_static_verifier: self.remote_static_verifier.clone(),
+            #[cfg(feature = "use_std")]
+            remote_signing_key_verifier: self.remote_signing_key_verifier.clone(),
         }
     }
 }
 
-impl<D, C, H> HandshakeState<D, C, H>
+impl<D, C, H, K, S> HandshakeState<D, C, H, K, S>
 where
     D: DH,
     C: Cipher,
     H: Hash,
+    K: Kem,
+    S: Signature,
 {
-    /// Get protocol name, e.g. Noise_IK_25519_ChaChaPoly_BLAKE2s.
+    /// Get protocol name, e.g. Noise_IK_25519_ChaChaPoly_BLAKE2s, or
+    /// Noise_XXhfs_25519+MLKEM768_ChaChaPoly_BLAKE2s with a KEM, or
+    /// Noise_XXsig_25519+Ed25519_ChaChaPoly_BLAKE2s with a signature scheme.
     fn get_name(pattern_name: &str) -> ArrayString<256> {
         let mut ret = ArrayString::new();
-        write!(
-            &mut ret,
-            "Noise_{}_{}_{}_{}",
-            pattern_name,
-            D::name(),
-            C::name(),
-            H::name()
-        )
-        .unwrap();
+        write!(&mut ret, "Noise_{}_{}", pattern_name, D::name()).unwrap();
+        if !K::name().is_empty() {
+            write!(&mut ret, "+{}", K::name()).unwrap();
+        }
+        if !S::name().is_empty() {
+            write!(&mut ret, "+{}", S::name()).unwrap();
+        }
+        write!(&mut ret, "_{}_{}", C::name(), H::name()).unwrap();
         ret
     }
 
@@ -91,18 +264,41 @@ where
     where
         P: AsRef<[u8]>,
     {
+        Self::new_impl(
+            pattern,
+            is_initiator,
+            is_elligator_encoded,
+            prologue.as_ref(),
+            s.map(StaticKey::Owned),
+            e,
+            rs,
+            re,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new_impl(
+        pattern: HandshakePattern,
+        is_initiator: bool,
+        is_elligator_encoded: bool,
+        prologue: &[u8],
+        s: Option<StaticKey<D>>,
+        e: Option<DhKeyPair<D::Key, D::Pubkey>>,
+        rs: Option<D::Pubkey>,
+        re: Option<D::Pubkey>,
+    ) -> Self {
         let mut symmetric = SymmetricState::new(Self::get_name(pattern.get_name()).as_bytes());
         let pattern_has_psk = pattern.has_psk();
 
         // Mix in prologue.
-        symmetric.mix_hash(prologue.as_ref());
+        symmetric.mix_hash(prologue);
 
         // Mix in static keys known ahead of time.
         for t in pattern.get_pre_i() {
             match *t {
                 Token::S => {
                     if is_initiator {
-                        symmetric.mix_hash(s.as_ref().unwrap().public.as_slice());
+                        symmetric.mix_hash(s.as_ref().unwrap().public().as_slice());
                     } else {
                         symmetric.mix_hash(rs.as_ref().unwrap().as_slice());
                     }
@@ -116,7 +312,7 @@ where
                     if is_initiator {
                         symmetric.mix_hash(rs.as_ref().unwrap().as_slice());
                     } else {
-                        symmetric.mix_hash(s.as_ref().unwrap().public.as_slice());
+                        symmetric.mix_hash(s.as_ref().unwrap().public().as_slice());
                     }
                 }
                 Token::E => {
@@ -144,40 +340,115 @@ where
             e,
             rs,
             re,
+            e1: None,
+            re1: None,
+            sig_key: None,
+            rsig: None,
             is_initiator,
             is_elligator_encoded,
+            is_static_elligator_encoded: false,
+            allow_low_order: false,
+            strict_pubkey_validation: false,
+            max_message_len: MAX_MESSAGE_LEN,
             pattern,
             message_index: 0,
             pattern_has_psk,
             psks: ArrayVec::new(),
+            started: Instant::now(),
+            is_fallback: false,
+            #[cfg(feature = "keylog")]
+            key_log: None,
+            #[cfg(feature = "use_std")]
+            ephemeral_observer: None,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            prologue: prologue.to_vec(),
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            keep_last_sent: false,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            last_sent: None,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            obfuscation: None,
+            #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+            next_padding: 0,
+            #[cfg(feature = "use_std")]
+            replay_cache: None,
+            #[cfg(feature = "use_std")]
+            static_dh_cache: None,
+            #[cfg(feature = "use_std")]
+            psk_provider: None,
+            #[cfg(feature = "use_std")]
+            remote_static_verifier: None,
+            #[cfg(feature = "use_std")]
+            remote_signing_key_verifier: None,
         }
     }
 
     /// Calculate the size overhead of the next message.
     ///
+    /// With [obfuscation](HandshakeState::set_obfuscation), this includes
+    /// the padding of the next message to write, but only the padding length
+    /// of the next message to read, whose padding is not known in advance.
+    ///
     /// # Panics
     ///
     /// If these is no more message to read/write, i.e., if the handshake is
     /// already completed.
     pub fn get_next_message_overhead(&self) -> usize {
+        let overhead = self.get_base_overhead();
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        if self.obfuscation.is_some() {
+            let padding = if self.is_write_turn() {
+                self.next_padding
+            } else {
+                0
+            };
+            return overhead + padding + 2;
+        }
+        overhead
+    }
+
+    // Overhead of the next message without padding.
+    fn get_base_overhead(&self) -> usize {
         let m = self.pattern.get_message_pattern(self.message_index);
+        Self::message_overhead(m, self.symmetric.has_key(), self.pattern_has_psk).0
+    }
 
+    // Overhead of the message `m`, given whether a key is set before it, and
+    // whether a key is set after it.
+    fn message_overhead(m: &[Token], mut has_key: bool, pattern_has_psk: bool) -> (usize, bool) {
         let mut overhead = 0;
 
-        let mut has_key = self.symmetric.has_key();
-
         for &t in m {
             match t {
                 Token::E => {
                     overhead += D::Pubkey::len();
-                    if self.pattern_has_psk {
+                    if pattern_has_psk {
                         has_key = true;
                     }
                 }
                 Token::S => {
                     overhead += D::Pubkey::len();
                     if has_key {
-                        overhead += 16;
+                        overhead += C::tag_len();
+                    }
+                }
+                Token::E1 => {
+                    overhead += K::Pubkey::len();
+                    if has_key {
+                        overhead += C::tag_len();
+                    }
+                }
+                Token::EKEM1 => {
+                    overhead += K::Ciphertext::len();
+                    if has_key {
+                        overhead += C::tag_len();
+                    }
+                    has_key = true;
+                }
+                Token::SIG => {
+                    overhead += S::Pubkey::len() + S::Sig::len();
+                    if has_key {
+                        overhead += 2 * C::tag_len();
                     }
                 }
                 _ => {
@@ -187,15 +458,37 @@ where
         }
 
         if has_key {
-            overhead += 16
+            overhead += C::tag_len()
         }
 
-        overhead
+        (overhead, has_key)
+    }
+
+    /// The longest payload of message `message_index` of `pattern` that fits
+    /// into 65535 bytes, e.g., to size buffers, without
+    /// [obfuscation](HandshakeState::set_obfuscation).
+    ///
+    /// With a lower [maximum message
+    /// length](HandshakeStateBuilder::set_max_message_len), subtract the
+    /// difference.
+    ///
+    /// # Panics
+    ///
+    /// If the pattern has no message `message_index`.
+    pub fn max_payload_len(pattern: &HandshakePattern, message_index: usize) -> usize {
+        let has_psk = pattern.has_psk();
+        let mut has_key = false;
+        for i in 0..message_index {
+            has_key = Self::message_overhead(pattern.get_message_pattern(i), has_key, has_psk).1;
+        }
+        let m = pattern.get_message_pattern(message_index);
+        MAX_MESSAGE_LEN - Self::message_overhead(m, has_key, has_psk).0
     }
 
     /// Like [`write_message`](HandshakeState::write_message), but returns a [`Vec`].
     #[cfg(any(feature = "use_std", feature = "use_alloc"))]
     pub fn write_message_vec(&mut self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check_turn(true)?;
         let mut out = vec![0u8; payload.len() + self.get_next_message_overhead()];
         self.write_message(payload, &mut out)?;
         Ok(out)
@@ -206,35 +499,187 @@ where
     ///
     /// # Error Kinds
     ///
-    /// - [DH](ErrorKind::DH): DH operation failed.
-    /// - [NeedPSK](ErrorKind::NeedPSK): A PSK token is encountered but none is available.
+    /// - [DH](crate::ErrorKind::DH): DH operation failed.
+    /// - [Kem](crate::ErrorKind::Kem): KEM operation failed.
+    /// - [NeedPSK](crate::ErrorKind::NeedPSK): A PSK token is encountered but none is available.
+    /// - [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey): A DH output is
+    ///   all zeros, cf.
+    ///   [`set_allow_low_order`](HandshakeStateBuilder::set_allow_low_order).
+    /// - [WrongTurn](crate::ErrorKind::WrongTurn): It is not our turn to write.
+    /// - [HandshakeComplete](crate::ErrorKind::HandshakeComplete): The handshake
+    ///   has already completed.
+    /// - [MissingKey](crate::ErrorKind::MissingKey): A required key is not set.
+    /// - [BufferTooSmall](crate::ErrorKind::BufferTooSmall): `out.len()` is not
+    ///   `payload.len() + self.get_next_message_overhead()`.
+    /// - [MessageTooLong](crate::ErrorKind::MessageTooLong): The message is
+    ///   longer than the
+    ///   [maximum](HandshakeStateBuilder::set_max_message_len).
     ///
-    /// # Panics
+    /// # Error Recovery
+    ///
+    /// If [`write_message`](HandshakeState::write_message) fails, the
+    /// [`HandshakeState`] is left as it was before the call.
+    pub fn write_message(&mut self, payload: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        self.write_message_with(payload, out, D::genkey)
+    }
+
+    /// Like [`write_message`](HandshakeState::write_message), but generates
+    /// the ephemeral key, if any, with `rng`, cf. [`DH::genkey_with_rng`].
+    ///
+    /// # Error Kinds
+    ///
+    /// Same as [`write_message`](HandshakeState::write_message).
+    pub fn write_message_with_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        payload: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        self.write_message_with(payload, out, |elligator| D::genkey_with_rng(rng, elligator))
+    }
+
+    fn write_message_with<G>(
+        &mut self,
+        payload: &[u8],
+        out: &mut [u8],
+        genkey: G,
+    ) -> Result<(), Error>
+    where
+        G: FnMut(bool) -> DhKeyPair<D::Key, D::Pubkey>,
+    {
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        let padded = self.pad(payload);
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        let payload = padded.as_deref().unwrap_or(payload);
+        let r = self.write_message_impl(payload, out, genkey);
+        self.record(&r);
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        if r.is_ok() {
+            if self.keep_last_sent {
+                self.last_sent = Some(out.to_vec());
+            }
+            if let Some(ref config) = self.obfuscation {
+                self.next_padding = config.padding_len();
+            }
+        }
+        r
+    }
+
+    // Append the padding and its length to `payload`, with obfuscation.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    fn pad(&self, payload: &[u8]) -> Option<Vec<u8>> {
+        let config = self.obfuscation.as_ref()?;
+        let mut padded = Vec::with_capacity(payload.len() + self.next_padding + 2);
+        padded.extend_from_slice(payload);
+        padded.resize(payload.len() + self.next_padding, 0);
+        (config.fill_random)(&mut padded[payload.len()..]);
+        padded.extend_from_slice(&(self.next_padding as u16).to_be_bytes());
+        Some(padded)
+    }
+
+    /// Like [`write_message`](HandshakeState::write_message), but `out` may
+    /// be longer than the message. Returns the length of the message.
     ///
-    /// * If a required static key is not set.
+    /// This does not allocate, unless the last sent message is
+    /// [kept](HandshakeState::set_keep_last_sent) or
+    /// [obfuscation](HandshakeState::set_obfuscation) is on.
     ///
-    /// * If `out.len() != payload.len() + self.get_next_message_overhead()`.
+    /// # Error Kinds
     ///
-    /// * If it is not our turn to write.
+    /// In addition to the errors of
+    /// [`write_message`](HandshakeState::write_message),
+    /// [BufferTooSmall](crate::ErrorKind::BufferTooSmall) if `out` is shorter than
+    /// `payload.len() + self.get_next_message_overhead()`.
+    pub fn write_message_to(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.check_turn(true)?;
+        let len = payload
+            .len()
+            .checked_add(self.get_next_message_overhead())
+            .filter(|&len| len <= out.len())
+            .ok_or_else(Error::buffer_too_small)?;
+        self.write_message(payload, &mut out[..len])?;
+        Ok(len)
+    }
+
+    /// Keep a copy of every message written, for
+    /// [`last_sent_message`](HandshakeState::last_sent_message) and
+    /// [`resend`](HandshakeState::resend).
     ///
-    /// * If the handshake has already completed.
-    pub fn write_message(&mut self, payload: &[u8], out: &mut [u8]) -> Result<(), Error> {
-        debug_assert_eq!(out.len(), payload.len() + self.get_next_message_overhead());
+    /// Off by default, as it allocates on every write.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn set_keep_last_sent(&mut self, keep: bool) {
+        self.keep_last_sent = keep;
+        if !keep {
+            self.last_sent = None;
+        }
+    }
+
+    /// Get the last message written with
+    /// [`write_message`](HandshakeState::write_message), if any, with
+    /// [`set_keep_last_sent`](HandshakeState::set_keep_last_sent).
+    ///
+    /// Calling [`write_message`](HandshakeState::write_message) again would
+    /// advance the handshake, so retransmit this exact message instead when it
+    /// may have been lost.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn last_sent_message(&self) -> Option<&[u8]> {
+        self.last_sent.as_deref()
+    }
+
+    /// Like [`last_sent_message`](HandshakeState::last_sent_message), but
+    /// returns a copy, ready to be sent again.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn resend(&self) -> Option<Vec<u8>> {
+        self.last_sent.clone()
+    }
 
+    fn write_message_impl<G>(
+        &mut self,
+        payload: &[u8],
+        out: &mut [u8],
+        genkey: G,
+    ) -> Result<(), Error>
+    where
+        G: FnMut(bool) -> DhKeyPair<D::Key, D::Pubkey>,
+    {
         // Check that it is our turn to send.
-        assert!(self.is_write_turn());
+        self.check_turn(true)?;
+
+        if out.len() != payload.len() + self.get_base_overhead() {
+            return Err(Error::buffer_too_small());
+        }
+        if out.len() > self.max_message_len {
+            return Err(Error::message_too_long());
+        }
+
+        let checkpoint = self.checkpoint();
+        let r = self.write_tokens(payload, out, genkey);
+        self.commit_or_roll_back(&r, checkpoint);
+        r
+    }
 
+    fn write_tokens<G>(
+        &mut self,
+        payload: &[u8],
+        out: &mut [u8],
+        mut genkey: G,
+    ) -> Result<(), Error>
+    where
+        G: FnMut(bool) -> DhKeyPair<D::Key, D::Pubkey>,
+    {
         // Get the message pattern.
         let m = self.pattern.get_message_pattern(self.message_index);
-        self.message_index += 1;
 
         let mut cur: usize = 0;
         // Process tokens.
-        for t in m {
+        for (i, t) in m.iter().enumerate() {
             match *t {
                 Token::E => {
                     if self.e.is_none() {
-                        self.e = Some(D::genkey(self.is_elligator_encoded));
+                        let e = genkey(self.is_elligator_encoded);
+                        #[cfg(feature = "use_std")]
+                        self.observe_ephemeral(EphemeralOrigin::Local, &e.public);
+                        self.e = Some(e);
                     }
                     let e_pk = &self.e.as_ref().unwrap().public;
                     self.symmetric.mix_hash(e_pk.as_slice());
@@ -246,27 +691,50 @@ where
                 }
                 Token::S => {
                     let len = if self.symmetric.has_key() {
-                        D::Pubkey::len() + 16
+                        D::Pubkey::len() + C::tag_len()
                     } else {
                         D::Pubkey::len()
                     };
 
+                    let s = self.s.as_ref().ok_or_else(Error::missing_key)?;
                     let encrypted_s_out = &mut out[cur..cur + len];
-                    self.symmetric.encrypt_and_hash(
-                        self.s.as_ref().unwrap().public.as_slice(),
-                        encrypted_s_out,
-                    );
+                    self.symmetric
+                        .encrypt_and_hash(s.public().as_slice(), encrypted_s_out);
                     cur += len;
                 }
-                Token::PSK => {
-                    if let Some(psk) = self.psks.pop_at(0) {
-                        self.symmetric.mix_key_and_hash(&psk);
-                    } else {
-                        return Err(Error::need_psk());
-                    }
+                Token::E1 => {
+                    let len = K::Pubkey::len() + self.symmetric.overhead();
+                    let e1 = K::genkey();
+                    self.symmetric
+                        .encrypt_and_hash(e1.1.as_slice(), &mut out[cur..cur + len]);
+                    self.e1 = Some(e1);
+                    cur += len;
                 }
+                Token::EKEM1 => {
+                    let len = K::Ciphertext::len() + self.symmetric.overhead();
+                    let re1 = self.re1.as_ref().ok_or_else(Error::missing_key)?;
+                    let (ct, output) = K::encapsulate(re1)?;
+                    self.symmetric
+                        .encrypt_and_hash(ct.as_slice(), &mut out[cur..cur + len]);
+                    self.symmetric.mix_key(output.as_slice());
+                    cur += len;
+                }
+                Token::SIG => {
+                    // Send the public key, then sign the hash that covers it.
+                    let key = self.sig_key.as_ref().ok_or_else(Error::missing_key)?;
+                    let len = S::Pubkey::len() + self.symmetric.overhead();
+                    self.symmetric
+                        .encrypt_and_hash(S::pubkey(key).as_slice(), &mut out[cur..cur + len]);
+                    cur += len;
+                    let sig = S::sign(key, self.symmetric.get_hash());
+                    let len = S::Sig::len() + self.symmetric.overhead();
+                    self.symmetric
+                        .encrypt_and_hash(sig.as_slice(), &mut out[cur..cur + len]);
+                    cur += len;
+                }
+                Token::PSK => self.mix_psk(i)?,
                 t => {
-                    let dh_result = self.perform_dh(t).map_err(|_| Error::dh())?;
+                    let dh_result = self.perform_dh(t)?;
                     self.symmetric.mix_key(dh_result.as_slice());
                 }
             }
@@ -281,40 +749,108 @@ where
     ///
     /// # Error Kinds
     ///
-    /// - [DH](ErrorKind::DH): DH operation failed.
-    /// - [NeedPSK](ErrorKind::NeedPSK): A PSK token is encountered but none is
+    /// - [DH](crate::ErrorKind::DH): DH operation failed.
+    /// - [Kem](crate::ErrorKind::Kem): KEM operation failed.
+    /// - [NeedPSK](crate::ErrorKind::NeedPSK): A PSK token is encountered but none is
     ///   available.
-    /// - [Decryption](ErrorKind::Decryption): Decryption failed.
+    /// - [Decryption](crate::ErrorKind::Decryption): Decryption failed.
+    /// - [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey): A DH output is
+    ///   all zeros, cf.
+    ///   [`set_allow_low_order`](HandshakeStateBuilder::set_allow_low_order),
+    ///   or a public key of the peer is rejected, cf.
+    ///   [`set_strict_pubkey_validation`](HandshakeStateBuilder::set_strict_pubkey_validation).
+    /// - [WrongTurn](crate::ErrorKind::WrongTurn): It is not our turn to read.
+    /// - [StaticKeyMismatch](crate::ErrorKind::StaticKeyMismatch): The static
+    ///   key of the peer differs from the one set with
+    ///   [`set_rs`](HandshakeStateBuilder::set_rs).
+    /// - [AuthenticationRejected](crate::ErrorKind::AuthenticationRejected): The
+    ///   static key or the signing key of the peer has been rejected, cf.
+    ///   [`set_remote_static_verifier`](HandshakeState::set_remote_static_verifier)
+    ///   and
+    ///   [`set_remote_signing_key_verifier`](HandshakeState::set_remote_signing_key_verifier).
+    /// - [HandshakeComplete](crate::ErrorKind::HandshakeComplete): The handshake
+    ///   has already completed.
+    /// - [MissingKey](crate::ErrorKind::MissingKey): A required key is not set.
+    /// - [TooShort](crate::ErrorKind::TooShort): `data` is shorter than
+    ///   `self.get_next_message_overhead()`.
+    /// - [BufferTooSmall](crate::ErrorKind::BufferTooSmall): `out.len()` is not
+    ///   `data.len() - self.get_next_message_overhead()`.
+    /// - [MessageTooLong](crate::ErrorKind::MessageTooLong): `data` is longer
+    ///   than the [maximum](HandshakeStateBuilder::set_max_message_len).
     ///
     /// # Error Recovery
     ///
-    /// If [`read_message`](HandshakeState::read_message) fails, the whole
-    /// [`HandshakeState`] may be in invalid state and should not be used to
-    /// read or write any further messages. (But
-    /// [`get_re()`](HandshakeState::get_re) and
-    /// [`get_rs()`](HandshakeState::get_rs) is allowed.) In case error recovery
-    /// is desirable, [`clone`](Clone::clone) the [`HandshakeState`] before
-    /// calling [`read_message`](HandshakeState::read_message).
-    ///
-    /// # Panics
-    ///
-    /// * If `out.len() + self.get_next_message_overhead() != data.len()`.
+    /// If [`read_message`](HandshakeState::read_message) fails, the
+    /// [`HandshakeState`] is left as it was before the call, except for the
+    /// ephemeral key of the peer, which is kept for
+    /// [`fallback`](HandshakeState::fallback) (cf.
+    /// [`get_re()`](HandshakeState::get_re)). The same message, or another
+    /// one, can thus be read again.
     ///
-    ///   (Notes that this implies `data.len() >= overhead`.)
+    /// # Obfuscation
     ///
-    /// * If a required static key is not set.
-    ///
-    /// * If it is not our turn to read.
-    ///
-    /// * If the handshake has already completed.
+    /// With [obfuscation](HandshakeState::set_obfuscation), `out` holds the
+    /// payload followed by its padding. Use
+    /// [`read_message_to`](HandshakeState::read_message_to) or
+    /// [`read_message_vec`](HandshakeState::read_message_vec) to strip it.
+    /// [TooShort](crate::ErrorKind::TooShort) is returned if the padding length is
+    /// longer than the payload.
     pub fn read_message(&mut self, data: &[u8], out: &mut [u8]) -> Result<(), Error> {
-        debug_assert_eq!(out.len() + self.get_next_message_overhead(), data.len());
+        self.read_message_padded(data, out).map(|_| ())
+    }
+
+    // Like `read_message`, but returns the length of the payload without
+    // padding.
+    fn read_message_padded(&mut self, data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        if self.obfuscation.is_some() {
+            let mut padded = vec![0u8; out.len() + 2];
+            let mut len = 0;
+            let r = self.read_message_impl(data, &mut padded).and_then(|()| {
+                let (rest, padding) = padded.split_at(out.len());
+                let padding = u16::from_be_bytes([padding[0], padding[1]]) as usize;
+                len = rest
+                    .len()
+                    .checked_sub(padding)
+                    .ok_or_else(Error::too_short)?;
+                Ok(())
+            });
+            self.record(&r);
+            r?;
+            out.copy_from_slice(&padded[..out.len()]);
+            return Ok(len);
+        }
+        let r = self.read_message_impl(data, out);
+        self.record(&r);
+        r.map(|()| out.len())
+    }
+
+    fn read_message_impl(&mut self, data: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        self.check_turn(false)?;
 
-        assert!(!self.is_write_turn());
+        if data.len() > self.max_message_len {
+            return Err(Error::message_too_long());
+        }
+        let overhead = self.get_base_overhead();
+        if data.len() < overhead {
+            return Err(Error::too_short());
+        }
+        if data.len() - overhead != out.len() {
+            return Err(Error::buffer_too_small());
+        }
 
+        let checkpoint = self.checkpoint();
+        let r = self.read_tokens(data, out);
+        // Keep the ephemeral key of the peer even on error, for `fallback`.
+        let re = self.re.take();
+        self.commit_or_roll_back(&r, checkpoint);
+        self.re = re;
+        r
+    }
+
+    fn read_tokens(&mut self, data: &[u8], out: &mut [u8]) -> Result<(), Error> {
         // Get the message pattern.
         let m = self.pattern.get_message_pattern(self.message_index);
-        self.message_index += 1;
 
         let mut data = data;
         // Consume the next `n` bytes of data.
@@ -325,10 +861,15 @@ where
         };
 
         // Process tokens.
-        for t in m {
+        for (i, t) in m.iter().enumerate() {
             match *t {
                 Token::E => {
                     let re = D::Pubkey::from_slice(get(D::Pubkey::len()));
+                    if self.strict_pubkey_validation {
+                        D::check_pubkey(&re, self.is_elligator_encoded)?;
+                    }
+                    #[cfg(feature = "use_std")]
+                    self.observe_ephemeral(EphemeralOrigin::Remote, &re);
                     self.symmetric.mix_hash(re.as_slice());
                     if self.pattern_has_psk {
                         self.symmetric.mix_key(re.as_slice());
@@ -337,60 +878,169 @@ where
                 }
                 Token::S => {
                     let temp = get(if self.symmetric.has_key() {
-                        D::Pubkey::len() + 16
+                        D::Pubkey::len() + C::tag_len()
                     } else {
                         D::Pubkey::len()
                     });
                     let mut rs = D::Pubkey::new();
-                    self.symmetric
-                        .decrypt_and_hash(temp, rs.as_mut())
-                        .map_err(|_| Error::decryption())?;
+                    self.symmetric.decrypt_and_hash(temp, rs.as_mut())?;
+                    if self.strict_pubkey_validation {
+                        D::check_pubkey(&rs, self.is_static_elligator_encoded)?;
+                    }
+                    if let Some(ref expected) = self.rs {
+                        if !ct_eq_pubkey(expected.as_slice(), rs.as_slice()) {
+                            return Err(Error::static_key_mismatch());
+                        }
+                    }
+                    #[cfg(feature = "use_std")]
+                    if let Some(ref verifier) = self.remote_static_verifier {
+                        if !verifier.verify_remote_static(rs.as_slice()) {
+                            return Err(Error::authentication_rejected());
+                        }
+                    }
                     self.rs = Some(rs);
                 }
-                Token::PSK => {
-                    if let Some(psk) = self.psks.pop_at(0) {
-                        self.symmetric.mix_key_and_hash(&psk);
-                    } else {
-                        return Err(Error::need_psk());
+                Token::E1 => {
+                    let temp = get(K::Pubkey::len() + self.symmetric.overhead());
+                    let mut re1 = K::Pubkey::new();
+                    self.symmetric.decrypt_and_hash(temp, re1.as_mut())?;
+                    self.re1 = Some(re1);
+                }
+                Token::EKEM1 => {
+                    let (k, _) = self.e1.as_ref().ok_or_else(Error::missing_key)?;
+                    let temp = get(K::Ciphertext::len() + self.symmetric.overhead());
+                    let mut ct = K::Ciphertext::new();
+                    self.symmetric.decrypt_and_hash(temp, ct.as_mut())?;
+                    let output = K::decapsulate(k, &ct)?;
+                    self.symmetric.mix_key(output.as_slice());
+                }
+                Token::SIG => {
+                    let temp = get(S::Pubkey::len() + self.symmetric.overhead());
+                    let mut rsig = S::Pubkey::new();
+                    self.symmetric.decrypt_and_hash(temp, rsig.as_mut())?;
+                    let h = H::Output::from_slice(self.symmetric.get_hash());
+                    let temp = get(S::Sig::len() + self.symmetric.overhead());
+                    let mut sig = S::Sig::new();
+                    self.symmetric.decrypt_and_hash(temp, sig.as_mut())?;
+                    S::verify(&rsig, h.as_slice(), &sig)?;
+                    #[cfg(feature = "use_std")]
+                    if let Some(ref verifier) = self.remote_signing_key_verifier {
+                        if !verifier.verify_remote_static(rsig.as_slice()) {
+                            return Err(Error::authentication_rejected());
+                        }
                     }
+                    self.rsig = Some(rsig);
                 }
+                Token::PSK => self.mix_psk(i)?,
                 t => {
-                    let dh_result = self.perform_dh(t).map_err(|_| Error::dh())?;
+                    let dh_result = self.perform_dh(t)?;
                     self.symmetric.mix_key(dh_result.as_slice());
                 }
             }
         }
 
-        self.symmetric
-            .decrypt_and_hash(data, out)
-            .map_err(|_| Error::decryption())
+        self.symmetric.decrypt_and_hash(data, out)?;
+
+        #[cfg(feature = "use_std")]
+        if self.message_index == 0 {
+            if let Some(ref cache) = self.replay_cache {
+                if !cache.insert(self.get_hash()) {
+                    return Err(Error::replay());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`read_message`](HandshakeState::read_message), but `out` may be
+    /// longer than the payload. Returns the length of the payload, without
+    /// its padding with [obfuscation](HandshakeState::set_obfuscation).
+    ///
+    /// This does not allocate, unless
+    /// [obfuscation](HandshakeState::set_obfuscation) is on.
+    ///
+    /// # Error Kinds
+    ///
+    /// In addition to the errors of
+    /// [`read_message`](HandshakeState::read_message):
+    ///
+    /// - [TooShort](crate::ErrorKind::TooShort): `data` is shorter than
+    ///   [`get_next_message_overhead`](HandshakeState::get_next_message_overhead).
+    /// - [BufferTooSmall](crate::ErrorKind::BufferTooSmall): `out` is too short for
+    ///   the payload.
+    pub fn read_message_to(&mut self, data: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.check_turn(false)?;
+        let len = data
+            .len()
+            .checked_sub(self.get_next_message_overhead())
+            .ok_or_else(Error::too_short)?;
+        if len > out.len() {
+            return Err(Error::buffer_too_small());
+        }
+        self.read_message_padded(data, &mut out[..len])
     }
 
     /// Similar to [`read_message`](HandshakeState::read_message), but returns
-    /// result as a [`Vec`].
+    /// result as a [`Vec`], without padding with
+    /// [obfuscation](HandshakeState::set_obfuscation).
     ///
     /// In addition to possible errors from
     /// [`read_message`](HandshakeState::read_message),
-    /// [TooShort](ErrorKind::TooShort) may be returned.
+    /// [TooShort](crate::ErrorKind::TooShort) may be returned.
     #[cfg(any(feature = "use_std", feature = "use_alloc"))]
     pub fn read_message_vec(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check_turn(false)?;
         let overhead = self.get_next_message_overhead();
         if data.len() < overhead {
             Err(Error::too_short())
         } else {
             let mut out = vec![0u8; data.len() - overhead];
-            self.read_message(data, &mut out)?;
+            let len = self.read_message_padded(data, &mut out)?;
+            out.truncate(len);
             Ok(out)
         }
     }
 
+    /// Mix the next PSK, from the PSK-queue or else the PSK provider, for
+    /// the `i`-th token of the current message.
+    #[cfg_attr(not(feature = "use_std"), allow(unused_variables))]
+    fn mix_psk(&mut self, i: usize) -> Result<(), Error> {
+        if let Some(psk) = self.psks.pop_at(0) {
+            self.symmetric.mix_key_and_hash(&psk[..]);
+            return Ok(());
+        }
+        #[cfg(feature = "use_std")]
+        if let Some(provider) = self.psk_provider.clone() {
+            let position = if self.message_index == 0 && i == 0 {
+                0
+            } else {
+                self.message_index + 1
+            };
+            if let Some(psk) = provider.psk(position, self.rs.as_ref()) {
+                let psk = Zeroizing::new(psk);
+                self.symmetric.mix_key_and_hash(&psk[..]);
+                return Ok(());
+            }
+        }
+        Err(Error::need_psk())
+    }
+
     /// Push a PSK to the PSK-queue.
     ///
     /// # Panics
     ///
     /// If the PSK-queue becomes longer than 4.
     pub fn push_psk(&mut self, psk: &[u8]) {
-        self.psks.push(U8Array::from_slice(psk));
+        self.psks.push(Zeroizing::new(U8Array::from_slice(psk)));
+    }
+
+    /// Set the signing key of the `sig` token.
+    ///
+    /// Writing a `sig` token fails with
+    /// [MissingKey](crate::ErrorKind::MissingKey) without it.
+    pub fn set_signing_key(&mut self, key: S::Key) {
+        self.sig_key = Some(key);
     }
 
     /// Whether handshake has completed.
@@ -403,6 +1053,16 @@ where
         self.symmetric.get_hash()
     }
 
+    /// Get a short, non-secret identifier of this session, derived from the
+    /// handshake hash.
+    ///
+    /// Both peers get the same value, so it can be used to correlate logs
+    /// etc. of the same connection. Should be called after handshake is
+    /// [`completed`](HandshakeState::completed).
+    pub fn get_session_id(&self) -> SessionId {
+        SessionId::from_handshake_hash::<H>(self.get_hash())
+    }
+
     /// Get ciphers that can be used to encrypt/decrypt further messages. The
     /// first [`CipherState`] is for initiator to responder, and the second for
     /// responder to initiator.
@@ -410,7 +1070,151 @@ where
     /// Should be called after handshake is
     /// [`completed`](HandshakeState::completed).
     pub fn get_ciphers(&self) -> (CipherState<C>, CipherState<C>) {
-        self.symmetric.split()
+        let ciphers = self.symmetric.split();
+        #[cfg(feature = "keylog")]
+        if let Some(ref log) = self.key_log {
+            use crate::keylog::{INITIATOR_LABEL, RESPONDER_LABEL};
+            let h = self.get_hash();
+            log.log(INITIATOR_LABEL, h, ciphers.0.clone().extract().0.as_slice());
+            log.log(RESPONDER_LABEL, h, ciphers.1.clone().extract().0.as_slice());
+        }
+        ciphers
+    }
+
+    /// Derive `out.len()` bytes of keying material for `label` from the
+    /// session, like a TLS exporter, e.g., to bind a signature or a
+    /// certificate to it.
+    ///
+    /// Unlike the [handshake hash](HandshakeState::get_hash), the output is
+    /// secret. It is derived from the final chaining key, independently of
+    /// the transport keys, and the same on both sides. Different labels or
+    /// lengths give unrelated outputs. [`TransportState::export_key_to`]
+    /// gives the same output.
+    ///
+    /// # Panics
+    ///
+    /// * If the handshake has not completed.
+    ///
+    /// * If `out` is longer than `255 * H::hash_len()`.
+    pub fn export_key_to(&self, label: &[u8], out: &mut [u8]) {
+        assert!(self.completed());
+        expand::<H>(self.symmetric.exporter_secret().as_slice(), label, out);
+    }
+
+    /// Like [`export_key_to`](HandshakeState::export_key_to), returning
+    /// `len` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Like [`export_key_to`](HandshakeState::export_key_to).
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn export_key(&self, label: &[u8], len: usize) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(vec![0; len]);
+        self.export_key_to(label, &mut out);
+        out
+    }
+
+    pub(crate) fn exporter_secret(&self) -> H::Output {
+        self.symmetric.exporter_secret()
+    }
+
+    // Number of messages read or written so far.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn message_index(&self) -> usize {
+        self.message_index
+    }
+
+    /// Like [`TransportState::from_handshake`], for one-way patterns, e.g.,
+    /// [`noise_n`](crate::patterns::noise_n): only the initiator-to-responder
+    /// cipher state is kept, and the session is marked as
+    /// [one-way](TransportState::is_one_way), so that sending as the
+    /// responder, or receiving as the initiator, fails with
+    /// [`PatternViolation`](crate::ErrorKind::PatternViolation).
+    ///
+    /// Fails with [`PatternViolation`](crate::ErrorKind::PatternViolation)
+    /// if the pattern is not one-way.
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has not completed.
+    pub fn split_one_way(&self) -> Result<TransportState<C, H>, Error> {
+        TransportState::one_way_from_handshake(self)
+    }
+
+    /// Log transport keys to `key_log` when they are split with
+    /// [`get_ciphers`](HandshakeState::get_ciphers).
+    ///
+    /// For debugging and testing only.
+    #[cfg(feature = "keylog")]
+    pub fn set_key_log(&mut self, key_log: Arc<dyn crate::keylog::KeyLog>) {
+        self.key_log = Some(key_log);
+    }
+
+    /// Call `observer` with every ephemeral key generated or received, e.g.,
+    /// an [`EphemeralReuseDetector`](crate::EphemeralReuseDetector).
+    #[cfg(feature = "use_std")]
+    pub fn set_ephemeral_observer(&mut self, observer: Arc<dyn EphemeralObserver>) {
+        self.ephemeral_observer = Some(observer);
+    }
+
+    /// Reject first messages already recorded in `cache`.
+    ///
+    /// Only has an effect for responders.
+    #[cfg(feature = "use_std")]
+    pub fn set_replay_cache(&mut self, cache: Arc<dyn ReplayCache>) {
+        self.replay_cache = Some(cache);
+    }
+
+    /// Enable the obfuscation mode, cf. [`ObfuscationConfig`], and Elligator
+    /// encoding of ephemeral keys.
+    ///
+    /// An ephemeral key set with
+    /// [`set_e`](HandshakeStateBuilder::set_e) must have been generated
+    /// with Elligator encoding. The mode is not exported with the handshake
+    /// state, so set it again after an import.
+    ///
+    /// # Panics
+    ///
+    /// * If a message has already been read or written.
+    ///
+    /// * If `config.min_padding > config.max_padding`.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn set_obfuscation(&mut self, config: ObfuscationConfig) {
+        assert_eq!(self.message_index, 0);
+        self.is_elligator_encoded = true;
+        self.next_padding = config.padding_len();
+        self.obfuscation = Some(config);
+    }
+
+    /// Use `cache` for the static-static DH, cf. [`StaticDhCache`].
+    #[cfg(feature = "use_std")]
+    pub fn set_static_dh_cache(&mut self, cache: Arc<dyn StaticDhCache<D>>) {
+        self.static_dh_cache = Some(cache);
+    }
+
+    /// Ask `provider` for PSKs when the PSK-queue is empty, cf.
+    /// [`PskProvider`].
+    #[cfg(feature = "use_std")]
+    pub fn set_psk_provider(&mut self, provider: Arc<dyn PskProvider<D>>) {
+        self.psk_provider = Some(provider);
+    }
+
+    /// Call `verifier` with the static key of the peer as soon as it is
+    /// received, cf. [`RemoteStaticVerifier`].
+    #[cfg(feature = "use_std")]
+    pub fn set_remote_static_verifier(&mut self, verifier: Arc<dyn RemoteStaticVerifier>) {
+        self.remote_static_verifier = Some(verifier);
+    }
+
+    /// Call `verifier` with the signing key of the peer as soon as its
+    /// signature of a `sig` token is verified, cf. [`RemoteStaticVerifier`].
+    ///
+    /// The DH static key and the signing key are different keys, so the
+    /// [static key verifier](HandshakeState::set_remote_static_verifier) is
+    /// not called with the signing key.
+    #[cfg(feature = "use_std")]
+    pub fn set_remote_signing_key_verifier(&mut self, verifier: Arc<dyn RemoteStaticVerifier>) {
+        self.remote_signing_key_verifier = Some(verifier);
     }
 
     /// Get remote static pubkey, if available.
@@ -418,6 +1222,18 @@ where
         self.rs.as_ref().map(U8Array::clone)
     }
 
+    /// Get remote static pubkey, if available, e.g., to authorize the peer
+    /// after an `XX` or `IK` handshake.
+    pub fn get_remote_static(&self) -> Option<&[u8]> {
+        self.rs.as_ref().map(U8Array::as_slice)
+    }
+
+    /// Get the verified signing key of the peer, from a `sig` token, if
+    /// available.
+    pub fn get_remote_signing_key(&self) -> Option<&S::Pubkey> {
+        self.rsig.as_ref()
+    }
+
     /// Get remote semi-ephemeral pubkey.
     ///
     /// Returns [`None`](None) if we do not know.
@@ -432,119 +1248,459 @@ where
         self.is_initiator
     }
 
+    /// Get the maximum message length, cf.
+    /// [`set_max_message_len`](HandshakeStateBuilder::set_max_message_len).
+    pub fn get_max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+
     /// Get handshake pattern this [`HandshakeState`] uses.
     pub fn get_pattern(&self) -> &HandshakePattern {
         &self.pattern
     }
 
+    /// Get the security properties of the payload of the next message to
+    /// write or read.
+    ///
+    /// E.g., a responder can check this before sending application data in
+    /// its first message, and an initiator can use the data as soon as it
+    /// reads that message, before writing the next one.
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has already completed.
+    pub fn get_next_payload_protection(&self) -> PayloadProtection {
+        self.pattern.payload_protection(self.message_index)
+    }
+
     /// Check whether it is our turn to send in the handshake state.
     pub fn is_write_turn(&self) -> bool {
         self.message_index % 2 == if self.is_initiator { 0 } else { 1 }
     }
 
-    fn perform_dh(&self, t: Token) -> Result<D::Output, ()> {
-        let dh = |a: Option<&DhKeyPair<D::Key, D::Pubkey>>, b: Option<&D::Pubkey>, e: bool| D::dh(&a.unwrap().private, b.unwrap(), e);
+    /// Check whether the next message is ours to write, i.e., the handshake
+    /// has not completed and it is our turn to send.
+    ///
+    /// Unlike [`is_write_turn`](HandshakeState::is_write_turn), this is
+    /// `false` once the handshake has completed.
+    pub fn is_my_turn(&self) -> bool {
+        !self.completed() && self.is_write_turn()
+    }
 
-        match t {
-            Token::EE => dh(self.e.as_ref(), self.re.as_ref(), self.is_elligator_encoded),
-            Token::ES => {
-                if self.is_initiator {
-                    dh(self.e.as_ref(), self.rs.as_ref(), false)
-                } else {
-                    dh(self.s.as_ref(), self.re.as_ref(), self.is_elligator_encoded)
-                }
-            }
-            Token::SE => {
-                if self.is_initiator {
-                    dh(self.s.as_ref(), self.re.as_ref(), self.is_elligator_encoded)
-                } else {
-                    dh(self.e.as_ref(), self.rs.as_ref(), false)
-                }
-            }
-            Token::SS => dh(self.s.as_ref(), self.rs.as_ref(), false),
-            _ => unreachable!(),
+    // Check that there is a next message, and that it is ours to write if
+    // `write`, or to read otherwise.
+    fn check_turn(&self, write: bool) -> Result<(), Error> {
+        if self.completed() {
+            Err(Error::handshake_complete())
+        } else if self.is_write_turn() != write {
+            Err(Error::wrong_turn())
+        } else {
+            Ok(())
         }
     }
-}
 
-/// Handshake error.
-#[derive(Debug)]
-pub struct Error {
-    kind: ErrorKind,
-}
+    /// Switch to a fallback pattern, e.g.,
+    /// [`noise_xx_fallback`](crate::patterns::noise_xx_fallback), after the
+    /// first message of this handshake, as in Noise Pipes.
+    ///
+    /// Roles are swapped: the responder, e.g., after it failed to read an
+    /// `IK` message encrypted to an old static key, becomes the initiator of
+    /// the fallback handshake, with the ephemeral key it received as `re`.
+    /// The initiator, after it failed to read the reply as an `IK` message,
+    /// becomes the responder with its own ephemeral key as `e`. Both keep
+    /// their static key and the prologue. Remote static keys, PSKs and hooks
+    /// are not carried over.
+    ///
+    /// Fails with [`ErrorKind::MissingKey`](crate::ErrorKind::MissingKey) if the ephemeral key is not known
+    /// yet.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn fallback(&self, pattern: HandshakePattern) -> Result<Self, Error> {
+        self.fallback_with_prologue(pattern, &self.prologue)
+    }
 
-/// Handshake error kind.
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum ErrorKind {
-    /// A DH operation has failed.
-    DH,
-    /// A PSK is needed, but none is available.
-    NeedPSK,
-    /// Decryption failed.
-    Decryption,
-    /// The message is too short, and impossible to read.
-    TooShort,
-}
+    /// Like [`fallback`](HandshakeState::fallback), but with a new prologue,
+    /// e.g., one binding the messages exchanged so far, cf.
+    /// [`negotiation`](crate::negotiation).
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn fallback_with_prologue(
+        &self,
+        pattern: HandshakePattern,
+        prologue: &[u8],
+    ) -> Result<Self, Error> {
+        let e = self.e.clone();
+        let re = self.re.as_ref().map(U8Array::clone);
+        if (self.is_initiator && e.is_none()) || (!self.is_initiator && re.is_none()) {
+            return Err(Error::missing_key());
+        }
+        let mut hs = Self::new_impl(
+            pattern,
+            !self.is_initiator,
+            self.is_elligator_encoded,
+            prologue,
+            self.s.clone(),
+            e,
+            None,
+            if self.is_initiator { None } else { re },
+        );
+        hs.is_static_elligator_encoded = self.is_static_elligator_encoded;
+        hs.allow_low_order = self.allow_low_order;
+        hs.strict_pubkey_validation = self.strict_pubkey_validation;
+        hs.max_message_len = self.max_message_len;
+        hs.sig_key = self.sig_key.as_ref().map(U8Array::clone);
+        hs.started = self.started;
+        hs.is_fallback = true;
+        #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+        if let Some(config) = self.obfuscation {
+            hs.set_obfuscation(config);
+        }
+        Ok(hs)
+    }
 
-impl Error {
-    fn dh() -> Error {
-        Error {
-            kind: ErrorKind::DH,
+    #[cfg(feature = "use_std")]
+    fn observe_ephemeral(&self, origin: EphemeralOrigin, e: &D::Pubkey) {
+        if let Some(ref observer) = self.ephemeral_observer {
+            let peer = self.rs.as_ref().map(U8Array::as_slice);
+            observer.observe(origin, e.as_slice(), peer);
         }
     }
 
-    fn need_psk() -> Error {
-        Error {
-            kind: ErrorKind::NeedPSK,
+    fn checkpoint(&self) -> Checkpoint<D, C, H, K, S> {
+        Checkpoint {
+            symmetric: self.symmetric.clone(),
+            e: self.e.clone(),
+            rs: self.rs.as_ref().map(U8Array::clone),
+            re: self.re.as_ref().map(U8Array::clone),
+            e1: self.e1.as_ref().map(|(k, pk)| (k.clone(), pk.clone())),
+            re1: self.re1.as_ref().map(U8Array::clone),
+            rsig: self.rsig.as_ref().map(U8Array::clone),
+            psks: self.psks.clone(),
         }
     }
 
-    fn decryption() -> Error {
-        Error {
-            kind: ErrorKind::Decryption,
+    // Move to the next message if `r` is `Ok`, else go back to `checkpoint`,
+    // so that a failed message leaves the state unchanged.
+    fn commit_or_roll_back(
+        &mut self,
+        r: &Result<(), Error>,
+        checkpoint: Checkpoint<D, C, H, K, S>,
+    ) {
+        if r.is_ok() {
+            self.message_index += 1;
+            return;
         }
+        self.symmetric = checkpoint.symmetric;
+        self.e = checkpoint.e;
+        self.rs = checkpoint.rs;
+        self.re = checkpoint.re;
+        self.e1 = checkpoint.e1;
+        self.re1 = checkpoint.re1;
+        self.rsig = checkpoint.rsig;
+        self.psks = checkpoint.psks;
     }
 
-    fn too_short() -> Error {
-        Error {
-            kind: ErrorKind::TooShort,
+    fn record(&self, r: &Result<(), Error>) {
+        match r {
+            Ok(()) => {
+                // Counted once the first message has gone through, so that
+                // failed attempts, e.g., with each key of a `KeyRing`, and
+                // fallbacks are not counted again.
+                if self.message_index == 1 && !self.is_fallback {
+                    instrument::handshake_started();
+                }
+                if self.completed() {
+                    instrument::handshake_completed(self.started);
+                }
+            }
+            Err(e) => instrument::handshake_failed(e.kind()),
         }
     }
 
-    /// Error kind.
-    pub fn kind(&self) -> ErrorKind {
-        self.kind
+    fn perform_dh(&self, t: Token) -> Result<D::Output, Error> {
+        // Reject the identity, without branching on the output.
+        let check = |output: D::Output| {
+            let acc = output.as_slice().iter().fold(0u8, |acc, b| acc | b);
+            if acc == 0 && !self.allow_low_order {
+                return Err(Error::invalid_public_key());
+            }
+            Ok(output)
+        };
+        let e_dh = |remote: Option<&D::Pubkey>, e: bool| {
+            let local = self.e.as_ref().ok_or_else(Error::missing_key)?;
+            let remote = remote.ok_or_else(Error::missing_key)?;
+            check(D::dh(&local.private, remote, e)?)
+        };
+        let s_dh = |remote: Option<&D::Pubkey>, e: bool| {
+            let local = self.s.as_ref().ok_or_else(Error::missing_key)?;
+            let remote = remote.ok_or_else(Error::missing_key)?;
+            check(local.dh(remote, e)?)
+        };
+
+        match t {
+            Token::EE => e_dh(self.re.as_ref(), self.is_elligator_encoded),
+            Token::ES if self.is_initiator => {
+                e_dh(self.rs.as_ref(), self.is_static_elligator_encoded)
+            }
+            Token::ES => s_dh(self.re.as_ref(), self.is_elligator_encoded),
+            Token::SE if self.is_initiator => s_dh(self.re.as_ref(), self.is_elligator_encoded),
+            Token::SE => e_dh(self.rs.as_ref(), self.is_static_elligator_encoded),
+            #[cfg(feature = "use_std")]
+            Token::SS if self.static_dh_cache.is_some() => {
+                let cache = self.static_dh_cache.as_ref().unwrap();
+                let local = self.s.as_ref().ok_or_else(Error::missing_key)?.public();
+                let remote = self.rs.as_ref().ok_or_else(Error::missing_key)?;
+                if let Some(output) = cache.get(local, remote) {
+                    return Ok(output);
+                }
+                let output = s_dh(self.rs.as_ref(), self.is_static_elligator_encoded)?;
+                cache.insert(local, remote, &output);
+                Ok(output)
+            }
+            Token::SS => s_dh(self.rs.as_ref(), self.is_static_elligator_encoded),
+            _ => unreachable!(),
+        }
     }
 }
 
-impl Display for Error {
-    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
-        write!(fmt, "{:?}", self)
+// Saved states, e.g., of precomputed messages, are only supported without a
+// KEM.
+impl<D, C, H> HandshakeState<D, C, H>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+{
+    /// Encode the state, except the static private key and hooks.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn encode_state(&self, out: &mut Vec<u8>) {
+        let name = Self::get_name(self.pattern.get_name());
+        // Names are at most 256 bytes long, which does not fit in a byte.
+        out.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(self.prologue.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.prologue);
+        out.push(
+            self.is_initiator as u8
+                | (self.is_elligator_encoded as u8) << 1
+                | (self.is_static_elligator_encoded as u8) << 2
+                | (self.allow_low_order as u8) << 3
+                | (self.strict_pubkey_validation as u8) << 4,
+        );
+        out.extend_from_slice(&(self.max_message_len as u16).to_be_bytes());
+        out.push(self.message_index as u8);
+        self.symmetric.export(out);
+        put_option(out, self.s.as_ref().map(StaticKey::public));
+        match self.e {
+            Some(ref e) => {
+                out.push(1);
+                put_array(out, &e.private);
+                put_array(out, &e.public);
+            }
+            None => out.push(0),
+        }
+        put_option(out, self.rs.as_ref());
+        put_option(out, self.re.as_ref());
+        out.push(self.psks.len() as u8);
+        for psk in &self.psks {
+            out.extend_from_slice(&psk[..]);
+        }
     }
-}
 
-#[cfg(feature = "use_std")]
-impl ::std::error::Error for Error {
-    fn description(&self) -> &'static str {
-        match self.kind {
-            ErrorKind::DH => "DH error",
-            ErrorKind::NeedPSK => "Need PSK",
-            ErrorKind::Decryption => "Decryption failed",
-            ErrorKind::TooShort => "Message is too short",
+    /// Decode a state encoded by [`encode_state`](HandshakeState::encode_state).
+    ///
+    /// `s` must be the same static key pair the state was created with.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn decode_state(
+        r: &mut Reader,
+        s: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    ) -> Option<Self> {
+        let name_len = r.u16()? as usize;
+        let name = r.take(name_len)?;
+        let name = core::str::from_utf8(name).ok()?;
+        let prologue_len = r.u32()? as usize;
+        let prologue = r.take(prologue_len)?.to_vec();
+        let suffix = Self::get_name("");
+        let pattern_name = name
+            .strip_prefix("Noise_")?
+            .strip_suffix(suffix.strip_prefix("Noise__")?)?
+            .strip_suffix('_')?;
+        let pattern = crate::handshakepattern::by_name(pattern_name)?;
+
+        let flags = r.u8()?;
+        if flags > 31 {
+            return None;
+        }
+        let max_message_len = r.u16()? as usize;
+        let message_index = r.u8()? as usize;
+        if message_index > pattern.get_message_patterns_len() {
+            return None;
+        }
+        let symmetric = SymmetricState::import(r)?;
+
+        let s_public: Option<D::Pubkey> = r.option()?;
+        match (&s, &s_public) {
+            (Some(s), Some(p)) if ct_eq_pubkey(s.public.as_slice(), p.as_slice()) => {}
+            (None, None) => {}
+            _ => return None,
+        }
+        let e = match r.u8()? {
+            0 => None,
+            1 => Some(DhKeyPair {
+                private: r.array()?,
+                public: r.array()?,
+            }),
+            _ => return None,
+        };
+        let rs = r.option()?;
+        let re = r.option()?;
+        let mut psks = ArrayVec::new();
+        for _ in 0..r.u8()? {
+            psks.try_push(Zeroizing::new(r.array()?)).ok()?;
+        }
+
+        Some(HandshakeState {
+            symmetric,
+            s: s.map(StaticKey::Owned),
+            e,
+            rs,
+            re,
+            e1: None,
+            re1: None,
+            sig_key: None,
+            rsig: None,
+            is_initiator: flags & 1 != 0,
+            is_elligator_encoded: flags & 2 != 0,
+            is_static_elligator_encoded: flags & 4 != 0,
+            allow_low_order: flags & 8 != 0,
+            strict_pubkey_validation: flags & 16 != 0,
+            max_message_len,
+            pattern_has_psk: pattern.has_psk(),
+            pattern,
+            message_index,
+            psks,
+            started: Instant::now(),
+            is_fallback: false,
+            #[cfg(feature = "keylog")]
+            key_log: None,
+            #[cfg(feature = "use_std")]
+            ephemeral_observer: None,
+            prologue,
+            keep_last_sent: false,
+            last_sent: None,
+            obfuscation: None,
+            next_padding: 0,
+            #[cfg(feature = "use_std")]
+            replay_cache: None,
+            #[cfg(feature = "use_std")]
+            static_dh_cache: None,
+            #[cfg(feature = "use_std")]
+            psk_provider: None,
+            #[cfg(feature = "use_std")]
+            remote_static_verifier: None,
+            #[cfg(feature = "use_std")]
+            remote_signing_key_verifier: None,
+        })
+    }
+
+    /// Save a handshake in progress, e.g., to resume it after a process
+    /// restart with [`import_state`](HandshakeState::import_state).
+    ///
+    /// The encoding starts with a version byte and the length of the rest,
+    /// and holds the protocol name, the prologue, the role and the other
+    /// settings of the builder, e.g., the maximum message length, the
+    /// message index, the
+    /// symmetric state, the keys and the PSKs. Not included are:
+    ///
+    /// * the static private key, which must be passed to
+    ///   [`import_state`](HandshakeState::import_state);
+    /// * hooks, e.g., the replay cache, the ephemeral observer or the key
+    ///   log, and the [obfuscation](HandshakeState::set_obfuscation) mode,
+    ///   which must be set again;
+    /// * the [last sent message](HandshakeState::last_sent_message) and
+    ///   whether it is kept.
+    ///
+    /// # Security
+    ///
+    /// The state contains the chaining key, the cipher key, the ephemeral
+    /// private key and the PSKs. It must be protected like a private key,
+    /// e.g., encrypted and authenticated at rest; it is zeroed on drop.
+    ///
+    /// A saved state must be resumed at most once. Resuming it twice, e.g.,
+    /// from a backup, reuses the ephemeral key and the cipher nonces, which
+    /// breaks confidentiality and forward secrecy. Delete it once it is
+    /// imported.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn export_state(&self) -> Zeroizing<Vec<u8>> {
+        let mut body = Zeroizing::new(Vec::with_capacity(1024));
+        self.encode_state(&mut body);
+        let mut out = Zeroizing::new(Vec::with_capacity(5 + body.len()));
+        out.push(STATE_VERSION);
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    /// Resume a handshake saved with
+    /// [`export_state`](HandshakeState::export_state).
+    ///
+    /// `s` is our static key pair, if the pattern has one. It must be the
+    /// one the handshake was created with.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [InvalidState](crate::ErrorKind::InvalidState): The state is malformed, of
+    ///   another version, was created for a different protocol, or `s` does
+    ///   not match.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn import_state(
+        data: &[u8],
+        s: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    ) -> Result<Self, Error> {
+        let mut r = Reader::new(data);
+        if r.u8() != Some(STATE_VERSION) {
+            return Err(Error::invalid_state());
+        }
+        let len = r.take(4).ok_or_else(Error::invalid_state)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let body = r.rest();
+        if body.len() != len {
+            return Err(Error::invalid_state());
+        }
+        let mut r = Reader::new(body);
+        match Self::decode_state(&mut r, s) {
+            Some(hs) if r.is_empty() => Ok(hs),
+            _ => Err(Error::invalid_state()),
         }
     }
 }
 
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+const STATE_VERSION: u8 = 2;
+
 /// Builder for `HandshakeState`.
 pub struct HandshakeStateBuilder<'a, D: DH> {
     pattern: Option<HandshakePattern>,
     is_initiator: Option<bool>,
     is_elligator_encoded: Option<bool>,
+    is_static_elligator_encoded: Option<bool>,
+    allow_low_order: bool,
+    strict_pubkey_validation: bool,
+    max_message_len: usize,
     prologue: Option<&'a [u8]>,
-    s: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    s: Option<StaticKey<D>>,
     e: Option<DhKeyPair<D::Key, D::Pubkey>>,
     rs: Option<D::Pubkey>,
     re: Option<D::Pubkey>,
+    psks: ArrayVec<Zeroizing<[u8; 32]>, 4>,
+    #[cfg(feature = "use_std")]
+    static_dh_cache: Option<Arc<dyn StaticDhCache<D>>>,
+    #[cfg(feature = "use_std")]
+    psk_provider: Option<Arc<dyn PskProvider<D>>>,
+    #[cfg(feature = "use_std")]
+    remote_static_verifier: Option<Arc<dyn RemoteStaticVerifier>>,
+    #[cfg(feature = "use_std")]
+    remote_signing_key_verifier: Option<Arc<dyn RemoteStaticVerifier>>,
+    #[cfg(feature = "use_std")]
+    ephemeral_pool: Option<Arc<EphemeralPool<D>>>,
 }
 
 impl<'a, D: DH> Default for HandshakeStateBuilder<'a, D> {
@@ -563,11 +1719,26 @@ where
             pattern: None,
             is_initiator: None,
             is_elligator_encoded: None,
+            is_static_elligator_encoded: None,
+            allow_low_order: false,
+            strict_pubkey_validation: false,
+            max_message_len: MAX_MESSAGE_LEN,
             prologue: None,
             s: None,
             e: None,
             rs: None,
             re: None,
+            psks: ArrayVec::new(),
+            #[cfg(feature = "use_std")]
+            static_dh_cache: None,
+            #[cfg(feature = "use_std")]
+            psk_provider: None,
+            #[cfg(feature = "use_std")]
+            remote_static_verifier: None,
+            #[cfg(feature = "use_std")]
+            remote_signing_key_verifier: None,
+            #[cfg(feature = "use_std")]
+            ephemeral_pool: None,
         }
     }
 
@@ -577,24 +1748,94 @@ where
         self
     }
 
+    /// Same as [`set_pattern`](HandshakeStateBuilder::set_pattern).
+    pub fn pattern(&mut self, p: HandshakePattern) -> &mut Self {
+        self.set_pattern(p)
+    }
+
     /// Set whether the [`HandshakeState`] is initiator.
     pub fn set_is_initiator(&mut self, is: bool) -> &mut Self {
         self.is_initiator = Some(is);
         self
     }
 
-     /// Set whether the ephemeral keys will be encoded in elligator form.
-     pub fn set_is_elligator_encoded(&mut self, is: bool) -> &mut Self {
+    /// Set whether the ephemeral keys will be encoded in elligator form.
+    pub fn set_is_elligator_encoded(&mut self, is: bool) -> &mut Self {
         self.is_elligator_encoded = Some(is);
         self
     }
 
+    /// Encode the ephemeral keys in elligator form, cf.
+    /// [`set_is_elligator_encoded`](HandshakeStateBuilder::set_is_elligator_encoded).
+    pub fn enable_elligator(&mut self) -> &mut Self {
+        self.set_is_elligator_encoded(true)
+    }
+
+    /// Set whether the static public keys, local and remote, are Elligator
+    /// representatives, so that static keys sent in the clear, e.g., in the
+    /// first message of `IX`, look random too.
+    ///
+    /// Generate the static key pair with [`DH::genkey`] with `elligator` set,
+    /// and pass the representative of the peer to
+    /// [`set_rs`](HandshakeStateBuilder::set_rs). Both peers must agree on
+    /// this setting.
+    pub fn set_is_static_elligator_encoded(&mut self, is: bool) -> &mut Self {
+        self.is_static_elligator_encoded = Some(is);
+        self
+    }
+
+    /// Set whether DH outputs that are all zeros, e.g., from a low order
+    /// remote public key, are accepted.
+    ///
+    /// By default, they are rejected, and reading or writing the message
+    /// fails with [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey), so
+    /// that a peer can not force a known shared secret. Only allow them in
+    /// protocols that do not require contributory behavior.
+    pub fn set_allow_low_order(&mut self, allow: bool) -> &mut Self {
+        self.allow_low_order = allow;
+        self
+    }
+
+    /// Set whether the public keys received from the peer, ephemeral and
+    /// static, are checked with [`DH::check_pubkey`]. With `noise-rust-crypto`,
+    /// `X25519` rejects non-canonical encodings and points of small order.
+    ///
+    /// Reading a message with such a key fails with
+    /// [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey), before any DH
+    /// with it. Disabled by default.
+    pub fn set_strict_pubkey_validation(&mut self, strict: bool) -> &mut Self {
+        self.strict_pubkey_validation = strict;
+        self
+    }
+
+    /// Set the maximum length of the messages read and written, in the
+    /// handshake and by the [`TransportState`] created from it. Default to
+    /// 65535 bytes, the maximum of the spec.
+    ///
+    /// Longer messages fail with
+    /// [MessageTooLong](crate::ErrorKind::MessageTooLong), before any
+    /// processing.
+    ///
+    /// # Panics
+    ///
+    /// If `len` is more than 65535.
+    pub fn set_max_message_len(&mut self, len: usize) -> &mut Self {
+        assert!(len <= MAX_MESSAGE_LEN);
+        self.max_message_len = len;
+        self
+    }
+
     /// Set prologue.
     pub fn set_prologue(&mut self, prologue: &'a [u8]) -> &mut Self {
         self.prologue = Some(prologue);
         self
     }
 
+    /// Same as [`set_prologue`](HandshakeStateBuilder::set_prologue).
+    pub fn prologue(&mut self, prologue: &'a [u8]) -> &mut Self {
+        self.set_prologue(prologue)
+    }
+
     /// Set ephemeral key.
     ///
     /// This is not encouraged and usually not necessary. Cf.
@@ -606,14 +1847,46 @@ where
 
     /// Set static key.
     pub fn set_s(&mut self, s: DhKeyPair<D::Key, D::Pubkey>) -> &mut Self {
-        self.s = Some(s);
+        self.s = Some(StaticKey::Owned(s));
         self
     }
 
+    /// Same as [`set_s`](HandshakeStateBuilder::set_s).
+    pub fn local_static(&mut self, s: DhKeyPair<D::Key, D::Pubkey>) -> &mut Self {
+        self.set_s(s)
+    }
+
     /// Set static key.
     pub fn set_s_private(&mut self, s: D::Key) -> &mut Self {
         let public = D::pubkey(&s);
-        self.s = Some((s, public).into());
+        self.s = Some(StaticKey::Owned((s, public).into()));
+        self
+    }
+
+    /// Set static key, shared with other handshake states instead of copied.
+    #[cfg(feature = "use_std")]
+    pub fn set_shared_s(&mut self, s: SharedStaticKey<D>) -> &mut Self {
+        self.s = Some(StaticKey::Shared(s));
+        self
+    }
+
+    /// Set static key, held outside of memory, e.g., in a TPM, so that DH
+    /// operations with it are delegated to `s`.
+    ///
+    /// The public key is queried once, here.
+    #[cfg(feature = "use_std")]
+    pub fn set_opaque_s(&mut self, s: Arc<dyn OpaqueDh<D>>) -> &mut Self {
+        let public = s.pubkey();
+        self.s = Some(StaticKey::Opaque(s, public));
+        self
+    }
+
+    /// Set static key, prepared once for many handshakes, cf.
+    /// [`PrecomputedStatic`]. Its `ss` cache, if any, is used too.
+    #[cfg(feature = "use_std")]
+    pub fn local_static_precomputed(&mut self, s: &PrecomputedStatic<D>) -> &mut Self {
+        self.s = Some(StaticKey::Shared(s.key().clone()));
+        self.static_dh_cache = s.dh_cache().cloned();
         self
     }
 
@@ -626,11 +1899,81 @@ where
     }
 
     /// Set peer static public key.
+    ///
+    /// Required by patterns where it is known ahead of time, e.g., `IK` or
+    /// `KK`. In patterns where the peer sends it, e.g., `XX`, it is the
+    /// expected key instead: reading a different one fails with
+    /// [`StaticKeyMismatch`](crate::ErrorKind::StaticKeyMismatch).
     pub fn set_rs(&mut self, rs: D::Pubkey) -> &mut Self {
         self.rs = Some(rs);
         self
     }
 
+    /// Same as [`set_rs`](HandshakeStateBuilder::set_rs).
+    pub fn remote_static(&mut self, rs: D::Pubkey) -> &mut Self {
+        self.set_rs(rs)
+    }
+
+    /// Push a PSK to the PSK-queue, cf.
+    /// [`HandshakeState::push_psk`]. PSKs are used by the `psk` tokens of
+    /// the pattern in order.
+    ///
+    /// # Panics
+    ///
+    /// If the PSK is not 32 bytes long, or the PSK-queue becomes longer
+    /// than 4.
+    pub fn push_psk(&mut self, psk: &[u8]) -> &mut Self {
+        self.psks.push(Zeroizing::new(U8Array::from_slice(psk)));
+        self
+    }
+
+    /// Ask `provider` for PSKs when the PSK-queue is empty, cf.
+    /// [`HandshakeState::set_psk_provider`].
+    #[cfg(feature = "use_std")]
+    pub fn set_psk_provider(&mut self, provider: Arc<dyn PskProvider<D>>) -> &mut Self {
+        self.psk_provider = Some(provider);
+        self
+    }
+
+    /// Verify the static key of the peer as soon as it is received, cf.
+    /// [`HandshakeState::set_remote_static_verifier`].
+    #[cfg(feature = "use_std")]
+    pub fn set_remote_static_verifier(
+        &mut self,
+        verifier: Arc<dyn RemoteStaticVerifier>,
+    ) -> &mut Self {
+        self.remote_static_verifier = Some(verifier);
+        self
+    }
+
+    /// Verify the signing key of the peer as soon as it is received, cf.
+    /// [`HandshakeState::set_remote_signing_key_verifier`].
+    #[cfg(feature = "use_std")]
+    pub fn set_remote_signing_key_verifier(
+        &mut self,
+        verifier: Arc<dyn RemoteStaticVerifier>,
+    ) -> &mut Self {
+        self.remote_signing_key_verifier = Some(verifier);
+        self
+    }
+
+    /// Take the ephemeral key from `pool`, if the pattern has us send one
+    /// and [`set_e`](HandshakeStateBuilder::set_e) has not been called.
+    ///
+    /// If the pool is empty, the key is generated when it is sent, as
+    /// usual.
+    ///
+    /// # Panics
+    ///
+    /// When building, if [`is_elligator`](EphemeralPool::is_elligator) of
+    /// the pool differs from
+    /// [`set_is_elligator_encoded`](HandshakeStateBuilder::set_is_elligator_encoded).
+    #[cfg(feature = "use_std")]
+    pub fn set_ephemeral_pool(&mut self, pool: Arc<EphemeralPool<D>>) -> &mut Self {
+        self.ephemeral_pool = Some(pool);
+        self
+    }
+
     /// Build [`HandshakeState`].
     ///
     /// # Panics
@@ -644,15 +1987,198 @@ where
         C: Cipher,
         H: Hash,
     {
-        HandshakeState::new(
+        self.build_hybrid_handshake_state()
+    }
+
+    /// Build [`HandshakeState`] with a KEM, for hybrid patterns, e.g.,
+    /// [`noise_xx_hfs`](crate::patterns::noise_xx_hfs).
+    ///
+    /// # Panics
+    ///
+    /// Like [`build_handshake_state`](HandshakeStateBuilder::build_handshake_state).
+    pub fn build_hybrid_handshake_state<C, H, K>(self) -> HandshakeState<D, C, H, K>
+    where
+        C: Cipher,
+        H: Hash,
+        K: Kem,
+    {
+        self.build_impl()
+    }
+
+    /// Build [`HandshakeState`] with a signature scheme, for signature
+    /// patterns, e.g., [`noise_xx_sig`](crate::patterns::noise_xx_sig), signing
+    /// with `signing_key`.
+    ///
+    /// # Panics
+    ///
+    /// Like [`build_handshake_state`](HandshakeStateBuilder::build_handshake_state).
+    pub fn build_signed_handshake_state<C, H, S>(
+        self,
+        signing_key: S::Key,
+    ) -> HandshakeState<D, C, H, NoKem, S>
+    where
+        C: Cipher,
+        H: Hash,
+        S: Signature,
+    {
+        let mut hs = self.build_impl();
+        hs.set_signing_key(signing_key);
+        hs
+    }
+
+    fn build_impl<C, H, K, S>(mut self) -> HandshakeState<D, C, H, K, S>
+    where
+        C: Cipher,
+        H: Hash,
+        K: Kem,
+        S: Signature,
+    {
+        let e = self.take_e();
+        let mut hs = HandshakeState::new_impl(
             self.pattern.unwrap(),
             self.is_initiator.unwrap(),
             self.is_elligator_encoded.unwrap_or(false),
             self.prologue.unwrap(),
             self.s,
-            self.e,
+            e,
             self.rs,
             self.re,
-        )
+        );
+        hs.is_static_elligator_encoded = self.is_static_elligator_encoded.unwrap_or(false);
+        hs.allow_low_order = self.allow_low_order;
+        hs.strict_pubkey_validation = self.strict_pubkey_validation;
+        hs.max_message_len = self.max_message_len;
+        hs.psks = self.psks;
+        #[cfg(feature = "use_std")]
+        if self.static_dh_cache.is_some() {
+            hs.static_dh_cache = self.static_dh_cache;
+        }
+        #[cfg(feature = "use_std")]
+        {
+            hs.psk_provider = self.psk_provider;
+            hs.remote_static_verifier = self.remote_static_verifier;
+            hs.remote_signing_key_verifier = self.remote_signing_key_verifier;
+        }
+        hs
+    }
+
+    /// The ephemeral key set with [`set_e`](HandshakeStateBuilder::set_e),
+    /// or else one from the ephemeral pool.
+    fn take_e(&mut self) -> Option<DhKeyPair<D::Key, D::Pubkey>> {
+        #[cfg(feature = "use_std")]
+        if let Some(pool) = self.ephemeral_pool.take() {
+            assert_eq!(
+                pool.is_elligator(),
+                self.is_elligator_encoded.unwrap_or(false),
+                "ephemeral pool and handshake disagree on Elligator encoding"
+            );
+            if self.e.is_none() && self.sends_e() {
+                return pool.take();
+            }
+        }
+        self.e.take()
+    }
+
+    /// Whether we send an ephemeral key in a message.
+    #[cfg(feature = "use_std")]
+    fn sends_e(&self) -> bool {
+        let pattern = self.pattern.unwrap();
+        let is_initiator = self.is_initiator.unwrap();
+        (0..pattern.get_message_patterns_len()).any(|i| {
+            (i % 2 == 0) == is_initiator
+                && pattern
+                    .get_message_pattern(i)
+                    .iter()
+                    .any(|t| matches!(t, Token::E))
+        })
+    }
+
+    /// Like [`build_handshake_state`](HandshakeStateBuilder::build_handshake_state),
+    /// but first check that the keys required by the pattern have been set,
+    /// i.e., the local static key if it is used, and any keys in pre-messages.
+    ///
+    /// Fails with [`ErrorKind::MissingKey`](crate::ErrorKind::MissingKey) otherwise, instead of panicking
+    /// in the middle of the handshake, and with
+    /// [`ErrorKind::MissingParameter`](crate::ErrorKind::MissingParameter)
+    /// if the pattern, the prologue or the role has not been set.
+    ///
+    /// `K` and `S` are as for
+    /// [`build_hybrid_handshake_state`](HandshakeStateBuilder::build_hybrid_handshake_state)
+    /// and
+    /// [`build_signed_handshake_state`](HandshakeStateBuilder::build_signed_handshake_state),
+    /// [`NoKem`] and [`NoSignature`] for other patterns. The signing key, if
+    /// any, is then set with
+    /// [`HandshakeState::set_signing_key`].
+    pub fn try_build_handshake_state<C, H, K, S>(
+        self,
+    ) -> Result<HandshakeState<D, C, H, K, S>, Error>
+    where
+        C: Cipher,
+        H: Hash,
+        K: Kem,
+        S: Signature,
+    {
+        let (Some(pattern), Some(is_initiator), Some(_)) =
+            (self.pattern, self.is_initiator, self.prologue)
+        else {
+            return Err(Error::missing_parameter());
+        };
+        let (pre_local, pre_remote) = if is_initiator {
+            (pattern.get_pre_i(), pattern.get_pre_r())
+        } else {
+            (pattern.get_pre_r(), pattern.get_pre_i())
+        };
+
+        let mut needs_s = pre_local.iter().any(|t| matches!(t, Token::S));
+        let needs_e = pre_local.iter().any(|t| matches!(t, Token::E));
+        let needs_rs = pre_remote.iter().any(|t| matches!(t, Token::S));
+        let needs_re = pre_remote.iter().any(|t| matches!(t, Token::E));
+        for i in 0..pattern.get_message_patterns_len() {
+            let local_turn = (i % 2 == 0) == is_initiator;
+            for t in pattern.get_message_pattern(i) {
+                needs_s |= match t {
+                    Token::S => local_turn,
+                    Token::SE => is_initiator,
+                    Token::ES => !is_initiator,
+                    Token::SS => true,
+                    _ => false,
+                };
+            }
+        }
+
+        if (needs_s && self.s.is_none())
+            || (needs_e && self.e.is_none())
+            || (needs_rs && self.rs.is_none())
+            || (needs_re && self.re.is_none())
+        {
+            return Err(Error::missing_key());
+        }
+        Ok(self.build_impl())
+    }
+
+    /// Build an initiator, checking the keys like
+    /// [`try_build_handshake_state`](HandshakeStateBuilder::try_build_handshake_state).
+    pub fn build_initiator<C, H, K, S>(mut self) -> Result<HandshakeState<D, C, H, K, S>, Error>
+    where
+        C: Cipher,
+        H: Hash,
+        K: Kem,
+        S: Signature,
+    {
+        self.is_initiator = Some(true);
+        self.try_build_handshake_state()
+    }
+
+    /// Build a responder, checking the keys like
+    /// [`try_build_handshake_state`](HandshakeStateBuilder::try_build_handshake_state).
+    pub fn build_responder<C, H, K, S>(mut self) -> Result<HandshakeState<D, C, H, K, S>, Error>
+    where
+        C: Cipher,
+        H: Hash,
+        K: Kem,
+        S: Signature,
+    {
+        self.is_initiator = Some(false);
+        self.try_build_handshake_state()
     }
 }
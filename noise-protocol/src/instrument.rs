@@ -0,0 +1,86 @@
+//! Metrics emitted via the `metrics` facade crate, if the `metrics` feature is
+//! enabled. Otherwise all of these are no-ops.
+
+use crate::error::ErrorKind;
+
+#[cfg(feature = "metrics")]
+pub use std::time::Instant;
+
+/// Placeholder of [`std::time::Instant`] when metrics are disabled.
+#[cfg(not(feature = "metrics"))]
+#[derive(Copy, Clone)]
+pub struct Instant;
+
+#[cfg(not(feature = "metrics"))]
+impl Instant {
+    pub fn now() -> Self {
+        Instant
+    }
+}
+
+/// The first message of a handshake has been written or read.
+pub fn handshake_started() {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("noise_handshakes_started_total");
+}
+
+/// A handshake has completed successfully.
+pub fn handshake_completed(_started: Instant) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::increment_counter!("noise_handshakes_completed_total");
+        metrics::histogram!(
+            "noise_handshake_duration_seconds",
+            _started.elapsed().as_secs_f64()
+        );
+    }
+}
+
+/// Reading or writing a handshake message has failed.
+pub fn handshake_failed(_kind: ErrorKind) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("noise_handshakes_failed_total", "reason" => reason(_kind));
+}
+
+/// Some transport payload bytes have been encrypted.
+pub fn bytes_sent(_n: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("noise_transport_bytes_total", _n as u64, "direction" => "sent");
+}
+
+/// Some transport payload bytes have been decrypted.
+pub fn bytes_received(_n: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("noise_transport_bytes_total", _n as u64, "direction" => "received");
+}
+
+/// A cipher state has been rekeyed.
+pub fn rekey() {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("noise_rekeys_total");
+}
+
+#[cfg(feature = "metrics")]
+fn reason(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::DH => "dh",
+        ErrorKind::NeedPSK => "need_psk",
+        ErrorKind::Decryption => "decryption",
+        ErrorKind::TooShort => "too_short",
+        ErrorKind::Replay => "replay",
+        ErrorKind::InvalidState => "invalid_state",
+        ErrorKind::MissingKey => "missing_key",
+        ErrorKind::MissingParameter => "missing_parameter",
+        ErrorKind::Kem => "kem",
+        ErrorKind::BufferTooSmall => "buffer_too_small",
+        ErrorKind::NonceExhausted => "nonce_exhausted",
+        ErrorKind::InvalidPublicKey => "invalid_public_key",
+        ErrorKind::WrongTurn => "wrong_turn",
+        ErrorKind::HandshakeComplete => "handshake_complete",
+        ErrorKind::PatternViolation => "pattern_violation",
+        ErrorKind::AuthenticationRejected => "authentication_rejected",
+        ErrorKind::StaticKeyMismatch => "static_key_mismatch",
+        ErrorKind::Signature => "signature",
+        ErrorKind::MessageTooLong => "message_too_long",
+    }
+}
@@ -0,0 +1,72 @@
+//! Export of transport keys for decrypting captured traffic, in a format
+//! similar to `SSLKEYLOGFILE`.
+//!
+//! **Only use this for debugging and testing.** Anyone who can read the key log
+//! can decrypt all logged sessions.
+
+use std::io::Write;
+use std::sync::Mutex;
+
+#[cfg(all(not(debug_assertions), not(feature = "keylog_in_release")))]
+compile_error!(
+    "the `keylog` feature exposes transport keys and is refused in release builds; \
+     also enable `keylog_in_release` if you really mean it"
+);
+
+/// Label of the initiator to responder transport key.
+pub const INITIATOR_LABEL: &str = "NOISE_INITIATOR_TRAFFIC_KEY";
+/// Label of the responder to initiator transport key.
+pub const RESPONDER_LABEL: &str = "NOISE_RESPONDER_TRAFFIC_KEY";
+
+/// A sink of transport keys.
+///
+/// Set one with
+/// [`HandshakeState::set_key_log`](crate::HandshakeState::set_key_log), it
+/// will be called for both keys when the ciphers are split.
+pub trait KeyLog: Send + Sync {
+    /// Log a secret of the session identified by `handshake_hash`.
+    fn log(&self, label: &str, handshake_hash: &[u8], secret: &[u8]);
+}
+
+/// A [`KeyLog`] writing one line per key to a writer:
+///
+/// ```text
+/// <label> <hex handshake hash> <hex key>
+/// ```
+pub struct KeyLogWriter<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> KeyLogWriter<W> {
+    /// Create a new [`KeyLogWriter`].
+    pub fn new(writer: W) -> Self {
+        KeyLogWriter {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> KeyLog for KeyLogWriter<W> {
+    fn log(&self, label: &str, handshake_hash: &[u8], secret: &[u8]) {
+        let mut line =
+            String::with_capacity(label.len() + 2 * (handshake_hash.len() + secret.len()) + 3);
+        line.push_str(label);
+        line.push(' ');
+        push_hex(&mut line, handshake_hash);
+        line.push(' ');
+        push_hex(&mut line, secret);
+        line.push('\n');
+
+        // Errors are ignored, logging keys is best effort.
+        if let Ok(mut w) = self.writer.lock() {
+            let _ = w.write_all(line.as_bytes()).and_then(|_| w.flush());
+        }
+    }
+}
+
+fn push_hex(s: &mut String, data: &[u8]) {
+    use std::fmt::Write;
+    for b in data {
+        write!(s, "{:02x}", b).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::Error;
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, DhKeyPair, Hash, DH};
+
+/// Which key of a [`KeyRing`] is meant.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum KeySlot {
+    /// The current static key.
+    Current,
+    /// The previous static key, still accepted during the overlap period.
+    Previous,
+}
+
+/// A current and an optional previous static key pair.
+///
+/// Used by responders to rotate their static key without downtime: while
+/// clients still use the old public key, both keys are tried when reading the
+/// first handshake message.
+pub struct KeyRing<D: DH> {
+    current: DhKeyPair<D::Key, D::Pubkey>,
+    previous: Option<DhKeyPair<D::Key, D::Pubkey>>,
+}
+
+impl<D: DH> Clone for KeyRing<D> {
+    fn clone(&self) -> Self {
+        KeyRing {
+            current: self.current.clone(),
+            previous: self.previous.clone(),
+        }
+    }
+}
+
+impl<D: DH> KeyRing<D> {
+    /// Create a key ring with only a current key.
+    pub fn new(current: DhKeyPair<D::Key, D::Pubkey>) -> Self {
+        KeyRing {
+            current,
+            previous: None,
+        }
+    }
+
+    /// Create a key ring with a current and a previous key.
+    pub fn with_previous(
+        current: DhKeyPair<D::Key, D::Pubkey>,
+        previous: DhKeyPair<D::Key, D::Pubkey>,
+    ) -> Self {
+        KeyRing {
+            current,
+            previous: Some(previous),
+        }
+    }
+
+    /// Make `new` the current key, and the current key the previous one.
+    ///
+    /// The old previous key, if any, is dropped.
+    pub fn rotate(&mut self, new: DhKeyPair<D::Key, D::Pubkey>) {
+        let old = core::mem::replace(&mut self.current, new);
+        self.previous = Some(old);
+    }
+
+    /// End the overlap period by dropping the previous key.
+    pub fn retire_previous(&mut self) {
+        self.previous = None;
+    }
+
+    /// Get the current key.
+    pub fn current(&self) -> &DhKeyPair<D::Key, D::Pubkey> {
+        &self.current
+    }
+
+    /// Get the previous key, if any.
+    pub fn previous(&self) -> Option<&DhKeyPair<D::Key, D::Pubkey>> {
+        self.previous.as_ref()
+    }
+
+    /// Get the key in a slot.
+    pub fn get(&self, slot: KeySlot) -> Option<&DhKeyPair<D::Key, D::Pubkey>> {
+        match slot {
+            KeySlot::Current => Some(&self.current),
+            KeySlot::Previous => self.previous.as_ref(),
+        }
+    }
+
+    /// Read the first handshake message as responder, trying the current key
+    /// and then the previous one.
+    ///
+    /// `new_state` is called with a static key pair and should build a fresh
+    /// responder [`HandshakeState`] using it. On success, the state that
+    /// accepted the message is returned along with the slot of the key it
+    /// used. If no key works, the error from the current key is returned.
+    ///
+    /// Note that the static key only makes a difference for patterns where
+    /// the first message depends on the responder static key, e.g., `IK`,
+    /// `XK` or `NK`. For others, e.g., `XX`, the current key always matches.
+    pub fn read_message<C, H, F>(
+        &self,
+        mut new_state: F,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<(HandshakeState<D, C, H>, KeySlot), Error>
+    where
+        C: Cipher,
+        H: Hash,
+        F: FnMut(DhKeyPair<D::Key, D::Pubkey>) -> HandshakeState<D, C, H>,
+    {
+        let mut state = new_state(self.current.clone());
+        let err = match state.read_message(data, out) {
+            Ok(()) => return Ok((state, KeySlot::Current)),
+            Err(e) => e,
+        };
+
+        if let Some(ref previous) = self.previous {
+            let mut state = new_state(previous.clone());
+            if state.read_message(data, out).is_ok() {
+                return Ok((state, KeySlot::Previous));
+            }
+        }
+
+        Err(err)
+    }
+}
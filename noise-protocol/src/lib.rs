@@ -7,7 +7,8 @@
 //! [`HandshakeStateBuilder`], call [`HandshakeState::write_message`] and
 //! [`HandshakeState::read_message`] to complete the handshake, and finally call
 //! [`HandshakeState::get_ciphers`] to get a pair of [`CipherState`] to
-//! encrypt/decrypt further transport messages.
+//! encrypt/decrypt further transport messages, or [`TransportState::from_handshake`]
+//! to get a [`TransportState`] that tracks which is which.
 //!
 //! # Crypto Primitives
 //!
@@ -18,26 +19,157 @@
 //!
 //! Other implementations of the crypto primitives can be easily plugged in by
 //! implementing the [`DH`], [`Cipher`] and [`Hash`] traits.
+//!
+//! # Hybrid Forward Secrecy
+//!
+//! Patterns with the `e1` and `ekem1` tokens, e.g., `XXhfs`, additionally
+//! mix the shared secret of a [`Kem`] into the handshake, so that it stays
+//! secret if either the DH or the KEM, e.g., a post-quantum one, is broken.
+//! The KEM is the last type parameter of [`HandshakeState`], and the protocol
+//! name then is, e.g., `Noise_XXhfs_25519+MLKEM768_ChaChaPoly_BLAKE2s`.
+//!
+//! # Signatures
+//!
+//! Patterns with the `sig` token, e.g., `XXsig`, authenticate a party with a
+//! [`Signature`] of the handshake hash instead of a static DH, e.g., when its
+//! identity key is an Ed25519 key that must not be used for DH. The signature
+//! scheme is the last type parameter of [`HandshakeState`], cf.
+//! [`HandshakeStateBuilder::build_signed_handshake_state`], and the protocol
+//! name then is, e.g., `Noise_XXsig_25519+Ed25519_ChaChaPoly_BLAKE2s`.
+//!
+//! # Metrics
+//!
+//! With the `metrics` feature, handshakes started/completed/failed, handshake
+//! latency, transport bytes and rekeys are reported via the
+//! [`metrics`](https://docs.rs/metrics) facade crate. Install a recorder (e.g.
+//! a Prometheus exporter) to collect them.
+//!
+//! # I/O
+//!
+//! With the `tokio_codec` feature, `codec::NoiseCodec` frames and encrypts
+//! transport messages for `tokio_util::codec::Framed`.
+//!
+//! With the `tokio_io` or the `futures_io` feature, `stream::NoiseStream`
+//! runs the handshake over an async byte stream and then implements
+//! `AsyncRead` and `AsyncWrite` for the transport phase. With the `use_std`
+//! feature, `socket::NoiseSocket` does the same over a blocking `Read +
+//! Write` stream.
+//!
+//! # Test Vectors
+//!
+//! With the `testvectors` feature, `testvectors` verifies and generates
+//! test vectors in the JSON format of cacophony and snow.
 
 #![warn(missing_docs)]
 #![cfg_attr(not(feature = "use_std"), no_std)]
 
+#[cfg(feature = "use_std")]
+mod boundedset;
 mod cipherstate;
+#[cfg(feature = "tokio_codec")]
+pub mod codec;
+#[cfg(feature = "use_std")]
+pub mod demux;
+#[cfg(feature = "use_std")]
+mod dhcache;
+#[cfg(feature = "use_std")]
+mod ephemeral;
+mod error;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub mod file;
+pub mod fingerprint;
 mod handshakepattern;
 mod handshakestate;
+mod instrument;
+#[cfg(feature = "keylog")]
+pub mod keylog;
+mod keyring;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub mod negotiation;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+mod obfuscation;
+#[cfg(feature = "use_std")]
+mod opaquedh;
+pub mod padding;
+pub mod pairing;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+mod precompute;
+#[cfg(feature = "use_std")]
+mod psk;
+pub mod puzzle;
+#[cfg(feature = "use_std")]
+pub mod ratelimit;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub mod registry;
+mod rekey;
+#[cfg(feature = "use_std")]
+mod remotestatic;
+#[cfg(feature = "use_std")]
+mod replay;
+mod replayfilter;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+mod resumption;
+mod sessionid;
+#[cfg(feature = "use_std")]
+mod sharedkey;
+#[cfg(feature = "use_std")]
+pub mod socket;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+mod stateio;
+#[cfg(any(feature = "tokio_io", feature = "futures_io"))]
+pub mod stream;
 mod symmetricstate;
+pub mod tai64n;
+#[cfg(feature = "testvectors")]
+pub mod testvectors;
 mod traits;
+mod transportstate;
+pub mod util;
 
 #[cfg(feature = "use_alloc")]
 #[macro_use]
 extern crate alloc;
 
 pub use crate::cipherstate::CipherState;
-pub use crate::traits::{Cipher, Hash, U8Array, DH, DhKeyPair};
+#[cfg(feature = "use_std")]
+pub use crate::dhcache::{MemoryStaticDhCache, StaticDhCache};
+#[cfg(feature = "use_std")]
+pub use crate::ephemeral::{
+    EphemeralObserver, EphemeralOrigin, EphemeralPool, EphemeralReuse, EphemeralReuseDetector,
+};
+pub use crate::keyring::{KeyRing, KeySlot};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub use crate::obfuscation::ObfuscationConfig;
+#[cfg(feature = "use_std")]
+pub use crate::opaquedh::OpaqueDh;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub use crate::precompute::PrecomputedMessage;
+#[cfg(feature = "use_std")]
+pub use crate::psk::PskProvider;
+pub use crate::rekey::{RekeyCheckpoint, RekeyPolicy};
+#[cfg(feature = "use_std")]
+pub use crate::remotestatic::RemoteStaticVerifier;
+#[cfg(feature = "use_std")]
+pub use crate::replay::{MemoryReplayCache, ReplayCache};
+pub use crate::replayfilter::ReplayFilter;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub use crate::resumption::{ResumptionTicket, TicketKey};
+pub use crate::sessionid::{SessionId, SESSION_ID_LEN};
+#[cfg(feature = "use_std")]
+pub use crate::sharedkey::{PrecomputedStatic, SharedStaticKey};
+pub use crate::symmetricstate::SymmetricState;
+pub use crate::traits::{Cipher, DhKeyPair, Hash, Kem, NoKem, NoSignature, Signature, U8Array, DH};
+pub use crate::transportstate::{TransportCheckpoint, TransportState};
 
 /// Handshake patterns.
 pub mod patterns {
     pub use crate::handshakepattern::*;
 }
 
-pub use crate::handshakestate::{Error, ErrorKind, HandshakeState, HandshakeStateBuilder};
+pub use crate::error::{Error, ErrorKind};
+pub use crate::handshakestate::{HandshakeState, HandshakeStateBuilder};
+
+/// Re-export of the [`rand_core`] crate, for [`DH::genkey_with_rng`].
+pub use rand_core;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub use zeroize::Zeroizing;
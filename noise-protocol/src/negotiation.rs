@@ -0,0 +1,234 @@
+//! Framing and negotiation of the NoiseSocket protocol.
+//!
+//! Each handshake message is sent with negotiation data, which the
+//! application uses to agree on the protocol, e.g., its version or the
+//! handshake pattern:
+//!
+//! ```text
+//! negotiation_data_len (2 bytes) || negotiation_data ||
+//! noise_message_len (2 bytes) || noise_message
+//! ```
+//!
+//! Transport messages only have the `noise_message` part. All lengths are
+//! big endian. The initiator sends the first message of a handshake with the
+//! prologue [`Negotiation::initial_prologue`]. The responder then either:
+//!
+//! * accepts it, and replies as usual;
+//! * switches to another protocol and replies with its first message, e.g.,
+//!   from `IK` to [`XXfallback`](crate::patterns::noise_xx_fallback), with
+//!   [`Negotiation::switch_prologue`];
+//! * asks the initiator to retry with another protocol, with an empty
+//!   `noise_message`, after which the initiator starts a new handshake with
+//!   [`Negotiation::retry_prologue`];
+//! * or rejects it, with an empty `noise_message`, and closes the connection.
+//!
+//! The responder tells which in its negotiation data, and both peers bind
+//! the negotiation to the handshake through the prologue.
+//!
+//! Payloads of handshake and transport messages are a body with its length,
+//! and padding, cf. [`encode_body`].
+
+use crate::error::Error;
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+const INIT1: &[u8] = b"NoiseSocketInit1";
+const INIT2: &[u8] = b"NoiseSocketInit2";
+const INIT3: &[u8] = b"NoiseSocketInit3";
+
+/// Maximum length of negotiation data, a Noise message or a body.
+pub const MAX_LEN: usize = 65535;
+
+/// A handshake message, split into its parts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HandshakeMessage<'a> {
+    /// Negotiation data.
+    pub negotiation_data: &'a [u8],
+    /// Noise message, empty for retry or reject.
+    pub noise_message: &'a [u8],
+}
+
+fn put(out: &mut Vec<u8>, data: &[u8]) {
+    assert!(data.len() <= MAX_LEN);
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+fn take(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    if data.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    let data = &data[2..];
+    if data.len() < len {
+        return None;
+    }
+    Some(data.split_at(len))
+}
+
+impl<'a> HandshakeMessage<'a> {
+    /// Create a handshake message.
+    pub fn new(negotiation_data: &'a [u8], noise_message: &'a [u8]) -> Self {
+        HandshakeMessage {
+            negotiation_data,
+            noise_message,
+        }
+    }
+
+    /// Append the encoded message to `out`.
+    ///
+    /// # Panics
+    ///
+    /// If a part is longer than [`MAX_LEN`].
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        put(out, self.negotiation_data);
+        put(out, self.noise_message);
+    }
+
+    /// Decode a message at the start of `data`, returning it and its encoded
+    /// length, or [`None`] if `data` does not contain a whole message yet.
+    pub fn decode(data: &'a [u8]) -> Option<(Self, usize)> {
+        let (negotiation_data, rest) = take(data)?;
+        let (noise_message, rest) = take(rest)?;
+        Some((
+            HandshakeMessage::new(negotiation_data, noise_message),
+            data.len() - rest.len(),
+        ))
+    }
+}
+
+/// Parts of at most [`MAX_LEN`] bytes, so that the message can be encoded.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for HandshakeMessage<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let part = |u: &mut arbitrary::Unstructured<'a>| {
+            let len = u.int_in_range(0..=MAX_LEN.min(u.len()))?;
+            u.bytes(len)
+        };
+        Ok(HandshakeMessage::new(part(u)?, part(u)?))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (2, Some(2 * (2 + MAX_LEN)))
+    }
+}
+
+/// Append a transport message to `out`.
+///
+/// # Panics
+///
+/// If `noise_message` is longer than [`MAX_LEN`].
+pub fn encode_transport_message(noise_message: &[u8], out: &mut Vec<u8>) {
+    put(out, noise_message);
+}
+
+/// Decode a transport message at the start of `data`, returning it and its
+/// encoded length, or [`None`] if `data` does not contain a whole message
+/// yet.
+pub fn decode_transport_message(data: &[u8]) -> Option<(&[u8], usize)> {
+    let (noise_message, rest) = take(data)?;
+    Some((noise_message, data.len() - rest.len()))
+}
+
+/// Encode a payload: the length of `body`, `body`, and zeros up to
+/// `padded_len` bytes, to hide the length of `body`.
+///
+/// # Panics
+///
+/// If `body` is longer than [`MAX_LEN`].
+pub fn encode_body(body: &[u8], padded_len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(padded_len.max(2 + body.len()));
+    put(&mut out, body);
+    if out.len() < padded_len {
+        out.resize(padded_len, 0);
+    }
+    out
+}
+
+/// Decode the body of a payload encoded with [`encode_body`].
+///
+/// Fails with [TooShort](crate::ErrorKind::TooShort) if the payload is too
+/// short for the body length.
+pub fn decode_body(payload: &[u8]) -> Result<&[u8], Error> {
+    take(payload)
+        .map(|(body, _)| body)
+        .ok_or_else(Error::too_short)
+}
+
+/// The negotiation of a NoiseSocket session, to compute the prologues.
+///
+/// Both peers create it from the negotiation data of the first message, and
+/// the initiator adds the Noise message once it is written, the responder
+/// once it is read.
+#[derive(Debug, Clone)]
+pub struct Negotiation {
+    negotiation_data: Vec<u8>,
+    initial_message: Option<Vec<u8>>,
+}
+
+impl Negotiation {
+    /// Start with the negotiation data of the first message.
+    ///
+    /// # Panics
+    ///
+    /// If `negotiation_data` is longer than [`MAX_LEN`].
+    pub fn new(negotiation_data: &[u8]) -> Self {
+        assert!(negotiation_data.len() <= MAX_LEN);
+        Negotiation {
+            negotiation_data: negotiation_data.to_vec(),
+            initial_message: None,
+        }
+    }
+
+    /// Set the Noise message of the first message, needed for
+    /// [`switch_prologue`](Negotiation::switch_prologue) and
+    /// [`retry_prologue`](Negotiation::retry_prologue).
+    ///
+    /// # Panics
+    ///
+    /// If `noise_message` is longer than [`MAX_LEN`].
+    pub fn set_initial_message(&mut self, noise_message: &[u8]) {
+        assert!(noise_message.len() <= MAX_LEN);
+        self.initial_message = Some(noise_message.to_vec());
+    }
+
+    /// Prologue of the first handshake.
+    pub fn initial_prologue(&self) -> Vec<u8> {
+        let mut out = INIT1.to_vec();
+        put(&mut out, &self.negotiation_data);
+        out
+    }
+
+    fn prologue(&self, label: &[u8], responder_negotiation_data: &[u8]) -> Vec<u8> {
+        let initial_message = self
+            .initial_message
+            .as_ref()
+            .expect("Initial message is not set");
+        let mut out = label.to_vec();
+        put(&mut out, &self.negotiation_data);
+        put(&mut out, initial_message);
+        put(&mut out, responder_negotiation_data);
+        out
+    }
+
+    /// Prologue of the handshake the responder switches to, with the
+    /// negotiation data of its reply.
+    ///
+    /// # Panics
+    ///
+    /// If the initial message has not been set.
+    pub fn switch_prologue(&self, responder_negotiation_data: &[u8]) -> Vec<u8> {
+        self.prologue(INIT2, responder_negotiation_data)
+    }
+
+    /// Prologue of the handshake the initiator retries with, with the
+    /// negotiation data of the reply of the responder.
+    ///
+    /// # Panics
+    ///
+    /// If the initial message has not been set.
+    pub fn retry_prologue(&self, responder_negotiation_data: &[u8]) -> Vec<u8> {
+        self.prologue(INIT3, responder_negotiation_data)
+    }
+}
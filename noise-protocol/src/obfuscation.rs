@@ -0,0 +1,39 @@
+/// Configuration of the obfuscation mode of a handshake, cf.
+/// [`HandshakeState::set_obfuscation`](crate::HandshakeState::set_obfuscation).
+///
+/// In this mode, ephemeral keys are always Elligator encoded, and every
+/// handshake payload is followed by `min_padding..=max_padding` random bytes
+/// and the 2-byte big endian length of the padding, so that handshake
+/// messages look uniformly random and have unpredictable lengths. The padding
+/// is encrypted and authenticated with the payload, and stripped when the
+/// message is read.
+///
+/// Both peers must use the obfuscation mode. The payload of a message sent
+/// before any key is mixed, e.g., the first message of `NN`, is not
+/// encrypted, so its padding length is in the clear: use a pattern where the
+/// initiator knows the static key of the responder, e.g., `NK` or `IK`, or a
+/// PSK, to hide it.
+#[derive(Debug, Clone, Copy)]
+pub struct ObfuscationConfig {
+    /// Minimum padding length.
+    pub min_padding: u16,
+    /// Maximum padding length.
+    pub max_padding: u16,
+    /// Fill a buffer with random bytes, e.g., from the OS.
+    pub fill_random: fn(&mut [u8]),
+}
+
+impl ObfuscationConfig {
+    /// Draw the length of the padding of the next message.
+    ///
+    /// # Panics
+    ///
+    /// If `min_padding > max_padding`.
+    pub(crate) fn padding_len(&self) -> usize {
+        assert!(self.min_padding <= self.max_padding);
+        let mut r = [0u8; 4];
+        (self.fill_random)(&mut r);
+        let range = (self.max_padding - self.min_padding) as u32 + 1;
+        (self.min_padding as u32 + u32::from_le_bytes(r) % range) as usize
+    }
+}
@@ -0,0 +1,24 @@
+use crate::error::Error;
+use crate::traits::DH;
+
+/// A static key whose private part is not in memory, e.g., a key in an HSM,
+/// a TPM or a remote signing service, used through a handle.
+///
+/// The handshake never sees the private key, it only asks for the public key,
+/// once, and for the DH operations of the `es`, `se` and `ss` tokens that
+/// involve the static key. Ephemeral keys are still generated in memory.
+///
+/// Set it with
+/// [`HandshakeStateBuilder::set_opaque_s`](crate::HandshakeStateBuilder::set_opaque_s).
+pub trait OpaqueDh<D: DH>: Send + Sync {
+    /// The public key.
+    fn pubkey(&self) -> D::Pubkey;
+
+    /// Perform a DH with the private key and `remote`, as [`DH::dh`] does.
+    /// `is_elligator_encoded` tells whether `remote` is an Elligator
+    /// representative.
+    ///
+    /// The identity is rejected by the handshake state, unless low order keys
+    /// are allowed, so this need not check the output.
+    fn dh(&self, remote: &D::Pubkey, is_elligator_encoded: bool) -> Result<D::Output, Error>;
+}
@@ -0,0 +1,223 @@
+//! Padding of transport messages, to hide the lengths of payloads from
+//! traffic analysis, as the Elligator encoding hides the handshake.
+//!
+//! A padded plaintext is the 2-byte big-endian length of the payload, the
+//! payload, and zeros up to a length chosen by a [`PaddingPolicy`]:
+//!
+//! ```text
+//! | len (2) | payload (len) | zeros |
+//! ```
+//!
+//! The length is encrypted and authenticated with the payload, so the peer
+//! can strip the padding, and an attacker can not change it.
+//!
+//! [`pad`] and [`unpad`] work with any [`CipherState`](crate::CipherState).
+//! [`PaddedTransport`] wraps a [`TransportState`](crate::TransportState)
+//! instead.
+
+use crate::error::Error;
+
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::traits::{Cipher, Hash};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::transportstate::TransportState;
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+/// Length of the length header of a padded plaintext.
+pub const HEADER_LEN: usize = 2;
+
+/// How much to pad.
+pub trait PaddingPolicy {
+    /// The length of a padded plaintext holding `len` bytes, i.e., the
+    /// payload and the header. Must be at least `len`.
+    ///
+    /// The result is capped to what fits into the output buffer and a Noise
+    /// message.
+    fn padded_len(&self, len: usize) -> usize;
+}
+
+/// Pad to a multiple of a block size, e.g., 256 bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockPadding(pub usize);
+
+impl PaddingPolicy for BlockPadding {
+    fn padded_len(&self, len: usize) -> usize {
+        if self.0 == 0 {
+            return len;
+        }
+        match len % self.0 {
+            0 => len,
+            r => len.saturating_add(self.0 - r),
+        }
+    }
+}
+
+/// Pad by a random length, from 0 to `max` bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomPadding {
+    /// Maximum padding length.
+    pub max: u16,
+    /// Fill a buffer with random bytes, e.g., from the OS.
+    pub fill_random: fn(&mut [u8]),
+}
+
+impl PaddingPolicy for RandomPadding {
+    fn padded_len(&self, len: usize) -> usize {
+        let mut r = [0u8; 4];
+        (self.fill_random)(&mut r);
+        len + (u32::from_le_bytes(r) % (u32::from(self.max) + 1)) as usize
+    }
+}
+
+/// Padmé, from _Reducing Metadata Leakage from Encrypted Files and
+/// Communication with PURBs_ (Nikitin et al., 2019).
+///
+/// Pads to a length whose binary representation only has its
+/// `O(log log len)` highest bits set, so the padded length leaks
+/// `O(log log len)` bits about the length, with an overhead of at most 12%.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Padme;
+
+impl PaddingPolicy for Padme {
+    fn padded_len(&self, len: usize) -> usize {
+        if len < 2 {
+            return len;
+        }
+        let e = usize::BITS - 1 - len.leading_zeros();
+        let s = u32::BITS - e.leading_zeros();
+        let mask = (1usize << (e - s)) - 1;
+        (len + mask) & !mask
+    }
+}
+
+/// Write `payload`, padded according to `policy`, to `out`, returning the
+/// padded length. Encrypt `&out[..len]` then.
+///
+/// The padding is capped to the length of `out`.
+///
+/// Fails with [BufferTooSmall](crate::ErrorKind::BufferTooSmall) if `out`
+/// can not hold the payload and the header, or if the payload is longer
+/// than `u16::MAX`.
+pub fn pad<P: PaddingPolicy + ?Sized>(
+    policy: &P,
+    payload: &[u8],
+    out: &mut [u8],
+) -> Result<usize, Error> {
+    let min = HEADER_LEN + payload.len();
+    if payload.len() > usize::from(u16::MAX) || min > out.len() {
+        return Err(Error::buffer_too_small());
+    }
+    let len = policy.padded_len(min).clamp(min, out.len());
+    out[..HEADER_LEN].copy_from_slice(&(payload.len() as u16).to_be_bytes());
+    out[HEADER_LEN..min].copy_from_slice(payload);
+    out[min..len].fill(0);
+    Ok(len)
+}
+
+/// Get the payload of a decrypted padded plaintext.
+///
+/// Fails with [TooShort](crate::ErrorKind::TooShort) if the header is
+/// missing, or says the payload is longer than the plaintext.
+pub fn unpad(padded: &[u8]) -> Result<&[u8], Error> {
+    if padded.len() < HEADER_LEN {
+        return Err(Error::too_short());
+    }
+    let len = usize::from(u16::from_be_bytes([padded[0], padded[1]]));
+    padded[HEADER_LEN..].get(..len).ok_or_else(Error::too_short)
+}
+
+/// A [`TransportState`] that pads every message according to a
+/// [`PaddingPolicy`].
+///
+/// Both peers must pad. Message lengths only depend on the policy, the
+/// payload lengths are hidden.
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub struct PaddedTransport<C: Cipher, H: Hash, P: PaddingPolicy> {
+    transport: TransportState<C, H>,
+    policy: P,
+    buf: Vec<u8>,
+}
+
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+impl<C, H, P> PaddedTransport<C, H, P>
+where
+    C: Cipher,
+    H: Hash,
+    P: PaddingPolicy,
+{
+    /// Pad the messages of `transport` according to `policy`.
+    pub fn new(transport: TransportState<C, H>, policy: P) -> Self {
+        PaddedTransport {
+            transport,
+            policy,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Pad and encrypt `payload` into `out`, returning the length of the
+    /// message.
+    ///
+    /// The padding is capped so that the message fits into `out` and into
+    /// the [maximum](TransportState::max_message_len) message length of the
+    /// transport.
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`TransportState::send`], and
+    /// [BufferTooSmall](crate::ErrorKind::BufferTooSmall) if the payload and
+    /// the header do not fit.
+    pub fn send(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        let max = out
+            .len()
+            .min(self.transport.max_message_len())
+            .checked_sub(self.transport.overhead())
+            .ok_or_else(Error::buffer_too_small)?;
+        self.buf.resize(max, 0);
+        let len = pad(&self.policy, payload, &mut self.buf)?;
+        self.transport.send(&self.buf[..len], out)
+    }
+
+    /// Decrypt `msg` and strip its padding, writing the payload to `out`
+    /// and returning its length.
+    ///
+    /// The payload length is only known after decryption, so `out` must
+    /// have room for the longest possible payload, i.e., `msg.len() -
+    /// self.overhead()` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`TransportState::recv`], and [TooShort](crate::ErrorKind::TooShort)
+    /// if the padding is malformed.
+    pub fn recv(&mut self, msg: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        let max = msg
+            .len()
+            .checked_sub(self.overhead())
+            .ok_or_else(Error::too_short)?;
+        if max > out.len() {
+            return Err(Error::buffer_too_small());
+        }
+        self.buf.resize(msg.len(), 0);
+        let len = self.transport.recv(msg, &mut self.buf)?;
+        let payload = unpad(&self.buf[..len])?;
+        out[..payload.len()].copy_from_slice(payload);
+        Ok(payload.len())
+    }
+
+    /// The minimum length that [`send`](PaddedTransport::send) adds to a
+    /// payload, i.e., the header and the overhead of the transport.
+    pub fn overhead(&self) -> usize {
+        HEADER_LEN + self.transport.overhead()
+    }
+
+    /// The wrapped transport state, e.g., to get the handshake hash.
+    pub fn transport(&mut self) -> &mut TransportState<C, H> {
+        &mut self.transport
+    }
+
+    /// Get the wrapped transport state back.
+    pub fn into_inner(self) -> TransportState<C, H> {
+        self.transport
+    }
+}
@@ -0,0 +1,141 @@
+//! Secure pairing of consumer devices, e.g., over Bluetooth LE.
+//!
+//! A [`PairingMode`] picks the handshake pattern:
+//!
+//! * [`OutOfBand`](PairingMode::OutOfBand): both devices already share a
+//!   high-entropy secret, e.g., from an NFC tag or a QR code, which is used as
+//!   the PSK of `NNpsk0`.
+//! * [`NumericComparison`](PairingMode::NumericComparison): the devices run
+//!   `XX`, then both display a [`ShortAuthString`] and the user checks that
+//!   they match. This detects a man in the middle, who can not make both
+//!   handshakes end with the same hash except by chance, i.e., with
+//!   probability 10⁻⁶. The exchanged static keys can then be stored for later
+//!   connections with, e.g., `KK`.
+//! * [`NumericComparisonPsk`](PairingMode::NumericComparisonPsk): the same
+//!   with `XXpsk3`, for devices that also share a secret, e.g., a fixed code
+//!   of the product.
+//!
+//! After the user has confirmed that the codes match on a device, it sends
+//! its [`confirmation_tag`] to the peer, which checks it with
+//! [`verify_confirmation_tag`]. A device without a display or a button thus
+//! learns that the pairing was confirmed on the other side. Send the tags as
+//! transport messages, so they are encrypted.
+
+use crate::handshakepattern::{noise_nn_psk0, noise_xx, noise_xx_psk3, HandshakePattern};
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, U8Array, DH};
+use core::fmt::{Display, Error as FmtError, Formatter};
+
+const SAS_MODULUS: u64 = 1_000_000;
+
+/// How devices authenticate each other while pairing.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PairingMode {
+    /// `NNpsk0` with a secret shared out-of-band.
+    OutOfBand,
+    /// `XX` and comparison of a [`ShortAuthString`].
+    NumericComparison,
+    /// `XXpsk3` and comparison of a [`ShortAuthString`].
+    NumericComparisonPsk,
+}
+
+impl PairingMode {
+    /// The handshake pattern of this mode.
+    pub const fn pattern(self) -> HandshakePattern {
+        match self {
+            PairingMode::OutOfBand => noise_nn_psk0(),
+            PairingMode::NumericComparison => noise_xx(),
+            PairingMode::NumericComparisonPsk => noise_xx_psk3(),
+        }
+    }
+
+    /// Whether the user must compare a [`ShortAuthString`].
+    pub const fn needs_comparison(self) -> bool {
+        !matches!(self, PairingMode::OutOfBand)
+    }
+}
+
+/// A six digit code for the user to compare on both devices.
+///
+/// Display it with leading zeros, e.g., `042817`, as its [`Display`] impl
+/// does.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ShortAuthString(u32);
+
+impl ShortAuthString {
+    /// Derive the code from a handshake hash.
+    ///
+    /// # Panics
+    ///
+    /// If `handshake_hash` is longer than the block length of `H`.
+    pub fn from_handshake_hash<H: Hash>(handshake_hash: &[u8]) -> Self {
+        let out = H::hmac(handshake_hash, b"noise pairing sas");
+        let v = u64::from_be_bytes(out.as_slice()[..8].try_into().unwrap());
+        ShortAuthString((v % SAS_MODULUS) as u32)
+    }
+
+    /// The code as a number below 1000000.
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+impl Display for ShortAuthString {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(fmt, "{:06}", self.0)
+    }
+}
+
+/// The code to compare after a completed handshake.
+///
+/// # Panics
+///
+/// If the handshake has not completed.
+pub fn short_auth_string<D: DH, C: Cipher, H: Hash>(
+    hs: &HandshakeState<D, C, H>,
+) -> ShortAuthString {
+    assert!(hs.completed());
+    ShortAuthString::from_handshake_hash::<H>(hs.get_hash())
+}
+
+fn tag<H: Hash>(handshake_hash: &[u8], initiator: bool) -> H::Output {
+    let role: &[u8] = if initiator {
+        b"initiator"
+    } else {
+        b"responder"
+    };
+    H::hmac_many(handshake_hash, &[b"noise pairing confirm ", role])
+}
+
+/// The tag proving to the peer that the user has confirmed the pairing on
+/// this device.
+///
+/// Only send it after the user has confirmed, and never if the codes differ.
+///
+/// # Panics
+///
+/// If the handshake has not completed.
+pub fn confirmation_tag<D: DH, C: Cipher, H: Hash>(hs: &HandshakeState<D, C, H>) -> H::Output {
+    assert!(hs.completed());
+    tag::<H>(hs.get_hash(), hs.get_is_initiator())
+}
+
+/// Check the [`confirmation_tag`] of the peer.
+///
+/// # Panics
+///
+/// If the handshake has not completed.
+pub fn verify_confirmation_tag<D: DH, C: Cipher, H: Hash>(
+    hs: &HandshakeState<D, C, H>,
+    peer_tag: &[u8],
+) -> bool {
+    assert!(hs.completed());
+    let expected = tag::<H>(hs.get_hash(), !hs.get_is_initiator());
+    let expected = expected.as_slice();
+    expected.len() == peer_tag.len()
+        && expected
+            .iter()
+            .zip(peer_tag)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
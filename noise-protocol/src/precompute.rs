@@ -0,0 +1,117 @@
+use crate::error::Error;
+use crate::handshakestate::HandshakeState;
+use crate::stateio::Reader;
+use crate::traits::{Cipher, DhKeyPair, Hash, DH};
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+const VERSION: u8 = 2;
+
+/// A first handshake message generated ahead of time, together with the
+/// state needed to continue the handshake.
+///
+/// Generating the first message involves generating an ephemeral key (and
+/// possibly finding an Elligator representative), DH and encryption. Clients
+/// for which connection latency matters can do this in advance, e.g., when
+/// idle, and send the message immediately on connect:
+///
+/// 1. Create an initiator [`HandshakeState`] and call
+///    [`precompute_first_message`](HandshakeState::precompute_first_message).
+/// 2. Optionally, save it with [`to_bytes`](PrecomputedMessage::to_bytes) and
+///    load it with [`from_bytes`](PrecomputedMessage::from_bytes).
+/// 3. On connect, send [`message`](PrecomputedMessage::message) and
+///    [`resume`](PrecomputedMessage::resume) the handshake.
+///
+/// Each precomputed message must be used at most once. The saved state
+/// contains the ephemeral private key and the chaining key, so it must be
+/// protected like a private key. The static private key is not included and
+/// must be passed to [`resume`](PrecomputedMessage::resume). Hooks like the
+/// replay cache are not included either.
+pub struct PrecomputedMessage {
+    message: Vec<u8>,
+    state: Vec<u8>,
+}
+
+impl PrecomputedMessage {
+    /// The first handshake message, to be sent to the responder.
+    pub fn message(&self) -> &[u8] {
+        &self.message
+    }
+
+    /// Encode, e.g., for storage.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 4 + self.message.len() + self.state.len());
+        out.push(VERSION);
+        out.extend_from_slice(&(self.message.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.message);
+        out.extend_from_slice(&self.state);
+        out
+    }
+
+    /// Decode a [`PrecomputedMessage`] encoded with
+    /// [`to_bytes`](PrecomputedMessage::to_bytes).
+    ///
+    /// The state is only fully checked by
+    /// [`resume`](PrecomputedMessage::resume).
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(data);
+        let parse = |r: &mut Reader| -> Option<Self> {
+            if r.u8()? != VERSION {
+                return None;
+            }
+            let mut len = [0u8; 4];
+            len.copy_from_slice(r.take(4)?);
+            let message = r.take(u32::from_be_bytes(len) as usize)?.to_vec();
+            let state = r.rest().to_vec();
+            Some(PrecomputedMessage { message, state })
+        };
+        parse(&mut r).ok_or_else(Error::invalid_state)
+    }
+
+    /// Restore the [`HandshakeState`] to read the responder's reply.
+    ///
+    /// `s` is the static key pair of the initiator, if the pattern has one.
+    ///
+    /// # Error Kinds
+    ///
+    /// - [InvalidState](crate::ErrorKind::InvalidState): The state is
+    ///   malformed, was created for a different protocol, or `s` does not
+    ///   match.
+    pub fn resume<D, C, H>(
+        &self,
+        s: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    ) -> Result<HandshakeState<D, C, H>, Error>
+    where
+        D: DH,
+        C: Cipher,
+        H: Hash,
+    {
+        let mut r = Reader::new(&self.state);
+        match HandshakeState::decode_state(&mut r, s) {
+            Some(hs) if r.is_empty() => Ok(hs),
+            _ => Err(Error::invalid_state()),
+        }
+    }
+}
+
+impl<D, C, H> HandshakeState<D, C, H>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+{
+    /// Write the first handshake message now, to be sent later, cf.
+    /// [`PrecomputedMessage`].
+    ///
+    /// # Panics
+    ///
+    /// If this is not an initiator that has not written any message yet.
+    pub fn precompute_first_message(mut self, payload: &[u8]) -> Result<PrecomputedMessage, Error> {
+        assert!(self.get_is_initiator() && self.message_index() == 0);
+        let message = self.write_message_vec(payload)?;
+        let mut state = Vec::new();
+        self.encode_state(&mut state);
+        Ok(PrecomputedMessage { message, state })
+    }
+}
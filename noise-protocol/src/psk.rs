@@ -0,0 +1,33 @@
+use crate::traits::DH;
+
+/// A source of PSKs, asked when a `psk` token is processed and the PSK-queue,
+/// cf. [`HandshakeState::push_psk`](crate::HandshakeState::push_psk), is
+/// empty.
+///
+/// Unlike the queue, which must be filled before the handshake starts, a
+/// provider can select the PSK from what has been learned so far, e.g., a
+/// responder of `IKpsk2` can look up the PSK bound to the static key of the
+/// initiator, sent in the first message, without staging the PSKs of every
+/// peer.
+///
+/// Set a provider with
+/// [`HandshakeState::set_psk_provider`](crate::HandshakeState::set_psk_provider).
+/// If it returns [`None`], the message fails with
+/// [`NeedPSK`](crate::ErrorKind::NeedPSK).
+pub trait PskProvider<D: DH>: Send + Sync {
+    /// Get the PSK of the `psk` token at `position`, i.e., `n` of the `pskn`
+    /// modifier: 0 at the start of the first message, or else the number of
+    /// the message it ends, counting from 1. `remote_static` is the static
+    /// key of the peer, if known at that point.
+    fn psk(&self, position: usize, remote_static: Option<&D::Pubkey>) -> Option<[u8; 32]>;
+}
+
+impl<D, F> PskProvider<D> for F
+where
+    D: DH,
+    F: Fn(usize, Option<&D::Pubkey>) -> Option<[u8; 32]> + Send + Sync,
+{
+    fn psk(&self, position: usize, remote_static: Option<&D::Pubkey>) -> Option<[u8; 32]> {
+        self(position, remote_static)
+    }
+}
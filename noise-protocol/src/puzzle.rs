@@ -0,0 +1,144 @@
+//! A proof-of-work puzzle to throttle handshake floods.
+//!
+//! Rate limiting by source, cf. [`ratelimit`](crate::ratelimit), does not
+//! work for initiators whose source address says nothing, e.g., behind Tor.
+//! A responder can instead ask them to do some work before it does any:
+//!
+//! 1. The responder sends a [`Challenge`] with a difficulty and a nonce.
+//! 2. The initiator finds a [`Solution`] with [`Challenge::solve`], which
+//!    takes about `2 ^ difficulty` hash computations, and sends it along with
+//!    its first handshake message.
+//! 3. The responder checks it with [`Challenge::verify`], which takes one
+//!    hash computation, before reading the handshake message.
+//!
+//! Both peers then use [`bind_prologue`] to bind the challenge and the
+//! solution to the handshake, so a solution can not be moved to another
+//! handshake with a different challenge.
+//!
+//! Nonces must be unpredictable, and the responder must only accept nonces it
+//! has issued, and each solution only once. A stateless responder can derive
+//! nonces with a MAC over the initiator address and a coarse timestamp, and
+//! use a [`ReplayCache`](crate::ReplayCache) for solutions.
+
+use crate::traits::{Hash, U8Array};
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+const DOMAIN: &[u8] = b"NoisePuzzle";
+
+/// Length of an encoded [`Challenge`].
+pub const CHALLENGE_LEN: usize = 17;
+
+/// Length of an encoded [`Solution`].
+pub const SOLUTION_LEN: usize = 8;
+
+/// A puzzle sent by the responder.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Challenge {
+    difficulty: u8,
+    nonce: [u8; 16],
+}
+
+/// A solution to a [`Challenge`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Solution(u64);
+
+impl Challenge {
+    /// Create a challenge.
+    ///
+    /// `difficulty` is the number of leading zero bits required of the hash,
+    /// so the expected work doubles with each step.
+    pub fn new(difficulty: u8, nonce: [u8; 16]) -> Self {
+        Challenge { difficulty, nonce }
+    }
+
+    /// The difficulty.
+    pub fn difficulty(&self) -> u8 {
+        self.difficulty
+    }
+
+    /// The nonce.
+    pub fn nonce(&self) -> &[u8; 16] {
+        &self.nonce
+    }
+
+    /// Encode the challenge.
+    pub fn to_bytes(&self) -> [u8; CHALLENGE_LEN] {
+        let mut out = [0u8; CHALLENGE_LEN];
+        out[0] = self.difficulty;
+        out[1..].copy_from_slice(&self.nonce);
+        out
+    }
+
+    /// Decode a challenge encoded with [`to_bytes`](Challenge::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() != CHALLENGE_LEN {
+            return None;
+        }
+        Some(Challenge {
+            difficulty: data[0],
+            nonce: data[1..].try_into().unwrap(),
+        })
+    }
+
+    fn leading_zeros<H: Hash>(&self, counter: u64) -> u32 {
+        let mut h = H::default();
+        h.input(DOMAIN);
+        h.input(&self.to_bytes());
+        h.input(&counter.to_be_bytes());
+        let output = h.result();
+
+        let mut zeros = 0;
+        for &b in output.as_slice() {
+            zeros += b.leading_zeros();
+            if b != 0 {
+                break;
+            }
+        }
+        zeros
+    }
+
+    /// Solve the challenge with the hash function `H`.
+    ///
+    /// # Panics
+    ///
+    /// If the difficulty is larger than the output of `H`, in bits.
+    pub fn solve<H: Hash>(&self) -> Solution {
+        assert!(self.difficulty as usize <= H::hash_len() * 8);
+        let mut counter = 0u64;
+        while self.leading_zeros::<H>(counter) < self.difficulty as u32 {
+            counter += 1;
+        }
+        Solution(counter)
+    }
+
+    /// Check a solution with the hash function `H`.
+    pub fn verify<H: Hash>(&self, solution: &Solution) -> bool {
+        self.leading_zeros::<H>(solution.0) >= self.difficulty as u32
+    }
+}
+
+impl Solution {
+    /// Encode the solution.
+    pub fn to_bytes(&self) -> [u8; SOLUTION_LEN] {
+        self.0.to_be_bytes()
+    }
+
+    /// Decode a solution encoded with [`to_bytes`](Solution::to_bytes).
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        Some(Solution(u64::from_be_bytes(data.try_into().ok()?)))
+    }
+}
+
+/// The prologue of a handshake preceded by a puzzle: `prologue`, followed by
+/// the challenge and the solution.
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub fn bind_prologue(prologue: &[u8], challenge: &Challenge, solution: &Solution) -> Vec<u8> {
+    let mut out = Vec::with_capacity(prologue.len() + DOMAIN.len() + CHALLENGE_LEN + SOLUTION_LEN);
+    out.extend_from_slice(prologue);
+    out.extend_from_slice(DOMAIN);
+    out.extend_from_slice(&challenge.to_bytes());
+    out.extend_from_slice(&solution.to_bytes());
+    out
+}
@@ -0,0 +1,350 @@
+//! Admission control for new handshakes, e.g., against DoS.
+//!
+//! A responder does at least one DH for every first handshake message it
+//! reads, so a flood of them is expensive. A [`HandshakePolicy`] is asked
+//! what to do with each new handshake before any cryptography happens, cf.
+//! [`Demux::route_from`](crate::demux::Demux::route_from). [`RateLimiter`] is
+//! a policy that counts attempts per source.
+//!
+//! A [`HandshakeLimiter`] bounds the handshakes in flight and the memory they
+//! use, whatever their source.
+
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash as NoiseHash, DH};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// What to do with a new handshake.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Admission {
+    /// Process it.
+    Accept,
+    /// Do not process it, but reply with a challenge, e.g., a cookie proving
+    /// that the initiator can receive at its source address like the cookie
+    /// reply of WireGuard, or a [`puzzle`](crate::puzzle).
+    Challenge,
+    /// Silently drop it.
+    Drop,
+    /// Process it after a delay.
+    Delay(Duration),
+}
+
+/// Decides what to do with new handshakes from a source `A`, e.g., a
+/// [`SocketAddr`](std::net::SocketAddr) or [`IpAddr`](std::net::IpAddr).
+pub trait HandshakePolicy<A>: Send + Sync {
+    /// Called for every new handshake from `source`.
+    ///
+    /// `has_cookie` is whether the initiator has answered a cookie challenge,
+    /// as checked by the application.
+    fn admit(&self, source: &A, has_cookie: bool) -> Admission;
+}
+
+impl<A, F> HandshakePolicy<A> for F
+where
+    F: Fn(&A, bool) -> Admission + Send + Sync,
+{
+    fn admit(&self, source: &A, has_cookie: bool) -> Admission {
+        self(source, has_cookie)
+    }
+}
+
+/// Handshake attempts seen from a source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AttemptRate {
+    /// Number of attempts in the current window, including this one.
+    pub attempts: u32,
+    /// Length of the window.
+    pub window: Duration,
+}
+
+struct Window {
+    start: Instant,
+    attempts: u32,
+}
+
+/// A [`HandshakePolicy`] counting attempts per source in fixed windows, and
+/// leaving the decision to a function of the source, the rate and whether
+/// there is a cookie.
+///
+/// Up to `capacity` sources are tracked. When there are more, new sources
+/// are reported with `u32::MAX` attempts, so they can be challenged.
+pub struct RateLimiter<A, F> {
+    windows: Mutex<HashMap<A, Window>>,
+    window: Duration,
+    capacity: usize,
+    decide: F,
+}
+
+impl<A, F> RateLimiter<A, F>
+where
+    A: Hash + Eq + Clone + Send,
+    F: Fn(&A, AttemptRate, bool) -> Admission + Send + Sync,
+{
+    /// Create a rate limiter.
+    pub fn new(window: Duration, capacity: usize, decide: F) -> Self {
+        RateLimiter {
+            windows: Mutex::new(HashMap::new()),
+            window,
+            capacity,
+            decide,
+        }
+    }
+
+    fn count(&self, source: &A) -> u32 {
+        let mut windows = match self.windows.lock() {
+            Ok(w) => w,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let now = Instant::now();
+        if !windows.contains_key(source) && windows.len() >= self.capacity {
+            windows.retain(|_, w| now.duration_since(w.start) < self.window);
+            if windows.len() >= self.capacity {
+                return u32::MAX;
+            }
+        }
+        let w = windows.entry(source.clone()).or_insert(Window {
+            start: now,
+            attempts: 0,
+        });
+        if now.duration_since(w.start) >= self.window {
+            w.start = now;
+            w.attempts = 0;
+        }
+        w.attempts = w.attempts.saturating_add(1);
+        w.attempts
+    }
+}
+
+/// Decision function of [`RateLimiter::with_limit`].
+pub type BoxedDecide<A> = Box<dyn Fn(&A, AttemptRate, bool) -> Admission + Send + Sync>;
+
+impl<A> RateLimiter<A, BoxedDecide<A>>
+where
+    A: Hash + Eq + Clone + Send,
+{
+    /// A rate limiter that accepts up to `limit` attempts per source and
+    /// window, and challenges further attempts without a cookie.
+    pub fn with_limit(window: Duration, capacity: usize, limit: u32) -> Self {
+        RateLimiter::new(
+            window,
+            capacity,
+            Box::new(move |_: &A, rate: AttemptRate, has_cookie: bool| {
+                if rate.attempts <= limit || has_cookie {
+                    Admission::Accept
+                } else {
+                    Admission::Challenge
+                }
+            }),
+        )
+    }
+}
+
+impl<A, F> HandshakePolicy<A> for RateLimiter<A, F>
+where
+    A: Hash + Eq + Clone + Send,
+    F: Fn(&A, AttemptRate, bool) -> Admission + Send + Sync,
+{
+    fn admit(&self, source: &A, has_cookie: bool) -> Admission {
+        let rate = AttemptRate {
+            attempts: self.count(source),
+            window: self.window,
+        };
+        (self.decide)(source, rate, has_cookie)
+    }
+}
+
+/// Limits of a [`HandshakeLimiter`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HandshakeLimits {
+    /// Maximum number of handshakes in flight.
+    pub max_handshakes: usize,
+    /// Maximum memory used by handshakes in flight, in bytes.
+    pub max_memory: usize,
+    /// Handshakes in flight for longer are expired.
+    pub timeout: Duration,
+}
+
+struct InFlight {
+    handshakes: HashMap<u64, (Instant, usize)>,
+    memory: usize,
+    next_id: u64,
+}
+
+struct Shared {
+    limits: HandshakeLimits,
+    state: Mutex<InFlight>,
+    released: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, InFlight> {
+        match self.state.lock() {
+            Ok(s) => s,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn expire(&self, state: &mut InFlight) -> usize {
+        let now = Instant::now();
+        let before = state.handshakes.len();
+        let timeout = self.limits.timeout;
+        let mut freed = 0;
+        state.handshakes.retain(|_, &mut (started, memory)| {
+            let keep = now.duration_since(started) < timeout;
+            if !keep {
+                freed += memory;
+            }
+            keep
+        });
+        state.memory -= freed;
+        let expired = before - state.handshakes.len();
+        if expired > 0 {
+            self.released.notify_all();
+        }
+        expired
+    }
+
+    fn try_acquire(
+        self: &Arc<Self>,
+        state: &mut InFlight,
+        memory: usize,
+    ) -> Option<HandshakePermit> {
+        if state.handshakes.len() >= self.limits.max_handshakes
+            || state.memory + memory > self.limits.max_memory
+        {
+            return None;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.handshakes.insert(id, (Instant::now(), memory));
+        state.memory += memory;
+        Some(HandshakePermit {
+            shared: self.clone(),
+            id,
+        })
+    }
+}
+
+/// Tracks handshakes in flight and their memory, so that new handshakes can
+/// be rejected or queued beyond the configured [`HandshakeLimits`].
+///
+/// Acquire a [`HandshakePermit`] before starting a handshake, and keep it
+/// until the handshake has completed or failed. Handshakes stalled for longer
+/// than the timeout are expired: their permits no longer count against the
+/// limits, and [`is_expired`](HandshakePermit::is_expired) tells their owner
+/// to abort them.
+///
+/// Cloning a limiter only clones a reference, so all clones share the same
+/// limits.
+#[derive(Clone)]
+pub struct HandshakeLimiter {
+    shared: Arc<Shared>,
+}
+
+impl HandshakeLimiter {
+    /// Create a limiter.
+    pub fn new(limits: HandshakeLimits) -> Self {
+        HandshakeLimiter {
+            shared: Arc::new(Shared {
+                limits,
+                state: Mutex::new(InFlight {
+                    handshakes: HashMap::new(),
+                    memory: 0,
+                    next_id: 0,
+                }),
+                released: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Memory used by a [`HandshakeState`], to pass to
+    /// [`try_acquire`](HandshakeLimiter::try_acquire), not counting buffers
+    /// of the application.
+    pub fn handshake_memory<D: DH, C: Cipher, H: NoiseHash>() -> usize {
+        core::mem::size_of::<HandshakeState<D, C, H>>()
+    }
+
+    /// Acquire a permit for a handshake using `memory` bytes, if within the
+    /// limits.
+    pub fn try_acquire(&self, memory: usize) -> Option<HandshakePermit> {
+        let mut state = self.shared.lock();
+        self.shared.expire(&mut state);
+        self.shared.try_acquire(&mut state, memory)
+    }
+
+    /// Like [`try_acquire`](HandshakeLimiter::try_acquire), but wait up to
+    /// `wait` for other handshakes to finish.
+    pub fn acquire_timeout(&self, memory: usize, wait: Duration) -> Option<HandshakePermit> {
+        if memory > self.shared.limits.max_memory {
+            return None;
+        }
+        let deadline = Instant::now() + wait;
+        let mut state = self.shared.lock();
+        loop {
+            self.shared.expire(&mut state);
+            if let Some(permit) = self.shared.try_acquire(&mut state, memory) {
+                return Some(permit);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            // Also wake up when the oldest handshake expires.
+            let wait = state
+                .handshakes
+                .values()
+                .map(|&(started, _)| {
+                    (started + self.shared.limits.timeout).saturating_duration_since(now)
+                })
+                .min()
+                .map_or(deadline - now, |w| w.min(deadline - now));
+            state = match self.shared.released.wait_timeout(state, wait) {
+                Ok((s, _)) => s,
+                Err(poisoned) => poisoned.into_inner().0,
+            };
+        }
+    }
+
+    /// Expire stalled handshakes, returning how many.
+    pub fn expire(&self) -> usize {
+        let mut state = self.shared.lock();
+        self.shared.expire(&mut state)
+    }
+
+    /// Number of handshakes in flight.
+    pub fn in_flight(&self) -> usize {
+        self.shared.lock().handshakes.len()
+    }
+
+    /// Memory used by handshakes in flight.
+    pub fn memory(&self) -> usize {
+        self.shared.lock().memory
+    }
+}
+
+/// A handshake in flight, cf. [`HandshakeLimiter`]. Released when dropped.
+pub struct HandshakePermit {
+    shared: Arc<Shared>,
+    id: u64,
+}
+
+impl HandshakePermit {
+    /// Whether the handshake has been expired, and should be aborted.
+    pub fn is_expired(&self) -> bool {
+        let mut state = self.shared.lock();
+        self.shared.expire(&mut state);
+        !state.handshakes.contains_key(&self.id)
+    }
+}
+
+impl Drop for HandshakePermit {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        if let Some((_, memory)) = state.handshakes.remove(&self.id) {
+            state.memory -= memory;
+            self.shared.released.notify_all();
+        }
+    }
+}
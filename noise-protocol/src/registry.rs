@@ -0,0 +1,392 @@
+//! A runtime registry of crypto suites, for choosing primitives by name.
+//!
+//! [`HandshakeState`] is generic over its primitives, so they must be known at
+//! compile time. A [`Registry`] maps names to concrete instantiations
+//! registered by downstream crates, so a protocol name like
+//! `Noise_XX_25519_ChaChaPoly_BLAKE2s` can be turned into a handshake at
+//! runtime:
+//!
+//! ```ignore
+//! let mut registry = Registry::new();
+//! registry.register::<X25519, ChaCha20Poly1305, Blake2s>();
+//! let hs = registry.new_handshake("Noise_NN_25519_ChaChaPoly_BLAKE2s", &params)?;
+//! ```
+
+use crate::cipherstate::CipherState;
+use crate::error::Error;
+use crate::handshakepattern::{by_name, HandshakePattern, Token};
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, U8Array, DH};
+use core::fmt::{Display, Error as FmtError, Formatter};
+
+#[cfg(feature = "use_alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+/// Object safe interface of a [`HandshakeState`].
+pub trait DynHandshakeState {
+    /// Cf. [`HandshakeState::write_message_vec`].
+    fn write_message_vec(&mut self, payload: &[u8]) -> Result<Vec<u8>, Error>;
+    /// Cf. [`HandshakeState::read_message_vec`].
+    fn read_message_vec(&mut self, data: &[u8]) -> Result<Vec<u8>, Error>;
+    /// Cf. [`HandshakeState::completed`].
+    fn completed(&self) -> bool;
+    /// Cf. [`HandshakeState::is_write_turn`].
+    fn is_write_turn(&self) -> bool;
+    /// Cf. [`HandshakeState::get_next_message_overhead`].
+    fn get_next_message_overhead(&self) -> usize;
+    /// Cf. [`HandshakeState::get_hash`].
+    fn get_hash(&self) -> &[u8];
+    /// Cf. [`HandshakeState::get_rs`].
+    fn get_rs(&self) -> Option<Vec<u8>>;
+    /// Cf. [`HandshakeState::get_ciphers`].
+    fn get_ciphers(&self) -> (Box<dyn DynCipherState>, Box<dyn DynCipherState>);
+}
+
+/// Object safe interface of a [`CipherState`].
+///
+/// Cipher states are [`Send`], so the sending and receiving halves of a
+/// session can be used from different threads.
+pub trait DynCipherState: Send {
+    /// Cf. [`CipherState::encrypt_vec`].
+    fn encrypt_vec(&mut self, plaintext: &[u8]) -> Vec<u8>;
+    /// Cf. [`CipherState::decrypt_vec`].
+    fn decrypt_vec(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, Error>;
+    /// Cf. [`CipherState::rekey`].
+    fn rekey(&mut self);
+    /// Cf. [`CipherState::get_next_n`].
+    fn get_next_n(&self) -> u64;
+}
+
+impl<D, C, H> DynHandshakeState for HandshakeState<D, C, H>
+where
+    D: DH,
+    C: Cipher + 'static,
+    C::Key: Send,
+    H: Hash,
+{
+    fn write_message_vec(&mut self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        HandshakeState::write_message_vec(self, payload)
+    }
+
+    fn read_message_vec(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        HandshakeState::read_message_vec(self, data)
+    }
+
+    fn completed(&self) -> bool {
+        HandshakeState::completed(self)
+    }
+
+    fn is_write_turn(&self) -> bool {
+        HandshakeState::is_write_turn(self)
+    }
+
+    fn get_next_message_overhead(&self) -> usize {
+        HandshakeState::get_next_message_overhead(self)
+    }
+
+    fn get_hash(&self) -> &[u8] {
+        HandshakeState::get_hash(self)
+    }
+
+    fn get_rs(&self) -> Option<Vec<u8>> {
+        HandshakeState::get_rs(self).map(|rs| rs.as_slice().to_vec())
+    }
+
+    fn get_ciphers(&self) -> (Box<dyn DynCipherState>, Box<dyn DynCipherState>) {
+        let (c1, c2) = HandshakeState::get_ciphers(self);
+        (Box::new(c1), Box::new(c2))
+    }
+}
+
+impl<C> DynCipherState for CipherState<C>
+where
+    C: Cipher,
+    C::Key: Send,
+{
+    fn encrypt_vec(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        CipherState::encrypt_vec(self, plaintext)
+    }
+
+    fn decrypt_vec(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        CipherState::decrypt_vec(self, ciphertext)
+    }
+
+    fn rekey(&mut self) {
+        CipherState::rekey(self)
+    }
+
+    fn get_next_n(&self) -> u64 {
+        CipherState::get_next_n(self)
+    }
+}
+
+/// A parsed protocol name, e.g., `Noise_XX_25519_ChaChaPoly_BLAKE2s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolName<'a> {
+    /// Pattern name, e.g., `XX` or `XXpsk3`.
+    pub pattern: &'a str,
+    /// DH name, e.g., `25519`.
+    pub dh: &'a str,
+    /// Cipher name, e.g., `ChaChaPoly`.
+    pub cipher: &'a str,
+    /// Hash name, e.g., `BLAKE2s`.
+    pub hash: &'a str,
+}
+
+impl<'a> ProtocolName<'a> {
+    /// Parse a protocol name.
+    pub fn parse(name: &'a str) -> Result<Self, RegistryError> {
+        let mut parts = name.split('_');
+        match (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) {
+            (Some("Noise"), Some(pattern), Some(dh), Some(cipher), Some(hash), None) => {
+                Ok(ProtocolName {
+                    pattern,
+                    dh,
+                    cipher,
+                    hash,
+                })
+            }
+            _ => Err(RegistryError::InvalidProtocolName),
+        }
+    }
+}
+
+/// The names of the patterns of this crate, cf. the [`Arbitrary`] impl of
+/// [`HandshakePattern`], and of the DH functions, ciphers and hashes of the
+/// Noise spec.
+///
+/// [`Arbitrary`]: arbitrary::Arbitrary
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ProtocolName<'a> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProtocolName {
+            pattern: u.arbitrary::<HandshakePattern>()?.get_name(),
+            dh: u.choose(&["25519", "448"])?,
+            cipher: u.choose(&["ChaChaPoly", "AESGCM"])?,
+            hash: u.choose(&["SHA256", "SHA512", "BLAKE2s", "BLAKE2b"])?,
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        let (min, max) = <HandshakePattern as arbitrary::Arbitrary>::size_hint(depth);
+        (min + 3, max.map(|max| max + 3))
+    }
+}
+
+impl Display for ProtocolName<'_> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            fmt,
+            "Noise_{}_{}_{}_{}",
+            self.pattern, self.dh, self.cipher, self.hash
+        )
+    }
+}
+
+/// Keys and settings for a handshake created by a [`Registry`].
+///
+/// Keys are given as raw bytes, in the format of the DH function.
+#[derive(Clone, Copy, Default)]
+pub struct HandshakeParams<'a> {
+    /// Whether we are the initiator.
+    pub is_initiator: bool,
+    /// Whether the ephemeral keys are Elligator encoded.
+    pub is_elligator_encoded: bool,
+    /// Prologue.
+    pub prologue: &'a [u8],
+    /// Local static private key.
+    pub s: Option<&'a [u8]>,
+    /// Remote static public key.
+    pub rs: Option<&'a [u8]>,
+    /// Pre-shared keys, in the order they are used.
+    pub psks: &'a [[u8; 32]],
+}
+
+/// Errors of a [`Registry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The protocol name is malformed.
+    InvalidProtocolName,
+    /// The handshake pattern is unknown.
+    UnknownPattern,
+    /// No suite with these DH, cipher and hash names is registered.
+    UnknownSuite,
+    /// A key has the wrong length.
+    InvalidKey,
+    /// A key needed for the pre-messages of the pattern is missing.
+    MissingKey,
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        fmt.write_str(match self {
+            RegistryError::InvalidProtocolName => "Invalid protocol name",
+            RegistryError::UnknownPattern => "Unknown handshake pattern",
+            RegistryError::UnknownSuite => "Unknown crypto suite",
+            RegistryError::InvalidKey => "Invalid key length",
+            RegistryError::MissingKey => "Missing pre-message key",
+        })
+    }
+}
+
+#[cfg(feature = "use_std")]
+impl ::std::error::Error for RegistryError {}
+
+type NewHandshake =
+    fn(HandshakePattern, &HandshakeParams) -> Result<Box<dyn DynHandshakeState>, RegistryError>;
+
+struct Suite {
+    dh: &'static str,
+    cipher: &'static str,
+    hash: &'static str,
+    new_handshake: NewHandshake,
+    genkey: fn(bool) -> (Vec<u8>, Vec<u8>),
+}
+
+/// A set of crypto suites, i.e., DH/cipher/hash combinations, that can be
+/// instantiated by name.
+#[derive(Default)]
+pub struct Registry {
+    suites: Vec<Suite>,
+}
+
+impl Registry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Registry { suites: Vec::new() }
+    }
+
+    /// Register a suite under the names of its primitives.
+    ///
+    /// A suite registered earlier with the same names is replaced.
+    pub fn register<D, C, H>(&mut self) -> &mut Self
+    where
+        D: DH + 'static,
+        C: Cipher + 'static,
+        C::Key: Send,
+        H: Hash + 'static,
+    {
+        let suite = Suite {
+            dh: D::name(),
+            cipher: C::name(),
+            hash: H::name(),
+            new_handshake: new_handshake::<D, C, H>,
+            genkey: genkey::<D>,
+        };
+        match self.find(suite.dh, suite.cipher, suite.hash) {
+            Some(i) => self.suites[i] = suite,
+            None => self.suites.push(suite),
+        }
+        self
+    }
+
+    /// Whether a suite is registered.
+    pub fn contains(&self, dh: &str, cipher: &str, hash: &str) -> bool {
+        self.find(dh, cipher, hash).is_some()
+    }
+
+    /// Iterate over the `(dh, cipher, hash)` names of registered suites, in
+    /// registration order.
+    pub fn suites(&self) -> impl Iterator<Item = (&'static str, &'static str, &'static str)> + '_ {
+        self.suites.iter().map(|s| (s.dh, s.cipher, s.hash))
+    }
+
+    /// Generate a key pair for the DH function `dh`, returned as
+    /// `(private, public)`.
+    pub fn generate_keypair(
+        &self,
+        dh: &str,
+        elligator: bool,
+    ) -> Result<(Vec<u8>, Vec<u8>), RegistryError> {
+        let suite = self
+            .suites
+            .iter()
+            .find(|s| s.dh == dh)
+            .ok_or(RegistryError::UnknownSuite)?;
+        Ok((suite.genkey)(elligator))
+    }
+
+    /// Create a handshake state for a protocol name, e.g.,
+    /// `Noise_XX_25519_ChaChaPoly_BLAKE2s`.
+    pub fn new_handshake(
+        &self,
+        protocol_name: &str,
+        params: &HandshakeParams,
+    ) -> Result<Box<dyn DynHandshakeState>, RegistryError> {
+        let name = ProtocolName::parse(protocol_name)?;
+        let pattern = by_name(name.pattern).ok_or(RegistryError::UnknownPattern)?;
+        let i = self
+            .find(name.dh, name.cipher, name.hash)
+            .ok_or(RegistryError::UnknownSuite)?;
+        (self.suites[i].new_handshake)(pattern, params)
+    }
+
+    fn find(&self, dh: &str, cipher: &str, hash: &str) -> Option<usize> {
+        self.suites
+            .iter()
+            .position(|s| s.dh == dh && s.cipher == cipher && s.hash == hash)
+    }
+}
+
+fn new_handshake<D, C, H>(
+    pattern: HandshakePattern,
+    params: &HandshakeParams,
+) -> Result<Box<dyn DynHandshakeState>, RegistryError>
+where
+    D: DH + 'static,
+    C: Cipher + 'static,
+    C::Key: Send,
+    H: Hash + 'static,
+{
+    let s = match params.s {
+        Some(s) if s.len() == D::Key::len() => {
+            let private = D::Key::from_slice(s);
+            let public = D::pubkey(&private);
+            Some((private, public).into())
+        }
+        Some(_) => return Err(RegistryError::InvalidKey),
+        None => None,
+    };
+    let rs = match params.rs {
+        Some(rs) if rs.len() == D::Pubkey::len() => Some(D::Pubkey::from_slice(rs)),
+        Some(_) => return Err(RegistryError::InvalidKey),
+        None => None,
+    };
+
+    let (local_pre, remote_pre) = if params.is_initiator {
+        (pattern.get_pre_i(), pattern.get_pre_r())
+    } else {
+        (pattern.get_pre_r(), pattern.get_pre_i())
+    };
+    let needs = |pre: &[Token], have: bool| pre.iter().all(|t| matches!(t, Token::S) && have);
+    if !needs(local_pre, s.is_some()) || !needs(remote_pre, rs.is_some()) {
+        return Err(RegistryError::MissingKey);
+    }
+
+    let mut hs = HandshakeState::<D, C, H>::new(
+        pattern,
+        params.is_initiator,
+        params.is_elligator_encoded,
+        params.prologue,
+        s,
+        None,
+        rs,
+        None,
+    );
+    for psk in params.psks {
+        hs.push_psk(psk);
+    }
+    Ok(Box::new(hs))
+}
+
+fn genkey<D: DH>(elligator: bool) -> (Vec<u8>, Vec<u8>) {
+    let k = D::genkey(elligator);
+    (k.private.as_slice().to_vec(), k.public.as_slice().to_vec())
+}
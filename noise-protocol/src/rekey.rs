@@ -0,0 +1,151 @@
+use core::time::Duration;
+
+/// When a [`TransportState`](crate::TransportState) rekeys by itself, cf.
+/// [`TransportState::set_rekey_policy`](crate::TransportState::set_rekey_policy).
+///
+/// Each direction is rekeyed once `messages` messages, or `bytes` payload
+/// bytes, have been sent in it since the last rekey. A threshold of 0 never
+/// triggers. The default policy never rekeys.
+///
+/// Without `in_band`, the receiver counts the same way, so both peers must
+/// use the same policy, and the transport must not lose or reorder messages.
+///
+/// With `in_band`, every payload is prefixed with a header byte, inside the
+/// ciphertext, whose lowest bit tells the receiver that the sender rekeys
+/// after this message. The receiver only follows these flags, so the
+/// thresholds of the peers may differ, and `time` can be used. Both peers
+/// must agree on `in_band`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RekeyPolicy {
+    /// Rekey after this many messages.
+    pub messages: u64,
+    /// Rekey after this many payload bytes.
+    pub bytes: u64,
+    /// Rekey when the first message after this much time since the last
+    /// rekey is sent. Needs `in_band`, as the receiver can not tell the
+    /// time of the sender, and the `use_std` feature.
+    pub time: Option<Duration>,
+    /// Signal rekeys in a header byte of each message.
+    pub in_band: bool,
+}
+
+/// The state of a [`RekeyPolicy`] in a
+/// [`TransportCheckpoint`](crate::TransportCheckpoint): the policy and, for
+/// each direction, the messages and payload bytes since the last rekey.
+///
+/// The time since the last rekey is not included: it starts over on
+/// restore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RekeyCheckpoint {
+    /// The policy.
+    pub policy: RekeyPolicy,
+    /// Messages and payload bytes sent since the last rekey.
+    pub sent: (u64, u64),
+    /// Messages and payload bytes received since the last rekey.
+    pub received: (u64, u64),
+}
+
+/// Rekey flag of the in-band header.
+pub(crate) const REKEY_FLAG: u8 = 1;
+
+/// What has been sent or received in one direction since the last rekey.
+#[derive(Clone, Copy)]
+pub(crate) struct RekeyCounter {
+    messages: u64,
+    bytes: u64,
+    #[cfg(feature = "use_std")]
+    since: std::time::Instant,
+}
+
+impl RekeyCounter {
+    pub fn new() -> Self {
+        RekeyCounter {
+            messages: 0,
+            bytes: 0,
+            #[cfg(feature = "use_std")]
+            since: std::time::Instant::now(),
+        }
+    }
+
+    /// Whether the direction is to be rekeyed after a message of `len`
+    /// payload bytes.
+    pub fn is_due(&self, policy: &RekeyPolicy, len: usize) -> bool {
+        let crossed = |threshold: u64, n: u64| threshold != 0 && n >= threshold;
+        if crossed(policy.messages, self.messages + 1)
+            || crossed(policy.bytes, self.bytes.saturating_add(len as u64))
+        {
+            return true;
+        }
+        #[cfg(feature = "use_std")]
+        if let (true, Some(time)) = (policy.in_band, policy.time) {
+            return self.since.elapsed() >= time;
+        }
+        false
+    }
+
+    fn counts(&self) -> (u64, u64) {
+        (self.messages, self.bytes)
+    }
+
+    fn with_counts((messages, bytes): (u64, u64)) -> Self {
+        RekeyCounter {
+            messages,
+            bytes,
+            ..RekeyCounter::new()
+        }
+    }
+
+    /// Count a message of `len` payload bytes, or start over if the
+    /// direction has been rekeyed after it.
+    pub fn record(&mut self, len: usize, rekeyed: bool) {
+        if rekeyed {
+            *self = RekeyCounter::new();
+        } else {
+            self.messages += 1;
+            self.bytes = self.bytes.saturating_add(len as u64);
+        }
+    }
+}
+
+/// A [`RekeyPolicy`] with the counters of both directions.
+#[derive(Clone, Copy)]
+pub(crate) struct AutoRekey {
+    pub policy: RekeyPolicy,
+    pub send: RekeyCounter,
+    pub recv: RekeyCounter,
+}
+
+impl AutoRekey {
+    pub fn new(policy: RekeyPolicy) -> Self {
+        assert!(
+            policy.time.is_none() || (policy.in_band && cfg!(feature = "use_std")),
+            "a rekey time needs in_band and the use_std feature"
+        );
+        AutoRekey {
+            policy,
+            send: RekeyCounter::new(),
+            recv: RekeyCounter::new(),
+        }
+    }
+
+    /// Length of the in-band header.
+    pub fn header_len(&self) -> usize {
+        usize::from(self.policy.in_band)
+    }
+
+    pub fn checkpoint(&self) -> RekeyCheckpoint {
+        RekeyCheckpoint {
+            policy: self.policy,
+            sent: self.send.counts(),
+            received: self.recv.counts(),
+        }
+    }
+
+    pub fn restore(checkpoint: &RekeyCheckpoint) -> Self {
+        AutoRekey {
+            send: RekeyCounter::with_counts(checkpoint.sent),
+            recv: RekeyCounter::with_counts(checkpoint.received),
+            ..AutoRekey::new(checkpoint.policy)
+        }
+    }
+}
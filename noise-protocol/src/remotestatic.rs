@@ -0,0 +1,28 @@
+/// A hook deciding whether to accept the static key of the peer, e.g., by
+/// looking it up in a list of authorized keys.
+///
+/// Set one with
+/// [`HandshakeState::set_remote_static_verifier`](crate::HandshakeState::set_remote_static_verifier).
+/// It is called as soon as the static key is decrypted from a handshake
+/// message, e.g., the first message of `IK` or the third of `XX`, and if it
+/// returns `false`, reading the message fails with
+/// [`AuthenticationRejected`](crate::ErrorKind::AuthenticationRejected)
+/// before any further token is processed. Static keys known ahead of time,
+/// i.e., set with
+/// [`set_rs`](crate::HandshakeStateBuilder::set_rs), are not verified.
+///
+/// With `sig` patterns, the signing key of the peer is verified by another
+/// hook, set with
+/// [`HandshakeState::set_remote_signing_key_verifier`](crate::HandshakeState::set_remote_signing_key_verifier),
+/// after its signature.
+pub trait RemoteStaticVerifier: Send + Sync {
+    /// Whether to accept the remote static public key `rs`, or the remote
+    /// signing key.
+    fn verify_remote_static(&self, rs: &[u8]) -> bool;
+}
+
+impl<F: Fn(&[u8]) -> bool + Send + Sync> RemoteStaticVerifier for F {
+    fn verify_remote_static(&self, rs: &[u8]) -> bool {
+        self(rs)
+    }
+}
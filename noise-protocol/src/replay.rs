@@ -0,0 +1,44 @@
+use crate::boundedset::BoundedSet;
+use std::sync::Mutex;
+
+/// A cache of first handshake messages a responder has accepted, to reject
+/// replays of them.
+///
+/// Zero-RTT data in the first message, e.g., in `IK`, can be replayed by an
+/// attacker. Set a cache with
+/// [`HandshakeState::set_replay_cache`](crate::HandshakeState::set_replay_cache)
+/// and the responder fails with [`Replay`](crate::ErrorKind::Replay) on a
+/// first message it has seen before. Messages are identified by the
+/// handshake hash after reading them, which covers the initiator ephemeral
+/// key and the payload.
+///
+/// A cache can only remember so many messages. Bound the age of acceptable
+/// first messages by other means, e.g., a timestamp in the payload, so
+/// messages old enough to be evicted are rejected anyway.
+pub trait ReplayCache: Send + Sync {
+    /// Record `key`, returning `false` if it has been seen before.
+    fn insert(&self, key: &[u8]) -> bool;
+}
+
+/// A [`ReplayCache`] remembering the most recent messages in memory.
+pub struct MemoryReplayCache {
+    seen: Mutex<BoundedSet>,
+}
+
+impl MemoryReplayCache {
+    /// Create a cache remembering up to `capacity` messages.
+    pub fn new(capacity: usize) -> Self {
+        MemoryReplayCache {
+            seen: Mutex::new(BoundedSet::new(capacity)),
+        }
+    }
+}
+
+impl ReplayCache for MemoryReplayCache {
+    fn insert(&self, key: &[u8]) -> bool {
+        match self.seen.lock() {
+            Ok(mut seen) => seen.insert(key),
+            Err(poisoned) => poisoned.into_inner().insert(key),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::cipherstate::CipherState;
+use crate::error::Error;
+use crate::traits::Cipher;
+
+/// A sliding window of transport nonces that have been received, to reject
+/// replayed datagrams, like the one of WireGuard.
+///
+/// When transport messages are sent over an unreliable transport, e.g., UDP,
+/// the sender includes the nonce in each packet, and the receiver decrypts
+/// with [`CipherState::decrypt_with_nonce`]. The filter then accepts each
+/// nonce at most once, and rejects nonces more than `64 * WORDS` messages
+/// older than the newest one, so that messages may be reordered within the
+/// window. The default window is 2048 messages.
+#[derive(Debug, Clone)]
+pub struct ReplayFilter<const WORDS: usize = 32> {
+    bitmap: [u64; WORDS],
+    // The highest accepted nonce plus one, or 0 if none.
+    next: u64,
+}
+
+impl<const WORDS: usize> Default for ReplayFilter<WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WORDS: usize> ReplayFilter<WORDS> {
+    const WINDOW: u64 = 64 * WORDS as u64;
+
+    /// Create a filter that has not accepted any nonce.
+    ///
+    /// # Panics
+    ///
+    /// If `WORDS` is 0.
+    pub fn new() -> Self {
+        assert!(WORDS > 0);
+        ReplayFilter {
+            bitmap: [0; WORDS],
+            next: 0,
+        }
+    }
+
+    /// Number of messages in the window.
+    pub fn window() -> u64 {
+        Self::WINDOW
+    }
+
+    fn bit(n: u64) -> (usize, u64) {
+        let i = n % Self::WINDOW;
+        ((i / 64) as usize, 1 << (i % 64))
+    }
+
+    /// Whether `n` would be accepted, i.e., it is within the window and has
+    /// not been accepted before. Nonce 2 ^ 64 - 1 is never accepted.
+    pub fn check(&self, n: u64) -> bool {
+        if n == u64::MAX {
+            return false;
+        }
+        if n >= self.next {
+            return true;
+        }
+        if self.next - n > Self::WINDOW {
+            return false;
+        }
+        let (word, mask) = Self::bit(n);
+        self.bitmap[word] & mask == 0
+    }
+
+    /// Mark `n` as received, returning `false` if it is rejected.
+    ///
+    /// Only call this after the message has been authenticated, i.e.,
+    /// decrypted, or a forged message could advance the window.
+    pub fn accept(&mut self, n: u64) -> bool {
+        if !self.check(n) {
+            return false;
+        }
+        if n >= self.next {
+            if n - self.next >= Self::WINDOW {
+                self.bitmap = [0; WORDS];
+            } else {
+                for m in self.next..n {
+                    let (word, mask) = Self::bit(m);
+                    self.bitmap[word] &= !mask;
+                }
+            }
+            self.next = n + 1;
+        }
+        let (word, mask) = Self::bit(n);
+        self.bitmap[word] |= mask;
+        true
+    }
+
+    /// Decrypt a message with the explicit nonce `n`, cf.
+    /// [`CipherState::decrypt_with_nonce`], failing if the nonce is rejected
+    /// by the filter.
+    ///
+    /// Fails with [Replay](crate::ErrorKind::Replay) if the nonce is
+    /// rejected. The nonce is only marked as received if decryption
+    /// succeeds.
+    pub fn decrypt<C: Cipher>(
+        &mut self,
+        cipher: &CipherState<C>,
+        n: u64,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        if !self.check(n) {
+            return Err(Error::replay());
+        }
+        cipher.decrypt_with_nonce(n, authtext, ciphertext, out)?;
+        self.accept(n);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::error::Error;
+use crate::traits::{Cipher, Hash, U8Array};
+use zeroize::Zeroizing;
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+const TICKET_ID_LEN: usize = 32;
+
+/// A resumption ticket: a PSK shared with the peer, and an opaque blob from
+/// which the issuer can recover it, cf.
+/// [`TransportState::issue_resumption_ticket`](crate::TransportState::issue_resumption_ticket).
+///
+/// To resume, the initiator sends the [blob](ResumptionTicket::blob) ahead
+/// of the first message of a `psk0` handshake, e.g., `NNpsk0`, and
+/// [pushes](crate::HandshakeState::push_psk) the
+/// [PSK](ResumptionTicket::psk). The responder recovers the PSK with
+/// [`TicketKey::open`] and pushes it too. The payload of the first message
+/// is 0-RTT data.
+///
+/// 0-RTT data has weaker guarantees than the rest of the session:
+///
+/// * It is encrypted with the PSK alone, so it is not forward secret: whoever
+///   obtains the ticket key, or the PSK stored by the initiator, can decrypt
+///   it.
+/// * It can be replayed by an attacker, at least until the ticket key is
+///   rotated. Use a [`ReplayCache`](crate::ReplayCache) on the responder, or
+///   only send idempotent requests.
+///
+/// The rest of the session has a fresh ephemeral-ephemeral DH, and so is
+/// forward secret as usual.
+pub struct ResumptionTicket {
+    psk: Zeroizing<[u8; 32]>,
+    blob: Vec<u8>,
+}
+
+impl ResumptionTicket {
+    /// Create a ticket from its parts, e.g., to load a stored ticket.
+    pub fn new(psk: [u8; 32], blob: Vec<u8>) -> Self {
+        ResumptionTicket {
+            psk: Zeroizing::new(psk),
+            blob,
+        }
+    }
+
+    /// The PSK for the resumed handshake.
+    pub fn psk(&self) -> &[u8; 32] {
+        &self.psk
+    }
+
+    /// The blob to send to the issuer.
+    pub fn blob(&self) -> &[u8] {
+        &self.blob
+    }
+}
+
+/// The key with which a responder encrypts the
+/// [resumption tickets](ResumptionTicket) it issues.
+///
+/// Tickets are only valid as long as the key is kept, so rotating the key
+/// expires them. The key protects the PSKs of all outstanding tickets, and
+/// with them the 0-RTT data of resumed sessions.
+pub struct TicketKey(Zeroizing<[u8; 32]>);
+
+impl TicketKey {
+    /// Create a ticket key from random bytes.
+    pub fn new(key: [u8; 32]) -> Self {
+        TicketKey(Zeroizing::new(key))
+    }
+
+    // Each ticket is encrypted with its own key, so a nonce of 0 is fine.
+    fn cipher_key<C: Cipher, H: Hash>(&self, id: &[u8]) -> C::Key {
+        let k = H::hkdf(&*self.0, id).0;
+        C::Key::from_slice(&k.as_slice()[..32])
+    }
+
+    pub(crate) fn seal<C: Cipher, H: Hash>(&self, id: &[u8], psk: &[u8; 32]) -> Vec<u8> {
+        let mut blob = vec![0u8; TICKET_ID_LEN + 32 + C::tag_len()];
+        let (blob_id, ciphertext) = blob.split_at_mut(TICKET_ID_LEN);
+        blob_id.copy_from_slice(id);
+        C::encrypt(&self.cipher_key::<C, H>(id), 0, id, psk, ciphertext);
+        blob
+    }
+
+    /// Recover the PSK of a ticket from its blob.
+    ///
+    /// `C` and `H` must be the cipher and hash of the session that issued
+    /// the ticket. Fails with [Decryption](crate::ErrorKind::Decryption) if
+    /// the blob has not been issued with this key, or has been tampered
+    /// with.
+    pub fn open<C: Cipher, H: Hash>(&self, blob: &[u8]) -> Result<Zeroizing<[u8; 32]>, Error> {
+        if blob.len() != TICKET_ID_LEN + 32 + C::tag_len() {
+            return Err(Error::decryption());
+        }
+        let (id, ciphertext) = blob.split_at(TICKET_ID_LEN);
+        let mut psk = Zeroizing::new([0u8; 32]);
+        C::decrypt(&self.cipher_key::<C, H>(id), 0, id, ciphertext, &mut *psk)?;
+        Ok(psk)
+    }
+}
+
+/// Derive the PSK and the ID of the resumption ticket of a session from its
+/// exporter secret.
+pub(crate) fn derive<H: Hash>(exporter: &[u8]) -> (Zeroizing<[u8; 32]>, [u8; TICKET_ID_LEN]) {
+    let (k, id) = H::hkdf(exporter, b"resumption");
+    let mut psk = Zeroizing::new([0u8; 32]);
+    psk.copy_from_slice(&k.as_slice()[..32]);
+    let mut ticket_id = [0u8; TICKET_ID_LEN];
+    ticket_id.copy_from_slice(&id.as_slice()[..TICKET_ID_LEN]);
+    (psk, ticket_id)
+}
@@ -0,0 +1,53 @@
+use crate::traits::{Hash, U8Array};
+use core::fmt::{Display, Error as FmtError, Formatter, LowerHex};
+
+/// Length of a [`SessionId`], in bytes.
+pub const SESSION_ID_LEN: usize = 16;
+
+/// A short, non-secret identifier of a Noise session.
+///
+/// It is derived from the handshake hash with a one-way function, so both
+/// peers arrive at the same value without it revealing anything about the
+/// transport keys. Useful for correlating logs, metrics, etc. of the same
+/// connection on both sides.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SessionId([u8; SESSION_ID_LEN]);
+
+impl SessionId {
+    /// Derive a session ID from a handshake hash.
+    ///
+    /// # Panics
+    ///
+    /// If `handshake_hash` is longer than the block length of `H`.
+    pub fn from_handshake_hash<H: Hash>(handshake_hash: &[u8]) -> Self {
+        let out = H::hmac(handshake_hash, b"noise session id");
+        let mut id = [0u8; SESSION_ID_LEN];
+        id.copy_from_slice(&out.as_slice()[..SESSION_ID_LEN]);
+        SessionId(id)
+    }
+
+    /// Create a session ID from raw bytes, e.g., one received from the peer.
+    pub fn from_bytes(bytes: [u8; SESSION_ID_LEN]) -> Self {
+        SessionId(bytes)
+    }
+
+    /// As bytes.
+    pub fn as_bytes(&self) -> &[u8; SESSION_ID_LEN] {
+        &self.0
+    }
+}
+
+impl LowerHex for SessionId {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        for b in &self.0 {
+            write!(fmt, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SessionId {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        LowerHex::fmt(self, fmt)
+    }
+}
@@ -0,0 +1,120 @@
+use crate::dhcache::StaticDhCache;
+use crate::traits::{DhKeyPair, DH};
+use std::sync::Arc;
+
+/// A static key pair shared by many [`HandshakeState`]s.
+///
+/// Cloning a `SharedStaticKey` only clones a reference, so servers running
+/// many concurrent handshakes with the same static key keep a single copy of
+/// the private key in memory instead of one per connection. The key is
+/// dropped, and zeroed if the key type does so, when the last clone is
+/// dropped.
+///
+/// Use it with
+/// [`HandshakeStateBuilder::set_shared_s`](crate::HandshakeStateBuilder::set_shared_s).
+///
+/// [`HandshakeState`]: crate::HandshakeState
+pub struct SharedStaticKey<D: DH>(Arc<DhKeyPair<D::Key, D::Pubkey>>);
+
+impl<D: DH> SharedStaticKey<D> {
+    /// Share a key pair.
+    pub fn new(key: DhKeyPair<D::Key, D::Pubkey>) -> Self {
+        SharedStaticKey(Arc::new(key))
+    }
+
+    /// Share a key pair, computing the public key from the private key.
+    pub fn from_private(private: D::Key) -> Self {
+        Self::new(DhKeyPair::from_private::<D>(private))
+    }
+
+    /// The public key.
+    pub fn public(&self) -> &D::Pubkey {
+        &self.0.public
+    }
+
+    pub(crate) fn keypair(&self) -> &DhKeyPair<D::Key, D::Pubkey> {
+        &self.0
+    }
+}
+
+impl<D: DH> Clone for SharedStaticKey<D> {
+    fn clone(&self) -> Self {
+        SharedStaticKey(self.0.clone())
+    }
+}
+
+impl<D: DH> From<DhKeyPair<D::Key, D::Pubkey>> for SharedStaticKey<D> {
+    fn from(key: DhKeyPair<D::Key, D::Pubkey>) -> Self {
+        Self::new(key)
+    }
+}
+
+/// A static key pair prepared once, e.g., at server start, for many
+/// handshakes.
+///
+/// The public key is computed once, and the key pair is shared as with
+/// [`SharedStaticKey`], so building a handshake state costs no scalar
+/// multiplication, unlike
+/// [`set_s_private`](crate::HandshakeStateBuilder::set_s_private). Other DH
+/// operations with the static key depend on a remote key: only `ss` results
+/// can be reused, with a [`StaticDhCache`].
+///
+/// Use it with
+/// [`HandshakeStateBuilder::local_static_precomputed`](crate::HandshakeStateBuilder::local_static_precomputed).
+pub struct PrecomputedStatic<D: DH> {
+    key: SharedStaticKey<D>,
+    dh_cache: Option<Arc<dyn StaticDhCache<D>>>,
+}
+
+impl<D: DH> PrecomputedStatic<D> {
+    /// Prepare a key pair.
+    pub fn new(key: DhKeyPair<D::Key, D::Pubkey>) -> Self {
+        PrecomputedStatic {
+            key: key.into(),
+            dh_cache: None,
+        }
+    }
+
+    /// Prepare a key pair, computing the public key from the private key.
+    pub fn from_private(private: D::Key) -> Self {
+        Self::new(DhKeyPair::from_private::<D>(private))
+    }
+
+    /// Also cache the `ss` results of the handshakes, cf.
+    /// [`HandshakeState::set_static_dh_cache`](crate::HandshakeState::set_static_dh_cache).
+    pub fn with_dh_cache(mut self, cache: Arc<dyn StaticDhCache<D>>) -> Self {
+        self.dh_cache = Some(cache);
+        self
+    }
+
+    /// The public key.
+    pub fn public(&self) -> &D::Pubkey {
+        self.key.public()
+    }
+
+    pub(crate) fn key(&self) -> &SharedStaticKey<D> {
+        &self.key
+    }
+
+    pub(crate) fn dh_cache(&self) -> Option<&Arc<dyn StaticDhCache<D>>> {
+        self.dh_cache.as_ref()
+    }
+}
+
+impl<D: DH> Clone for PrecomputedStatic<D> {
+    fn clone(&self) -> Self {
+        PrecomputedStatic {
+            key: self.key.clone(),
+            dh_cache: self.dh_cache.clone(),
+        }
+    }
+}
+
+impl<D: DH> From<SharedStaticKey<D>> for PrecomputedStatic<D> {
+    fn from(key: SharedStaticKey<D>) -> Self {
+        PrecomputedStatic {
+            key,
+            dh_cache: None,
+        }
+    }
+}
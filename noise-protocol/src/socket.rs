@@ -0,0 +1,196 @@
+//! Noise over blocking byte streams, e.g., [`TcpStream`](std::net::TcpStream).
+//!
+//! A [`NoiseSocket`] runs the handshake over any [`Read`] + [`Write`], then
+//! encrypts everything written to it and decrypts everything read from it.
+//! Each handshake and transport message is framed with a 2-byte big endian
+//! length prefix:
+//!
+//! ```ignore
+//! let mut socket = NoiseSocket::handshake(tcp_stream, hs)?;
+//! socket.write_all(b"hello")?;
+//! ```
+//!
+//! Handshake payloads are empty. To send payloads during the handshake, run
+//! it by hand and create the socket with [`NoiseSocket::new`]. The `stream`
+//! module does the same for async byte streams.
+
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, Kem, Signature, DH};
+use crate::transportstate::TransportState;
+use std::io::{self, Read, Write};
+
+/// Maximum length of a Noise message.
+const MAX_MESSAGE_LEN: usize = 65535;
+
+/// An encrypted stream over a blocking byte stream `T`.
+///
+/// Writes longer than one message are split into messages of up to the
+/// [maximum](TransportState::max_message_len) of the transport, 65535 bytes
+/// by default. A message that fails to decrypt fails reading with
+/// [`InvalidData`](io::ErrorKind::InvalidData), after which the socket must
+/// be dropped.
+pub struct NoiseSocket<T, C: Cipher, H: Hash> {
+    io: T,
+    transport: TransportState<C, H>,
+    // Incoming message.
+    rbuf: Vec<u8>,
+    // Decrypted payload, and how much of it has been returned.
+    plain: Vec<u8>,
+    rpos: usize,
+    // Outgoing message.
+    wbuf: Vec<u8>,
+}
+
+fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn write_frame<T: Write>(io: &mut T, frame: &mut [u8], msg_len: usize) -> io::Result<()> {
+    frame[..2].copy_from_slice(&(msg_len as u16).to_be_bytes());
+    io.write_all(&frame[..2 + msg_len])
+}
+
+// Read the length prefix, returning `None` on EOF before it.
+fn read_len<T: Read>(io: &mut T) -> io::Result<Option<usize>> {
+    let mut len = [0u8; 2];
+    let n = loop {
+        match io.read(&mut len) {
+            Ok(n) => break n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    };
+    if n == 0 {
+        return Ok(None);
+    }
+    io.read_exact(&mut len[n..])?;
+    Ok(Some(u16::from_be_bytes(len) as usize))
+}
+
+impl<T, C, H> NoiseSocket<T, C, H>
+where
+    T: Read + Write,
+    C: Cipher,
+    H: Hash,
+{
+    /// Create a socket after a completed handshake.
+    pub fn new(io: T, transport: TransportState<C, H>) -> Self {
+        NoiseSocket {
+            io,
+            transport,
+            rbuf: Vec::new(),
+            plain: Vec::new(),
+            rpos: 0,
+            wbuf: Vec::new(),
+        }
+    }
+
+    /// Run the handshake `hs` over `io`.
+    ///
+    /// # Errors
+    ///
+    /// Handshake errors are returned as
+    /// [`InvalidData`](io::ErrorKind::InvalidData).
+    pub fn handshake<D: DH, K: Kem, S: Signature>(
+        mut io: T,
+        mut hs: HandshakeState<D, C, H, K, S>,
+    ) -> io::Result<Self> {
+        let mut buf = vec![0u8; 2 + MAX_MESSAGE_LEN];
+        while !hs.completed() {
+            if hs.is_write_turn() {
+                let len = hs.get_next_message_overhead();
+                if len > MAX_MESSAGE_LEN {
+                    return Err(io::ErrorKind::InvalidInput.into());
+                }
+                hs.write_message(&[], &mut buf[2..2 + len])
+                    .map_err(invalid_data)?;
+                write_frame(&mut io, &mut buf, len)?;
+                io.flush()?;
+            } else {
+                let len = read_len(&mut io)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+                io.read_exact(&mut buf[..len])?;
+                hs.read_message_vec(&buf[..len]).map_err(invalid_data)?;
+            }
+        }
+        Ok(NoiseSocket::new(io, TransportState::from_handshake(&hs)))
+    }
+
+    /// The transport state, e.g., to get the handshake hash or to rekey.
+    pub fn transport(&mut self) -> &mut TransportState<C, H> {
+        &mut self.transport
+    }
+
+    /// The underlying stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// The underlying stream, mutably. Reading from or writing to it
+    /// corrupts the Noise stream.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Get back the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<T, C, H> Read for NoiseSocket<T, C, H>
+where
+    T: Read + Write,
+    C: Cipher,
+    H: Hash,
+{
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.rpos == self.plain.len() && !out.is_empty() {
+            let len = match read_len(&mut self.io)? {
+                Some(len) => len,
+                None => return Ok(0),
+            };
+            self.rbuf.resize(len, 0);
+            self.io.read_exact(&mut self.rbuf)?;
+            self.plain.resize(len.saturating_sub(C::tag_len()), 0);
+            self.rpos = 0;
+            let n = self
+                .transport
+                .recv(&self.rbuf, &mut self.plain)
+                .map_err(invalid_data)?;
+            self.plain.truncate(n);
+        }
+        let n = out.len().min(self.plain.len() - self.rpos);
+        out[..n].copy_from_slice(&self.plain[self.rpos..self.rpos + n]);
+        self.rpos += n;
+        Ok(n)
+    }
+}
+
+impl<T, C, H> Write for NoiseSocket<T, C, H>
+where
+    T: Read + Write,
+    C: Cipher,
+    H: Hash,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let overhead = self.transport.overhead();
+        let n = buf.len().min(self.transport.max_payload_len());
+        let len = n + overhead;
+        self.wbuf.resize(2 + len, 0);
+        self.transport
+            .send(&buf[..n], &mut self.wbuf[2..])
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        write_frame(&mut self.io, &mut self.wbuf, len)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
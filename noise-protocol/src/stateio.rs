@@ -0,0 +1,81 @@
+//! Helpers for the binary encoding of saved handshake states.
+
+use crate::traits::U8Array;
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+pub fn put_array<A: U8Array>(out: &mut Vec<u8>, a: &A) {
+    out.extend_from_slice(a.as_slice());
+}
+
+pub fn put_option<A: U8Array>(out: &mut Vec<u8>, a: Option<&A>) {
+    match a {
+        Some(a) => {
+            out.push(1);
+            put_array(out, a);
+        }
+        None => out.push(0),
+    }
+}
+
+/// Reads from a byte slice, returning `None` if it is too short or
+/// malformed.
+pub struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader(data)
+    }
+
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (a, b) = self.0.split_at(n);
+        self.0 = b;
+        Some(a)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        let mut b = [0u8; 2];
+        b.copy_from_slice(self.take(2)?);
+        Some(u16::from_be_bytes(b))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(self.take(4)?);
+        Some(u32::from_be_bytes(b))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(b))
+    }
+
+    pub fn array<A: U8Array>(&mut self) -> Option<A> {
+        self.take(A::len()).map(A::from_slice)
+    }
+
+    pub fn option<A: U8Array>(&mut self) -> Option<Option<A>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => self.array().map(Some),
+            _ => None,
+        }
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,421 @@
+//! Noise over async byte streams, e.g., TCP.
+//!
+//! A [`NoiseStream`] runs the handshake over any `AsyncRead + AsyncWrite`,
+//! then encrypts everything written to it and decrypts everything read from
+//! it. Each handshake and transport message is framed with a 2-byte big
+//! endian length prefix, like [`codec`](crate::codec) does.
+//!
+//! With the `tokio_io` feature, it works with the traits of `tokio`:
+//!
+//! ```ignore
+//! let mut stream = NoiseStream::handshake_tokio(tcp_stream, hs).await?;
+//! stream.write_all(b"hello").await?;
+//! stream.flush().await?;
+//! ```
+//!
+//! With the `futures_io` feature, [`NoiseStream::handshake_futures`] does
+//! the same for the traits of `futures-io`.
+//!
+//! Handshake payloads are empty. To send payloads during the handshake, run
+//! it by hand and create the stream with [`NoiseStream::new`].
+
+use crate::handshakestate::HandshakeState;
+use crate::traits::{Cipher, Hash, Kem, Signature, DH};
+use crate::transportstate::TransportState;
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{ready, Context, Poll};
+use std::io;
+
+/// An encrypted stream over an async byte stream `T`.
+///
+/// Writes are encrypted into messages of up to the
+/// [maximum](TransportState::max_message_len) of the transport, 65535 bytes
+/// by default, which are only written to `T` on the next write or on flush,
+/// so flush the stream after writing. A message that fails to decrypt fails reading with
+/// [`InvalidData`](io::ErrorKind::InvalidData), after which the stream must
+/// be dropped.
+pub struct NoiseStream<T, C: Cipher, H: Hash> {
+    io: T,
+    transport: TransportState<C, H>,
+    // Incoming message, with the length prefix, and how much of it is read.
+    rbuf: Vec<u8>,
+    rfilled: usize,
+    // Decrypted payload, and how much of it has been returned.
+    plain: Vec<u8>,
+    rpos: usize,
+    // Outgoing messages, and how much of them is written.
+    wbuf: Vec<u8>,
+    wpos: usize,
+}
+
+// Fields are never pinned.
+impl<T: Unpin, C: Cipher, H: Hash> Unpin for NoiseStream<T, C, H> {}
+
+fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+async fn read_exact<T, R>(io: &mut T, read: &mut R, buf: &mut [u8]) -> io::Result<()>
+where
+    T: Unpin,
+    R: FnMut(Pin<&mut T>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+{
+    let mut pos = 0;
+    while pos < buf.len() {
+        let n = poll_fn(|cx| read(Pin::new(&mut *io), cx, &mut buf[pos..])).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        pos += n;
+    }
+    Ok(())
+}
+
+async fn write_all<T, W>(io: &mut T, write: &mut W, mut buf: &[u8]) -> io::Result<()>
+where
+    T: Unpin,
+    W: FnMut(Pin<&mut T>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+{
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| write(Pin::new(&mut *io), cx, buf)).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[n..];
+    }
+    Ok(())
+}
+
+impl<T, C, H> NoiseStream<T, C, H>
+where
+    T: Unpin,
+    C: Cipher,
+    H: Hash,
+{
+    /// Create a stream after a completed handshake.
+    pub fn new(io: T, transport: TransportState<C, H>) -> Self {
+        NoiseStream {
+            io,
+            transport,
+            rbuf: vec![0u8; 2],
+            rfilled: 0,
+            plain: Vec::new(),
+            rpos: 0,
+            wbuf: Vec::new(),
+            wpos: 0,
+        }
+    }
+
+    async fn handshake_with<D, K, S, R, W, F>(
+        mut io: T,
+        mut hs: HandshakeState<D, C, H, K, S>,
+        mut read: R,
+        mut write: W,
+        mut flush: F,
+    ) -> io::Result<Self>
+    where
+        D: DH,
+        K: Kem,
+        S: Signature,
+        R: FnMut(Pin<&mut T>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+        W: FnMut(Pin<&mut T>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+        F: FnMut(Pin<&mut T>, &mut Context<'_>) -> Poll<io::Result<()>>,
+    {
+        while !hs.completed() {
+            if hs.is_write_turn() {
+                let msg = hs.write_message_vec(&[]).map_err(invalid_data)?;
+                let len = u16::try_from(msg.len()).map_err(invalid_data)?;
+                let mut frame = Vec::with_capacity(2 + msg.len());
+                frame.extend_from_slice(&len.to_be_bytes());
+                frame.extend_from_slice(&msg);
+                write_all(&mut io, &mut write, &frame).await?;
+                poll_fn(|cx| flush(Pin::new(&mut io), cx)).await?;
+            } else {
+                let mut len = [0u8; 2];
+                read_exact(&mut io, &mut read, &mut len).await?;
+                let mut msg = vec![0u8; u16::from_be_bytes(len) as usize];
+                read_exact(&mut io, &mut read, &mut msg).await?;
+                hs.read_message_vec(&msg).map_err(invalid_data)?;
+            }
+        }
+        Ok(NoiseStream::new(io, TransportState::from_handshake(&hs)))
+    }
+
+    /// The transport state, e.g., to get the handshake hash or to rekey.
+    pub fn transport(&mut self) -> &mut TransportState<C, H> {
+        &mut self.transport
+    }
+
+    /// The underlying stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// The underlying stream, mutably. Reading from or writing to it
+    /// corrupts the Noise stream.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    /// Get back the underlying stream.
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
+    // Read a whole message into `rbuf`, returning `false` on EOF before it.
+    fn poll_fill<R>(&mut self, cx: &mut Context<'_>, read: &mut R) -> Poll<io::Result<bool>>
+    where
+        R: FnMut(Pin<&mut T>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if self.rfilled == self.rbuf.len() {
+                if self.rfilled > 2 {
+                    return Poll::Ready(Ok(true));
+                }
+                let len = u16::from_be_bytes([self.rbuf[0], self.rbuf[1]]) as usize;
+                self.rbuf.resize(2 + len, 0);
+                if len == 0 {
+                    return Poll::Ready(Ok(true));
+                }
+            }
+            let n = ready!(read(
+                Pin::new(&mut self.io),
+                cx,
+                &mut self.rbuf[self.rfilled..]
+            ))?;
+            if n == 0 {
+                if self.rfilled == 0 {
+                    return Poll::Ready(Ok(false));
+                }
+                return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+            }
+            self.rfilled += n;
+        }
+    }
+
+    fn poll_read_with<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        out: &mut [u8],
+        mut read: R,
+    ) -> Poll<io::Result<usize>>
+    where
+        R: FnMut(Pin<&mut T>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if self.rpos < self.plain.len() || out.is_empty() {
+                let n = out.len().min(self.plain.len() - self.rpos);
+                out[..n].copy_from_slice(&self.plain[self.rpos..self.rpos + n]);
+                self.rpos += n;
+                return Poll::Ready(Ok(n));
+            }
+            if !ready!(self.poll_fill(cx, &mut read))? {
+                return Poll::Ready(Ok(0));
+            }
+            let msg = &self.rbuf[2..];
+            self.plain.resize(msg.len().saturating_sub(C::tag_len()), 0);
+            self.rpos = 0;
+            let n = self
+                .transport
+                .recv(msg, &mut self.plain)
+                .map_err(invalid_data)?;
+            self.plain.truncate(n);
+            self.rbuf.truncate(2);
+            self.rfilled = 0;
+        }
+    }
+
+    fn poll_drain<W>(&mut self, cx: &mut Context<'_>, write: &mut W) -> Poll<io::Result<()>>
+    where
+        W: FnMut(Pin<&mut T>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    {
+        while self.wpos < self.wbuf.len() {
+            let n = ready!(write(Pin::new(&mut self.io), cx, &self.wbuf[self.wpos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.wpos += n;
+        }
+        self.wbuf.clear();
+        self.wpos = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_write_with<W>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        mut write: W,
+    ) -> Poll<io::Result<usize>>
+    where
+        W: FnMut(Pin<&mut T>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    {
+        ready!(self.poll_drain(cx, &mut write))?;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let overhead = self.transport.overhead();
+        let n = buf.len().min(self.transport.max_payload_len());
+        let len = n + overhead;
+        self.wbuf.resize(2 + len, 0);
+        self.wbuf[..2].copy_from_slice(&(len as u16).to_be_bytes());
+        self.transport
+            .send(&buf[..n], &mut self.wbuf[2..])
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(feature = "tokio_io")]
+mod tokio_impl {
+    use super::*;
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    fn read<T: AsyncRead>(
+        io: Pin<&mut T>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(io.poll_read(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    impl<T, C, H> NoiseStream<T, C, H>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        /// Run the handshake `hs` over `io`, with the traits of `tokio`.
+        ///
+        /// # Errors
+        ///
+        /// Handshake errors are returned as
+        /// [`InvalidData`](io::ErrorKind::InvalidData).
+        pub async fn handshake_tokio<D: DH, K: Kem, S: Signature>(
+            io: T,
+            hs: HandshakeState<D, C, H, K, S>,
+        ) -> io::Result<Self> {
+            Self::handshake_with(io, hs, read, T::poll_write, T::poll_flush).await
+        }
+    }
+
+    impl<T, C, H> AsyncRead for NoiseStream<T, C, H>
+    where
+        T: AsyncRead + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            let n = ready!(self
+                .get_mut()
+                .poll_read_with(cx, buf.initialize_unfilled(), read))?;
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<T, C, H> AsyncWrite for NoiseStream<T, C, H>
+    where
+        T: AsyncWrite + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_with(cx, buf, T::poll_write)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_drain(cx, &mut T::poll_write))?;
+            Pin::new(&mut this.io).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_drain(cx, &mut T::poll_write))?;
+            Pin::new(&mut this.io).poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(feature = "futures_io")]
+mod futures_impl {
+    use super::*;
+    use futures_io::{AsyncRead, AsyncWrite};
+
+    impl<T, C, H> NoiseStream<T, C, H>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        /// Run the handshake `hs` over `io`, with the traits of `futures-io`.
+        ///
+        /// # Errors
+        ///
+        /// Handshake errors are returned as
+        /// [`InvalidData`](io::ErrorKind::InvalidData).
+        pub async fn handshake_futures<D: DH, K: Kem, S: Signature>(
+            io: T,
+            hs: HandshakeState<D, C, H, K, S>,
+        ) -> io::Result<Self> {
+            Self::handshake_with(io, hs, T::poll_read, T::poll_write, T::poll_flush).await
+        }
+    }
+
+    impl<T, C, H> AsyncRead for NoiseStream<T, C, H>
+    where
+        T: AsyncRead + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_read_with(cx, buf, T::poll_read)
+        }
+    }
+
+    impl<T, C, H> AsyncWrite for NoiseStream<T, C, H>
+    where
+        T: AsyncWrite + Unpin,
+        C: Cipher,
+        H: Hash,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write_with(cx, buf, T::poll_write)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_drain(cx, &mut T::poll_write))?;
+            Pin::new(&mut this.io).poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_drain(cx, &mut T::poll_write))?;
+            Pin::new(&mut this.io).poll_close(cx)
+        }
+    }
+}
@@ -1,11 +1,37 @@
 use crate::cipherstate::CipherState;
+use crate::error::Error;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::stateio::{put_array, Reader};
 use crate::traits::{Cipher, Hash, U8Array};
+use crate::util::Sensitive;
 
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+/// The `SymmetricState` object of the spec: the handshake hash, the chaining
+/// key and the current handshake key.
+///
+/// [`HandshakeState`](crate::HandshakeState) is built on it. It is public for
+/// protocols that are not expressible as handshake patterns, e.g., with
+/// custom tokens or extra transcript data, so they can reuse the same
+/// primitives:
+///
+/// ```ignore
+/// let mut ss = SymmetricState::<ChaCha20Poly1305, Blake2s>::new(b"MyProtocol_v1");
+/// ss.mix_hash(prologue);
+/// ss.mix_key(&shared_secret);
+/// let msg = ss.encrypt_and_hash_vec(payload);
+/// let (c1, c2) = ss.split();
+/// ```
+///
+/// Each step must be replayed in the same order by the peer. Designing a
+/// protocol this way gives up the guarantees of the handshake patterns, so
+/// prefer them where possible.
 pub struct SymmetricState<C: Cipher, H: Hash> {
     // Instead of `has_key`, use an `Option`.
     cipherstate: Option<CipherState<C>>,
     h: H::Output,
-    ck: H::Output,
+    ck: Sensitive<H::Output>,
 }
 
 impl<C, H> Clone for SymmetricState<C, H>
@@ -17,7 +43,7 @@ where
         Self {
             cipherstate: self.cipherstate.clone(),
             h: self.h.clone(),
-            ck: self.ck.clone(),
+            ck: Sensitive::from((*self.ck).clone()),
         }
     }
 }
@@ -27,7 +53,8 @@ where
     C: Cipher,
     H: Hash,
 {
-    /// Initialize a `SymmetricState` with a handshake name.
+    /// Initialize a `SymmetricState` with a protocol name, like
+    /// `InitializeSymmetric()`.
     pub fn new(handshake_name: &[u8]) -> SymmetricState<C, H> {
         let mut h = H::Output::new();
 
@@ -39,17 +66,21 @@ where
 
         SymmetricState {
             cipherstate: None,
-            ck: h.clone(),
+            ck: Sensitive::from(h.clone()),
             h,
         }
     }
 
+    /// `MixKey()`: mix key material into the chaining key, and set a new
+    /// handshake key.
     pub fn mix_key(&mut self, data: &[u8]) {
         let (k1, k2) = H::hkdf(self.ck.as_slice(), data);
-        self.ck = k1;
+        let k2 = Sensitive::from(k2);
+        self.ck = Sensitive::from(k1);
         self.cipherstate = Some(CipherState::new(&k2.as_slice()[..C::key_len()], 0));
     }
 
+    /// `MixHash()`: mix data into the handshake hash.
     pub fn mix_hash(&mut self, data: &[u8]) {
         let mut h: H = Default::default();
         h.input(self.h.as_slice());
@@ -57,29 +88,56 @@ where
         self.h = h.result();
     }
 
+    /// `MixKeyAndHash()`: mix key material into both, e.g., a PSK.
     pub fn mix_key_and_hash(&mut self, input_key_material: &[u8]) {
         let (ck, temp_h, temp_k) = H::hkdf3(self.ck.as_slice(), input_key_material);
-        self.ck = ck;
+        let (temp_h, temp_k) = (Sensitive::from(temp_h), Sensitive::from(temp_k));
+        self.ck = Sensitive::from(ck);
         self.mix_hash(temp_h.as_slice());
         self.cipherstate = Some(CipherState::new(&temp_k.as_slice()[..C::key_len()], 0));
     }
 
+    /// Whether there is a handshake key, i.e., whether
+    /// [`encrypt_and_hash`](SymmetricState::encrypt_and_hash) encrypts.
     pub fn has_key(&self) -> bool {
         self.cipherstate.is_some()
     }
 
+    /// Length added by [`encrypt_and_hash`](SymmetricState::encrypt_and_hash):
+    /// the tag length if there is a key, or zero.
+    pub fn overhead(&self) -> usize {
+        if self.has_key() {
+            C::tag_len()
+        } else {
+            0
+        }
+    }
+
+    /// `EncryptAndHash()`: encrypt `plaintext` with the handshake hash as
+    /// associated data if there is a key, and mix the ciphertext into the
+    /// hash.
+    ///
+    /// # Panics
+    ///
+    /// If `out.len() != plaintext.len() + self.overhead()`.
     pub fn encrypt_and_hash(&mut self, plaintext: &[u8], out: &mut [u8]) {
         if let Some(ref mut c) = self.cipherstate {
-            c.encrypt_ad(self.h.as_slice(), plaintext, out);
+            c.encrypt_ad_impl(self.h.as_slice(), plaintext, out);
         } else {
             out.copy_from_slice(plaintext);
         };
         self.mix_hash(out);
     }
 
-    pub fn decrypt_and_hash(&mut self, data: &[u8], out: &mut [u8]) -> Result<(), ()> {
+    /// `DecryptAndHash()`, the reverse of
+    /// [`encrypt_and_hash`](SymmetricState::encrypt_and_hash).
+    ///
+    /// # Panics
+    ///
+    /// If `out.len() + self.overhead() != data.len()`.
+    pub fn decrypt_and_hash(&mut self, data: &[u8], out: &mut [u8]) -> Result<(), Error> {
         if let Some(ref mut c) = self.cipherstate {
-            c.decrypt_ad(self.h.as_slice(), data, out)?;
+            c.decrypt_ad_impl(self.h.as_slice(), data, out)?;
         } else {
             out.copy_from_slice(data)
         }
@@ -87,14 +145,100 @@ where
         Ok(())
     }
 
+    /// Like [`encrypt_and_hash`](SymmetricState::encrypt_and_hash), but
+    /// returns the ciphertext in a new vector of the right length.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn encrypt_and_hash_vec(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; plaintext.len() + self.overhead()];
+        self.encrypt_and_hash(plaintext, &mut out);
+        out
+    }
+
+    /// Like [`decrypt_and_hash`](SymmetricState::decrypt_and_hash), but
+    /// returns the plaintext in a new vector.
+    ///
+    /// Fails with [TooShort](crate::ErrorKind::TooShort) instead of
+    /// panicking if `data` is shorter than the
+    /// [overhead](SymmetricState::overhead). On failure, the state is not
+    /// changed.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn decrypt_and_hash_vec(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let len = data
+            .len()
+            .checked_sub(self.overhead())
+            .ok_or_else(Error::too_short)?;
+        let mut out = vec![0u8; len];
+        self.decrypt_and_hash(data, &mut out)?;
+        Ok(out)
+    }
+
+    /// `Split()`: the transport [`CipherState`]s, the first for initiator to
+    /// responder, and the second for responder to initiator.
     pub fn split(&self) -> (CipherState<C>, CipherState<C>) {
         let (k1, k2) = H::hkdf(self.ck.as_slice(), &[]);
+        let (k1, k2) = (Sensitive::from(k1), Sensitive::from(k2));
         let c1 = CipherState::new(&k1.as_slice()[..C::key_len()], 0);
         let c2 = CipherState::new(&k2.as_slice()[..C::key_len()], 0);
         (c1, c2)
     }
 
+    /// The handshake hash.
     pub fn get_hash(&self) -> &[u8] {
         self.h.as_slice()
     }
+
+    /// The secret of the key exporter, cf.
+    /// [`HandshakeState::export_key`](crate::HandshakeState::export_key),
+    /// derived from the chaining key independently of the transport keys.
+    pub(crate) fn exporter_secret(&self) -> H::Output {
+        H::hkdf(self.ck.as_slice(), b"exporter").0
+    }
+
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn export(&self, out: &mut Vec<u8>) {
+        put_array(out, &self.h);
+        put_array(out, &self.ck);
+        match self.cipherstate {
+            Some(ref c) => {
+                let (key, n) = c.clone().extract();
+                out.push(1);
+                put_array(out, &key);
+                out.extend_from_slice(&n.to_be_bytes());
+            }
+            None => out.push(0),
+        }
+    }
+
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub(crate) fn import(r: &mut Reader) -> Option<Self> {
+        let h = r.array()?;
+        let ck = Sensitive::from(r.array()?);
+        let cipherstate = match r.u8()? {
+            0 => None,
+            1 => {
+                let key = r.take(C::key_len())?;
+                Some(CipherState::new(key, r.u64()?))
+            }
+            _ => return None,
+        };
+        Some(SymmetricState { cipherstate, h, ck })
+    }
+}
+
+/// Expand `secret` into `out` like HKDF-Expand, with `label` and the length
+/// of `out` as info.
+///
+/// # Panics
+///
+/// If `out` is longer than `255 * H::hash_len()`.
+pub(crate) fn expand<H: Hash>(secret: &[u8], label: &[u8], out: &mut [u8]) {
+    assert!(out.len() <= 255 * H::hash_len());
+    let len = (out.len() as u64).to_be_bytes();
+    let mut t = Sensitive::from(H::Output::new());
+    for (i, chunk) in out.chunks_mut(H::hash_len()).enumerate() {
+        let prev = if i == 0 { &[][..] } else { t.as_slice() };
+        let next = H::hmac_many(secret, &[prev, label, &len, &[i as u8 + 1]]);
+        t = Sensitive::from(next);
+        chunk.copy_from_slice(&t.as_slice()[..chunk.len()]);
+    }
 }
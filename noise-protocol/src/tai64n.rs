@@ -0,0 +1,212 @@
+//! TAI64N timestamps in first handshake messages, to reject replays of
+//! zero-RTT data, as WireGuard does.
+//!
+//! In `KKpsk0`, both peers know each other's static key and share a PSK, so
+//! the first message of the initiator is authenticated and can carry data.
+//! An attacker can still replay it. With a timestamp in its payload, the
+//! responder can reject old and repeated messages:
+//!
+//! 1. The initiator writes its first message with [`write_timestamped`].
+//! 2. The responder keeps a [`TimestampWindow`] per initiator, and reads the
+//!    message with [`read_timestamped`], which fails with
+//!    [`Replay`](crate::ErrorKind::Replay) unless the timestamp is newer
+//!    than any accepted before, and close to the current time.
+//!
+//! The windows must survive restarts of the responder, or old messages
+//! within the window are accepted again. Clocks of initiators must be
+//! roughly in sync with the responder, and must not jump backwards.
+//!
+//! The same works with any pattern where the responder knows the initiator
+//! after the first message, e.g., `IK`, using
+//! [`get_rs`](crate::HandshakeState::get_rs) to find the window.
+
+use crate::error::Error;
+
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::handshakestate::HandshakeState;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::traits::{Cipher, Hash, Kem, Signature, DH};
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+/// Length of an encoded [`Tai64N`].
+pub const LEN: usize = 12;
+
+/// TAI64 label of the Unix epoch, `2 ^ 62` plus the 10 seconds TAI was
+/// ahead of UTC then, as in WireGuard.
+const EPOCH: u64 = 0x4000_0000_0000_000a;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// A TAI64N timestamp, i.e., the TAI64 label of a second and the
+/// nanoseconds within it, both big-endian.
+///
+/// Timestamps compare like the times they encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tai64N {
+    secs: u64,
+    nanos: u32,
+}
+
+impl Tai64N {
+    /// The timestamp `secs` seconds and `nanos` nanoseconds after the Unix
+    /// epoch.
+    ///
+    /// # Panics
+    ///
+    /// If `nanos` is not less than one second.
+    pub fn from_unix(secs: u64, nanos: u32) -> Self {
+        assert!(nanos < NANOS_PER_SEC);
+        Tai64N {
+            secs: EPOCH.saturating_add(secs),
+            nanos,
+        }
+    }
+
+    /// The current time.
+    ///
+    /// # Panics
+    ///
+    /// If the system clock is before the Unix epoch.
+    #[cfg(feature = "use_std")]
+    pub fn now() -> Self {
+        let d = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        Self::from_unix(d.as_secs(), d.subsec_nanos())
+    }
+
+    /// Encode.
+    pub fn to_bytes(&self) -> [u8; LEN] {
+        let mut out = [0u8; LEN];
+        out[..8].copy_from_slice(&self.secs.to_be_bytes());
+        out[8..].copy_from_slice(&self.nanos.to_be_bytes());
+        out
+    }
+
+    /// Decode, failing with [TooShort](crate::ErrorKind::TooShort) if
+    /// `bytes` is shorter than [`LEN`], and with
+    /// [Replay](crate::ErrorKind::Replay) if it is not a valid timestamp.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes = bytes.get(..LEN).ok_or_else(Error::too_short)?;
+        let mut secs = [0u8; 8];
+        let mut nanos = [0u8; 4];
+        secs.copy_from_slice(&bytes[..8]);
+        nanos.copy_from_slice(&bytes[8..]);
+        let (secs, nanos) = (u64::from_be_bytes(secs), u32::from_be_bytes(nanos));
+        if secs >= 1 << 63 || nanos >= NANOS_PER_SEC {
+            return Err(Error::replay());
+        }
+        Ok(Tai64N { secs, nanos })
+    }
+
+    fn as_nanos(&self) -> u128 {
+        u128::from(self.secs) * u128::from(NANOS_PER_SEC) + u128::from(self.nanos)
+    }
+}
+
+/// The newest timestamp accepted from a peer, and how far timestamps may be
+/// from the current time.
+#[derive(Debug, Clone, Copy)]
+pub struct TimestampWindow {
+    newest: Option<Tai64N>,
+    max_skew_secs: u64,
+}
+
+impl TimestampWindow {
+    /// Accept timestamps at most `max_skew_secs` seconds before or after the
+    /// current time, e.g., 120.
+    pub fn new(max_skew_secs: u64) -> Self {
+        TimestampWindow {
+            newest: None,
+            max_skew_secs,
+        }
+    }
+
+    /// Continue from the newest timestamp accepted before, e.g., when
+    /// restarting.
+    pub fn with_newest(max_skew_secs: u64, newest: Tai64N) -> Self {
+        TimestampWindow {
+            newest: Some(newest),
+            max_skew_secs,
+        }
+    }
+
+    /// The newest timestamp accepted, to save.
+    pub fn newest(&self) -> Option<Tai64N> {
+        self.newest
+    }
+
+    /// Accept `timestamp` at the time `now` if it is newer than any accepted
+    /// before and within the window around `now`.
+    ///
+    /// Fails with [Replay](crate::ErrorKind::Replay) otherwise, leaving the
+    /// window unchanged.
+    pub fn check(&mut self, timestamp: Tai64N, now: Tai64N) -> Result<(), Error> {
+        let skew = u128::from(self.max_skew_secs) * u128::from(NANOS_PER_SEC);
+        let in_window = timestamp.as_nanos().abs_diff(now.as_nanos()) <= skew;
+        if !in_window || matches!(self.newest, Some(newest) if timestamp <= newest) {
+            return Err(Error::replay());
+        }
+        self.newest = Some(timestamp);
+        Ok(())
+    }
+}
+
+/// Write a handshake message with the timestamp `now` before `payload`.
+///
+/// # Errors
+///
+/// Cf. [`HandshakeState::write_message_vec`].
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub fn write_timestamped<D, C, H, K, S>(
+    hs: &mut HandshakeState<D, C, H, K, S>,
+    now: Tai64N,
+    payload: &[u8],
+) -> Result<Vec<u8>, Error>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+    K: Kem,
+    S: Signature,
+{
+    let mut timestamped = Vec::with_capacity(LEN + payload.len());
+    timestamped.extend_from_slice(&now.to_bytes());
+    timestamped.extend_from_slice(payload);
+    hs.write_message_vec(&timestamped)
+}
+
+/// Read a handshake message written with [`write_timestamped`], check its
+/// timestamp with `window` at the time `now`, and return the rest of the
+/// payload.
+///
+/// The timestamp is only checked after decryption, so on failure the
+/// handshake state has advanced and must be discarded.
+///
+/// # Errors
+///
+/// Cf. [`HandshakeState::read_message_vec`] and [`TimestampWindow::check`],
+/// and [TooShort](crate::ErrorKind::TooShort) if the payload has no
+/// timestamp.
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+pub fn read_timestamped<D, C, H, K, S>(
+    hs: &mut HandshakeState<D, C, H, K, S>,
+    msg: &[u8],
+    window: &mut TimestampWindow,
+    now: Tai64N,
+) -> Result<Vec<u8>, Error>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+    K: Kem,
+    S: Signature,
+{
+    let mut payload = hs.read_message_vec(msg)?;
+    let timestamp = Tai64N::from_bytes(&payload)?;
+    window.check(timestamp, now)?;
+    payload.drain(..LEN);
+    Ok(payload)
+}
@@ -0,0 +1,571 @@
+//! Test vectors in the JSON format of
+//! [cacophony and snow](https://github.com/noiseprotocol/noise_wiki/wiki/Test-vectors).
+//!
+//! A [`VectorRunner`] verifies vectors of all the suites registered with
+//! it, and [`generate`] produces vectors for this crate, including handshakes
+//! with Elligator encoded ephemeral keys, so that other implementations of
+//! the extension can check their interoperability.
+//!
+//! ```ignore
+//! let mut runner = VectorRunner::new();
+//! runner.register::<X25519, ChaCha20Poly1305, Blake2s>();
+//! for v in parse(&json)? {
+//!     match runner.verify(&v) {
+//!         Ok(()) | Err(VectorError::Unsupported) => {}
+//!         Err(e) => panic!("{}: {}", v.protocol_name, e),
+//!     }
+//! }
+//! ```
+//!
+//! # Elligator
+//!
+//! Vectors with Elligator encoded ephemeral keys set `elligator_encoded`, and
+//! give the representatives of the ephemeral public keys in
+//! `init_ephemeral_public` and `resp_ephemeral_public`, which can not be
+//! computed from the private keys. Other implementations ignore these fields
+//! and fail these vectors.
+
+use crate::error::Error;
+use crate::handshakepattern::{by_name, HandshakePattern, Token};
+use crate::handshakestate::HandshakeState;
+use crate::registry::ProtocolName;
+use crate::traits::{Cipher, DhKeyPair, Hash, U8Array, DH};
+use core::fmt::{Display, Error as FmtError, Formatter};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// A test vector.
+///
+/// Keys are raw bytes, in the format of the DH function. Private keys are
+/// given, public keys are computed, except for the Elligator
+/// representatives.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vector {
+    /// Protocol name, e.g., `Noise_XX_25519_ChaChaPoly_BLAKE2s`.
+    pub protocol_name: String,
+    /// Hybrid forward secrecy vectors are not supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hybrid: Option<String>,
+    /// Fallback vectors are not supported.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub fallback: bool,
+    /// Whether the ephemeral keys are Elligator encoded.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub elligator_encoded: bool,
+    /// Initiator prologue.
+    #[serde(with = "hex_bytes")]
+    pub init_prologue: Vec<u8>,
+    /// Initiator PSKs.
+    #[serde(default, with = "hex_list", skip_serializing_if = "Vec::is_empty")]
+    pub init_psks: Vec<Vec<u8>>,
+    /// Initiator static private key.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub init_static: Option<Vec<u8>>,
+    /// Initiator ephemeral private key.
+    #[serde(with = "hex_bytes")]
+    pub init_ephemeral: Vec<u8>,
+    /// Representative of the initiator ephemeral public key, with Elligator.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub init_ephemeral_public: Option<Vec<u8>>,
+    /// Responder static public key, known to the initiator.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub init_remote_static: Option<Vec<u8>>,
+    /// Responder prologue.
+    #[serde(with = "hex_bytes")]
+    pub resp_prologue: Vec<u8>,
+    /// Responder PSKs.
+    #[serde(default, with = "hex_list", skip_serializing_if = "Vec::is_empty")]
+    pub resp_psks: Vec<Vec<u8>>,
+    /// Responder static private key.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub resp_static: Option<Vec<u8>>,
+    /// Responder ephemeral private key.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub resp_ephemeral: Option<Vec<u8>>,
+    /// Representative of the responder ephemeral public key, with Elligator.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub resp_ephemeral_public: Option<Vec<u8>>,
+    /// Initiator static public key, known to the responder.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub resp_remote_static: Option<Vec<u8>>,
+    /// Handshake hash.
+    #[serde(default, with = "hex_option", skip_serializing_if = "Option::is_none")]
+    pub handshake_hash: Option<Vec<u8>>,
+    /// Handshake messages, then transport messages.
+    pub messages: Vec<Message>,
+}
+
+/// A message of a [`Vector`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    /// Payload.
+    #[serde(with = "hex_bytes")]
+    pub payload: Vec<u8>,
+    /// Handshake or transport message.
+    #[serde(with = "hex_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Vectors {
+    vectors: Vec<Vector>,
+}
+
+/// Parse a file of vectors, i.e., `{"vectors": [...]}`.
+pub fn parse(json: &str) -> Result<Vec<Vector>, serde_json::Error> {
+    serde_json::from_str::<Vectors>(json).map(|v| v.vectors)
+}
+
+/// Write a file of vectors, in the format read by [`parse`].
+pub fn to_json(vectors: &[Vector]) -> String {
+    let vectors = Vectors {
+        vectors: vectors.to_vec(),
+    };
+    serde_json::to_string_pretty(&vectors).unwrap()
+}
+
+/// Errors of [`VectorRunner::verify`].
+#[derive(Debug)]
+pub enum VectorError {
+    /// The protocol is not supported: its suite is not registered, the
+    /// pattern is unknown, or the vector is a fallback or hybrid one.
+    Unsupported,
+    /// A key has the wrong length, or a key required by the pattern is
+    /// missing.
+    InvalidVector,
+    /// Reading or writing a message failed.
+    Handshake(Error),
+    /// A message, or the handshake hash if its index is the number of
+    /// messages, differs from the vector.
+    Mismatch(usize),
+}
+
+impl Display for VectorError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), FmtError> {
+        match self {
+            VectorError::Unsupported => fmt.write_str("Unsupported vector"),
+            VectorError::InvalidVector => fmt.write_str("Invalid vector"),
+            VectorError::Handshake(e) => write!(fmt, "Handshake failed: {}", e),
+            VectorError::Mismatch(i) => write!(fmt, "Mismatch at message {}", i),
+        }
+    }
+}
+
+impl ::std::error::Error for VectorError {}
+
+impl From<Error> for VectorError {
+    fn from(e: Error) -> Self {
+        VectorError::Handshake(e)
+    }
+}
+
+type Verify = fn(&Vector, HandshakePattern) -> Result<(), VectorError>;
+
+/// Verifies vectors of the registered suites, i.e., DH/cipher/hash
+/// combinations.
+#[derive(Default)]
+pub struct VectorRunner {
+    suites: Vec<(&'static str, &'static str, &'static str, Verify)>,
+}
+
+impl VectorRunner {
+    /// Create a runner without suites.
+    pub fn new() -> Self {
+        VectorRunner { suites: Vec::new() }
+    }
+
+    /// Register a suite under the names of its primitives.
+    pub fn register<D, C, H>(&mut self) -> &mut Self
+    where
+        D: DH,
+        C: Cipher,
+        H: Hash,
+    {
+        self.suites
+            .push((D::name(), C::name(), H::name(), verify::<D, C, H>));
+        self
+    }
+
+    /// Verify a vector.
+    pub fn verify(&self, v: &Vector) -> Result<(), VectorError> {
+        if v.fallback || v.hybrid.is_some() {
+            return Err(VectorError::Unsupported);
+        }
+        let name = ProtocolName::parse(&v.protocol_name).map_err(|_| VectorError::Unsupported)?;
+        let pattern = by_name(name.pattern)
+            .filter(|&p| !is_hybrid(p))
+            .ok_or(VectorError::Unsupported)?;
+        let &(.., verify) = self
+            .suites
+            .iter()
+            .find(|&&(d, c, h, _)| (d, c, h) == (name.dh, name.cipher, name.hash))
+            .ok_or(VectorError::Unsupported)?;
+        verify(v, pattern)
+    }
+}
+
+fn array<A: U8Array>(k: &[u8]) -> Result<A, VectorError> {
+    if k.len() != A::len() {
+        return Err(VectorError::InvalidVector);
+    }
+    Ok(A::from_slice(k))
+}
+
+fn keypair<D: DH>(
+    private: &[u8],
+    public: Option<&Vec<u8>>,
+) -> Result<DhKeyPair<D::Key, D::Pubkey>, VectorError> {
+    let private: D::Key = array(private)?;
+    Ok(match public {
+        Some(public) => DhKeyPair {
+            private,
+            public: array(public)?,
+        },
+        None => DhKeyPair::from_private::<D>(private),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn new_handshake<D, C, H>(
+    pattern: HandshakePattern,
+    is_initiator: bool,
+    is_elligator_encoded: bool,
+    prologue: &[u8],
+    s: Option<&Vec<u8>>,
+    e: Option<DhKeyPair<D::Key, D::Pubkey>>,
+    rs: Option<&Vec<u8>>,
+    psks: &[Vec<u8>],
+) -> Result<HandshakeState<D, C, H>, VectorError>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+{
+    let s = s.map(|s| keypair::<D>(s, None)).transpose()?;
+    let rs = rs.map(|rs| array(rs)).transpose()?;
+    let mut builder = crate::HandshakeStateBuilder::<D>::new();
+    builder
+        .set_pattern(pattern)
+        .set_is_initiator(is_initiator)
+        .set_is_elligator_encoded(is_elligator_encoded)
+        .set_prologue(prologue)
+        .set_allow_low_order(true);
+    if let Some(s) = s {
+        builder.set_s(s);
+    }
+    if let Some(e) = e {
+        builder.set_e(e);
+    }
+    if let Some(rs) = rs {
+        builder.set_rs(rs);
+    }
+    for psk in psks {
+        if psk.len() != 32 {
+            return Err(VectorError::InvalidVector);
+        }
+        builder.push_psk(psk);
+    }
+    builder
+        .try_build_handshake_state()
+        .map_err(|_| VectorError::InvalidVector)
+}
+
+fn verify<D, C, H>(v: &Vector, pattern: HandshakePattern) -> Result<(), VectorError>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+{
+    let ie = keypair::<D>(&v.init_ephemeral, v.init_ephemeral_public.as_ref())?;
+    let re = v
+        .resp_ephemeral
+        .as_ref()
+        .map(|e| keypair::<D>(e, v.resp_ephemeral_public.as_ref()))
+        .transpose()?;
+    let mut i = new_handshake::<D, C, H>(
+        pattern,
+        true,
+        v.elligator_encoded,
+        &v.init_prologue,
+        v.init_static.as_ref(),
+        Some(ie),
+        v.init_remote_static.as_ref(),
+        &v.init_psks,
+    )?;
+    let mut r = new_handshake::<D, C, H>(
+        pattern,
+        false,
+        v.elligator_encoded,
+        &v.resp_prologue,
+        v.resp_static.as_ref(),
+        re,
+        v.resp_remote_static.as_ref(),
+        &v.resp_psks,
+    )?;
+
+    let payloads = v.messages.iter().map(|m| m.payload.as_slice());
+    let messages = run(&mut i, &mut r, pattern, payloads)?;
+    for (n, (c, m)) in messages.0.iter().zip(&v.messages).enumerate() {
+        if c != &m.ciphertext {
+            return Err(VectorError::Mismatch(n));
+        }
+    }
+    match v.handshake_hash {
+        Some(ref h) if h != &messages.1 => Err(VectorError::Mismatch(v.messages.len())),
+        _ => Ok(()),
+    }
+}
+
+// Exchange the payloads, first in handshake messages, then in transport
+// messages. Returns the messages and the handshake hash.
+fn run<'a, D, C, H>(
+    i: &mut HandshakeState<D, C, H>,
+    r: &mut HandshakeState<D, C, H>,
+    pattern: HandshakePattern,
+    payloads: impl Iterator<Item = &'a [u8]>,
+) -> Result<(Vec<Vec<u8>>, Vec<u8>), VectorError>
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+{
+    let mut messages = Vec::new();
+    let mut ciphers = None;
+    let mut init_send = true;
+    for payload in payloads {
+        let c = match ciphers {
+            None => {
+                let (send, recv) = if init_send {
+                    (&mut *i, &mut *r)
+                } else {
+                    (&mut *r, &mut *i)
+                };
+                let c = send.write_message_vec(payload)?;
+                recv.read_message_vec(&c)?;
+                if i.completed() {
+                    ciphers = Some((i.get_ciphers(), r.get_ciphers()));
+                }
+                c
+            }
+            Some(((ref mut i_send, ref mut i_recv), (ref mut r_recv, ref mut r_send))) => {
+                let (send, recv) = if init_send {
+                    (i_send, r_recv)
+                } else {
+                    (r_send, i_recv)
+                };
+                let c = send.encrypt_vec(payload);
+                recv.decrypt_vec(&c)?;
+                c
+            }
+        };
+        messages.push(c);
+        if !pattern.is_one_way() {
+            init_send = !init_send;
+        }
+    }
+    Ok((messages, i.get_hash().to_vec()))
+}
+
+/// Generate a vector for `pattern`, with keys and PSKs drawn from `rng`,
+/// the handshake messages and `transport_messages` transport messages.
+///
+/// With `elligator`, the ephemeral keys are Elligator encoded. The static
+/// keys are not.
+///
+/// # Panics
+///
+/// If `pattern` is a hybrid one, e.g., `XXhfs`, or a signature one, e.g.,
+/// `XXsig`.
+pub fn generate<D, C, H, R>(
+    pattern: HandshakePattern,
+    elligator: bool,
+    transport_messages: usize,
+    rng: &mut R,
+) -> Vector
+where
+    D: DH,
+    C: Cipher,
+    H: Hash,
+    R: RngCore + CryptoRng,
+{
+    assert!(!is_hybrid(pattern), "hybrid patterns are not supported");
+    assert!(!is_signed(pattern), "signature patterns are not supported");
+
+    // Whether the static key of a party is used, i.e., in a pre-message or
+    // sent.
+    let uses_s = |pre: &[Token], initiator: bool| {
+        has(pre, Token::S)
+            || (0..pattern.get_message_patterns_len())
+                .filter(|m| (m % 2 == 0) == initiator)
+                .any(|m| has(pattern.get_message_pattern(m), Token::S))
+    };
+    let is = uses_s(pattern.get_pre_i(), true).then(|| D::genkey_with_rng(rng, false));
+    let rs = uses_s(pattern.get_pre_r(), false).then(|| D::genkey_with_rng(rng, false));
+    let ie = D::genkey_with_rng(rng, elligator);
+    let re = (!pattern.is_one_way()).then(|| D::genkey_with_rng(rng, elligator));
+    let psk_count = (0..pattern.get_message_patterns_len())
+        .flat_map(|m| pattern.get_message_pattern(m))
+        .filter(|t| matches!(t, Token::PSK))
+        .count();
+    let psks: Vec<Vec<u8>> = (0..psk_count)
+        .map(|_| {
+            let mut psk = vec![0u8; 32];
+            rng.fill_bytes(&mut psk);
+            psk
+        })
+        .collect();
+    let prologue = b"John Galt".to_vec();
+
+    let mut v = Vector {
+        protocol_name: format!(
+            "Noise_{}_{}_{}_{}",
+            pattern.get_name(),
+            D::name(),
+            C::name(),
+            H::name()
+        ),
+        elligator_encoded: elligator,
+        init_prologue: prologue.clone(),
+        init_psks: psks.clone(),
+        init_static: is.as_ref().map(|k| k.private.as_slice().to_vec()),
+        init_ephemeral: ie.private.as_slice().to_vec(),
+        init_ephemeral_public: elligator.then(|| ie.public.as_slice().to_vec()),
+        init_remote_static: has(pattern.get_pre_r(), Token::S)
+            .then(|| rs.as_ref().unwrap().public.as_slice().to_vec()),
+        resp_prologue: prologue,
+        resp_psks: psks,
+        resp_static: rs.as_ref().map(|k| k.private.as_slice().to_vec()),
+        resp_ephemeral: re.as_ref().map(|k| k.private.as_slice().to_vec()),
+        resp_ephemeral_public: re
+            .as_ref()
+            .filter(|_| elligator)
+            .map(|k| k.public.as_slice().to_vec()),
+        resp_remote_static: has(pattern.get_pre_i(), Token::S)
+            .then(|| is.as_ref().unwrap().public.as_slice().to_vec()),
+        ..Vector::default()
+    };
+
+    let n = pattern.get_message_patterns_len() + transport_messages;
+    let payloads: Vec<Vec<u8>> = (0..n)
+        .map(|i| format!("test message {}", i).into_bytes())
+        .collect();
+    let build = |initiator| {
+        let (s, e, rs, psks) = if initiator {
+            (
+                &v.init_static,
+                Some(ie.clone()),
+                &v.init_remote_static,
+                &v.init_psks,
+            )
+        } else {
+            (
+                &v.resp_static,
+                re.clone(),
+                &v.resp_remote_static,
+                &v.resp_psks,
+            )
+        };
+        new_handshake::<D, C, H>(
+            pattern,
+            initiator,
+            elligator,
+            &v.init_prologue,
+            s.as_ref(),
+            e,
+            rs.as_ref(),
+            psks,
+        )
+        .unwrap()
+    };
+    let (mut i, mut r) = (build(true), build(false));
+    let (messages, h) = run(&mut i, &mut r, pattern, payloads.iter().map(|p| &p[..]))
+        .expect("handshake of generated keys failed");
+    v.messages = payloads
+        .into_iter()
+        .zip(messages)
+        .map(|(payload, ciphertext)| Message {
+            payload,
+            ciphertext,
+        })
+        .collect();
+    v.handshake_hash = Some(h);
+    v
+}
+
+fn has(tokens: &[Token], token: Token) -> bool {
+    tokens
+        .iter()
+        .any(|&t| core::mem::discriminant(&t) == core::mem::discriminant(&token))
+}
+
+// Whether the pattern has `e1` or `ekem1` tokens.
+fn is_hybrid(pattern: HandshakePattern) -> bool {
+    (0..pattern.get_message_patterns_len()).any(|m| {
+        let m = pattern.get_message_pattern(m);
+        has(m, Token::E1) || has(m, Token::EKEM1)
+    })
+}
+
+// Whether the pattern has `sig` tokens.
+fn is_signed(pattern: HandshakePattern) -> bool {
+    (0..pattern.get_message_patterns_len()).any(|m| has(pattern.get_message_pattern(m), Token::SIG))
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+mod hex_bytes {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&hex::encode(v))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(d)?;
+        hex::decode(s).map_err(D::Error::custom)
+    }
+}
+
+mod hex_option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    struct Hex(#[serde(with = "super::hex_bytes")] Vec<u8>);
+
+    pub fn serialize<S: Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(v) => super::hex_bytes::serialize(v, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Ok(Option::<Hex>::deserialize(d)?.map(|h| h.0))
+    }
+}
+
+mod hex_list {
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    struct Hex(#[serde(with = "super::hex_bytes")] Vec<u8>);
+
+    pub fn serialize<S: Serializer>(v: &[Vec<u8>], s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(v.len()))?;
+        for v in v {
+            seq.serialize_element(&hex::encode(v))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Ok(Vec::<Hex>::deserialize(d)?
+            .into_iter()
+            .map(|h| h.0)
+            .collect())
+    }
+}
@@ -1,3 +1,8 @@
+use crate::error::Error;
+use crate::util::Sensitive;
+use rand_core::{CryptoRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
+
 /// A trait for fixed size u8 array.
 
 // Inspired by ArrayVec and SmallVec, but no unsafe.
@@ -55,9 +60,22 @@ macro_rules! impl_array {
     };
 }
 
+impl_array!(0);
+// AES-128 keys.
+impl_array!(16);
 impl_array!(32);
+impl_array!(48);
+impl_array!(56);
 impl_array!(64);
+// Rate of SHA3-512.
+impl_array!(72);
 impl_array!(128);
+// Rate of SHA3-256 and cSHAKE256.
+impl_array!(136);
+// ML-KEM-768 ciphertexts, encapsulation keys and decapsulation keys.
+impl_array!(1088);
+impl_array!(1184);
+impl_array!(2400);
 
 /// A DH.
 pub trait DH {
@@ -74,12 +92,42 @@ pub trait DH {
     /// Randomly generate a new private key.
     fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey>;
 
+    /// Generate a new private key with `rng`, e.g., a seeded RNG for
+    /// deterministic tests or fuzzing, or on platforms without an OS RNG.
+    ///
+    /// The default implementation ignores `rng` and calls
+    /// [`genkey`](DH::genkey), so override it where `rng` must be used.
+    fn genkey_with_rng<R: RngCore + CryptoRng>(
+        _: &mut R,
+        elligator: bool,
+    ) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        Self::genkey(elligator)
+    }
+
     /// Calculate public key from a private key.
     /// THIS DOES NOT ENCODE THE KEY AS AN ELLIGATOR KEY, PLEASE USE THE PUBLIC KEY PROVIDED AT CREATION
     fn pubkey(_: &Self::Key) -> Self::Pubkey;
 
     /// Perform DH key exchange.
-    fn dh(_: &Self::Key, _: &Self::Pubkey, is_elligator_encoded: bool) -> Result<Self::Output, ()>;
+    ///
+    /// Fails with [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey) if
+    /// the public key is invalid, or with [DH](crate::ErrorKind::DH).
+    fn dh(
+        _: &Self::Key,
+        _: &Self::Pubkey,
+        is_elligator_encoded: bool,
+    ) -> Result<Self::Output, Error>;
+
+    /// Check a public key received from the peer, if
+    /// [strict validation](crate::HandshakeStateBuilder::set_strict_pubkey_validation)
+    /// is enabled.
+    ///
+    /// Fails with [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey) if
+    /// the key is malformed or weak. The default accepts every key.
+    fn check_pubkey(_: &Self::Pubkey, is_elligator_encoded: bool) -> Result<(), Error> {
+        let _ = is_elligator_encoded;
+        Ok(())
+    }
 }
 
 /// Union of a DiffieHellman private and public keys
@@ -101,7 +149,10 @@ impl<S, P> DhKeyPair<S, P> {
 
 impl<S: U8Array, P: U8Array> Clone for DhKeyPair<S, P> {
     fn clone(&self) -> Self {
-        Self { private: self.private.clone(), public: self.public.clone() }
+        Self {
+            private: self.private.clone(),
+            public: self.public.clone(),
+        }
     }
 }
 
@@ -112,6 +163,117 @@ impl<S, P> From<(S, P)> for DhKeyPair<S, P> {
     }
 }
 
+/// A key encapsulation mechanism, for hybrid forward secrecy, cf. the `e1`
+/// and `ekem1` [tokens](crate::patterns::Token).
+pub trait Kem {
+    /// Type of private (decapsulation) key.
+    type Key: U8Array;
+    /// Type of public (encapsulation) key.
+    type Pubkey: U8Array;
+    /// Type of ciphertext.
+    type Ciphertext: U8Array;
+    /// Type of shared secret.
+    type Output: U8Array;
+
+    /// Name of this KEM, e.g., “MLKEM768”.
+    fn name() -> &'static str;
+
+    /// Randomly generate a new key pair.
+    fn genkey() -> (Self::Key, Self::Pubkey);
+
+    /// Generate a shared secret and encapsulate it to `pk`.
+    ///
+    /// Fails with [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey) if
+    /// `pk` is invalid, or with [Kem](crate::ErrorKind::Kem).
+    fn encapsulate(pk: &Self::Pubkey) -> Result<(Self::Ciphertext, Self::Output), Error>;
+
+    /// Decapsulate the shared secret from a ciphertext.
+    ///
+    /// Fails with [Kem](crate::ErrorKind::Kem).
+    fn decapsulate(k: &Self::Key, ct: &Self::Ciphertext) -> Result<Self::Output, Error>;
+}
+
+/// No KEM, the default for [`HandshakeState`](crate::HandshakeState).
+///
+/// Patterns with `e1` or `ekem1` tokens can not be used with it.
+pub enum NoKem {}
+
+impl Kem for NoKem {
+    type Key = [u8; 0];
+    type Pubkey = [u8; 0];
+    type Ciphertext = [u8; 0];
+    type Output = [u8; 0];
+
+    fn name() -> &'static str {
+        ""
+    }
+
+    fn genkey() -> (Self::Key, Self::Pubkey) {
+        panic!("Pattern needs a KEM")
+    }
+
+    fn encapsulate(_: &Self::Pubkey) -> Result<(Self::Ciphertext, Self::Output), Error> {
+        panic!("Pattern needs a KEM")
+    }
+
+    fn decapsulate(_: &Self::Key, _: &Self::Ciphertext) -> Result<Self::Output, Error> {
+        panic!("Pattern needs a KEM")
+    }
+}
+
+/// A signature scheme, for the `sig` [token](crate::patterns::Token), e.g.,
+/// of [`noise_xx_sig`](crate::patterns::noise_xx_sig).
+pub trait Signature {
+    /// Type of private (signing) key.
+    type Key: U8Array;
+    /// Type of public (verifying) key.
+    type Pubkey: U8Array;
+    /// Type of signature.
+    type Sig: U8Array;
+
+    /// Name of this signature scheme, e.g., “Ed25519”.
+    fn name() -> &'static str;
+
+    /// Calculate the public key of a private key.
+    fn pubkey(k: &Self::Key) -> Self::Pubkey;
+
+    /// Sign `msg`.
+    fn sign(k: &Self::Key, msg: &[u8]) -> Self::Sig;
+
+    /// Verify a signature of `msg`.
+    ///
+    /// Fails with [Signature](crate::ErrorKind::Signature) if it is invalid,
+    /// or with [InvalidPublicKey](crate::ErrorKind::InvalidPublicKey).
+    fn verify(pk: &Self::Pubkey, msg: &[u8], sig: &Self::Sig) -> Result<(), Error>;
+}
+
+/// No signature scheme, the default for
+/// [`HandshakeState`](crate::HandshakeState).
+///
+/// Patterns with `sig` tokens can not be used with it.
+pub enum NoSignature {}
+
+impl Signature for NoSignature {
+    type Key = [u8; 0];
+    type Pubkey = [u8; 0];
+    type Sig = [u8; 0];
+
+    fn name() -> &'static str {
+        ""
+    }
+
+    fn pubkey(_: &Self::Key) -> Self::Pubkey {
+        panic!("Pattern needs a signature scheme")
+    }
+
+    fn sign(_: &Self::Key, _: &[u8]) -> Self::Sig {
+        panic!("Pattern needs a signature scheme")
+    }
+
+    fn verify(_: &Self::Pubkey, _: &[u8], _: &Self::Sig) -> Result<(), Error> {
+        panic!("Pattern needs a signature scheme")
+    }
+}
 
 /// An AEAD.
 pub trait Cipher {
@@ -153,8 +315,33 @@ pub trait Cipher {
         plaintext_len: usize,
     ) -> usize;
 
+    /// AEAD encryption of the concatenation of `bufs`, e.g., to encrypt
+    /// non-contiguous buffers without first copying them into one.
+    ///
+    /// The default implementation copies `bufs` to `out` and encrypts it in
+    /// place.
+    ///
+    /// # Panics
+    ///
+    /// If `out.len()` is not the total length of `bufs` plus
+    /// `Self::tag_len()`.
+    fn encrypt_vectored(k: &Self::Key, nonce: u64, ad: &[u8], bufs: &[&[u8]], out: &mut [u8]) {
+        let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+        assert!(len.checked_add(Self::tag_len()) == Some(out.len()));
+
+        let mut cur = 0;
+        for b in bufs {
+            out[cur..cur + b.len()].copy_from_slice(b);
+            cur += b.len();
+        }
+        Self::encrypt_in_place(k, nonce, ad, out, len);
+    }
+
     /// AEAD decryption.
     ///
+    /// Fails with [Decryption](crate::ErrorKind::Decryption) if the
+    /// ciphertext is not authentic.
+    ///
     /// # Panics
     ///
     /// If `out.len() != ciphertext.len() - Self::tag_len()`
@@ -164,7 +351,7 @@ pub trait Cipher {
         ad: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()>;
+    ) -> Result<(), Error>;
 
     /// AEAD decryption, but decrypt on one buffer.
     /// return the length of plaintext.
@@ -178,14 +365,20 @@ pub trait Cipher {
         ad: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()>;
+    ) -> Result<usize, Error>;
 
     /// Rekey. Returns a new cipher key as a pseudorandom function of `k`.
+    ///
+    /// The default implementation is `REKEY()` of the spec (section 11.3),
+    /// i.e., the first 32 bytes of encrypting 32 zero bytes with nonce
+    /// 2 ^ 64 - 1 and empty associated data. It supports tags of up to 32
+    /// bytes. For keys shorter than 32 bytes, the output is truncated, like
+    /// keys derived in the handshake.
     fn rekey(k: &Self::Key) -> Self::Key {
-        // XXX: `k1` is not zeroed.
-        let mut k1 = [0u8; 48];
-        Self::encrypt(k, 0u64.wrapping_sub(1), &[], &[0; 32], &mut k1);
-        Self::Key::from_slice(&k1[..32])
+        let mut k1 = Zeroizing::new([0u8; 64]);
+        let len = 32 + Self::tag_len();
+        Self::encrypt(k, 0u64.wrapping_sub(1), &[], &[0; 32], &mut k1[..len]);
+        Self::Key::from_slice(&k1[..Self::key_len()])
     }
 }
 
@@ -247,12 +440,18 @@ pub trait Hash: Default {
         for d in data {
             hasher.input(d);
         }
-        let inner_output = hasher.result();
+        let inner_output = Sensitive::from(hasher.result());
 
         hasher.reset();
         hasher.input(opad);
         hasher.input(inner_output.as_slice());
-        hasher.result()
+        // The pads are derived from the key, as is the state of the hasher,
+        // which a reset clears.
+        ipad.zeroize();
+        opad.zeroize();
+        let output = hasher.result();
+        hasher.reset();
+        output
     }
 
     /// Calculate HMAC-THIS-HASH, with some `key` and a message.
@@ -262,7 +461,7 @@ pub trait Hash: Default {
 
     /// Calculate HKDF, as specified in the noise spec.
     fn hkdf(chaining_key: &[u8], input_key_material: &[u8]) -> (Self::Output, Self::Output) {
-        let temp_key = Self::hmac(chaining_key, input_key_material);
+        let temp_key = Sensitive::from(Self::hmac(chaining_key, input_key_material));
         let out1 = Self::hmac(temp_key.as_slice(), &[1u8]);
         let out2 = Self::hmac_many(temp_key.as_slice(), &[out1.as_slice(), &[2u8]]);
         (out1, out2)
@@ -273,7 +472,7 @@ pub trait Hash: Default {
         chaining_key: &[u8],
         input_key_material: &[u8],
     ) -> (Self::Output, Self::Output, Self::Output) {
-        let temp_key = Self::hmac(chaining_key, input_key_material);
+        let temp_key = Sensitive::from(Self::hmac(chaining_key, input_key_material));
         let out1 = Self::hmac(temp_key.as_slice(), &[1u8]);
         let out2 = Self::hmac_many(temp_key.as_slice(), &[out1.as_slice(), &[2u8]]);
         let out3 = Self::hmac_many(temp_key.as_slice(), &[out2.as_slice(), &[3u8]]);
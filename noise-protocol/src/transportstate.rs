@@ -0,0 +1,616 @@
+use crate::cipherstate::CipherState;
+use crate::error::Error;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::file::{StreamDecryptor, StreamEncryptor};
+use crate::handshakestate::HandshakeState;
+use crate::instrument;
+use crate::rekey::{AutoRekey, RekeyCheckpoint, RekeyPolicy, REKEY_FLAG};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use crate::resumption::{self, ResumptionTicket, TicketKey};
+use crate::sessionid::SessionId;
+use crate::symmetricstate::expand;
+use crate::traits::{Cipher, Hash, Kem, Signature, U8Array, DH};
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "use_alloc")]
+use alloc::vec::Vec;
+
+/// Maximum length of a Noise message.
+pub(crate) const MAX_MESSAGE_LEN: usize = 65535;
+
+// Flags of the chunks of `send_large`.
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+const CHUNK_MORE: u8 = 0;
+#[cfg(any(feature = "use_std", feature = "use_alloc"))]
+const CHUNK_LAST: u8 = 1;
+
+/// The transport phase of a Noise session.
+///
+/// Owns the sending and the receiving [`CipherState`], picked according to
+/// the role in the handshake, and the handshake hash for channel binding.
+///
+/// Sending and receiving fail with
+/// [NonceExhausted](crate::ErrorKind::NonceExhausted) when a nonce has
+/// reached 2 ^ 64 - 1.
+///
+/// After a one-way pattern, e.g., `N`, `K` or `X`, use
+/// [`HandshakeState::split_one_way`] instead, so that only the initiator
+/// can send and only the responder can receive.
+pub struct TransportState<C: Cipher, H: Hash> {
+    send: CipherState<C>,
+    recv: CipherState<C>,
+    h: H::Output,
+    // Not available after a restore from a checkpoint without it.
+    exporter: Option<H::Output>,
+    is_initiator: bool,
+    is_one_way: bool,
+    rekey: Option<AutoRekey>,
+    max_message_len: usize,
+}
+
+impl<C, H> Clone for TransportState<C, H>
+where
+    C: Cipher,
+    H: Hash,
+{
+    fn clone(&self) -> Self {
+        Self {
+            send: self.send.clone(),
+            recv: self.recv.clone(),
+            h: self.h.clone(),
+            exporter: self.exporter.as_ref().map(U8Array::clone),
+            is_initiator: self.is_initiator,
+            is_one_way: self.is_one_way,
+            rekey: self.rekey,
+            max_message_len: self.max_message_len,
+        }
+    }
+}
+
+impl<C, H> TransportState<C, H>
+where
+    C: Cipher,
+    H: Hash,
+{
+    /// Create a transport state from a completed handshake.
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has not completed.
+    pub fn from_handshake<D: DH, K: Kem, S: Signature>(hs: &HandshakeState<D, C, H, K, S>) -> Self {
+        assert!(hs.completed());
+        let (c1, c2) = hs.get_ciphers();
+        let is_initiator = hs.get_is_initiator();
+        let (send, recv) = if is_initiator { (c1, c2) } else { (c2, c1) };
+        TransportState {
+            send,
+            recv,
+            h: H::Output::from_slice(hs.get_hash()),
+            exporter: Some(hs.exporter_secret()),
+            is_initiator,
+            is_one_way: false,
+            rekey: None,
+            max_message_len: hs.get_max_message_len(),
+        }
+    }
+
+    /// Create a unidirectional transport state from a completed one-way
+    /// handshake, cf. [`HandshakeState::split_one_way`].
+    ///
+    /// # Panics
+    ///
+    /// If the handshake has not completed.
+    pub(crate) fn one_way_from_handshake<D: DH, K: Kem, S: Signature>(
+        hs: &HandshakeState<D, C, H, K, S>,
+    ) -> Result<Self, Error> {
+        if !hs.get_pattern().is_one_way() {
+            return Err(Error::pattern_violation());
+        }
+        let mut t = Self::from_handshake(hs);
+        // The responder never sends, so only one direction is keyed.
+        let unused = CipherState::new(C::Key::new().as_slice(), 0);
+        if t.is_initiator {
+            t.recv = unused;
+        } else {
+            t.send = unused;
+        }
+        t.is_one_way = true;
+        Ok(t)
+    }
+
+    /// Encrypt `payload` into `out`, returning the length of the message,
+    /// i.e., `payload.len() + self.overhead()`.
+    ///
+    /// # Errors
+    ///
+    /// * [BufferTooSmall](crate::ErrorKind::BufferTooSmall) if `out` is too
+    ///   short.
+    ///
+    /// * [NonceExhausted](crate::ErrorKind::NonceExhausted) if the sending
+    ///   nonce has reached 2 ^ 64 - 1.
+    ///
+    /// * [PatternViolation](crate::ErrorKind::PatternViolation) if we are
+    ///   the responder of a [one-way](TransportState::is_one_way) session.
+    ///
+    /// * [MessageTooLong](crate::ErrorKind::MessageTooLong) if the message
+    ///   would be longer than the
+    ///   [maximum](TransportState::set_max_message_len).
+    pub fn send(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.send_with_ad(&[], payload, out)
+    }
+
+    /// Like [`send`](TransportState::send), but also authenticate `ad`,
+    /// e.g., a plaintext header with routing information sent along with
+    /// the message.
+    ///
+    /// `ad` is not part of the message, and the peer must pass the same to
+    /// [`recv_with_ad`](TransportState::recv_with_ad).
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`send`](TransportState::send).
+    pub fn send_with_ad(
+        &mut self,
+        ad: &[u8],
+        payload: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        if self.is_one_way && !self.is_initiator {
+            return Err(Error::pattern_violation());
+        }
+        let len = payload
+            .len()
+            .checked_add(self.overhead())
+            .ok_or_else(Error::message_too_long)?;
+        if len > self.max_message_len {
+            return Err(Error::message_too_long());
+        }
+        if len > out.len() {
+            return Err(Error::buffer_too_small());
+        }
+        let auto = match &mut self.rekey {
+            Some(auto) => auto,
+            None => {
+                self.send.try_encrypt_ad(ad, payload, &mut out[..len])?;
+                return Ok(len);
+            }
+        };
+        let due = auto.send.is_due(&auto.policy, payload.len());
+        if auto.policy.in_band {
+            if self.send.is_exhausted() {
+                return Err(Error::nonce_exhausted());
+            }
+            let header = if due { REKEY_FLAG } else { 0 };
+            self.send
+                .encrypt_ad_vectored_impl(ad, &[&[header], payload], &mut out[..len]);
+            instrument::bytes_sent(payload.len());
+        } else {
+            self.send.try_encrypt_ad(ad, payload, &mut out[..len])?;
+        }
+        auto.send.record(payload.len(), due);
+        if due {
+            self.send.rekey();
+        }
+        Ok(len)
+    }
+
+    /// Decrypt `msg` into `out`, returning the length of the payload, i.e.,
+    /// `msg.len() - self.overhead()`.
+    ///
+    /// # Errors
+    ///
+    /// * [TooShort](crate::ErrorKind::TooShort) if `msg` is shorter than
+    ///   the overhead.
+    ///
+    /// * [BufferTooSmall](crate::ErrorKind::BufferTooSmall) if `out` is too
+    ///   short. With an `in_band` [rekey policy](RekeyPolicy), `out` must
+    ///   have room for the header byte too.
+    ///
+    /// * [NonceExhausted](crate::ErrorKind::NonceExhausted) if the
+    ///   receiving nonce has reached 2 ^ 64 - 1.
+    ///
+    /// * [Decryption](crate::ErrorKind::Decryption) if `msg` fails to
+    ///   decrypt. The receiving nonce is not advanced then.
+    ///
+    /// * [PatternViolation](crate::ErrorKind::PatternViolation) if we are
+    ///   the initiator of a [one-way](TransportState::is_one_way) session.
+    ///
+    /// * [MessageTooLong](crate::ErrorKind::MessageTooLong) if `msg` is
+    ///   longer than the [maximum](TransportState::set_max_message_len).
+    pub fn recv(&mut self, msg: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        self.recv_with_ad(&[], msg, out)
+    }
+
+    /// Like [`recv`](TransportState::recv), for a message sent with
+    /// [`send_with_ad`](TransportState::send_with_ad).
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`recv`](TransportState::recv). Decryption fails if `ad` differs
+    /// from the one of the sender.
+    pub fn recv_with_ad(&mut self, ad: &[u8], msg: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+        if self.is_one_way && self.is_initiator {
+            return Err(Error::pattern_violation());
+        }
+        if msg.len() > self.max_message_len {
+            return Err(Error::message_too_long());
+        }
+        if self.recv.is_exhausted() {
+            return Err(Error::nonce_exhausted());
+        }
+        let header = self.rekey.as_ref().map_or(0, AutoRekey::header_len);
+        let len = msg
+            .len()
+            .checked_sub(self.overhead())
+            .ok_or_else(Error::too_short)?;
+        if len + header > out.len() {
+            return Err(Error::buffer_too_small());
+        }
+        self.recv
+            .decrypt_ad_impl(ad, msg, &mut out[..len + header])?;
+        instrument::bytes_received(len);
+        if let Some(auto) = &mut self.rekey {
+            let due = if auto.policy.in_band {
+                let flags = out[0];
+                out.copy_within(1..len + 1, 0);
+                flags & REKEY_FLAG != 0
+            } else {
+                auto.recv.is_due(&auto.policy, len)
+            };
+            auto.recv.record(len, due);
+            if due {
+                self.recv.rekey();
+            }
+        }
+        Ok(len)
+    }
+
+    /// Encrypt a payload of any length into as many messages as needed,
+    /// passing each message to `emit`, in order.
+    ///
+    /// Each message is a chunk of the payload, prefixed with a flag byte
+    /// inside the ciphertext that marks the last chunk, so the peer can
+    /// reassemble the payload with [`recv_large`](TransportState::recv_large)
+    /// and detect truncation. Every message fits into the
+    /// [maximum](TransportState::set_max_message_len), and is authenticated
+    /// on its own.
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`send`](TransportState::send). Messages emitted before an error
+    /// are valid, but the payload is incomplete.
+    ///
+    /// Fails with [BufferTooSmall](crate::ErrorKind::BufferTooSmall),
+    /// before emitting any message, if the
+    /// [maximum payload](TransportState::max_payload_len) is shorter than 2
+    /// bytes, i.e., a chunk can not hold any of the payload.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn send_large<F: FnMut(&[u8])>(
+        &mut self,
+        payload: &[u8],
+        mut emit: F,
+    ) -> Result<(), Error> {
+        if self.max_payload_len() < 2 {
+            return Err(Error::buffer_too_small());
+        }
+        let max = self.max_payload_len() - 1;
+        let mut chunk = Vec::with_capacity(max + 1);
+        let mut out = vec![0u8; self.max_message_len];
+        let mut rest = payload;
+        loop {
+            let (data, tail) = rest.split_at(rest.len().min(max));
+            let flag = if tail.is_empty() {
+                CHUNK_LAST
+            } else {
+                CHUNK_MORE
+            };
+            chunk.clear();
+            chunk.push(flag);
+            chunk.extend_from_slice(data);
+            let len = self.send(&chunk, &mut out)?;
+            emit(&out[..len]);
+            if tail.is_empty() {
+                return Ok(());
+            }
+            rest = tail;
+        }
+    }
+
+    /// Decrypt a message of [`send_large`](TransportState::send_large),
+    /// appending its chunk of the payload to `out`.
+    ///
+    /// Returns whether it was the last chunk, i.e., whether `out` now holds
+    /// the whole payload. Chunks of the next payload are appended after it,
+    /// so clear `out` in between. Limit the number of messages read to bound
+    /// the payload size.
+    ///
+    /// # Errors
+    ///
+    /// Cf. [`recv`](TransportState::recv), and
+    /// [Decryption](crate::ErrorKind::Decryption) if the message is not a
+    /// chunk. `out` is left unchanged then.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn recv_large(&mut self, msg: &[u8], out: &mut Vec<u8>) -> Result<bool, Error> {
+        let start = out.len();
+        out.resize(start + msg.len(), 0);
+        let result = self.recv(msg, &mut out[start..]);
+        let last = match result {
+            Ok(len) if len > 0 => {
+                let flag = out[start];
+                out.copy_within(start + 1..start + len, start);
+                out.truncate(start + len - 1);
+                match flag {
+                    CHUNK_MORE => Ok(false),
+                    CHUNK_LAST => Ok(true),
+                    _ => Err(Error::decryption()),
+                }
+            }
+            Ok(_) => Err(Error::decryption()),
+            Err(e) => Err(e),
+        };
+        if last.is_err() {
+            out.truncate(start);
+        }
+        last
+    }
+
+    /// Rekey automatically according to `policy`, replacing any previous
+    /// policy and starting to count anew.
+    ///
+    /// Rekeying by hand, with
+    /// [`rekey_outgoing`](TransportState::rekey_outgoing), is still
+    /// possible, but the peer must then know about it by other means.
+    ///
+    /// # Panics
+    ///
+    /// If `policy` has a `time` but is not `in_band`, or without the
+    /// `use_std` feature.
+    pub fn set_rekey_policy(&mut self, policy: RekeyPolicy) {
+        self.rekey = Some(AutoRekey::new(policy));
+    }
+
+    /// The length that [`send`](TransportState::send) adds to a payload: the
+    /// tag, and the header byte of an `in_band` [rekey policy](RekeyPolicy).
+    pub fn overhead(&self) -> usize {
+        C::tag_len() + self.rekey.as_ref().map_or(0, AutoRekey::header_len)
+    }
+
+    /// Set the maximum length of the messages sent and received, 65535 bytes
+    /// by default, or as set with
+    /// [`HandshakeStateBuilder::set_max_message_len`](crate::HandshakeStateBuilder::set_max_message_len).
+    ///
+    /// # Panics
+    ///
+    /// If `len` is more than 65535, or less than the
+    /// [overhead](TransportState::overhead) plus one.
+    pub fn set_max_message_len(&mut self, len: usize) {
+        assert!(len <= MAX_MESSAGE_LEN && len > self.overhead());
+        self.max_message_len = len;
+    }
+
+    /// The maximum message length.
+    pub fn max_message_len(&self) -> usize {
+        self.max_message_len
+    }
+
+    /// The longest payload that [`send`](TransportState::send) accepts,
+    /// i.e., the maximum message length minus the
+    /// [overhead](TransportState::overhead).
+    pub fn max_payload_len(&self) -> usize {
+        self.max_message_len.saturating_sub(self.overhead())
+    }
+
+    /// Rekey the sending cipher state.
+    ///
+    /// The peer must call [`rekey_incoming`](TransportState::rekey_incoming)
+    /// at the same point of the stream.
+    pub fn rekey_outgoing(&mut self) {
+        self.send.rekey();
+    }
+
+    /// Rekey the receiving cipher state.
+    pub fn rekey_incoming(&mut self) {
+        self.recv.rekey();
+    }
+
+    /// Get handshake hash. Useful for e.g., channel binding.
+    pub fn get_hash(&self) -> &[u8] {
+        self.h.as_slice()
+    }
+
+    /// Get the [session ID](HandshakeState::get_session_id) of the
+    /// handshake, which is the same on both peers, and after a
+    /// [restore](TransportState::restore).
+    pub fn session_id(&self) -> SessionId {
+        SessionId::from_handshake_hash::<H>(self.h.as_slice())
+    }
+
+    /// Derive `out.len()` bytes of keying material for `label`, like
+    /// [`HandshakeState::export_key_to`], which gives the same output.
+    ///
+    /// Fails with [MissingKey](crate::ErrorKind::MissingKey) if the
+    /// transport state has been [restored](TransportState::restore) from a
+    /// checkpoint without the exporter secret.
+    ///
+    /// # Panics
+    ///
+    /// If `out` is longer than `255 * H::hash_len()`.
+    pub fn export_key_to(&self, label: &[u8], out: &mut [u8]) -> Result<(), Error> {
+        let secret = self.exporter.as_ref().ok_or_else(Error::missing_key)?;
+        expand::<H>(secret.as_slice(), label, out);
+        Ok(())
+    }
+
+    /// Like [`export_key_to`](TransportState::export_key_to), returning
+    /// `len` bytes.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn export_key(&self, label: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let mut out = Zeroizing::new(vec![0; len]);
+        self.export_key_to(label, &mut out)?;
+        Ok(out)
+    }
+
+    /// Issue a [`ResumptionTicket`] for this session, encrypted with
+    /// `key`. Send its [blob](ResumptionTicket::blob) to the peer, who gets
+    /// the same ticket with
+    /// [`accept_resumption_ticket`](TransportState::accept_resumption_ticket).
+    ///
+    /// Fails with [MissingKey](crate::ErrorKind::MissingKey) if the
+    /// transport state has been [restored](TransportState::restore) from a
+    /// checkpoint without the exporter secret.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn issue_resumption_ticket(&self, key: &TicketKey) -> Result<ResumptionTicket, Error> {
+        let exporter = self.exporter.as_ref().ok_or_else(Error::missing_key)?;
+        let (psk, id) = resumption::derive::<H>(exporter.as_slice());
+        let blob = key.seal::<C, H>(&id, &psk);
+        Ok(ResumptionTicket::new(*psk, blob))
+    }
+
+    /// Pair the blob of a ticket issued by the peer with
+    /// [`issue_resumption_ticket`](TransportState::issue_resumption_ticket)
+    /// with the PSK derived on our side.
+    ///
+    /// Fails with [MissingKey](crate::ErrorKind::MissingKey) if the
+    /// transport state has been [restored](TransportState::restore) from a
+    /// checkpoint without the exporter secret.
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn accept_resumption_ticket(&self, blob: Vec<u8>) -> Result<ResumptionTicket, Error> {
+        let exporter = self.exporter.as_ref().ok_or_else(Error::missing_key)?;
+        let (psk, _) = resumption::derive::<H>(exporter.as_slice());
+        Ok(ResumptionTicket::new(*psk, blob))
+    }
+
+    /// Whether the handshake was initiated by us.
+    pub fn is_initiator(&self) -> bool {
+        self.is_initiator
+    }
+
+    /// Whether the session is unidirectional, i.e., created with
+    /// [`HandshakeState::split_one_way`].
+    pub fn is_one_way(&self) -> bool {
+        self.is_one_way
+    }
+
+    /// Get the next sending nonce.
+    pub fn sending_nonce(&self) -> u64 {
+        self.send.get_next_n()
+    }
+
+    /// Get the next receiving nonce.
+    pub fn receiving_nonce(&self) -> u64 {
+        self.recv.get_next_n()
+    }
+
+    /// Get the sending and the receiving cipher states.
+    ///
+    /// In a [one-way](TransportState::is_one_way) session, the unused one
+    /// has an all-zero key.
+    pub fn into_ciphers(self) -> (CipherState<C>, CipherState<C>) {
+        (self.send, self.recv)
+    }
+
+    /// Turn the session into a [`StreamEncryptor`] for data to the peer and
+    /// a [`StreamDecryptor`] for data from the peer, e.g., to store a large
+    /// file that only the peer can decrypt, in chunks of `chunk_size` bytes
+    /// with the format of [`file`](crate::file).
+    ///
+    /// The peer must use the same chunk size. The chunks continue the nonces
+    /// of the transport messages, so the encrypted stream is bound to the
+    /// session and can not be confused with its messages.
+    ///
+    /// Fails with [PatternViolation](crate::ErrorKind::PatternViolation) in
+    /// a [one-way](TransportState::is_one_way) session, which has only one
+    /// key. Use [`FileEncryptor`](crate::file::FileEncryptor) instead.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is zero or larger than
+    /// [`MAX_CHUNK_SIZE`](crate::file::MAX_CHUNK_SIZE).
+    #[cfg(any(feature = "use_std", feature = "use_alloc"))]
+    pub fn into_stream_encryptor(
+        self,
+        chunk_size: usize,
+    ) -> Result<(StreamEncryptor<C>, StreamDecryptor<C>), Error> {
+        if self.is_one_way {
+            return Err(Error::pattern_violation());
+        }
+        Ok((
+            StreamEncryptor::new(self.send, chunk_size),
+            StreamDecryptor::new(self.recv, chunk_size),
+        ))
+    }
+
+    /// Checkpoint the session, e.g., to persist it across restarts, cf.
+    /// [`CipherState::extract`].
+    ///
+    /// The checkpoint holds everything needed to
+    /// [restore](TransportState::restore) the session as it is: the keys
+    /// and nonces, the handshake hash, the exporter secret, the role,
+    /// whether the session is [one-way](TransportState::is_one_way), the
+    /// [rekey policy](TransportState::set_rekey_policy) with its counters,
+    /// and the [maximum message length](TransportState::set_max_message_len).
+    pub fn extract(self) -> TransportCheckpoint<C, H> {
+        TransportCheckpoint {
+            rekey: self.rekey.as_ref().map(AutoRekey::checkpoint),
+            send: self.send.extract(),
+            recv: self.recv.extract(),
+            h: self.h,
+            exporter: self.exporter,
+            is_initiator: self.is_initiator,
+            is_one_way: self.is_one_way,
+            max_message_len: self.max_message_len,
+        }
+    }
+
+    /// Restore a transport state from a checkpoint taken with
+    /// [`extract`](TransportState::extract).
+    ///
+    /// As for [`CipherState::restore`], never restore the same, or an older,
+    /// checkpoint twice.
+    ///
+    /// # Panics
+    ///
+    /// If the maximum message length of the checkpoint is invalid, cf.
+    /// [`set_max_message_len`](TransportState::set_max_message_len).
+    pub fn restore(checkpoint: TransportCheckpoint<C, H>) -> Self {
+        let mut t = TransportState {
+            send: CipherState::restore(checkpoint.send.0, checkpoint.send.1),
+            recv: CipherState::restore(checkpoint.recv.0, checkpoint.recv.1),
+            h: checkpoint.h,
+            exporter: checkpoint.exporter,
+            is_initiator: checkpoint.is_initiator,
+            is_one_way: checkpoint.is_one_way,
+            rekey: checkpoint.rekey.as_ref().map(AutoRekey::restore),
+            max_message_len: MAX_MESSAGE_LEN,
+        };
+        t.set_max_message_len(checkpoint.max_message_len);
+        t
+    }
+}
+
+/// A checkpoint of a [`TransportState`], cf.
+/// [`TransportState::extract`].
+///
+/// It contains the transport keys and the exporter secret: keep it in
+/// secure storage. The exporter secret may be dropped before saving the
+/// checkpoint, if [`export_key_to`](TransportState::export_key_to) and
+/// resumption tickets are not needed after a restore.
+pub struct TransportCheckpoint<C: Cipher, H: Hash> {
+    /// The key and the next nonce of the sending cipher state.
+    pub send: (C::Key, u64),
+    /// The key and the next nonce of the receiving cipher state.
+    pub recv: (C::Key, u64),
+    /// The handshake hash.
+    pub h: H::Output,
+    /// The exporter secret.
+    pub exporter: Option<H::Output>,
+    /// Whether the handshake was initiated by us.
+    pub is_initiator: bool,
+    /// Whether the session is [one-way](TransportState::is_one_way).
+    pub is_one_way: bool,
+    /// The [rekey policy](TransportState::set_rekey_policy), if any.
+    pub rekey: Option<RekeyCheckpoint>,
+    /// The [maximum message length](TransportState::set_max_message_len).
+    pub max_message_len: usize,
+}
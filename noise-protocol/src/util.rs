@@ -0,0 +1,99 @@
+//! Miscellaneous helpers.
+
+use crate::traits::{Hash, U8Array};
+use arrayvec::ArrayVec;
+use zeroize::Zeroize;
+
+/// Compare two public keys in constant time, e.g., a received static key
+/// against a known one.
+///
+/// Only the lengths are compared in variable time.
+pub fn ct_eq_pubkey(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |d, (x, y)| d | (x ^ y));
+    core::hint::black_box(diff) == 0
+}
+
+/// An array that is zeroed on drop, whatever its type, e.g., a
+/// [`Hash::Output`] of a hash function whose outputs are plain arrays.
+pub struct Sensitive<A: U8Array>(A);
+
+impl<A: U8Array> Sensitive<A> {
+    /// Wrap `a`, to be zeroed on drop.
+    pub fn from(a: A) -> Self {
+        Sensitive(a)
+    }
+}
+
+impl<A: U8Array> core::ops::Deref for Sensitive<A> {
+    type Target = A;
+    fn deref(&self) -> &A {
+        &self.0
+    }
+}
+
+impl<A: U8Array> core::ops::DerefMut for Sensitive<A> {
+    fn deref_mut(&mut self) -> &mut A {
+        &mut self.0
+    }
+}
+
+impl<A: U8Array> Drop for Sensitive<A> {
+    fn drop(&mut self) {
+        self.0.as_mut().zeroize();
+    }
+}
+
+impl<A: U8Array> U8Array for Sensitive<A> {
+    fn new() -> Self {
+        Sensitive(A::new())
+    }
+
+    fn new_with(v: u8) -> Self {
+        Sensitive(A::new_with(v))
+    }
+
+    fn from_slice(s: &[u8]) -> Self {
+        Sensitive(A::from_slice(s))
+    }
+
+    fn len() -> usize {
+        A::len()
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut()
+    }
+}
+
+/// The `HKDF()` function of the spec, with `num_outputs` outputs, e.g., to
+/// derive auxiliary keys from a secret in the same way as the handshake.
+///
+/// The outputs are the same as those of [`Hash::hkdf`] and [`Hash::hkdf3`],
+/// and are zeroed on drop, as is the intermediate key.
+///
+/// # Panics
+///
+/// If `num_outputs` is not 1, 2 or 3, or if `chaining_key` is longer than
+/// [`H::block_len()`](Hash::block_len).
+pub fn hkdf<H: Hash>(
+    chaining_key: &[u8],
+    input_key_material: &[u8],
+    num_outputs: usize,
+) -> ArrayVec<Sensitive<H::Output>, 3> {
+    assert!((1..=3).contains(&num_outputs));
+    let temp_key = Sensitive::from(H::hmac(chaining_key, input_key_material));
+    let mut outputs = ArrayVec::new();
+    for i in 1..=num_outputs as u8 {
+        let prev = outputs.last().map_or(&[][..], U8Array::as_slice);
+        let output = H::hmac_many(temp_key.as_slice(), &[prev, &[i]]);
+        outputs.push(Sensitive::from(output));
+    }
+    outputs
+}
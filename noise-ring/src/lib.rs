@@ -3,7 +3,7 @@
 pub mod sensitive;
 use sensitive::Sensitive;
 
-use noise_protocol::{Cipher, Hash};
+use noise_protocol::{Cipher, Error, ErrorKind, Hash};
 use ring::{
     aead::{self, LessSafeKey, UnboundKey},
     digest,
@@ -135,7 +135,7 @@ impl Cipher for ChaCha20Poly1305 {
         ad: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()> {
+    ) -> Result<(), Error> {
         assert!(ciphertext.len().checked_sub(TAGLEN) == Some(out.len()));
 
         let mut nonce_bytes = [0u8; 12];
@@ -148,7 +148,7 @@ impl Cipher for ChaCha20Poly1305 {
 
         let out0 = key
             .open_in_place(nonce, aead::Aad::from(ad), &mut in_out)
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         out[..out0.len()].copy_from_slice(out0);
         Ok(())
@@ -160,7 +160,7 @@ impl Cipher for ChaCha20Poly1305 {
         ad: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
         assert!(ciphertext_len <= in_out.len());
         assert!(ciphertext_len >= TAGLEN);
 
@@ -171,7 +171,7 @@ impl Cipher for ChaCha20Poly1305 {
         let key =
             LessSafeKey::new(UnboundKey::new(&aead::CHACHA20_POLY1305, k.as_slice()).unwrap());
         key.open_in_place(nonce, aead::Aad::from(ad), &mut in_out[..ciphertext_len])
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         Ok(ciphertext_len - TAGLEN)
     }
@@ -236,7 +236,7 @@ impl Cipher for Aes256Gcm {
         ad: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()> {
+    ) -> Result<(), Error> {
         assert!(ciphertext.len().checked_sub(TAGLEN) == Some(out.len()));
 
         let mut nonce_bytes = [0u8; 12];
@@ -248,7 +248,7 @@ impl Cipher for Aes256Gcm {
 
         let out0 = key
             .open_in_place(nonce, aead::Aad::from(ad), &mut in_out)
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         out[..out0.len()].copy_from_slice(out0);
         Ok(())
@@ -260,7 +260,7 @@ impl Cipher for Aes256Gcm {
         ad: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
         assert!(ciphertext_len <= in_out.len());
         assert!(ciphertext_len >= TAGLEN);
 
@@ -270,7 +270,7 @@ impl Cipher for Aes256Gcm {
 
         let key = LessSafeKey::new(UnboundKey::new(&aead::AES_256_GCM, k.as_slice()).unwrap());
         key.open_in_place(nonce, aead::Aad::from(ad), &mut in_out[..ciphertext_len])
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         Ok(ciphertext_len - TAGLEN)
     }
@@ -0,0 +1,91 @@
+//! Detection of the CPU crypto extensions used by the AES-GCM and SHA-2
+//! implementations.
+//!
+//! Backend selection happens inside the RustCrypto crates, at runtime where
+//! possible:
+//!
+//! * x86/x86-64: AES-NI, CLMUL and SHA extensions are always detected and
+//!   used when present.
+//! * ARMv8: AES and PMULL are only used when building with
+//!   `RUSTFLAGS="--cfg aes_armv8 --cfg polyval_armv8"`. SHA-2 extensions are
+//!   only used with the `sha2-asm` feature of this crate.
+//! * RISC-V: the versions of `aes` and `sha2` in use have no backends for the
+//!   scalar/vector crypto extensions, so nothing is reported as accelerated.
+//!
+//! Use [`detect`] to find out what is actually in use, e.g., to prefer
+//! ChaChaPoly over AESGCM when AES is not accelerated.
+//!
+//! This module only reports, it does not select backends: the ARMv8
+//! backends of `aes` 0.8 and `polyval` 0.6 can not be enabled by a feature
+//! of a dependent crate. For AES-GCM with the ARMv8 extensions without
+//! RUSTFLAGS, use the ring backend, i.e., the `noise-ring` crate, which
+//! detects them at runtime.
+
+// `aes_armv8` and `polyval_armv8` are set by hand with RUSTFLAGS, as the
+// `aes` and `polyval` crates have no features for their ARMv8 backends.
+#![allow(unexpected_cfgs)]
+
+#[cfg(all(
+    feature = "aes-hw",
+    target_arch = "aarch64",
+    not(all(aes_armv8, polyval_armv8))
+))]
+compile_error!("the `aes-hw` feature needs `--cfg aes_armv8 --cfg polyval_armv8` on aarch64");
+
+/// Crypto extensions in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Acceleration {
+    /// AES instructions, used by AES-256-GCM.
+    pub aes: bool,
+    /// Carry-less multiplication (CLMUL/PMULL), used by GHASH in AES-256-GCM.
+    pub clmul: bool,
+    /// SHA-256 instructions.
+    pub sha256: bool,
+    /// Accelerated SHA-512, by SHA-512 instructions or AVX2.
+    pub sha512: bool,
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+cpufeatures::new!(aes_cpuid, "aes");
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+cpufeatures::new!(clmul_cpuid, "pclmulqdq");
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+cpufeatures::new!(sha256_cpuid, "sha", "sse2", "ssse3", "sse4.1");
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+cpufeatures::new!(sha512_cpuid, "avx2");
+
+// `aes` implies PMULL.
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(aes_hwcap, "aes");
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(sha256_hwcap, "sha2");
+#[cfg(target_arch = "aarch64")]
+cpufeatures::new!(sha512_hwcap, "sha3");
+
+/// Detect the crypto extensions in use on this CPU.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn detect() -> Acceleration {
+    Acceleration {
+        aes: aes_cpuid::get(),
+        clmul: clmul_cpuid::get(),
+        sha256: sha256_cpuid::get(),
+        sha512: sha512_cpuid::get(),
+    }
+}
+
+/// Detect the crypto extensions in use on this CPU.
+#[cfg(target_arch = "aarch64")]
+pub fn detect() -> Acceleration {
+    Acceleration {
+        aes: cfg!(aes_armv8) && aes_hwcap::get(),
+        clmul: cfg!(polyval_armv8) && aes_hwcap::get(),
+        sha256: cfg!(feature = "sha2-asm") && sha256_hwcap::get(),
+        sha512: cfg!(feature = "sha2-asm") && sha512_hwcap::get(),
+    }
+}
+
+/// Detect the crypto extensions in use on this CPU.
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn detect() -> Acceleration {
+    Acceleration::default()
+}
@@ -0,0 +1,251 @@
+//! A small command line tool for debugging Noise deployments.
+//!
+//! ```text
+//! noise genkey [FILE]
+//! noise pubkey PRIVATE_KEY_FILE
+//! noise fingerprint KEY_FILE
+//! noise connect HOST:PORT PROTOCOL_NAME [OPTIONS]
+//! noise listen ADDR:PORT PROTOCOL_NAME [OPTIONS]
+//! ```
+//!
+//! `connect` and `listen` run a handshake, then pipe stdin to the peer and
+//! the peer to stdout. Every message is sent with a 2-byte big endian length
+//! prefix. Keys are X25519 key files, cf. `noise_rust_crypto::keyfile`.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::exit;
+use std::thread;
+
+use noise_protocol::fingerprint::{ShortFingerprint, ZBase32};
+use noise_protocol::registry::{DynCipherState, DynHandshakeState, HandshakeParams, Registry};
+use noise_protocol::{U8Array, DH};
+use noise_rust_crypto::keyfile::*;
+use noise_rust_crypto::*;
+
+const USAGE: &str = "\
+Usage:
+    noise genkey [FILE]                 Generate a key pair, and save it to FILE
+                                        and FILE.pub, or print it
+    noise pubkey PRIVATE_KEY_FILE       Print the public key of a private key
+    noise fingerprint KEY_FILE          Print fingerprints of a key
+    noise connect HOST:PORT PROTOCOL_NAME [OPTIONS]
+    noise listen ADDR:PORT PROTOCOL_NAME [OPTIONS]
+
+Options for connect and listen:
+    --key FILE          Local static private key, random by default
+    --remote-key FILE   Remote static public key
+    --psk HEX           A 32-byte PSK, may be repeated
+    --prologue TEXT     Prologue
+    --elligator         Elligator encode ephemeral keys
+
+Example:
+    noise connect example.com:1234 Noise_XX_25519_ChaChaPoly_BLAKE2s --key key";
+
+type BoxError = Box<dyn std::error::Error>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("genkey") if args.len() <= 2 => genkey(args.get(1)),
+        Some("pubkey") if args.len() == 2 => pubkey(&args[1]),
+        Some("fingerprint") if args.len() == 2 => fingerprint(&args[1]),
+        Some(cmd @ ("connect" | "listen")) if args.len() >= 3 => {
+            run(cmd == "connect", &args[1], &args[2], &args[3..])
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("noise: {}", e);
+        exit(1);
+    }
+}
+
+fn registry() -> Registry {
+    let mut r = Registry::new();
+    r.register::<X25519, ChaCha20Poly1305, Sha256>()
+        .register::<X25519, ChaCha20Poly1305, Sha512>()
+        .register::<X25519, ChaCha20Poly1305, Blake2s>()
+        .register::<X25519, ChaCha20Poly1305, Blake2b>()
+        .register::<X25519, Aes256Gcm, Sha256>()
+        .register::<X25519, Aes256Gcm, Sha512>()
+        .register::<X25519, Aes256Gcm, Blake2s>()
+        .register::<X25519, Aes256Gcm, Blake2b>();
+    r
+}
+
+fn genkey(file: Option<&String>) -> Result<(), BoxError> {
+    let k = X25519::genkey(false);
+    match file {
+        Some(file) => {
+            save_private_key::<X25519>(file, &k.private)?;
+            save_public_key::<X25519>(format!("{}.pub", file), &k.public)?;
+        }
+        None => {
+            print!("{}", *encode_private_key::<X25519>(&k.private));
+            print!("{}", encode_public_key::<X25519>(&k.public));
+        }
+    }
+    Ok(())
+}
+
+fn pubkey(file: &str) -> Result<(), BoxError> {
+    let k = load_private_key::<X25519>(file)?;
+    print!("{}", encode_public_key::<X25519>(&X25519::pubkey(&k)));
+    Ok(())
+}
+
+fn load_any_public_key(file: &str) -> Result<[u8; 32], BoxError> {
+    let text = std::fs::read_to_string(file)?;
+    match decode_public_key::<X25519>(&text) {
+        Err(KeyFileError::Malformed) => Ok(X25519::pubkey(&decode_private_key::<X25519>(&text)?)),
+        r => Ok(r?),
+    }
+}
+
+fn fingerprint(file: &str) -> Result<(), BoxError> {
+    let k = load_any_public_key(file)?;
+    println!("{}", ZBase32(&k));
+    println!("{}", ShortFingerprint::of(&k));
+    Ok(())
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|c| {
+            if c.len() != 2 || !c.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            u8::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok()
+        })
+        .collect()
+}
+
+fn run(is_initiator: bool, addr: &str, protocol: &str, opts: &[String]) -> Result<(), BoxError> {
+    let mut s = None;
+    let mut rs = None;
+    let mut psks = Vec::new();
+    let mut prologue = String::new();
+    let mut elligator = false;
+
+    let mut opts = opts.iter();
+    while let Some(opt) = opts.next() {
+        let mut value = || {
+            opts.next()
+                .ok_or_else(|| format!("missing value for {}", opt))
+        };
+        match opt.as_str() {
+            "--key" => s = Some(load_private_key::<X25519>(value()?)?),
+            "--remote-key" => rs = Some(load_any_public_key(value()?)?),
+            "--psk" => {
+                let psk = parse_hex(value()?)
+                    .filter(|p| p.len() == 32)
+                    .ok_or("PSK must be 64 hex digits")?;
+                psks.push(<[u8; 32]>::from_slice(&psk));
+            }
+            "--prologue" => prologue = value()?.clone(),
+            "--elligator" => elligator = true,
+            _ => return Err(format!("unknown option {}", opt).into()),
+        }
+    }
+
+    // Patterns that don't use a local static key ignore it.
+    let s = s.unwrap_or_else(|| X25519::genkey(false).private);
+    let params = HandshakeParams {
+        is_initiator,
+        is_elligator_encoded: elligator,
+        prologue: prologue.as_bytes(),
+        s: Some(s.as_slice()),
+        rs: rs.as_ref().map(|rs| &rs[..]),
+        psks: &psks,
+    };
+    let mut hs = registry().new_handshake(protocol, &params)?;
+
+    let stream = if is_initiator {
+        TcpStream::connect(addr)?
+    } else {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, peer) = listener.accept()?;
+        eprintln!("noise: accepted connection from {}", peer);
+        stream
+    };
+
+    let mut reader = stream.try_clone()?;
+    let mut writer = stream;
+    handshake(&mut *hs, &mut reader, &mut writer)?;
+    if let Some(rs) = hs.get_rs() {
+        eprintln!("noise: remote static key {}", ShortFingerprint::of(&rs));
+    }
+
+    let (c1, c2) = hs.get_ciphers();
+    let (send, recv) = if is_initiator { (c1, c2) } else { (c2, c1) };
+    let upload = thread::spawn(move || pipe_out(send, writer));
+    pipe_in(recv, reader)?;
+    upload.join().map_err(|_| "upload thread panicked")??;
+    Ok(())
+}
+
+fn handshake(
+    hs: &mut dyn DynHandshakeState,
+    reader: &mut TcpStream,
+    writer: &mut TcpStream,
+) -> Result<(), BoxError> {
+    while !hs.completed() {
+        if hs.is_write_turn() {
+            write_frame(writer, &hs.write_message_vec(&[])?)?;
+        } else {
+            let msg = read_frame(reader)?.ok_or("connection closed during handshake")?;
+            hs.read_message_vec(&msg)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_frame(w: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(data.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(data)
+}
+
+fn read_frame(r: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 2];
+    match r.read_exact(&mut len) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        r => r?,
+    }
+    let mut data = vec![0u8; u16::from_be_bytes(len) as usize];
+    r.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Stdin to the peer.
+fn pipe_out(mut cipher: Box<dyn DynCipherState>, mut writer: TcpStream) -> Result<(), io::Error> {
+    let mut stdin = io::stdin().lock();
+    let mut buf = [0u8; 65535 - 16];
+    loop {
+        let n = stdin.read(&mut buf)?;
+        if n == 0 {
+            return writer.shutdown(std::net::Shutdown::Write);
+        }
+        write_frame(&mut writer, &cipher.encrypt_vec(&buf[..n]))?;
+    }
+}
+
+/// The peer to stdout.
+fn pipe_in(mut cipher: Box<dyn DynCipherState>, mut reader: TcpStream) -> Result<(), BoxError> {
+    let mut stdout = io::stdout().lock();
+    while let Some(msg) = read_frame(&mut reader)? {
+        let data = cipher
+            .decrypt_vec(&msg)
+            .map_err(|_| "failed to decrypt transport message")?;
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! ECDH on the Brainpool curves (RFC 5639).
+//!
+//! Public keys and DH outputs are x-coordinates only, `DHLEN` is thus the
+//! field size, i.e., 32 bytes for P256r1 and 48 bytes for P384r1. As for
+//! X25519, the y-coordinate does not matter for the shared secret. Private
+//! keys are big-endian scalars in `[1, n)`.
+//!
+//! The arithmetic is done with [`crypto_bigint`], with the complete
+//! projective addition formulas of Renes, Costello and Batina ("Complete
+//! addition formulas for prime order elliptic curves", 2015), so that scalar
+//! multiplication runs in constant time.
+//!
+//! These curves do not support Elligator: [`DH::genkey`] panics if asked for
+//! an Elligator encoded key, and [`DH::dh`] fails with an Elligator encoded
+//! public key.
+
+use crypto_bigint::modular::constant_mod::{Residue, ResidueParams};
+use crypto_bigint::subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeLess};
+use crypto_bigint::{impl_modulus, Encoding, Uint, Zero};
+use noise_protocol::rand_core::{CryptoRng, RngCore};
+use noise_protocol::{DhKeyPair, Error, ErrorKind, U8Array, DH};
+use zeroize::Zeroizing;
+
+use crate::entropy::EntropyRng;
+use crate::sensitive::Sensitive;
+
+type Fe<M, const L: usize> = Residue<M, L>;
+
+/// A point in projective coordinates, (0 : 1 : 0) being the identity.
+#[derive(Clone, Copy)]
+struct Point<M: ResidueParams<L>, const L: usize> {
+    x: Fe<M, L>,
+    y: Fe<M, L>,
+    z: Fe<M, L>,
+}
+
+/// The parameters of a short Weierstrass curve y^2 = x^3 + a x + b.
+struct Curve<M: ResidueParams<L>, const L: usize> {
+    a: Fe<M, L>,
+    b: Fe<M, L>,
+    /// (p + 1) / 4, to compute square roots, as p = 3 mod 4.
+    sqrt: Uint<L>,
+    generator: Point<M, L>,
+}
+
+impl<M: ResidueParams<L> + Copy, const L: usize> Point<M, L> {
+    const IDENTITY: Self = Point {
+        x: Fe::ZERO,
+        y: Fe::ONE,
+        z: Fe::ZERO,
+    };
+
+    fn select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Point {
+            x: Fe::conditional_select(&a.x, &b.x, choice),
+            y: Fe::conditional_select(&a.y, &b.y, choice),
+            z: Fe::conditional_select(&a.z, &b.z, choice),
+        }
+    }
+}
+
+impl<M: ResidueParams<L> + Copy, const L: usize> Curve<M, L> {
+    /// `p + q`, with Algorithm 1 of Renes, Costello and Batina, which also
+    /// works for doublings and the identity.
+    fn add(&self, p: &Point<M, L>, q: &Point<M, L>) -> Point<M, L> {
+        let (a, b3) = (self.a, self.b + self.b + self.b);
+        let t0 = p.x * q.x;
+        let t1 = p.y * q.y;
+        let t2 = p.z * q.z;
+        let t3 = (p.x + p.y) * (q.x + q.y) - (t0 + t1);
+        let t4 = (p.x + p.z) * (q.x + q.z) - (t0 + t2);
+        let t5 = (p.y + p.z) * (q.y + q.z) - (t1 + t2);
+        let z3 = a * t4 + b3 * t2;
+        let x3 = t1 - z3;
+        let z3 = t1 + z3;
+        let y3 = x3 * z3;
+        let t1 = t0 + t0 + t0 + a * t2;
+        let t2 = a * (t0 - a * t2);
+        let t4 = b3 * t4 + t2;
+        Point {
+            x: t3 * x3 - t5 * t4,
+            y: y3 + t1 * t4,
+            z: t5 * z3 + t3 * t1,
+        }
+    }
+
+    /// `k * p`, for a big-endian scalar `k`, with a double-and-add-always
+    /// ladder.
+    fn mul(&self, k: &[u8], p: &Point<M, L>) -> Point<M, L> {
+        let mut r = Point::IDENTITY;
+        for byte in k {
+            for i in (0..8).rev() {
+                r = self.add(&r, &r);
+                let sum = self.add(&r, p);
+                r = Point::select(&r, &sum, Choice::from((byte >> i) & 1));
+            }
+        }
+        r
+    }
+
+    /// The affine x-coordinate of `p`, or `None` for the identity.
+    fn x(&self, p: &Point<M, L>) -> Option<Uint<L>> {
+        let (z_inv, is_some) = p.z.invert();
+        if !bool::from(Choice::from(is_some)) {
+            return None;
+        }
+        Some((p.x * z_inv).retrieve())
+    }
+
+    /// A point with x-coordinate `x`, if there is one. Which of the two does
+    /// not matter, as `k * (x, y)` and `k * (x, -y)` have the same
+    /// x-coordinate.
+    fn lift_x(&self, x: &Uint<L>) -> Option<Point<M, L>> {
+        if !bool::from(x.ct_lt(&M::MODULUS)) {
+            return None;
+        }
+        let x = Fe::new(x);
+        let y2 = (x.square() + self.a) * x + self.b;
+        let y = y2.pow(&self.sqrt);
+        if !bool::from(y.square().ct_eq(&y2)) {
+            return None;
+        }
+        Some(Point { x, y, z: Fe::ONE })
+    }
+}
+
+/// Whether `k` is a valid private key, i.e., in `[1, n)`.
+fn is_scalar<const L: usize>(k: &Uint<L>, n: &Uint<L>) -> Choice {
+    !k.is_zero() & k.ct_lt(n)
+}
+
+macro_rules! impl_brainpool {
+    (
+        $(#[$attr:meta])*
+        $name:ident, $noise_name:expr, $modulus:ident, $uint:ident, $len:expr,
+        p = $p:expr,
+        a = $a:expr,
+        b = $b:expr,
+        x = $x:expr,
+        y = $y:expr,
+        n = $n:expr $(,)?
+    ) => {
+        impl_modulus!($modulus, crypto_bigint::$uint, $p);
+
+        impl $name {
+            const CURVE: Curve<$modulus, { crypto_bigint::$uint::LIMBS }> = Curve {
+                a: Fe::new(&crypto_bigint::$uint::from_be_hex($a)),
+                b: Fe::new(&crypto_bigint::$uint::from_be_hex($b)),
+                sqrt: <$modulus as ResidueParams<{ crypto_bigint::$uint::LIMBS }>>::MODULUS
+                    .wrapping_add(&crypto_bigint::$uint::ONE)
+                    .shr_vartime(2),
+                generator: Point {
+                    x: Fe::new(&crypto_bigint::$uint::from_be_hex($x)),
+                    y: Fe::new(&crypto_bigint::$uint::from_be_hex($y)),
+                    z: Fe::ONE,
+                },
+            };
+            const ORDER: crypto_bigint::$uint = crypto_bigint::$uint::from_be_hex($n);
+        }
+
+        $(#[$attr])*
+        pub enum $name {}
+
+        impl DH for $name {
+            type Key = Sensitive<[u8; $len]>;
+            type Pubkey = [u8; $len];
+            type Output = Sensitive<[u8; $len]>;
+
+            fn name() -> &'static str {
+                $noise_name
+            }
+
+            fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey> {
+                Self::genkey_with_rng(&mut EntropyRng, elligator)
+            }
+
+            fn genkey_with_rng<R: RngCore + CryptoRng>(
+                rng: &mut R,
+                elligator: bool,
+            ) -> DhKeyPair<Self::Key, Self::Pubkey> {
+                assert!(!elligator, "{} does not support Elligator", $noise_name);
+
+                // Rejection sampling, n being close to 2^(8 * len).
+                let mut k = Self::Key::new();
+                loop {
+                    rng.fill_bytes(k.as_mut());
+                    let scalar = Zeroizing::new(crypto_bigint::$uint::from_be_slice(k.as_slice()));
+                    if bool::from(is_scalar(&scalar, &Self::ORDER)) {
+                        break;
+                    }
+                }
+                let public = Self::pubkey(&k);
+                (k, public).into()
+            }
+
+            fn pubkey(k: &Self::Key) -> Self::Pubkey {
+                let scalar = Zeroizing::new(crypto_bigint::$uint::from_be_slice(k.as_slice()));
+                assert!(bool::from(is_scalar(&scalar, &Self::ORDER)), "invalid private key");
+                let p = Self::CURVE.mul(k.as_slice(), &Self::CURVE.generator);
+                Self::CURVE.x(&p).expect("invalid private key").to_be_bytes()
+            }
+
+            fn dh(
+                k: &Self::Key,
+                pk: &Self::Pubkey,
+                is_elligator_encoded: bool,
+            ) -> Result<Self::Output, Error> {
+                if is_elligator_encoded {
+                    return Err(ErrorKind::InvalidPublicKey.into());
+                }
+
+                let scalar = Zeroizing::new(crypto_bigint::$uint::from_be_slice(k.as_slice()));
+                if !bool::from(is_scalar(&scalar, &Self::ORDER)) {
+                    return Err(ErrorKind::DH.into());
+                }
+                let pk = Self::CURVE
+                    .lift_x(&crypto_bigint::$uint::from_be_slice(pk))
+                    .ok_or(ErrorKind::InvalidPublicKey)?;
+
+                // The curves have prime order, so a point on the curve times
+                // a scalar in [1, n) is never the identity.
+                let shared = Self::CURVE.mul(k.as_slice(), &pk);
+                let x = Zeroizing::new(Self::CURVE.x(&shared).ok_or(ErrorKind::DH)?);
+                Ok(Self::Output::from_slice(&x.to_be_bytes()))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "use-bp256")]
+impl_brainpool!(
+    /// ECDH on BrainpoolP256r1.
+    BrainpoolP256r1,
+    "BrainpoolP256r1",
+    BrainpoolP256r1Modulus,
+    U256,
+    32,
+    p = "a9fb57dba1eea9bc3e660a909d838d726e3bf623d52620282013481d1f6e5377",
+    a = "7d5a0975fc2c3057eef67530417affe7fb8055c126dc5c6ce94a4b44f330b5d9",
+    b = "26dc5c6ce94a4b44f330b5d9bbd77cbf958416295cf7e1ce6bccdc18ff8c07b6",
+    x = "8bd2aeb9cb7e57cb2c4b482ffc81b7afb9de27e1e3bd23c23a4453bd9ace3262",
+    y = "547ef835c3dac4fd97f8461a14611dc9c27745132ded8e545c1d54c72f046997",
+    n = "a9fb57dba1eea9bc3e660a909d838d718c397aa3b561a6f7901e0e82974856a7",
+);
+
+#[cfg(feature = "use-bp384")]
+impl_brainpool!(
+    /// ECDH on BrainpoolP384r1.
+    BrainpoolP384r1,
+    "BrainpoolP384r1",
+    BrainpoolP384r1Modulus,
+    U384,
+    48,
+    p = "8cb91e82a3386d280f5d6f7e50e641df152f7109ed5456b412b1da197fb71123acd3a729901d1a71874700133107ec53",
+    a = "7bc382c63d8c150c3c72080ace05afa0c2bea28e4fb22787139165efba91f90f8aa5814a503ad4eb04a8c7dd22ce2826",
+    b = "04a8c7dd22ce28268b39b55416f0447c2fb77de107dcd2a62e880ea53eeb62d57cb4390295dbc9943ab78696fa504c11",
+    x = "1d1c64f068cf45ffa2a63a81b7c13f6b8847a3e77ef14fe3db7fcafe0cbd10e8e826e03436d646aaef87b2e247d4af1e",
+    y = "8abe1d7520f9c2a45cb1eb8e95cfd55262b70b29feec5864e19c054ff99129280e4646217791811142820341263c5315",
+    n = "8cb91e82a3386d280f5d6f7e50e641df152f7109ed5456b31f166e6cac0425a7cf3ab6af6b7fc3103b883202e9046565",
+);
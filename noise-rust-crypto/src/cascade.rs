@@ -0,0 +1,102 @@
+use noise_protocol::{Cipher, Error, Hash, U8Array};
+use zeroize::Zeroizing;
+
+use crate::sensitive::{SecretBuffer, Sensitive};
+use crate::{Aes256Gcm, ChaCha20Poly1305, Sha256};
+
+/// Length of the tag of the inner and the outer cipher.
+const TAG_LEN: usize = 16;
+
+/// A cascade of AES-256-GCM and ChaCha20-Poly1305, named
+/// `ChaChaPoly+AESGCM`.
+///
+/// Plaintexts are encrypted with AES-256-GCM, and the result is encrypted
+/// again with ChaCha20-Poly1305, so messages stay confidential and authentic
+/// as long as either cipher is secure. The two keys are derived from the
+/// Noise cipher key with HMAC-SHA256 under different labels.
+///
+/// Tags are 32 bytes long, cf. [`Cipher::tag_len`], and this is not a
+/// standard Noise cipher, so it only interoperates with peers using this
+/// crate. Decryption, except in place, needs a temporary buffer, so this
+/// requires the `std` feature.
+pub enum ChaChaPolyAesGcm {}
+
+fn subkeys(k: &Sensitive<[u8; 32]>) -> (Sensitive<[u8; 32]>, Sensitive<[u8; 32]>) {
+    let outer = Sha256::hmac(k.as_slice(), b"ChaChaPoly");
+    let inner = Sha256::hmac(k.as_slice(), b"AESGCM");
+    (outer, inner)
+}
+
+impl Cipher for ChaChaPolyAesGcm {
+    fn name() -> &'static str {
+        "ChaChaPoly+AESGCM"
+    }
+
+    type Key = Sensitive<[u8; 32]>;
+
+    fn tag_len() -> usize {
+        2 * TAG_LEN
+    }
+
+    fn encrypt(k: &Self::Key, nonce: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
+        assert!(plaintext.len().checked_add(2 * TAG_LEN) == Some(out.len()));
+
+        let (outer, inner) = subkeys(k);
+        out[..plaintext.len()].copy_from_slice(plaintext);
+        let len = Aes256Gcm::encrypt_in_place(&inner, nonce, ad, out, plaintext.len());
+        ChaCha20Poly1305::encrypt_in_place(&outer, nonce, ad, out, len);
+    }
+
+    fn encrypt_in_place(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        assert!(plaintext_len
+            .checked_add(2 * TAG_LEN)
+            .map_or(false, |l| l <= in_out.len()));
+
+        let (outer, inner) = subkeys(k);
+        let len = Aes256Gcm::encrypt_in_place(&inner, nonce, ad, in_out, plaintext_len);
+        ChaCha20Poly1305::encrypt_in_place(&outer, nonce, ad, in_out, len)
+    }
+
+    fn decrypt(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        assert!(ciphertext.len().checked_sub(2 * TAG_LEN) == Some(out.len()));
+
+        // The outer plaintext is `TAG_LEN` bytes longer than `out`.
+        let mut buf = SecretBuffer::from_slice(ciphertext);
+        let len = Self::decrypt_in_place(k, nonce, ad, &mut buf, ciphertext.len())?;
+        out.copy_from_slice(&buf[..len]);
+        Ok(())
+    }
+
+    fn decrypt_in_place(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        in_out: &mut [u8],
+        ciphertext_len: usize,
+    ) -> Result<usize, Error> {
+        assert!(ciphertext_len <= in_out.len());
+        assert!(ciphertext_len >= 2 * TAG_LEN);
+
+        let (outer, inner) = subkeys(k);
+        let len = ChaCha20Poly1305::decrypt_in_place(&outer, nonce, ad, in_out, ciphertext_len)?;
+        Aes256Gcm::decrypt_in_place(&inner, nonce, ad, in_out, len)
+    }
+
+    fn rekey(k: &Self::Key) -> Self::Key {
+        let mut k1 = Zeroizing::new([0u8; 32 + 2 * TAG_LEN]);
+        Self::encrypt(k, 0u64.wrapping_sub(1), &[], &[0; 32], &mut k1[..]);
+        Self::Key::from_slice(&k1[..32])
+    }
+}
@@ -0,0 +1,50 @@
+//! Conversion of Ed25519 keys to X25519 keys, to use an existing Ed25519
+//! identity as the static key pair of [`X25519`].
+//!
+//! The results are those of libsodium's `crypto_sign_ed25519_sk_to_curve25519`
+//! and `crypto_sign_ed25519_pk_to_curve25519`, so peers converting with
+//! libsodium agree on the keys:
+//!
+//! ```
+//! use noise_protocol::{DhKeyPair, DH};
+//! use noise_rust_crypto::convert::{ed25519_to_x25519_private, ed25519_to_x25519_public};
+//! use noise_rust_crypto::X25519;
+//!
+//! # let seed = [7u8; 32];
+//! let key = DhKeyPair::from_private::<X25519>(ed25519_to_x25519_private(&seed));
+//! ```
+//!
+//! Using one key for both signatures and DH is safe for Ed25519 and X25519,
+//! but ties the two uses together: revoking one revokes both.
+
+use crate::sensitive::Sensitive;
+use crate::{Sha512, X25519};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use noise_protocol::{Hash, U8Array, DH};
+
+/// The X25519 private key of the Ed25519 private key `seed`, i.e., the
+/// 32-byte seed, not the 64-byte expanded key, whose first half is the seed.
+///
+/// This is the clamped first half of the SHA-512 hash of the seed, which is
+/// the scalar of the Ed25519 key.
+pub fn ed25519_to_x25519_private(seed: &[u8; 32]) -> <X25519 as DH>::Key {
+    let h = Sha512::hash(seed);
+    let mut key = Sensitive::<[u8; 32]>::from_slice(&h[..32]);
+    key[0] &= 248;
+    key[31] &= 127;
+    key[31] |= 64;
+    key
+}
+
+/// The X25519 public key of the Ed25519 public key `pk`, i.e., the
+/// Montgomery form of the Edwards point.
+///
+/// Returns [`None`] if `pk` is not a point on the curve, or if the point has
+/// a small order.
+pub fn ed25519_to_x25519_public(pk: &[u8; 32]) -> Option<<X25519 as DH>::Pubkey> {
+    let point = CompressedEdwardsY(*pk).decompress()?;
+    if point.is_small_order() {
+        return None;
+    }
+    Some(point.to_montgomery().to_bytes())
+}
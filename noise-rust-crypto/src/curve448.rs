@@ -0,0 +1,68 @@
+//! X448 (RFC 7748).
+//!
+//! Keys, public keys and DH outputs are 56 bytes long.
+//!
+//! Elligator encoded public keys are 56-byte representatives, cf.
+//! [`elligator448`](crate::elligator448).
+
+use noise_protocol::rand_core::{CryptoRng, RngCore};
+use noise_protocol::{DhKeyPair, Error, ErrorKind, U8Array, DH};
+use x448::{PublicKey, Secret};
+
+use crate::entropy::EntropyRng;
+use crate::sensitive::Sensitive;
+
+pub enum X448 {}
+
+impl DH for X448 {
+    type Key = Sensitive<[u8; 56]>;
+    type Pubkey = [u8; 56];
+    type Output = Sensitive<[u8; 56]>;
+
+    fn name() -> &'static str {
+        "448"
+    }
+
+    fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        Self::genkey_with_rng(&mut EntropyRng, elligator)
+    }
+
+    fn genkey_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        elligator: bool,
+    ) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        if elligator {
+            return crate::elligator448::generate(rng).into();
+        }
+
+        let mut k = Self::Key::new();
+        rng.fill_bytes(k.as_mut());
+        let public = Self::pubkey(&k);
+        (k, public).into()
+    }
+
+    fn pubkey(k: &Self::Key) -> Self::Pubkey {
+        let k = Secret::from_bytes(k.as_slice()).unwrap();
+        *PublicKey::from(&k).as_bytes()
+    }
+
+    fn dh(
+        k: &Self::Key,
+        pk: &Self::Pubkey,
+        is_elligator_encoded: bool,
+    ) -> Result<Self::Output, Error> {
+        let pk = if is_elligator_encoded {
+            crate::elligator448::decode_representative(pk)
+        } else {
+            *pk
+        };
+
+        let k = Secret::from_bytes(k.as_slice()).ok_or(ErrorKind::DH)?;
+        // Low order public keys are rejected.
+        let pk = PublicKey::from_bytes(&pk).ok_or(ErrorKind::InvalidPublicKey)?;
+        let shared = k
+            .as_diffie_hellman(&pk)
+            .ok_or(ErrorKind::InvalidPublicKey)?;
+        Ok(Self::Output::from_slice(shared.as_bytes()))
+    }
+}
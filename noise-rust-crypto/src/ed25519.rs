@@ -0,0 +1,92 @@
+//! Ed25519 signatures, cf. [RFC 8032], for the `sig` token.
+//!
+//! [RFC 8032]: https://www.rfc-editor.org/rfc/rfc8032.html
+
+use crate::entropy::EntropyRng;
+use crate::sensitive::Sensitive;
+use crate::Sha512;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::EdwardsPoint;
+use noise_protocol::rand_core::RngCore;
+use noise_protocol::{Error, ErrorKind, Hash, Signature, U8Array};
+
+/// Ed25519, named `Ed25519`.
+///
+/// Private keys are 32-byte seeds, as in RFC 8032 and most libraries, not
+/// expanded keys. Verification is the cofactorless one of RFC 8032.
+pub enum Ed25519 {}
+
+impl Ed25519 {
+    /// Generate a new private key with the OS RNG.
+    pub fn genkey() -> Sensitive<[u8; 32]> {
+        let mut k = Sensitive::<[u8; 32]>::new();
+        EntropyRng.fill_bytes(k.as_mut());
+        k
+    }
+}
+
+// The secret scalar and the nonce prefix of a seed.
+fn expand(seed: &[u8; 32]) -> (Scalar, Sensitive<[u8; 64]>) {
+    let h = Sha512::hash(seed);
+    let mut a = Sensitive::<[u8; 32]>::from_slice(&h[..32]);
+    a[0] &= 248;
+    a[31] &= 127;
+    a[31] |= 64;
+    (Scalar::from_bytes_mod_order(*a), h)
+}
+
+fn challenge(r: &[u8], pk: &[u8], msg: &[u8]) -> Scalar {
+    let mut h = Sha512::default();
+    h.input(r);
+    h.input(pk);
+    h.input(msg);
+    Scalar::from_bytes_mod_order_wide(&h.result())
+}
+
+impl Signature for Ed25519 {
+    type Key = Sensitive<[u8; 32]>;
+    type Pubkey = [u8; 32];
+    type Sig = [u8; 64];
+
+    fn name() -> &'static str {
+        "Ed25519"
+    }
+
+    fn pubkey(k: &Self::Key) -> Self::Pubkey {
+        let (a, _) = expand(k);
+        EdwardsPoint::mul_base(&a).compress().to_bytes()
+    }
+
+    fn sign(k: &Self::Key, msg: &[u8]) -> Self::Sig {
+        let (a, h) = expand(k);
+        let pk = EdwardsPoint::mul_base(&a).compress();
+        let mut nonce = Sha512::default();
+        nonce.input(&h[32..]);
+        nonce.input(msg);
+        let r = Scalar::from_bytes_mod_order_wide(&nonce.result());
+        let big_r = EdwardsPoint::mul_base(&r).compress();
+        let s = r + challenge(big_r.as_bytes(), pk.as_bytes(), msg) * a;
+
+        let mut sig = [0u8; 64];
+        sig[..32].copy_from_slice(big_r.as_bytes());
+        sig[32..].copy_from_slice(s.as_bytes());
+        sig
+    }
+
+    fn verify(pk: &Self::Pubkey, msg: &[u8], sig: &Self::Sig) -> Result<(), Error> {
+        let a = CompressedEdwardsY(*pk)
+            .decompress()
+            .ok_or(ErrorKind::InvalidPublicKey)?;
+        let s = Option::<Scalar>::from(Scalar::from_canonical_bytes(sig[32..].try_into().unwrap()))
+            .ok_or(ErrorKind::Signature)?;
+        let k = challenge(&sig[..32], pk, msg);
+        // R = sB - kA
+        let r = EdwardsPoint::vartime_double_scalar_mul_basepoint(&k, &-a, &s);
+        if r.compress().as_bytes()[..] == sig[..32] {
+            Ok(())
+        } else {
+            Err(ErrorKind::Signature.into())
+        }
+    }
+}
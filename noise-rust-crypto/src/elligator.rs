@@ -0,0 +1,61 @@
+//! Elligator 2 representatives of X25519 public keys outside a handshake,
+//! e.g., to publish a static key out of band, with the conventions of
+//! [`X25519`](crate::X25519).
+//!
+//! For the RFC 9380 conventions of [`X25519Rfc9380`](crate::X25519Rfc9380),
+//! use [`rfc9380::representative`](crate::rfc9380::representative) and
+//! [`rfc9380::map_to_curve`](crate::rfc9380::map_to_curve) instead.
+
+use curve25519_dalek::MontgomeryPoint;
+
+/// A representative of the public key `pk`, if there is one, i.e., for about
+/// half the keys.
+///
+/// The lowest bit of `tweak` is bit 255 of the representative, which
+/// decoding ignores, so random tweaks give random representatives.
+///
+/// Keys from [`DH::genkey`](noise_protocol::DH::genkey) without Elligator
+/// lie in the prime order subgroup, which makes their representatives
+/// distinguishable from random bytes. Generate keys to be published encoded
+/// with `genkey(true)`, which returns a representative directly.
+pub fn encode_pubkey(pk: &[u8; 32], tweak: u8) -> Option<[u8; 32]> {
+    let mut representative = MontgomeryPoint(*pk).to_elligator_representative()?;
+    representative[31] |= (tweak & 1) << 7;
+    Some(representative)
+}
+
+/// The public key a representative maps to, e.g., to
+/// [`set_rs`](noise_protocol::HandshakeStateBuilder::set_rs) without
+/// [`set_is_static_elligator_encoded`](noise_protocol::HandshakeStateBuilder::set_is_static_elligator_encoded).
+///
+/// Every 32-byte string is a representative.
+pub fn decode_representative(representative: &[u8; 32]) -> [u8; 32] {
+    MontgomeryPoint::from_elligator_representative(representative).to_bytes()
+}
+
+/// Representative bytes for structure-aware fuzzing: the representative of
+/// the public key of a private key taken from `u` if it has one, 32 bytes
+/// taken from `u`, or an edge case, e.g., all zeros.
+#[cfg(feature = "arbitrary")]
+pub fn arbitrary_representative(
+    u: &mut arbitrary::Unstructured<'_>,
+) -> arbitrary::Result<[u8; 32]> {
+    use noise_protocol::{U8Array, DH};
+
+    match u.int_in_range(0..=2u8)? {
+        0 => {
+            let k: [u8; 32] = u.arbitrary()?;
+            let pk = crate::X25519::pubkey(&U8Array::from_slice(&k));
+            match encode_pubkey(&pk, u.arbitrary()?) {
+                Some(representative) => Ok(representative),
+                None => u.arbitrary(),
+            }
+        }
+        1 => u.arbitrary(),
+        _ => {
+            let mut top_cleared = [0xff; 32];
+            top_cleared[31] = 0x3f;
+            Ok(*u.choose(&[[0; 32], [0xff; 32], top_cleared])?)
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Elligator 2 representatives of X448 public keys, with the conventions of
+//! [`X448`](crate::X448).
+//!
+//! A representative is 56 bytes, read as a little-endian integer modulo
+//! p = 2^448 - 2^224 - 1, so every 56-byte string is a representative. The
+//! map uses the non-square -1, and maps a representative `r` and `-r` to the
+//! same public key.
+
+use crypto_bigint::modular::constant_mod::Residue;
+use crypto_bigint::subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use crypto_bigint::{impl_modulus, Encoding, Integer, U448};
+use noise_protocol::rand_core::{CryptoRng, RngCore};
+use noise_protocol::{U8Array, DH};
+
+use crate::sensitive::Sensitive;
+use zeroize::Zeroizing;
+
+impl_modulus!(
+    P448,
+    U448,
+    "fffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+);
+
+type Fe = Residue<P448, { U448::LIMBS }>;
+
+/// A of the Montgomery curve v^2 = u^3 + A u^2 + u.
+const A: Fe = Fe::new(&U448::from_u64(156326));
+/// (p - 1) / 2, to compute the Legendre symbol.
+const LEGENDRE: U448 = U448::from_be_hex(
+    "7fffffffffffffffffffffffffffffffffffffffffffffffffffffff7fffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+);
+/// (p + 1) / 4, to compute square roots, as p = 3 mod 4.
+const SQRT: U448 = U448::from_be_hex(
+    "3fffffffffffffffffffffffffffffffffffffffffffffffffffffffc0000000000000000000000000000000000000000000000000000000",
+);
+
+fn from_bytes(b: &[u8; 56]) -> Fe {
+    Fe::new(&U448::from_le_slice(b))
+}
+
+fn to_bytes(x: &Fe) -> [u8; 56] {
+    x.retrieve().to_le_bytes()
+}
+
+fn is_square(x: &Fe) -> Choice {
+    let l = x.pow(&LEGENDRE);
+    l.ct_eq(&Fe::ONE) | l.ct_eq(&Fe::ZERO)
+}
+
+/// A square root of `x`, if `x` is a square.
+fn sqrt(x: &Fe) -> Fe {
+    x.pow(&SQRT)
+}
+
+/// The inverse of `x`, or 0 for 0.
+fn inv0(x: &Fe) -> Fe {
+    let (inv, is_some) = x.invert();
+    Fe::conditional_select(&Fe::ZERO, &inv, is_some.into())
+}
+
+/// u^3 + A u^2 + u, a square if and only if `u` is on the curve.
+fn curve(u: &Fe) -> Fe {
+    *u * (*u * (*u + A) + Fe::ONE)
+}
+
+/// A representative of the public key `pk`, if there is one, i.e., for about
+/// half the keys.
+///
+/// The lowest bit of `tweak` chooses between `r` and `-r`, so random tweaks
+/// give random representatives.
+///
+/// Keys from [`DH::genkey`] without Elligator lie in the prime order
+/// subgroup, which makes their representatives distinguishable from random
+/// bytes. Generate keys to be published encoded with `genkey(true)`, which
+/// returns a representative directly.
+pub fn encode_pubkey(pk: &[u8; 56], tweak: u8) -> Option<[u8; 56]> {
+    let u = from_bytes(pk);
+    let u_plus_a = u + A;
+    if bool::from(u.ct_eq(&Fe::ZERO) | u_plus_a.ct_eq(&Fe::ZERO) | !is_square(&curve(&u))) {
+        return None;
+    }
+
+    // Decoding gives u for r^2 = (u + A) / u.
+    let r2 = u_plus_a * inv0(&u);
+    let r = sqrt(&r2);
+    if !bool::from(r.square().ct_eq(&r2)) {
+        return None;
+    }
+    let negate = r.retrieve().is_odd() ^ Choice::from(tweak & 1);
+    Some(to_bytes(&Fe::conditional_select(&r, &-r, negate)))
+}
+
+/// The public key a representative maps to, e.g., to
+/// [`set_rs`](noise_protocol::HandshakeStateBuilder::set_rs) without
+/// [`set_is_static_elligator_encoded`](noise_protocol::HandshakeStateBuilder::set_is_static_elligator_encoded).
+pub fn decode_representative(representative: &[u8; 56]) -> [u8; 56] {
+    let r = from_bytes(representative);
+    let x1 = -A * inv0(&(Fe::ONE - r.square()));
+    let x1 = Fe::conditional_select(&x1, &-A, x1.ct_eq(&Fe::ZERO));
+    let x2 = -x1 - A;
+    to_bytes(&Fe::conditional_select(&x2, &x1, is_square(&curve(&x1))))
+}
+
+/// Add the low order point chosen by the two lowest bits of `c` to the point
+/// of `pk`. X448 clears the cofactor, so DH outputs are unchanged.
+fn add_low_order(pk: &[u8; 56], c: u8) -> [u8; 56] {
+    let u = from_bytes(pk);
+    let v = sqrt(&curve(&u));
+    // The point of order 4, (-1, t), and its negation.
+    let t = sqrt(&(A - Fe::ONE - Fe::ONE));
+    let plus_t = |t: Fe| {
+        let l = (t - v) * inv0(&(-Fe::ONE - u));
+        l.square() - A - u + Fe::ONE
+    };
+    let mut out = u;
+    out.conditional_assign(&inv0(&u), Choice::from((c & 3 == 1) as u8));
+    out.conditional_assign(&plus_t(t), Choice::from((c & 3 == 2) as u8));
+    out.conditional_assign(&plus_t(-t), Choice::from((c & 3 == 3) as u8));
+    to_bytes(&out)
+}
+
+/// A private key and a representative of its public key with a random low
+/// order component, so that representatives are indistinguishable from
+/// random bytes.
+pub(crate) fn generate<R: RngCore + CryptoRng>(rng: &mut R) -> (Sensitive<[u8; 56]>, [u8; 56]) {
+    loop {
+        let mut k = Sensitive::<[u8; 56]>::new();
+        rng.fill_bytes(k.as_mut());
+        let mut tweak = Zeroizing::new([0u8]);
+        rng.fill_bytes(&mut tweak[..]);
+        let pk = add_low_order(&crate::X448::pubkey(&k), tweak[0]);
+        if let Some(representative) = encode_pubkey(&pk, tweak[0] >> 2) {
+            return (k, representative);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Entropy for key generation.
+//!
+//! [`DH::genkey`](noise_protocol::DH::genkey) and the KEMs draw random bytes
+//! from [`EntropyRng`]. It uses the [`EntropySource`] set with
+//! [`set_entropy_source`], if any, or else the OS RNG with the `getrandom`
+//! feature (default).
+//!
+//! On targets without an OS RNG, e.g., bare-metal Cortex-M, disable the
+//! `getrandom` feature and set an entropy source, e.g., a TRNG peripheral,
+//! before generating keys:
+//!
+//! ```ignore
+//! fn read_trng(dest: &mut [u8]) {
+//!     // ...
+//! }
+//!
+//! static TRNG: &dyn EntropySource = &read_trng;
+//! set_entropy_source(&TRNG);
+//! ```
+//!
+//! Alternatively, pass an RNG to
+//! [`DH::genkey_with_rng`](noise_protocol::DH::genkey_with_rng) and
+//! [`HandshakeState::write_message_with_rng`](noise_protocol::HandshakeState::write_message_with_rng).
+
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use noise_protocol::rand_core::{CryptoRng, Error, RngCore};
+
+/// A source of cryptographically secure random bytes.
+pub trait EntropySource: Sync {
+    /// Fill `dest` with random bytes.
+    fn fill_bytes(&self, dest: &mut [u8]);
+}
+
+impl<F: Fn(&mut [u8]) + Sync> EntropySource for F {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self(dest)
+    }
+}
+
+static SOURCE: AtomicPtr<&'static dyn EntropySource> = AtomicPtr::new(ptr::null_mut());
+
+/// Set the entropy source of [`EntropyRng`], replacing the previous one, if
+/// any.
+pub fn set_entropy_source(source: &'static &'static dyn EntropySource) {
+    SOURCE.store(source as *const _ as *mut _, Ordering::Release);
+}
+
+fn entropy_source() -> Option<&'static dyn EntropySource> {
+    let source = SOURCE.load(Ordering::Acquire);
+    // SAFETY: `source` is null or comes from a `&'static` reference.
+    unsafe { source.as_ref() }.copied()
+}
+
+/// The RNG used for key generation, cf. the [module docs](self).
+///
+/// # Panics
+///
+/// Without the `getrandom` feature, if no entropy source is set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntropyRng;
+
+impl RngCore for EntropyRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut b = [0u8; 4];
+        self.fill_bytes(&mut b);
+        u32::from_le_bytes(b)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut b = [0u8; 8];
+        self.fill_bytes(&mut b);
+        u64::from_le_bytes(b)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Some(source) = entropy_source() {
+            return source.fill_bytes(dest);
+        }
+        #[cfg(feature = "getrandom")]
+        return rand_core::OsRng.fill_bytes(dest);
+        #[cfg(not(feature = "getrandom"))]
+        panic!("no entropy source, see noise_rust_crypto::entropy");
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for EntropyRng {}
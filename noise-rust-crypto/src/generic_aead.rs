@@ -0,0 +1,191 @@
+//! Noise ciphers from any RustCrypto AEAD.
+//!
+//! [`impl_noise_cipher!`](crate::impl_noise_cipher) defines a
+//! [`Cipher`](noise_protocol::Cipher) from a type implementing
+//! [`AeadInPlace`] and [`KeyInit`], e.g., Ascon or Deoxys, without writing
+//! the in-place encryption and decryption by hand:
+//!
+//! ```ignore
+//! noise_rust_crypto::impl_noise_cipher!(
+//!     /// Deoxys-II-256.
+//!     pub DeoxysII256,
+//!     deoxys::DeoxysII256,
+//!     "DeoxysII256",
+//!     32
+//! );
+//! ```
+//!
+//! The arguments are the name of the new type, the AEAD, the name of the
+//! cipher in protocol names and the key length of the AEAD, at most 32 bytes
+//! as the handshake derives 32 bytes of key material. The AEAD must have a
+//! tag of 16 bytes and a nonce of at least 8 bytes. The nonce is the
+//! big-endian counter, left-padded with zeros, as for `AESGCM`.
+
+pub use aead;
+use aead::generic_array::typenum::Unsigned;
+use aead::{AeadInPlace, KeyInit, Nonce, Tag};
+use noise_protocol::ErrorKind;
+#[doc(hidden)]
+pub use noise_protocol::{Cipher, Error};
+
+fn new<A: AeadInPlace + KeyInit>(key: &[u8]) -> A {
+    assert_eq!(A::TagSize::USIZE, 16);
+    A::new_from_slice(key).unwrap()
+}
+
+/// The nonce of the AEAD, the big-endian `n` left-padded with zeros.
+pub fn nonce<A: AeadInPlace>(n: u64) -> Nonce<A> {
+    let mut nonce = Nonce::<A>::default();
+    let len = nonce.len();
+    nonce[len - 8..].copy_from_slice(&n.to_be_bytes());
+    nonce
+}
+
+/// [`Cipher::encrypt`] with the AEAD `A`.
+pub fn encrypt<A>(key: &[u8], n: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8])
+where
+    A: AeadInPlace + KeyInit,
+{
+    assert!(plaintext.len().checked_add(16) == Some(out.len()));
+
+    let (in_out, tag_out) = out.split_at_mut(plaintext.len());
+    in_out.copy_from_slice(plaintext);
+
+    let tag = new::<A>(key)
+        .encrypt_in_place_detached(&nonce::<A>(n), ad, in_out)
+        .unwrap();
+    tag_out.copy_from_slice(tag.as_ref());
+}
+
+/// [`Cipher::encrypt_in_place`] with the AEAD `A`.
+pub fn encrypt_in_place<A>(
+    key: &[u8],
+    n: u64,
+    ad: &[u8],
+    in_out: &mut [u8],
+    plaintext_len: usize,
+) -> usize
+where
+    A: AeadInPlace + KeyInit,
+{
+    assert!(plaintext_len
+        .checked_add(16)
+        .map_or(false, |l| l <= in_out.len()));
+
+    let (in_out, tag_out) = in_out[..plaintext_len + 16].split_at_mut(plaintext_len);
+
+    let tag = new::<A>(key)
+        .encrypt_in_place_detached(&nonce::<A>(n), ad, in_out)
+        .unwrap();
+    tag_out.copy_from_slice(tag.as_ref());
+
+    plaintext_len + 16
+}
+
+/// [`Cipher::decrypt`] with the AEAD `A`.
+pub fn decrypt<A>(
+    key: &[u8],
+    n: u64,
+    ad: &[u8],
+    ciphertext: &[u8],
+    out: &mut [u8],
+) -> Result<(), Error>
+where
+    A: AeadInPlace + KeyInit,
+{
+    assert!(ciphertext.len().checked_sub(16) == Some(out.len()));
+
+    out.copy_from_slice(&ciphertext[..out.len()]);
+    let tag = &ciphertext[out.len()..];
+
+    new::<A>(key)
+        .decrypt_in_place_detached(&nonce::<A>(n), ad, out, Tag::<A>::from_slice(tag))
+        .map_err(|_| ErrorKind::Decryption.into())
+}
+
+/// [`Cipher::decrypt_in_place`] with the AEAD `A`.
+pub fn decrypt_in_place<A>(
+    key: &[u8],
+    n: u64,
+    ad: &[u8],
+    in_out: &mut [u8],
+    ciphertext_len: usize,
+) -> Result<usize, Error>
+where
+    A: AeadInPlace + KeyInit,
+{
+    assert!(ciphertext_len <= in_out.len());
+    assert!(ciphertext_len >= 16);
+
+    let (in_out, tag) = in_out[..ciphertext_len].split_at_mut(ciphertext_len - 16);
+
+    new::<A>(key)
+        .decrypt_in_place_detached(&nonce::<A>(n), ad, in_out, Tag::<A>::from_slice(tag))
+        .map_err(|_| ErrorKind::Decryption)?;
+
+    Ok(in_out.len())
+}
+
+/// Define a Noise [`Cipher`](noise_protocol::Cipher) from a RustCrypto AEAD,
+/// cf. the [module docs](crate::generic_aead).
+#[macro_export]
+macro_rules! impl_noise_cipher {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $aead:ty, $noise_name:expr, $key_len:expr) => {
+        $(#[$attr])*
+        $vis enum $name {}
+
+        impl $crate::generic_aead::Cipher for $name {
+            fn name() -> &'static str {
+                $noise_name
+            }
+
+            type Key = $crate::sensitive::Sensitive<[u8; $key_len]>;
+
+            fn encrypt(k: &Self::Key, nonce: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
+                $crate::generic_aead::encrypt::<$aead>(&**k, nonce, ad, plaintext, out)
+            }
+
+            fn encrypt_in_place(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                in_out: &mut [u8],
+                plaintext_len: usize,
+            ) -> usize {
+                $crate::generic_aead::encrypt_in_place::<$aead>(
+                    &**k,
+                    nonce,
+                    ad,
+                    in_out,
+                    plaintext_len,
+                )
+            }
+
+            fn decrypt(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                ciphertext: &[u8],
+                out: &mut [u8],
+            ) -> Result<(), $crate::generic_aead::Error> {
+                $crate::generic_aead::decrypt::<$aead>(&**k, nonce, ad, ciphertext, out)
+            }
+
+            fn decrypt_in_place(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                in_out: &mut [u8],
+                ciphertext_len: usize,
+            ) -> Result<usize, $crate::generic_aead::Error> {
+                $crate::generic_aead::decrypt_in_place::<$aead>(
+                    &**k,
+                    nonce,
+                    ad,
+                    in_out,
+                    ciphertext_len,
+                )
+            }
+        }
+    };
+}
@@ -0,0 +1,108 @@
+//! Noise hashes from any RustCrypto digest.
+//!
+//! [`DigestHash`] implements [`Hash`] for a type implementing [`Digest`],
+//! e.g., SM3 or Whirlpool, so that it can be used without writing a wrapper
+//! by hand. Its name in protocol names is given by a [`HashName`]:
+//!
+//! ```ignore
+//! enum Sm3Name {}
+//!
+//! impl HashName for Sm3Name {
+//!     const NAME: &'static str = "SM3";
+//! }
+//!
+//! type Sm3 = DigestHash<sm3::Sm3, Sm3Name>;
+//! ```
+//!
+//! The output of the digest must not be longer than its block, as HMAC keys
+//! are hash outputs.
+
+use core::marker::PhantomData;
+pub use digest;
+use digest::core_api::BlockSizeUser;
+use digest::generic_array::{ArrayLength, GenericArray};
+use digest::{Digest, OutputSizeUser};
+use noise_protocol::{Hash, U8Array};
+use zeroize::Zeroize;
+
+/// The name of a [`DigestHash`] in protocol names.
+pub trait HashName {
+    /// Name, e.g., `SM3`.
+    const NAME: &'static str;
+}
+
+/// A [`U8Array`] of `N` bytes, zeroed on drop, for the blocks and outputs of
+/// a [`DigestHash`].
+pub struct DigestArray<N: ArrayLength<u8>>(GenericArray<u8, N>);
+
+impl<N: ArrayLength<u8>> U8Array for DigestArray<N> {
+    fn new() -> Self {
+        DigestArray(GenericArray::default())
+    }
+
+    fn new_with(v: u8) -> Self {
+        let mut a = Self::new();
+        a.0.iter_mut().for_each(|b| *b = v);
+        a
+    }
+
+    fn from_slice(s: &[u8]) -> Self {
+        DigestArray(GenericArray::clone_from_slice(s))
+    }
+
+    fn len() -> usize {
+        N::USIZE
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl<N: ArrayLength<u8>> Drop for DigestArray<N> {
+    fn drop(&mut self) {
+        self.0.as_mut_slice().zeroize();
+    }
+}
+
+/// A [`Hash`] from the digest `D`, named by `N`, cf. the
+/// [module docs](self).
+pub struct DigestHash<D, N>(D, PhantomData<N>);
+
+impl<D: Digest, N> Default for DigestHash<D, N> {
+    fn default() -> Self {
+        DigestHash(D::new(), PhantomData)
+    }
+}
+
+impl<D: Clone, N> Clone for DigestHash<D, N> {
+    fn clone(&self) -> Self {
+        DigestHash(self.0.clone(), PhantomData)
+    }
+}
+
+impl<D, N> Hash for DigestHash<D, N>
+where
+    D: Digest + BlockSizeUser + Clone,
+    N: HashName,
+{
+    fn name() -> &'static str {
+        N::NAME
+    }
+
+    type Block = DigestArray<D::BlockSize>;
+    type Output = DigestArray<<D as OutputSizeUser>::OutputSize>;
+
+    fn input(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        let d = core::mem::replace(&mut self.0, D::new());
+        DigestArray(d.finalize())
+    }
+}
@@ -0,0 +1,123 @@
+use core::marker::PhantomData;
+
+use noise_protocol::{Hash, U8Array};
+use sha3::digest::{ExtendableOutput, Update};
+use sha3::{CShake256, CShake256Core};
+
+use crate::sensitive::Sensitive;
+
+/// Rate of cSHAKE256, in bytes.
+const RATE: usize = 136;
+
+/// Customization string for the cSHAKE256 and KMAC256 calls of [`Kmac256`].
+///
+/// To bind all hashing and key derivation to a protocol, use the full
+/// protocol name:
+///
+/// ```
+/// use noise_rust_crypto::KeccakDomain;
+///
+/// enum MyProtocol {}
+///
+/// impl KeccakDomain for MyProtocol {
+///     const CUSTOMIZATION: &'static [u8] = b"Noise_XX_25519_ChaChaPoly_KMAC256";
+/// }
+/// ```
+pub trait KeccakDomain {
+    /// The customization string `S`.
+    const CUSTOMIZATION: &'static [u8];
+}
+
+/// The default domain, with customization string `"Noise"`.
+pub enum NoiseDomain {}
+
+impl KeccakDomain for NoiseDomain {
+    const CUSTOMIZATION: &'static [u8] = b"Noise";
+}
+
+/// A Keccak based hash, for use with the Noise protocol.
+///
+/// * `HASH(data)` is `cSHAKE256(data, 512, "", S)`.
+/// * `HMAC-HASH(key, data)` is `KMAC256(key, data, 512, S)`, instead of HMAC.
+///
+/// `S` is the customization string of `D`. `HKDF` is built on `KMAC256` in
+/// the usual way.
+///
+/// This is not a standard Noise hash function. Its name is `KMAC256`.
+pub struct Kmac256<D: KeccakDomain = NoiseDomain> {
+    inner: CShake256,
+    _domain: PhantomData<D>,
+}
+
+impl<D: KeccakDomain> Default for Kmac256<D> {
+    fn default() -> Self {
+        Kmac256 {
+            inner: CShake256::from_core(CShake256Core::new(D::CUSTOMIZATION)),
+            _domain: PhantomData,
+        }
+    }
+}
+
+impl<D: KeccakDomain> Clone for Kmac256<D> {
+    fn clone(&self) -> Self {
+        Kmac256 {
+            inner: self.inner.clone(),
+            _domain: PhantomData,
+        }
+    }
+}
+
+impl<D: KeccakDomain> Hash for Kmac256<D> {
+    fn name() -> &'static str {
+        "KMAC256"
+    }
+
+    type Block = [u8; RATE];
+    type Output = Sensitive<[u8; 64]>;
+
+    fn input(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        let mut out = Self::Output::new();
+        core::mem::take(self).inner.finalize_xof_into(out.as_mut());
+        out
+    }
+
+    fn hmac_many(key: &[u8], data: &[&[u8]]) -> Self::Output {
+        let mut h = CShake256::from_core(CShake256Core::new_with_function_name(
+            b"KMAC",
+            D::CUSTOMIZATION,
+        ));
+
+        // bytepad(encode_string(key), RATE)
+        let mut rate_buf = [0u8; 9];
+        let mut key_len_buf = [0u8; 9];
+        let rate = left_encode(RATE as u64, &mut rate_buf);
+        let key_len = left_encode(key.len() as u64 * 8, &mut key_len_buf);
+        h.update(rate);
+        h.update(key_len);
+        h.update(key);
+        let len = rate.len() + key_len.len() + key.len();
+        h.update(&[0u8; RATE][..(RATE - len % RATE) % RATE]);
+
+        for d in data {
+            h.update(d);
+        }
+        // right_encode(512)
+        h.update(&[0x02, 0x00, 0x02]);
+
+        let mut out = Self::Output::new();
+        h.finalize_xof_into(out.as_mut());
+        out
+    }
+}
+
+/// `left_encode` of NIST SP 800-185.
+fn left_encode(x: u64, buf: &mut [u8; 9]) -> &[u8] {
+    buf[1..].copy_from_slice(&x.to_be_bytes());
+    let n = 8 - x.max(1).leading_zeros() as usize / 8;
+    buf[8 - n] = n as u8;
+    &buf[8 - n..]
+}
@@ -6,16 +6,153 @@
 //! * [`x25519-dalek`](https://crates.io/crates/x25519-dalek)
 //! * [`chacha20poly1305`](https://crates.io/crates/chacha20poly1305)
 //! * [`aes-gcm`](https://crates.io/crates/aes-gcm)
+//! * [`aes-gcm-siv`](https://crates.io/crates/aes-gcm-siv), with the
+//!   `use-aes-256-gcm-siv` feature
+//! * [`ascon-aead`](https://crates.io/crates/ascon-aead) and
+//!   [`deoxys`](https://crates.io/crates/deoxys), with the `use-ascon` and
+//!   `use-deoxys` features
 //! * [`sha2`](https://crates.io/crates/sha2)
 //! * [`blake2`](https://crates.io/crates/blake2)
+//! * [`blake3`](https://crates.io/crates/blake3), with the `use-blake3`
+//!   feature
+//! * [`kuznyechik`](https://crates.io/crates/kuznyechik),
+//!   [`mgm`](https://crates.io/crates/mgm) and
+//!   [`streebog`](https://crates.io/crates/streebog), with the `use-gost`
+//!   feature
+//! * [`sha3`](https://crates.io/crates/sha3), with the `use-sha3` and
+//!   `use-kmac` features
+//! * [`crypto-bigint`](https://crates.io/crates/crypto-bigint), for the
+//!   Brainpool curves P256r1 and P384r1, with the `use-bp256` and `use-bp384`
+//!   features
+//! * [`x448`](https://crates.io/crates/x448), with the `use-x448` feature,
+//!   and [`crypto-bigint`](https://crates.io/crates/crypto-bigint) for its
+//!   Elligator representatives
+//! * [`ml-kem`](https://crates.io/crates/ml-kem), with the `use-mlkem`
+//!   feature, for hybrid patterns
+//!
+//! With the `use-xchacha20poly1305` feature, [`XChaCha20Poly1305`] is
+//! ChaCha20-Poly1305 with 24-byte nonces, named `XChaChaPoly`.
+//!
+//! With the `use-aes-128-gcm` feature, [`Aes128Gcm`] is AES-GCM with a
+//! 128-bit key, named `AES128GCM`.
+//!
+//! With the `use-aes-256-gcm-siv` feature, [`Aes256GcmSiv`] is the nonce
+//! misuse-resistant AES-GCM-SIV, named `AESGCMSIV`.
+//!
+//! With the `use-ascon` feature, [`Ascon128a`] is the lightweight Ascon-128a,
+//! named `Ascon128a`. With the `use-deoxys` feature, [`DeoxysII256`] is the
+//! nonce misuse-resistant Deoxys-II-256, named `DeoxysII256`.
+//!
+//! With the `use-aead` feature, [`impl_noise_cipher!`] turns any RustCrypto
+//! AEAD into a [`Cipher`], cf. [`generic_aead`].
+//!
+//! With the `use-digest` feature, [`generic_hash::DigestHash`] turns any
+//! RustCrypto digest into a [`Hash`].
+//!
+//! With the `use-cascade` feature, [`ChaChaPolyAesGcm`] cascades
+//! ChaCha20-Poly1305 over AES-256-GCM.
+//!
+//! With the `aes-hw` feature, AES-GCM must use hardware AES and carry-less
+//! multiplication where the CPU may have them, cf. [`accel`]. On ARMv8,
+//! this requires `RUSTFLAGS="--cfg aes_armv8 --cfg polyval_armv8"`, and
+//! the build fails without; `noise-ring` uses them without. Use
+//! [`Aes256Gcm::is_hardware_accelerated`] to fall back to ChaChaPoly at
+//! runtime on CPUs without them.
+//!
+//! With the `sha2-asm` feature, SHA-256 and SHA-512 use the assembly of
+//! [`sha2-asm`](https://crates.io/crates/sha2-asm), which uses the SHA-2
+//! extensions on ARMv8. RISC-V crypto extensions are not used, as the
+//! RustCrypto crates in use have no backends for them.
+//!
+//! With the `use-ed25519` feature, [`Ed25519`] signs the `sig` token of
+//! signature patterns, e.g., `XXsig`.
+//!
+//! With the `ed25519-compat` feature, [`convert`] turns Ed25519 keys into
+//! X25519 keys.
+//!
+//! With the `registry` feature, [`registry::resolve`] creates a handshake
+//! from a protocol name, with any of the primitives enabled above.
+//! [`protocol!`] does so at compile time, for a fixed protocol name.
+//!
+//! With the `arbitrary` feature, [`elligator::arbitrary_representative`]
+//! gives representative bytes from fuzzer input.
+//!
+//! With the `snow-compat` feature, [`snow_compat::SnowResolver`] provides
+//! these primitives, and X25519 with Elligator, to code written against
+//! [`snow`](https://crates.io/crates/snow).
+//!
+//! Keys are generated with the OS RNG, through `getrandom`. Without the
+//! `getrandom` feature, e.g., on bare-metal targets, set an entropy source,
+//! cf. [`entropy`].
+//!
+//! On `wasm32-unknown-unknown`, enable the `wasm` feature so that the OS RNG
+//! is `crypto.getRandomValues` of the browser or Node.js, through the `js`
+//! backend of `getrandom`. Run the smoke tests with
+//! `wasm-pack test --headless --firefox -- --features wasm`.
 
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod accel;
+#[cfg(any(feature = "use-bp256", feature = "use-bp384"))]
+mod brainpool;
+#[cfg(feature = "use-cascade")]
+mod cascade;
+#[cfg(feature = "ed25519-compat")]
+pub mod convert;
+#[cfg(feature = "use-x448")]
+mod curve448;
+#[cfg(feature = "use-ed25519")]
+mod ed25519;
+#[cfg(feature = "x25519")]
+pub mod elligator;
+#[cfg(feature = "use-x448")]
+pub mod elligator448;
+pub mod entropy;
+#[cfg(feature = "use-aead")]
+pub mod generic_aead;
+#[cfg(feature = "use-digest")]
+pub mod generic_hash;
+#[cfg(feature = "std")]
+pub mod keyfile;
+#[cfg(feature = "use-kmac")]
+mod kmac;
+#[cfg(feature = "use-mlkem")]
+mod mlkem;
+pub mod protocol;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "x25519")]
+pub mod rfc9380;
 pub mod sensitive;
+#[cfg(feature = "snow-compat")]
+pub mod snow_compat;
+
+#[cfg(feature = "use-bp256")]
+pub use brainpool::BrainpoolP256r1;
+#[cfg(feature = "use-bp384")]
+pub use brainpool::BrainpoolP384r1;
+#[cfg(feature = "use-cascade")]
+pub use cascade::ChaChaPolyAesGcm;
+#[cfg(feature = "use-x448")]
+pub use curve448::X448;
+#[cfg(feature = "use-ed25519")]
+pub use ed25519::Ed25519;
+#[cfg(feature = "use-kmac")]
+pub use kmac::{KeccakDomain, Kmac256, NoiseDomain};
+#[cfg(feature = "use-mlkem")]
+pub use mlkem::MlKem768;
+#[cfg(feature = "x25519")]
+pub use rfc9380::X25519Rfc9380;
 
-use aes_gcm::aead::{OsRng, rand_core::RngCore};
 #[cfg(feature = "x25519")]
 use curve25519_dalek::MontgomeryPoint;
+#[cfg(feature = "x25519")]
+use entropy::EntropyRng;
+#[cfg(feature = "x25519")]
+use noise_protocol::rand_core::{CryptoRng, RngCore};
 use sensitive::Sensitive;
 
 use noise_protocol::*;
@@ -35,13 +172,20 @@ impl DH for X25519 {
     }
 
     fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        Self::genkey_with_rng(&mut EntropyRng, elligator)
+    }
+
+    fn genkey_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        elligator: bool,
+    ) -> DhKeyPair<Self::Key, Self::Pubkey> {
         if elligator {
-            let (priv_key, pub_key) = MontgomeryPoint::generate_ephemeral_elligator_random(&mut OsRng);
-            let priv_key =  Sensitive::from(Zeroizing::new(priv_key));
+            let (priv_key, pub_key) = MontgomeryPoint::generate_ephemeral_elligator_random(rng);
+            let priv_key = Sensitive::from(Zeroizing::new(priv_key));
             (priv_key, pub_key).into()
         } else {
             let mut priv_key = Self::Key::new();
-            OsRng.fill_bytes(priv_key.as_mut_slice());
+            rng.fill_bytes(priv_key.as_mut_slice());
             let pub_key = MontgomeryPoint::mul_base_clamped(*priv_key);
             (priv_key, *pub_key.as_bytes()).into()
         }
@@ -51,16 +195,122 @@ impl DH for X25519 {
         MontgomeryPoint::mul_base_clamped(**k).to_bytes()
     }
 
-    fn dh(k: &Self::Key, pk: &Self::Pubkey, is_elligator_encoded: bool) -> Result<Self::Output, ()> {
+    fn dh(
+        k: &Self::Key,
+        pk: &Self::Pubkey,
+        is_elligator_encoded: bool,
+    ) -> Result<Self::Output, Error> {
         let pk = if is_elligator_encoded {
             MontgomeryPoint::from_elligator_representative(pk)
         } else {
             MontgomeryPoint(*pk)
         };
-        let data = pk.mul_clamped(**k).to_bytes();
-        let data = Sensitive::from(Zeroizing::new(data));
+        let mut shared = pk.mul_clamped(**k);
+        let data = Sensitive::from(Zeroizing::new(shared.to_bytes()));
+        zeroize::Zeroize::zeroize(&mut shared.0);
         Ok(data)
     }
+
+    fn check_pubkey(pk: &Self::Pubkey, is_elligator_encoded: bool) -> Result<(), Error> {
+        let pk = if is_elligator_encoded {
+            MontgomeryPoint::from_elligator_representative(pk).to_bytes()
+        } else {
+            *pk
+        };
+        Self::validate_pubkey(&pk).map_err(|_| ErrorKind::InvalidPublicKey.into())
+    }
+}
+
+/// Why [`X25519::validate_pubkey`] rejected a public key.
+#[cfg(feature = "x25519")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubkeyError {
+    /// The key is not the canonical encoding of a field element, i.e., its
+    /// top bit is set, or it is not reduced modulo 2^255 - 19.
+    NonCanonical,
+    /// The key is a point of small order, so the DH output is known.
+    SmallOrder,
+}
+
+#[cfg(feature = "x25519")]
+impl core::fmt::Display for PubkeyError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PubkeyError::NonCanonical => fmt.write_str("Non-canonical public key"),
+            PubkeyError::SmallOrder => fmt.write_str("Small order public key"),
+        }
+    }
+}
+
+#[cfg(all(feature = "x25519", feature = "std"))]
+impl std::error::Error for PubkeyError {}
+
+// The u-coordinates of the points of order 1, 2, 4 and 8, little endian.
+#[cfg(feature = "x25519")]
+const SMALL_ORDER_25519: [[u8; 32]; 5] = [
+    [0; 32],
+    [
+        1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0,
+    ],
+    [
+        0xe0, 0xeb, 0x7a, 0x7c, 0x3b, 0x41, 0xb8, 0xae, 0x16, 0x56, 0xe3, 0xfa, 0xf1, 0x9f, 0xc4,
+        0x6a, 0xda, 0x09, 0x8d, 0xeb, 0x9c, 0x32, 0xb1, 0xfd, 0x86, 0x62, 0x05, 0x16, 0x5f, 0x49,
+        0xb8, 0x00,
+    ],
+    [
+        0x5f, 0x9c, 0x95, 0xbc, 0xa3, 0x50, 0x8c, 0x24, 0xb1, 0xd0, 0xb1, 0x55, 0x9c, 0x83, 0xef,
+        0x5b, 0x04, 0x44, 0x5c, 0xc4, 0x58, 0x1c, 0x8e, 0x86, 0xd8, 0x22, 0x4e, 0xdd, 0xd0, 0x9f,
+        0x11, 0x57,
+    ],
+    [
+        0xec, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0x7f,
+    ],
+];
+
+#[cfg(feature = "x25519")]
+impl X25519 {
+    /// Check that `pk` is the canonical encoding of a u-coordinate, and not
+    /// one of the points of small order, whose DH outputs are known.
+    ///
+    /// Keys from [`DH::genkey`] always pass. Elligator representatives must
+    /// be decoded first, cf. [`DH::check_pubkey`], which is called for the
+    /// keys of the peer with
+    /// [`set_strict_pubkey_validation`](noise_protocol::HandshakeStateBuilder::set_strict_pubkey_validation).
+    pub fn validate_pubkey(pk: &[u8; 32]) -> Result<(), PubkeyError> {
+        // u >= 2^255 - 19, i.e., 0x7fff...ffed, or the top bit set.
+        let unreduced = pk[31] == 0x7f && pk[1..31].iter().all(|&b| b == 0xff) && pk[0] >= 0xed;
+        if pk[31] & 0x80 != 0 || unreduced {
+            return Err(PubkeyError::NonCanonical);
+        }
+        if SMALL_ORDER_25519.contains(pk) {
+            return Err(PubkeyError::SmallOrder);
+        }
+        Ok(())
+    }
+}
+
+// Copy `bufs` to `out`, and split it into the plaintext and the tag.
+//
+// Panics if `out` is not 16 bytes longer than `bufs`.
+#[cfg(any(
+    feature = "use-chacha20poly1305",
+    feature = "use-aes-256-gcm",
+    feature = "use-aes-128-gcm"
+))]
+fn gather<'a>(bufs: &[&[u8]], out: &'a mut [u8]) -> (&'a mut [u8], &'a mut [u8]) {
+    let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+    assert!(len.checked_add(16) == Some(out.len()));
+
+    let (in_out, tag_out) = out.split_at_mut(len);
+    let mut cur = 0;
+    for b in bufs {
+        in_out[cur..cur + b.len()].copy_from_slice(b);
+        cur += b.len();
+    }
+    (in_out, tag_out)
 }
 
 #[cfg(feature = "use-chacha20poly1305")]
@@ -84,7 +334,7 @@ impl Cipher for ChaCha20Poly1305 {
         in_out.copy_from_slice(plaintext);
 
         use chacha20poly1305::{AeadInPlace, KeyInit};
-        let tag = chacha20poly1305::ChaCha20Poly1305::new(&(**k).into())
+        let tag = chacha20poly1305::ChaCha20Poly1305::new(k.as_slice().into())
             .encrypt_in_place_detached(&full_nonce.into(), ad, in_out)
             .unwrap();
 
@@ -108,7 +358,7 @@ impl Cipher for ChaCha20Poly1305 {
         let (in_out, tag_out) = in_out[..plaintext_len + 16].split_at_mut(plaintext_len);
 
         use chacha20poly1305::{AeadInPlace, KeyInit};
-        let tag = chacha20poly1305::ChaCha20Poly1305::new(&(**k).into())
+        let tag = chacha20poly1305::ChaCha20Poly1305::new(k.as_slice().into())
             .encrypt_in_place_detached(&full_nonce.into(), ad, in_out)
             .unwrap();
         tag_out.copy_from_slice(tag.as_ref());
@@ -116,13 +366,26 @@ impl Cipher for ChaCha20Poly1305 {
         plaintext_len + 16
     }
 
+    fn encrypt_vectored(k: &Self::Key, nonce: u64, ad: &[u8], bufs: &[&[u8]], out: &mut [u8]) {
+        let mut full_nonce = [0u8; 12];
+        full_nonce[4..].copy_from_slice(&nonce.to_le_bytes());
+
+        let (in_out, tag_out) = gather(bufs, out);
+
+        use chacha20poly1305::{AeadInPlace, KeyInit};
+        let tag = chacha20poly1305::ChaCha20Poly1305::new(k.as_slice().into())
+            .encrypt_in_place_detached(&full_nonce.into(), ad, in_out)
+            .unwrap();
+        tag_out.copy_from_slice(tag.as_ref());
+    }
+
     fn decrypt(
         k: &Self::Key,
         nonce: u64,
         ad: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()> {
+    ) -> Result<(), Error> {
         assert!(ciphertext.len().checked_sub(16) == Some(out.len()));
 
         let mut full_nonce = [0u8; 12];
@@ -132,9 +395,9 @@ impl Cipher for ChaCha20Poly1305 {
         let tag = &ciphertext[out.len()..];
 
         use chacha20poly1305::{AeadInPlace, KeyInit};
-        chacha20poly1305::ChaCha20Poly1305::new(&(**k).into())
+        chacha20poly1305::ChaCha20Poly1305::new(k.as_slice().into())
             .decrypt_in_place_detached(&full_nonce.into(), ad, out, tag.into())
-            .map_err(|_| ())
+            .map_err(|_| ErrorKind::Decryption.into())
     }
 
     fn decrypt_in_place(
@@ -143,7 +406,7 @@ impl Cipher for ChaCha20Poly1305 {
         ad: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
         assert!(ciphertext_len <= in_out.len());
         assert!(ciphertext_len >= 16);
 
@@ -153,21 +416,333 @@ impl Cipher for ChaCha20Poly1305 {
         let (in_out, tag) = in_out[..ciphertext_len].split_at_mut(ciphertext_len - 16);
 
         use chacha20poly1305::{AeadInPlace, KeyInit};
-        chacha20poly1305::ChaCha20Poly1305::new(&(**k).into())
+        chacha20poly1305::ChaCha20Poly1305::new(k.as_slice().into())
             .decrypt_in_place_detached(&full_nonce.into(), ad, in_out, tag.as_ref().into())
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         Ok(in_out.len())
     }
 }
 
-#[cfg(feature = "use-aes-256-gcm")]
-pub enum Aes256Gcm {}
+/// ChaCha20-Poly1305 with the 24-byte nonces of XChaCha20, named
+/// `XChaChaPoly`. It is not a standard Noise cipher, so both peers must use it.
+#[cfg(feature = "use-xchacha20poly1305")]
+pub enum XChaCha20Poly1305 {}
+
+/// The 24-byte nonce: 16 zero bytes and the 64-bit counter in little endian,
+/// so that it extends the nonce of `ChaChaPoly`.
+#[cfg(feature = "use-xchacha20poly1305")]
+fn xchacha_nonce(nonce: u64) -> [u8; 24] {
+    let mut full_nonce = [0u8; 24];
+    full_nonce[16..].copy_from_slice(&nonce.to_le_bytes());
+    full_nonce
+}
+
+#[cfg(feature = "use-xchacha20poly1305")]
+impl Cipher for XChaCha20Poly1305 {
+    fn name() -> &'static str {
+        "XChaChaPoly"
+    }
+
+    type Key = Sensitive<[u8; 32]>;
+
+    fn encrypt(k: &Self::Key, nonce: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
+        assert!(plaintext.len().checked_add(16) == Some(out.len()));
+
+        let (in_out, tag_out) = out.split_at_mut(plaintext.len());
+        in_out.copy_from_slice(plaintext);
+
+        use chacha20poly1305::{AeadInPlace, KeyInit};
+        let tag = chacha20poly1305::XChaCha20Poly1305::new(k.as_slice().into())
+            .encrypt_in_place_detached(&xchacha_nonce(nonce).into(), ad, in_out)
+            .unwrap();
+
+        tag_out.copy_from_slice(tag.as_ref())
+    }
+
+    fn encrypt_in_place(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        in_out: &mut [u8],
+        plaintext_len: usize,
+    ) -> usize {
+        assert!(plaintext_len
+            .checked_add(16)
+            .map_or(false, |l| l <= in_out.len()));
+
+        let (in_out, tag_out) = in_out[..plaintext_len + 16].split_at_mut(plaintext_len);
+
+        use chacha20poly1305::{AeadInPlace, KeyInit};
+        let tag = chacha20poly1305::XChaCha20Poly1305::new(k.as_slice().into())
+            .encrypt_in_place_detached(&xchacha_nonce(nonce).into(), ad, in_out)
+            .unwrap();
+        tag_out.copy_from_slice(tag.as_ref());
+
+        plaintext_len + 16
+    }
+
+    fn decrypt(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), Error> {
+        assert!(ciphertext.len().checked_sub(16) == Some(out.len()));
+
+        out.copy_from_slice(&ciphertext[..out.len()]);
+        let tag = &ciphertext[out.len()..];
+
+        use chacha20poly1305::{AeadInPlace, KeyInit};
+        chacha20poly1305::XChaCha20Poly1305::new(k.as_slice().into())
+            .decrypt_in_place_detached(&xchacha_nonce(nonce).into(), ad, out, tag.into())
+            .map_err(|_| ErrorKind::Decryption.into())
+    }
+
+    fn decrypt_in_place(
+        k: &Self::Key,
+        nonce: u64,
+        ad: &[u8],
+        in_out: &mut [u8],
+        ciphertext_len: usize,
+    ) -> Result<usize, Error> {
+        assert!(ciphertext_len <= in_out.len());
+        assert!(ciphertext_len >= 16);
+
+        let (in_out, tag) = in_out[..ciphertext_len].split_at_mut(ciphertext_len - 16);
+
+        use chacha20poly1305::{AeadInPlace, KeyInit};
+        chacha20poly1305::XChaCha20Poly1305::new(k.as_slice().into())
+            .decrypt_in_place_detached(
+                &xchacha_nonce(nonce).into(),
+                ad,
+                in_out,
+                tag.as_ref().into(),
+            )
+            .map_err(|_| ErrorKind::Decryption)?;
+
+        Ok(in_out.len())
+    }
+}
+
+#[cfg(any(feature = "use-aes-256-gcm", feature = "use-aes-128-gcm"))]
+fn aes_gcm_nonce(nonce: u64) -> [u8; 12] {
+    let mut full_nonce = [0u8; 12];
+    full_nonce[4..].copy_from_slice(&nonce.to_be_bytes());
+    full_nonce
+}
+
+#[cfg(any(feature = "use-aes-256-gcm", feature = "use-aes-128-gcm"))]
+macro_rules! impl_aes_gcm {
+    ($(#[$attr:meta])* $name:ident, $feature:literal, $inner:ty, $noise_name:expr, $len:expr) => {
+        $(#[$attr])*
+        #[cfg(feature = $feature)]
+        pub enum $name {}
+
+        #[cfg(feature = $feature)]
+        impl $name {
+            /// Whether AES and carry-less multiplication are hardware
+            /// accelerated on this CPU, cf. [`accel::detect`].
+            ///
+            /// Without them, ChaChaPoly is usually much faster than the
+            /// constant time software implementation of AES-GCM.
+            pub fn is_hardware_accelerated() -> bool {
+                let accel = accel::detect();
+                accel.aes && accel.clmul
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl Cipher for $name {
+            fn name() -> &'static str {
+                $noise_name
+            }
+
+            type Key = Sensitive<[u8; $len]>;
+
+            fn encrypt(k: &Self::Key, nonce: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
+                assert!(plaintext.len().checked_add(16) == Some(out.len()));
+
+                let (in_out, tag_out) = out.split_at_mut(plaintext.len());
+                in_out.copy_from_slice(plaintext);
+
+                use aes_gcm::{AeadInPlace, KeyInit};
+                let tag = <$inner>::new(k.as_slice().into())
+                    .encrypt_in_place_detached(&aes_gcm_nonce(nonce).into(), ad, in_out)
+                    .unwrap();
+
+                tag_out.copy_from_slice(tag.as_ref())
+            }
+
+            fn encrypt_in_place(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                in_out: &mut [u8],
+                plaintext_len: usize,
+            ) -> usize {
+                assert!(plaintext_len
+                    .checked_add(16)
+                    .map_or(false, |l| l <= in_out.len()));
+
+                let (in_out, tag_out) = in_out[..plaintext_len + 16].split_at_mut(plaintext_len);
+
+                use aes_gcm::{AeadInPlace, KeyInit};
+                let tag = <$inner>::new(k.as_slice().into())
+                    .encrypt_in_place_detached(&aes_gcm_nonce(nonce).into(), ad, in_out)
+                    .unwrap();
+                tag_out.copy_from_slice(tag.as_ref());
+
+                plaintext_len + 16
+            }
+
+            fn encrypt_vectored(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                bufs: &[&[u8]],
+                out: &mut [u8],
+            ) {
+                let (in_out, tag_out) = gather(bufs, out);
+
+                use aes_gcm::{AeadInPlace, KeyInit};
+                let tag = <$inner>::new(k.as_slice().into())
+                    .encrypt_in_place_detached(&aes_gcm_nonce(nonce).into(), ad, in_out)
+                    .unwrap();
+                tag_out.copy_from_slice(tag.as_ref());
+            }
+
+            fn decrypt(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                ciphertext: &[u8],
+                out: &mut [u8],
+            ) -> Result<(), Error> {
+                assert!(ciphertext.len().checked_sub(16) == Some(out.len()));
+
+                out.copy_from_slice(&ciphertext[..out.len()]);
+                let tag = &ciphertext[out.len()..];
+
+                use aes_gcm::{AeadInPlace, KeyInit};
+                <$inner>::new(k.as_slice().into())
+                    .decrypt_in_place_detached(&aes_gcm_nonce(nonce).into(), ad, out, tag.into())
+                    .map_err(|_| ErrorKind::Decryption.into())
+            }
+
+            fn decrypt_in_place(
+                k: &Self::Key,
+                nonce: u64,
+                ad: &[u8],
+                in_out: &mut [u8],
+                ciphertext_len: usize,
+            ) -> Result<usize, Error> {
+                assert!(ciphertext_len <= in_out.len());
+                assert!(ciphertext_len >= 16);
+
+                let (in_out, tag) = in_out[..ciphertext_len].split_at_mut(ciphertext_len - 16);
+
+                use aes_gcm::{AeadInPlace, KeyInit};
+                <$inner>::new(k.as_slice().into())
+                    .decrypt_in_place_detached(
+                        &aes_gcm_nonce(nonce).into(),
+                        ad,
+                        in_out,
+                        tag.as_ref().into(),
+                    )
+                    .map_err(|_| ErrorKind::Decryption)?;
+
+                Ok(in_out.len())
+            }
+        }
+    };
+}
 
 #[cfg(feature = "use-aes-256-gcm")]
-impl Cipher for Aes256Gcm {
+impl_aes_gcm!(
+    Aes256Gcm,
+    "use-aes-256-gcm",
+    aes_gcm::Aes256Gcm,
+    "AESGCM",
+    32
+);
+
+#[cfg(feature = "use-aes-128-gcm")]
+impl_aes_gcm!(
+    /// AES-GCM with a 128-bit key, for hardware that only accelerates
+    /// AES-128.
+    ///
+    /// This is not a standard Noise cipher, so it is called `AES128GCM`. The
+    /// key is the first 16 bytes of the 32 bytes the handshake derives, and
+    /// the nonce is the same as for `AESGCM`.
+    Aes128Gcm,
+    "use-aes-128-gcm",
+    aes_gcm::Aes128Gcm,
+    "AES128GCM",
+    16
+);
+
+#[cfg(feature = "use-aes-256-gcm-siv")]
+crate::impl_noise_cipher!(
+    /// AES-256-GCM-SIV (RFC 8452), a nonce misuse-resistant AEAD.
+    ///
+    /// Reusing a nonce, e.g., after restoring an old checkpoint of a cipher
+    /// state when a process crashed, only reveals whether the same message
+    /// was encrypted twice with the same nonce and associated data, instead
+    /// of breaking confidentiality and authenticity as with AES-GCM. Nonces
+    /// should still never repeat.
+    ///
+    /// This is not a standard Noise cipher, so it is called `AESGCMSIV`. The
+    /// nonce is the same as for `AESGCM`.
+    pub Aes256GcmSiv,
+    aes_gcm_siv::Aes256GcmSiv,
+    "AESGCMSIV",
+    32
+);
+
+#[cfg(feature = "use-ascon")]
+crate::impl_noise_cipher!(
+    /// Ascon-128a, the lightweight AEAD selected by NIST, for constrained
+    /// devices.
+    ///
+    /// This is not a standard Noise cipher, so it is called `Ascon128a`. The
+    /// key is the first 16 bytes of the 32 bytes the handshake derives, and
+    /// the 16-byte nonce is 8 zero bytes followed by the big-endian counter.
+    pub Ascon128a,
+    ascon_aead::Ascon128a,
+    "Ascon128a",
+    16
+);
+
+#[cfg(feature = "use-deoxys")]
+crate::impl_noise_cipher!(
+    /// Deoxys-II-256, a nonce misuse-resistant AEAD, cf.
+    /// [`Aes256GcmSiv`].
+    ///
+    /// This is not a standard Noise cipher, so it is called `DeoxysII256`.
+    /// The 15-byte nonce is 7 zero bytes followed by the big-endian counter.
+    pub DeoxysII256,
+    deoxys::DeoxysII256,
+    "DeoxysII256",
+    32
+);
+
+/// Kuznyechik (GOST R 34.12-2015) in MGM mode (RFC 9058).
+///
+/// There is no standard Noise name for this cipher, so it is called
+/// `KuznyechikMGM`. The 16 byte MGM nonce is 8 zero bytes followed by the
+/// big-endian counter.
+#[cfg(feature = "use-gost")]
+pub enum KuznyechikMgm {}
+
+#[cfg(feature = "use-gost")]
+type KuznyechikMgmImpl = mgm::Mgm<kuznyechik::Kuznyechik>;
+
+#[cfg(feature = "use-gost")]
+impl Cipher for KuznyechikMgm {
     fn name() -> &'static str {
-        "AESGCM"
+        "KuznyechikMGM"
     }
 
     type Key = Sensitive<[u8; 32]>;
@@ -175,14 +750,14 @@ impl Cipher for Aes256Gcm {
     fn encrypt(k: &Self::Key, nonce: u64, ad: &[u8], plaintext: &[u8], out: &mut [u8]) {
         assert!(plaintext.len().checked_add(16) == Some(out.len()));
 
-        let mut full_nonce = [0u8; 12];
-        full_nonce[4..].copy_from_slice(&nonce.to_be_bytes());
+        let mut full_nonce = [0u8; 16];
+        full_nonce[8..].copy_from_slice(&nonce.to_be_bytes());
 
         let (in_out, tag_out) = out.split_at_mut(plaintext.len());
         in_out.copy_from_slice(plaintext);
 
-        use aes_gcm::{AeadInPlace, KeyInit};
-        let tag = aes_gcm::Aes256Gcm::new(&(**k).into())
+        use mgm::aead::{AeadInPlace, KeyInit};
+        let tag = KuznyechikMgmImpl::new(k.as_slice().into())
             .encrypt_in_place_detached(&full_nonce.into(), ad, in_out)
             .unwrap();
 
@@ -200,13 +775,13 @@ impl Cipher for Aes256Gcm {
             .checked_add(16)
             .map_or(false, |l| l <= in_out.len()));
 
-        let mut full_nonce = [0u8; 12];
-        full_nonce[4..].copy_from_slice(&nonce.to_be_bytes());
+        let mut full_nonce = [0u8; 16];
+        full_nonce[8..].copy_from_slice(&nonce.to_be_bytes());
 
         let (in_out, tag_out) = in_out[..plaintext_len + 16].split_at_mut(plaintext_len);
 
-        use aes_gcm::{AeadInPlace, KeyInit};
-        let tag = aes_gcm::Aes256Gcm::new(&(**k).into())
+        use mgm::aead::{AeadInPlace, KeyInit};
+        let tag = KuznyechikMgmImpl::new(k.as_slice().into())
             .encrypt_in_place_detached(&full_nonce.into(), ad, in_out)
             .unwrap();
         tag_out.copy_from_slice(tag.as_ref());
@@ -220,19 +795,19 @@ impl Cipher for Aes256Gcm {
         ad: &[u8],
         ciphertext: &[u8],
         out: &mut [u8],
-    ) -> Result<(), ()> {
+    ) -> Result<(), Error> {
         assert!(ciphertext.len().checked_sub(16) == Some(out.len()));
 
-        let mut full_nonce = [0u8; 12];
-        full_nonce[4..].copy_from_slice(&nonce.to_be_bytes());
+        let mut full_nonce = [0u8; 16];
+        full_nonce[8..].copy_from_slice(&nonce.to_be_bytes());
 
         out.copy_from_slice(&ciphertext[..out.len()]);
         let tag = &ciphertext[out.len()..];
 
-        use aes_gcm::{AeadInPlace, KeyInit};
-        aes_gcm::Aes256Gcm::new(&(**k).into())
+        use mgm::aead::{AeadInPlace, KeyInit};
+        KuznyechikMgmImpl::new(k.as_slice().into())
             .decrypt_in_place_detached(&full_nonce.into(), ad, out, tag.into())
-            .map_err(|_| ())
+            .map_err(|_| ErrorKind::Decryption.into())
     }
 
     fn decrypt_in_place(
@@ -241,19 +816,19 @@ impl Cipher for Aes256Gcm {
         ad: &[u8],
         in_out: &mut [u8],
         ciphertext_len: usize,
-    ) -> Result<usize, ()> {
+    ) -> Result<usize, Error> {
         assert!(ciphertext_len <= in_out.len());
         assert!(ciphertext_len >= 16);
 
-        let mut full_nonce = [0u8; 12];
-        full_nonce[4..].copy_from_slice(&nonce.to_be_bytes());
+        let mut full_nonce = [0u8; 16];
+        full_nonce[8..].copy_from_slice(&nonce.to_be_bytes());
 
         let (in_out, tag) = in_out[..ciphertext_len].split_at_mut(ciphertext_len - 16);
 
-        use aes_gcm::{AeadInPlace, KeyInit};
-        aes_gcm::Aes256Gcm::new(&(**k).into())
+        use mgm::aead::{AeadInPlace, KeyInit};
+        KuznyechikMgmImpl::new(k.as_slice().into())
             .decrypt_in_place_detached(&full_nonce.into(), ad, in_out, tag.as_ref().into())
-            .map_err(|_| ())?;
+            .map_err(|_| ErrorKind::Decryption)?;
 
         Ok(in_out.len())
     }
@@ -307,6 +882,54 @@ impl Hash for Sha512 {
     }
 }
 
+#[cfg(feature = "use-sha3")]
+#[derive(Default, Clone)]
+pub struct Sha3_256(sha3::Sha3_256);
+
+#[cfg(feature = "use-sha3")]
+impl Hash for Sha3_256 {
+    fn name() -> &'static str {
+        "SHA3-256"
+    }
+
+    type Block = [u8; 136];
+    type Output = Sensitive<[u8; 32]>;
+
+    fn input(&mut self, data: &[u8]) {
+        use sha3::Digest;
+        self.0.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        use sha3::Digest;
+        Self::Output::from_slice(self.0.finalize_reset().as_ref())
+    }
+}
+
+#[cfg(feature = "use-sha3")]
+#[derive(Default, Clone)]
+pub struct Sha3_512(sha3::Sha3_512);
+
+#[cfg(feature = "use-sha3")]
+impl Hash for Sha3_512 {
+    fn name() -> &'static str {
+        "SHA3-512"
+    }
+
+    type Block = [u8; 72];
+    type Output = Sensitive<[u8; 64]>;
+
+    fn input(&mut self, data: &[u8]) {
+        use sha3::Digest;
+        self.0.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        use sha3::Digest;
+        Self::Output::from_slice(self.0.finalize_reset().as_ref())
+    }
+}
+
 #[cfg(feature = "use-blake2")]
 #[derive(Default, Clone)]
 pub struct Blake2s(blake2::Blake2s256);
@@ -354,3 +977,77 @@ impl Hash for Blake2b {
         Self::Output::from_slice(self.0.finalize_reset().as_ref())
     }
 }
+
+#[cfg(feature = "use-blake3")]
+#[derive(Default, Clone)]
+pub struct Blake3(blake3::Hasher);
+
+#[cfg(feature = "use-blake3")]
+impl Hash for Blake3 {
+    fn name() -> &'static str {
+        "BLAKE3"
+    }
+
+    type Block = [u8; 64];
+    type Output = Sensitive<[u8; 32]>;
+
+    fn input(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        let output = Self::Output::from_slice(self.0.finalize().as_bytes());
+        self.0.reset();
+        output
+    }
+}
+
+/// Streebog-256 (GOST R 34.11-2012).
+#[cfg(feature = "use-gost")]
+#[derive(Default, Clone)]
+pub struct Streebog256(streebog::Streebog256);
+
+#[cfg(feature = "use-gost")]
+impl Hash for Streebog256 {
+    fn name() -> &'static str {
+        "Streebog256"
+    }
+
+    type Block = [u8; 64];
+    type Output = Sensitive<[u8; 32]>;
+
+    fn input(&mut self, data: &[u8]) {
+        use streebog::Digest;
+        self.0.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        use streebog::Digest;
+        Self::Output::from_slice(self.0.finalize_reset().as_ref())
+    }
+}
+
+/// Streebog-512 (GOST R 34.11-2012).
+#[cfg(feature = "use-gost")]
+#[derive(Default, Clone)]
+pub struct Streebog512(streebog::Streebog512);
+
+#[cfg(feature = "use-gost")]
+impl Hash for Streebog512 {
+    fn name() -> &'static str {
+        "Streebog512"
+    }
+
+    type Block = [u8; 64];
+    type Output = Sensitive<[u8; 64]>;
+
+    fn input(&mut self, data: &[u8]) {
+        use streebog::Digest;
+        self.0.update(data);
+    }
+
+    fn result(&mut self) -> Self::Output {
+        use streebog::Digest;
+        Self::Output::from_slice(self.0.finalize_reset().as_ref())
+    }
+}
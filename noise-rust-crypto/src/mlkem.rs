@@ -0,0 +1,57 @@
+//! ML-KEM-768 (FIPS 203), the standardized Kyber768.
+//!
+//! Encapsulation keys are 1184 bytes, decapsulation keys 2400 bytes and
+//! ciphertexts 1088 bytes long, so hybrid handshakes have much larger messages
+//! than classical ones.
+
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{Ciphertext, Encoded, EncodedSizeUser, KemCore, MlKem768 as Inner};
+use noise_protocol::{Error, ErrorKind, Kem, U8Array};
+
+use crate::entropy::EntropyRng;
+use crate::sensitive::Sensitive;
+
+type DecapsulationKey = <Inner as KemCore>::DecapsulationKey;
+type EncapsulationKey = <Inner as KemCore>::EncapsulationKey;
+
+pub enum MlKem768 {}
+
+impl Kem for MlKem768 {
+    type Key = Sensitive<[u8; 2400]>;
+    type Pubkey = [u8; 1184];
+    type Ciphertext = [u8; 1088];
+    type Output = Sensitive<[u8; 32]>;
+
+    fn name() -> &'static str {
+        "MLKEM768"
+    }
+
+    fn genkey() -> (Self::Key, Self::Pubkey) {
+        let (dk, ek) = Inner::generate(&mut EntropyRng);
+        (
+            Self::Key::from_slice(&dk.as_bytes()),
+            Self::Pubkey::from_slice(&ek.as_bytes()),
+        )
+    }
+
+    fn encapsulate(pk: &Self::Pubkey) -> Result<(Self::Ciphertext, Self::Output), Error> {
+        let pk = Encoded::<EncapsulationKey>::try_from(pk.as_slice())
+            .map_err(|_| ErrorKind::InvalidPublicKey)?;
+        let (ct, shared) = EncapsulationKey::from_bytes(&pk)
+            .encapsulate(&mut EntropyRng)
+            .map_err(|_| ErrorKind::Kem)?;
+        Ok((
+            Self::Ciphertext::from_slice(&ct),
+            Self::Output::from_slice(&shared),
+        ))
+    }
+
+    fn decapsulate(k: &Self::Key, ct: &Self::Ciphertext) -> Result<Self::Output, Error> {
+        let k = Encoded::<DecapsulationKey>::try_from(k.as_slice()).map_err(|_| ErrorKind::Kem)?;
+        let ct = Ciphertext::<Inner>::try_from(ct.as_slice()).map_err(|_| ErrorKind::Kem)?;
+        let shared = DecapsulationKey::from_bytes(&k)
+            .decapsulate(&ct)
+            .map_err(|_| ErrorKind::Kem)?;
+        Ok(Self::Output::from_slice(&shared))
+    }
+}
@@ -0,0 +1,249 @@
+//! Types for a protocol name known at compile time, cf. [`protocol!`](crate::protocol!).
+//!
+//! ```
+//! noise_rust_crypto::protocol!(pub mod ik = "Noise_IK_25519_ChaChaPoly_BLAKE2s");
+//!
+//! use noise_protocol::{HandshakeStateBuilder, DH};
+//!
+//! let mut builder = HandshakeStateBuilder::<ik::Dh>::new();
+//! builder
+//!     .set_pattern(ik::PATTERN)
+//!     .set_is_initiator(false)
+//!     .set_prologue(b"")
+//!     .set_s(ik::Dh::genkey(false));
+//! let hs: ik::HandshakeState = builder.build_handshake_state();
+//! ```
+//!
+//! The name is checked when compiling, so a typo, or a primitive whose
+//! feature is not enabled, is a compile error instead of a runtime error as
+//! with [`registry`](crate::registry):
+//!
+//! ```compile_fail
+//! noise_rust_crypto::protocol!(mod ik = "Noise_IK_25519_ChaChaPoly_BLAKE2");
+//! ```
+//!
+//! Only the patterns found by
+//! [`by_name`](noise_protocol::patterns::by_name) are supported, i.e., not
+//! the hybrid and signature patterns. `X25519Rfc9380` is not supported either,
+//! as it has the same name as [`X25519`](crate::X25519).
+
+#[doc(hidden)]
+pub use noise_protocol::patterns::HandshakePattern;
+#[doc(hidden)]
+pub use noise_protocol::{HandshakeState, TransportState};
+
+/// Define a module with the types of a protocol name, e.g.,
+/// `Noise_IK_25519_ChaChaPoly_BLAKE2s`:
+///
+/// * `Dh`, `Cipher` and `Hash`, the primitives,
+/// * `HandshakeState` and `TransportState`, with these primitives,
+/// * `PATTERN`, the handshake pattern, and `NAME`, the protocol name.
+///
+/// Compilation fails if the name is malformed, or if the pattern or a
+/// primitive is unknown or not enabled, cf. the [module docs](crate::protocol).
+#[macro_export]
+macro_rules! protocol {
+    ($(#[$attr:meta])* $vis:vis mod $module:ident = $name:literal) => {
+        $(#[$attr])*
+        $vis mod $module {
+            const _: () = $crate::protocol::check($name);
+
+            /// The DH function.
+            pub type Dh =
+                <$crate::protocol::DhIndex<{ $crate::protocol::dh($name) }>
+                    as $crate::protocol::Select>::Type;
+            /// The cipher.
+            pub type Cipher =
+                <$crate::protocol::CipherIndex<{ $crate::protocol::cipher($name) }>
+                    as $crate::protocol::Select>::Type;
+            /// The hash function.
+            pub type Hash =
+                <$crate::protocol::HashIndex<{ $crate::protocol::hash($name) }>
+                    as $crate::protocol::Select>::Type;
+            /// The handshake state.
+            pub type HandshakeState = $crate::protocol::HandshakeState<Dh, Cipher, Hash>;
+            /// The transport state.
+            pub type TransportState = $crate::protocol::TransportState<Cipher, Hash>;
+            /// The handshake pattern.
+            pub const PATTERN: $crate::protocol::HandshakePattern =
+                $crate::protocol::pattern($name);
+            /// The protocol name.
+            pub const NAME: &str = $name;
+        }
+    };
+}
+
+#[doc(hidden)]
+pub trait Select {
+    type Type;
+}
+
+#[doc(hidden)]
+pub struct DhIndex<const N: usize>;
+
+#[doc(hidden)]
+pub struct CipherIndex<const N: usize>;
+
+#[doc(hidden)]
+pub struct HashIndex<const N: usize>;
+
+macro_rules! table {
+    ($table:ident, $index:ident, $($n:literal: $name:literal, $feature:literal, $ty:ty;)*) => {
+        const $table: &[(&str, bool)] = &[$(($name, cfg!(feature = $feature))),*];
+
+        $(
+            #[cfg(feature = $feature)]
+            impl Select for $index<$n> {
+                type Type = $ty;
+            }
+        )*
+    };
+}
+
+table! {
+    DHS, DhIndex,
+    0: "25519", "x25519", crate::X25519;
+    1: "448", "use-x448", crate::X448;
+    2: "BrainpoolP256r1", "use-bp256", crate::BrainpoolP256r1;
+    3: "BrainpoolP384r1", "use-bp384", crate::BrainpoolP384r1;
+}
+
+table! {
+    CIPHERS, CipherIndex,
+    0: "ChaChaPoly", "use-chacha20poly1305", crate::ChaCha20Poly1305;
+    1: "XChaChaPoly", "use-xchacha20poly1305", crate::XChaCha20Poly1305;
+    2: "AESGCM", "use-aes-256-gcm", crate::Aes256Gcm;
+    3: "AES128GCM", "use-aes-128-gcm", crate::Aes128Gcm;
+    4: "AESGCMSIV", "use-aes-256-gcm-siv", crate::Aes256GcmSiv;
+    5: "KuznyechikMGM", "use-gost", crate::KuznyechikMgm;
+    6: "ChaChaPoly+AESGCM", "use-cascade", crate::ChaChaPolyAesGcm;
+    7: "Ascon128a", "use-ascon", crate::Ascon128a;
+    8: "DeoxysII256", "use-deoxys", crate::DeoxysII256;
+}
+
+table! {
+    HASHES, HashIndex,
+    0: "SHA256", "use-sha2", crate::Sha256;
+    1: "SHA512", "use-sha2", crate::Sha512;
+    2: "SHA3-256", "use-sha3", crate::Sha3_256;
+    3: "SHA3-512", "use-sha3", crate::Sha3_512;
+    4: "BLAKE2s", "use-blake2", crate::Blake2s;
+    5: "BLAKE2b", "use-blake2", crate::Blake2b;
+    6: "BLAKE3", "use-blake3", crate::Blake3;
+    7: "Streebog256", "use-gost", crate::Streebog256;
+    8: "Streebog512", "use-gost", crate::Streebog512;
+    9: "KMAC256", "use-kmac", crate::Kmac256;
+}
+
+/// Check the whole protocol name, even if only some of its types are used.
+#[doc(hidden)]
+pub const fn check(name: &str) {
+    pattern(name);
+    dh(name);
+    cipher(name);
+    hash(name);
+}
+
+#[doc(hidden)]
+pub const fn pattern(name: &str) -> HandshakePattern {
+    match noise_protocol::patterns::by_name_const(part(name, 1)) {
+        Some(pattern) => pattern,
+        None => panic!("unknown handshake pattern"),
+    }
+}
+
+#[doc(hidden)]
+pub const fn dh(name: &str) -> usize {
+    match find(DHS, part(name, 2)) {
+        Some(i) => i,
+        None => panic!("unknown DH function"),
+    }
+}
+
+#[doc(hidden)]
+pub const fn cipher(name: &str) -> usize {
+    match find(CIPHERS, part(name, 3)) {
+        Some(i) => i,
+        None => panic!("unknown cipher"),
+    }
+}
+
+#[doc(hidden)]
+pub const fn hash(name: &str) -> usize {
+    match find(HASHES, part(name, 4)) {
+        Some(i) => i,
+        None => panic!("unknown hash function"),
+    }
+}
+
+/// The index of `name` in `table`, panicking if it is disabled.
+const fn find(table: &[(&str, bool)], name: &str) -> Option<usize> {
+    let mut i = 0;
+    while i < table.len() {
+        if eq(table[i].0.as_bytes(), name.as_bytes()) {
+            if !table[i].1 {
+                panic!("primitive not enabled, cf. the features of noise-rust-crypto");
+            }
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The `n`th part of `Noise_<pattern>_<dh>_<cipher>_<hash>`.
+const fn part(name: &str, n: usize) -> &str {
+    let bytes = name.as_bytes();
+    let (mut start, mut end, mut count) = (0, 0, 0);
+    let mut i = 0;
+    while i <= bytes.len() {
+        if i == bytes.len() || bytes[i] == b'_' {
+            if count == n {
+                end = i;
+            } else if count + 1 == n {
+                start = i + 1;
+            }
+            count += 1;
+        }
+        i += 1;
+    }
+    if count != 5 || !eq(slice(bytes, 0, 6), b"Noise_") || start >= end {
+        panic!("malformed protocol name");
+    }
+    match core::str::from_utf8(slice(bytes, start, end)) {
+        Ok(part) => part,
+        Err(_) => panic!("malformed protocol name"),
+    }
+}
+
+/// `&bytes[start..end]`, which is not const on older compilers.
+const fn slice(mut bytes: &[u8], start: usize, end: usize) -> &[u8] {
+    if end > bytes.len() {
+        return &[];
+    }
+    while bytes.len() > end {
+        if let [rest @ .., _] = bytes {
+            bytes = rest;
+        }
+    }
+    while bytes.len() > end - start {
+        if let [_, rest @ ..] = bytes {
+            bytes = rest;
+        }
+    }
+    bytes
+}
+
+const fn eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
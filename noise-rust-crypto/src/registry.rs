@@ -0,0 +1,100 @@
+//! The crypto suites of this crate, for choosing them by protocol name at
+//! runtime, cf. [`noise_protocol::registry`].
+//!
+//! ```
+//! use noise_protocol::registry::HandshakeParams;
+//! use noise_rust_crypto::registry::resolve;
+//!
+//! let params = HandshakeParams {
+//!     is_initiator: true,
+//!     ..Default::default()
+//! };
+//! let mut hs = resolve("Noise_NN_25519_ChaChaPoly_BLAKE2s", &params).unwrap();
+//! let message = hs.write_message_vec(b"").unwrap();
+//! ```
+//!
+//! Every combination of the DH functions, ciphers and hashes enabled by the
+//! `use-*` features is registered. `X25519Rfc9380` is not, as it has the same
+//! name as [`X25519`](crate::X25519).
+
+use noise_protocol::registry::{DynHandshakeState, HandshakeParams, Registry, RegistryError};
+use noise_protocol::{Cipher, DH};
+use std::boxed::Box;
+
+/// A registry with every compiled-in suite.
+#[allow(unused_mut)]
+pub fn registry() -> Registry {
+    let mut registry = Registry::new();
+    #[cfg(feature = "x25519")]
+    register_ciphers::<crate::X25519>(&mut registry);
+    #[cfg(feature = "use-x448")]
+    register_ciphers::<crate::X448>(&mut registry);
+    #[cfg(feature = "use-bp256")]
+    register_ciphers::<crate::BrainpoolP256r1>(&mut registry);
+    #[cfg(feature = "use-bp384")]
+    register_ciphers::<crate::BrainpoolP384r1>(&mut registry);
+    registry
+}
+
+/// Create a handshake state for a protocol name, e.g.,
+/// `Noise_XX_25519_ChaChaPoly_BLAKE2s`, from the compiled-in suites.
+///
+/// This builds the [`registry`] on every call. To create many handshakes,
+/// build it once and use [`Registry::new_handshake`].
+pub fn resolve(
+    protocol_name: &str,
+    params: &HandshakeParams,
+) -> Result<Box<dyn DynHandshakeState>, RegistryError> {
+    registry().new_handshake(protocol_name, params)
+}
+
+#[allow(dead_code, unused_variables)]
+fn register_ciphers<D: DH + 'static>(registry: &mut Registry) {
+    #[cfg(feature = "use-chacha20poly1305")]
+    register_hashes::<D, crate::ChaCha20Poly1305>(registry);
+    #[cfg(feature = "use-xchacha20poly1305")]
+    register_hashes::<D, crate::XChaCha20Poly1305>(registry);
+    #[cfg(feature = "use-aes-256-gcm")]
+    register_hashes::<D, crate::Aes256Gcm>(registry);
+    #[cfg(feature = "use-aes-128-gcm")]
+    register_hashes::<D, crate::Aes128Gcm>(registry);
+    #[cfg(feature = "use-aes-256-gcm-siv")]
+    register_hashes::<D, crate::Aes256GcmSiv>(registry);
+    #[cfg(feature = "use-ascon")]
+    register_hashes::<D, crate::Ascon128a>(registry);
+    #[cfg(feature = "use-deoxys")]
+    register_hashes::<D, crate::DeoxysII256>(registry);
+    #[cfg(feature = "use-gost")]
+    register_hashes::<D, crate::KuznyechikMgm>(registry);
+    #[cfg(feature = "use-cascade")]
+    register_hashes::<D, crate::ChaChaPolyAesGcm>(registry);
+}
+
+#[allow(dead_code, unused_variables)]
+fn register_hashes<D, C>(registry: &mut Registry)
+where
+    D: DH + 'static,
+    C: Cipher + 'static,
+    C::Key: Send,
+{
+    #[cfg(feature = "use-blake2")]
+    registry
+        .register::<D, C, crate::Blake2s>()
+        .register::<D, C, crate::Blake2b>();
+    #[cfg(feature = "use-sha2")]
+    registry
+        .register::<D, C, crate::Sha256>()
+        .register::<D, C, crate::Sha512>();
+    #[cfg(feature = "use-sha3")]
+    registry
+        .register::<D, C, crate::Sha3_256>()
+        .register::<D, C, crate::Sha3_512>();
+    #[cfg(feature = "use-blake3")]
+    registry.register::<D, C, crate::Blake3>();
+    #[cfg(feature = "use-gost")]
+    registry
+        .register::<D, C, crate::Streebog256>()
+        .register::<D, C, crate::Streebog512>();
+    #[cfg(feature = "use-kmac")]
+    registry.register::<D, C, crate::Kmac256>();
+}
@@ -0,0 +1,315 @@
+//! X25519 with the Elligator 2 conventions of [RFC 9380].
+//!
+//! [`X25519`](crate::X25519) encodes Elligator representatives with the
+//! conventions of the `curve25519-dalek` fork. [`X25519Rfc9380`] instead
+//! decodes representatives with `map_to_curve_elligator2_curve25519` from
+//! RFC 9380 (section 6.7.1, `Z = 2`), for protocols that standardize on the
+//! IETF formulation. Both are named `25519`, so the handshake picks the
+//! encoding through the `DH` type parameter.
+//!
+//! A representative is a field element in little endian, with bit 255 set at
+//! random. Generated public keys include a random low order component, so
+//! that their representatives are indistinguishable from random bytes; it
+//! does not change the result of DH with a clamped private key.
+//!
+//! [RFC 9380]: https://www.rfc-editor.org/rfc/rfc9380.html
+
+use crate::entropy::EntropyRng;
+use crate::sensitive::Sensitive;
+use curve25519_dalek::constants::EIGHT_TORSION;
+use curve25519_dalek::{EdwardsPoint, MontgomeryPoint};
+use noise_protocol::rand_core::{CryptoRng, RngCore};
+use noise_protocol::{DhKeyPair, Error, U8Array, DH};
+use zeroize::Zeroizing;
+
+const MASK: u64 = (1 << 51) - 1;
+
+/// An element of GF(2^255 - 19), in radix 2^51.
+#[derive(Clone, Copy)]
+struct Fe([u64; 5]);
+
+// Exponents, little endian.
+const P_MINUS_2: [u8; 32] = exponent(0xeb, 0x7f);
+const P_MINUS_1_HALF: [u8; 32] = exponent(0xf6, 0x3f);
+const P_PLUS_3_EIGHTH: [u8; 32] = exponent(0xfe, 0x0f);
+
+const SQRT_M1: [u8; 32] = [
+    0xb0, 0xa0, 0x0e, 0x4a, 0x27, 0x1b, 0xee, 0xc4, 0x78, 0xe4, 0x2f, 0xad, 0x06, 0x18, 0x43, 0x2f,
+    0xa7, 0xd7, 0xfb, 0x3d, 0x99, 0x00, 0x4d, 0x2b, 0x0b, 0xdf, 0xc1, 0x4f, 0x80, 0x24, 0x83, 0x2b,
+];
+
+const fn exponent(low: u8, high: u8) -> [u8; 32] {
+    let mut e = [0xff; 32];
+    e[0] = low;
+    e[31] = high;
+    e
+}
+
+impl Fe {
+    const ZERO: Fe = Fe([0; 5]);
+    const ONE: Fe = Fe([1, 0, 0, 0, 0]);
+    const A: Fe = Fe([486662, 0, 0, 0, 0]);
+
+    /// Decode, ignoring bit 255. Non-canonical encodings are accepted.
+    fn from_bytes(b: &[u8; 32]) -> Fe {
+        let load = |i: usize| u64::from_le_bytes(b[i..i + 8].try_into().unwrap());
+        Fe([
+            load(0) & MASK,
+            (load(6) >> 3) & MASK,
+            (load(12) >> 6) & MASK,
+            (load(19) >> 1) & MASK,
+            (load(24) >> 12) & MASK,
+        ])
+    }
+
+    /// Canonical encoding.
+    fn to_bytes(self) -> [u8; 32] {
+        let mut l = Fe::carry(self.0).0;
+        // Now l < 2p, so l >= p iff l + 19 >= 2^255, i.e., q = 1.
+        let mut q = (l[0] + 19) >> 51;
+        for &limb in &l[1..] {
+            q = (limb + q) >> 51;
+        }
+        l[0] += 19 * q;
+        for i in 0..4 {
+            l[i + 1] += l[i] >> 51;
+            l[i] &= MASK;
+        }
+        l[4] &= MASK;
+
+        let mut out = [0u8; 32];
+        let (mut acc, mut bits, mut i) = (0u128, 0, 0);
+        for limb in l {
+            acc |= (limb as u128) << bits;
+            bits += 51;
+            while bits >= 8 {
+                out[i] = acc as u8;
+                acc >>= 8;
+                bits -= 8;
+                i += 1;
+            }
+        }
+        out[31] = acc as u8;
+        out
+    }
+
+    fn carry(mut l: [u64; 5]) -> Fe {
+        for i in 0..4 {
+            l[i + 1] += l[i] >> 51;
+            l[i] &= MASK;
+        }
+        l[0] += 19 * (l[4] >> 51);
+        l[4] &= MASK;
+        Fe(l)
+    }
+
+    fn add(self, rhs: Fe) -> Fe {
+        let mut l = self.0;
+        for (a, b) in l.iter_mut().zip(rhs.0) {
+            *a += b;
+        }
+        Fe::carry(l)
+    }
+
+    fn sub(self, rhs: Fe) -> Fe {
+        // Add 16p first, so limbs do not underflow.
+        let mut l = self.0;
+        l[0] += 36028797018963664;
+        for a in &mut l[1..] {
+            *a += 36028797018963952;
+        }
+        for (a, b) in l.iter_mut().zip(rhs.0) {
+            *a -= b;
+        }
+        Fe::carry(l)
+    }
+
+    fn neg(self) -> Fe {
+        Fe::ZERO.sub(self)
+    }
+
+    fn mul(self, rhs: Fe) -> Fe {
+        let m = |x: u64, y: u64| x as u128 * y as u128;
+        let [a0, a1, a2, a3, a4] = self.0;
+        let [b0, b1, b2, b3, b4] = rhs.0;
+        let (b1_19, b2_19, b3_19, b4_19) = (b1 * 19, b2 * 19, b3 * 19, b4 * 19);
+
+        let c0 = m(a0, b0) + m(a4, b1_19) + m(a3, b2_19) + m(a2, b3_19) + m(a1, b4_19);
+        let mut c1 = m(a1, b0) + m(a0, b1) + m(a4, b2_19) + m(a3, b3_19) + m(a2, b4_19);
+        let mut c2 = m(a2, b0) + m(a1, b1) + m(a0, b2) + m(a4, b3_19) + m(a3, b4_19);
+        let mut c3 = m(a3, b0) + m(a2, b1) + m(a1, b2) + m(a0, b3) + m(a4, b4_19);
+        let mut c4 = m(a4, b0) + m(a3, b1) + m(a2, b2) + m(a1, b3) + m(a0, b4);
+
+        c1 += c0 >> 51;
+        c2 += c1 >> 51;
+        c3 += c2 >> 51;
+        c4 += c3 >> 51;
+        let mut l = [
+            c0 as u64 & MASK,
+            c1 as u64 & MASK,
+            c2 as u64 & MASK,
+            c3 as u64 & MASK,
+            c4 as u64 & MASK,
+        ];
+        l[0] += (c4 >> 51) as u64 * 19;
+        l[1] += l[0] >> 51;
+        l[0] &= MASK;
+        Fe(l)
+    }
+
+    fn square(self) -> Fe {
+        self.mul(self)
+    }
+
+    /// `self ^ e`. The exponent is public.
+    fn pow(self, e: &[u8; 32]) -> Fe {
+        let mut r = Fe::ONE;
+        for i in (0..256).rev() {
+            r = r.square();
+            if (e[i / 8] >> (i % 8)) & 1 == 1 {
+                r = r.mul(self);
+            }
+        }
+        r
+    }
+
+    /// The inverse, or zero for zero.
+    fn invert(self) -> Fe {
+        self.pow(&P_MINUS_2)
+    }
+
+    fn ct_eq(self, rhs: Fe) -> bool {
+        let (a, b) = (self.to_bytes(), rhs.to_bytes());
+        a.iter().zip(&b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
+    fn is_zero(self) -> bool {
+        self.ct_eq(Fe::ZERO)
+    }
+
+    fn is_square(self) -> bool {
+        let l = self.pow(&P_MINUS_1_HALF);
+        l.ct_eq(Fe::ONE) | l.is_zero()
+    }
+
+    /// A square root of a square.
+    fn sqrt(self) -> Fe {
+        let r = self.pow(&P_PLUS_3_EIGHTH);
+        let r_m1 = r.mul(Fe::from_bytes(&SQRT_M1));
+        Fe::select(r_m1, r, r.square().ct_eq(self))
+    }
+
+    /// `a` if `!choice`, `b` if `choice`.
+    fn select(a: Fe, b: Fe, choice: bool) -> Fe {
+        let mask = 0u64.wrapping_sub(choice as u64);
+        let mut l = a.0;
+        for (x, y) in l.iter_mut().zip(b.0) {
+            *x ^= mask & (*x ^ y);
+        }
+        Fe(l)
+    }
+}
+
+/// `map_to_curve_elligator2_curve25519` of RFC 9380: the Montgomery `u`
+/// coordinate of the point a representative maps to.
+///
+/// Bit 255 of the representative is ignored.
+pub fn map_to_curve(representative: &[u8; 32]) -> [u8; 32] {
+    let u = Fe::from_bytes(representative);
+    let tv = Fe::ONE.add(u.square().add(u.square()));
+    let x1 = Fe::A.neg().mul(tv.invert());
+    let x1 = Fe::select(x1, Fe::A.neg(), x1.is_zero());
+    let gx1 = x1.mul(x1.mul(x1.add(Fe::A)).add(Fe::ONE));
+    let x2 = x1.neg().sub(Fe::A);
+    Fe::select(x2, x1, gx1.is_square()).to_bytes()
+}
+
+/// A representative of the point with Montgomery `u` coordinate `public`, if
+/// there is one, i.e., for about half the points.
+///
+/// Each point has up to four representatives. The lower three bits of
+/// `tweak` pick one of them and bit 255, so random tweaks give uniformly
+/// random representatives of random points.
+pub fn representative(public: &[u8; 32], tweak: u8) -> Option<[u8; 32]> {
+    let x = Fe::from_bytes(public);
+    let two = Fe::ONE.add(Fe::ONE);
+    let x_plus_a = x.add(Fe::A);
+    // u^2 = -x1 / (2 * (x1 + A)) for either x1 = x or x1 = -x - A.
+    let (n, d) = if tweak & 1 == 0 {
+        (x_plus_a, two.mul(x))
+    } else {
+        (x, two.mul(x_plus_a))
+    };
+    let u2 = n.neg().mul(d.invert());
+    if !u2.is_square() {
+        return None;
+    }
+    let u = u2.sqrt();
+    let u = Fe::select(u, u.neg(), tweak & 2 != 0);
+
+    let mut out = u.to_bytes();
+    if map_to_curve(&out) != x.to_bytes() {
+        return None;
+    }
+    out[31] |= (tweak & 4) << 5;
+    Some(out)
+}
+
+/// X25519 with RFC 9380 Elligator 2 representatives.
+pub enum X25519Rfc9380 {}
+
+impl DH for X25519Rfc9380 {
+    type Key = Sensitive<[u8; 32]>;
+    type Pubkey = [u8; 32];
+    type Output = Sensitive<[u8; 32]>;
+
+    fn name() -> &'static str {
+        "25519"
+    }
+
+    fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        Self::genkey_with_rng(&mut EntropyRng, elligator)
+    }
+
+    fn genkey_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        elligator: bool,
+    ) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        let mut priv_key = Self::Key::new();
+        if !elligator {
+            rng.fill_bytes(priv_key.as_mut_slice());
+            let pub_key = MontgomeryPoint::mul_base_clamped(*priv_key);
+            return (priv_key, pub_key.to_bytes()).into();
+        }
+        loop {
+            rng.fill_bytes(priv_key.as_mut_slice());
+            let tweak = Zeroizing::new(rng.next_u32() as u8);
+            let point = EdwardsPoint::mul_base_clamped(*priv_key)
+                + EIGHT_TORSION[(*tweak >> 3) as usize & 7];
+            let point = Zeroizing::new(point.to_montgomery().to_bytes());
+            if let Some(r) = representative(&point, *tweak) {
+                return (priv_key, r).into();
+            }
+        }
+    }
+
+    fn pubkey(k: &Self::Key) -> Self::Pubkey {
+        MontgomeryPoint::mul_base_clamped(**k).to_bytes()
+    }
+
+    fn dh(
+        k: &Self::Key,
+        pk: &Self::Pubkey,
+        is_elligator_encoded: bool,
+    ) -> Result<Self::Output, Error> {
+        let pk = if is_elligator_encoded {
+            MontgomeryPoint(map_to_curve(pk))
+        } else {
+            MontgomeryPoint(*pk)
+        };
+        let mut shared = pk.mul_clamped(**k);
+        let data = Sensitive::from(Zeroizing::new(shared.to_bytes()));
+        zeroize::Zeroize::zeroize(&mut shared.0);
+        Ok(data)
+    }
+}
@@ -1,4 +1,6 @@
 use noise_protocol::U8Array;
+#[cfg(feature = "std")]
+use std::vec::Vec;
 use zeroize::{Zeroize, Zeroizing};
 
 /// Struct holding a value that is safely zeroed on drop.
@@ -51,3 +53,77 @@ where
         self.0.as_mut()
     }
 }
+
+/// A variable-length buffer of secret bytes, e.g., a decrypted plaintext,
+/// that is safely zeroed on drop.
+///
+/// The length is fixed on creation, so the bytes are never reallocated,
+/// which would leave copies behind.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct SecretBuffer(Zeroizing<Vec<u8>>);
+
+#[cfg(feature = "std")]
+impl SecretBuffer {
+    /// A buffer of `len` zeros.
+    pub fn new(len: usize) -> Self {
+        SecretBuffer(Zeroizing::new(std::vec![0; len]))
+    }
+
+    /// Copy `data` into a new buffer.
+    pub fn from_slice(data: &[u8]) -> Self {
+        SecretBuffer(Zeroizing::new(data.to_vec()))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Vec<u8>> for SecretBuffer {
+    fn from(v: Vec<u8>) -> Self {
+        SecretBuffer(Zeroizing::new(v))
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::ops::Deref for SecretBuffer {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::ops::DerefMut for SecretBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Debug for SecretBuffer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SecretBuffer({} bytes)", self.0.len())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_buffer() {
+        let mut b = SecretBuffer::new(4);
+        b.copy_from_slice(b"key!");
+        assert_eq!(&b[..], b"key!");
+        assert_eq!(std::format!("{:?}", b), "SecretBuffer(4 bytes)");
+        assert_eq!(&SecretBuffer::from(b"abc".to_vec())[..], b"abc");
+        assert_eq!(&SecretBuffer::from_slice(b"abc")[..], b"abc");
+    }
+
+    #[test]
+    fn sensitive() {
+        let mut k = Sensitive::<[u8; 32]>::from_slice(&[7; 32]);
+        assert_eq!(k.as_slice(), [7; 32]);
+        k.as_mut()[0] = 0;
+        assert_eq!(k[..2], [0, 7]);
+    }
+}
@@ -0,0 +1,272 @@
+//! The primitives of this crate behind the traits of
+//! [`snow`](https://crates.io/crates/snow), so that code written against
+//! snow can use X25519 with Elligator.
+//!
+//! ```
+//! use noise_rust_crypto::snow_compat::SnowResolver;
+//!
+//! let params = "Noise_NN_25519_ChaChaPoly_BLAKE2s".parse().unwrap();
+//! let resolver = SnowResolver::new(true);
+//! let mut initiator = snow::Builder::with_resolver(params, Box::new(resolver))
+//!     .build_initiator()
+//!     .unwrap();
+//! ```
+//!
+//! snow does not tell a [`Dh`](snow::types::Dh) whether a remote public key
+//! is ephemeral or static, so with Elligator, all public keys are
+//! representatives, as with both [`set_is_elligator_encoded`] and
+//! [`set_is_static_elligator_encoded`] of a [`HandshakeStateBuilder`].
+//! Generate static keys with [`snow::Builder::generate_keypair`] on a
+//! builder with the same resolver. Their private keys are the private key of
+//! the DH function followed by the representative, which can not be computed
+//! from the private key.
+//!
+//! Both peers must use Elligator, or neither. Without Elligator, the
+//! handshakes are the same as with the default resolver of snow.
+//!
+//! [`set_is_elligator_encoded`]: noise_protocol::HandshakeStateBuilder::set_is_elligator_encoded
+//! [`set_is_static_elligator_encoded`]: noise_protocol::HandshakeStateBuilder::set_is_static_elligator_encoded
+//! [`HandshakeStateBuilder`]: noise_protocol::HandshakeStateBuilder
+
+use noise_protocol::{Cipher, Hash, U8Array, DH};
+use snow::params::{CipherChoice, DHChoice, HashChoice};
+use snow::resolvers::CryptoResolver;
+use snow::types::Random;
+use std::boxed::Box;
+use std::vec::Vec;
+use zeroize::Zeroizing;
+
+use crate::entropy::EntropyRng;
+
+impl Random for EntropyRng {}
+
+/// Resolves the primitives of this crate enabled by the `use-*` features,
+/// and [`EntropyRng`].
+///
+/// Combine it with another resolver with
+/// [`FallbackResolver`](snow::resolvers::FallbackResolver) for the
+/// primitives it lacks.
+#[derive(Debug, Clone, Copy)]
+pub struct SnowResolver {
+    elligator: bool,
+}
+
+impl SnowResolver {
+    /// A resolver whose public keys are Elligator representatives if
+    /// `elligator` is set, cf. the [module docs](self).
+    ///
+    /// Only X25519 supports Elligator. With it set, other DH functions are
+    /// not resolved.
+    pub fn new(elligator: bool) -> Self {
+        SnowResolver { elligator }
+    }
+}
+
+impl CryptoResolver for SnowResolver {
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        Some(Box::new(EntropyRng))
+    }
+
+    #[allow(unreachable_patterns)]
+    fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn snow::types::Dh>> {
+        match choice {
+            #[cfg(feature = "x25519")]
+            DHChoice::Curve25519 => Some(Box::new(SnowDh::<crate::X25519>::new(self.elligator))),
+            #[cfg(feature = "use-x448")]
+            DHChoice::Ed448 if !self.elligator => Some(Box::new(SnowDh::<crate::X448>::new(false))),
+            _ => None,
+        }
+    }
+
+    #[allow(unreachable_patterns)]
+    fn resolve_hash(&self, choice: &HashChoice) -> Option<Box<dyn snow::types::Hash>> {
+        match choice {
+            #[cfg(feature = "use-sha2")]
+            HashChoice::SHA256 => Some(Box::new(SnowHash::<crate::Sha256>::default())),
+            #[cfg(feature = "use-sha2")]
+            HashChoice::SHA512 => Some(Box::new(SnowHash::<crate::Sha512>::default())),
+            #[cfg(feature = "use-blake2")]
+            HashChoice::Blake2s => Some(Box::new(SnowHash::<crate::Blake2s>::default())),
+            #[cfg(feature = "use-blake2")]
+            HashChoice::Blake2b => Some(Box::new(SnowHash::<crate::Blake2b>::default())),
+            _ => None,
+        }
+    }
+
+    #[allow(unreachable_patterns)]
+    fn resolve_cipher(&self, choice: &CipherChoice) -> Option<Box<dyn snow::types::Cipher>> {
+        match choice {
+            #[cfg(feature = "use-chacha20poly1305")]
+            CipherChoice::ChaChaPoly => {
+                Some(Box::new(SnowCipher::<crate::ChaCha20Poly1305>::default()))
+            }
+            #[cfg(feature = "use-aes-256-gcm")]
+            CipherChoice::AESGCM => Some(Box::new(SnowCipher::<crate::Aes256Gcm>::default())),
+            _ => None,
+        }
+    }
+}
+
+/// A [`DH`] as a snow [`Dh`](snow::types::Dh).
+pub struct SnowDh<D: DH> {
+    elligator: bool,
+    // The private key, followed by the representative with Elligator.
+    private: Zeroizing<Vec<u8>>,
+    public: D::Pubkey,
+}
+
+impl<D: DH> SnowDh<D> {
+    /// A DH without a key, whose public keys are Elligator representatives
+    /// if `elligator` is set.
+    pub fn new(elligator: bool) -> Self {
+        SnowDh {
+            elligator,
+            private: Zeroizing::new(Vec::new()),
+            public: D::Pubkey::new(),
+        }
+    }
+}
+
+impl<D> snow::types::Dh for SnowDh<D>
+where
+    D: DH,
+    D::Pubkey: Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        D::name()
+    }
+
+    fn pub_len(&self) -> usize {
+        D::Pubkey::len()
+    }
+
+    fn priv_len(&self) -> usize {
+        if self.elligator {
+            D::Key::len() + D::Pubkey::len()
+        } else {
+            D::Key::len()
+        }
+    }
+
+    fn set(&mut self, privkey: &[u8]) {
+        let privkey = &privkey[..self.priv_len()];
+        self.public = if self.elligator {
+            D::Pubkey::from_slice(&privkey[D::Key::len()..])
+        } else {
+            D::pubkey(&D::Key::from_slice(privkey))
+        };
+        self.private = Zeroizing::new(privkey.to_vec());
+    }
+
+    fn generate(&mut self, rng: &mut dyn Random) {
+        let keypair = D::genkey_with_rng(&mut &mut *rng, self.elligator);
+        let mut private = Zeroizing::new(keypair.private.as_slice().to_vec());
+        if self.elligator {
+            private.extend_from_slice(keypair.public.as_slice());
+        }
+        self.private = private;
+        self.public = keypair.public;
+    }
+
+    fn pubkey(&self) -> &[u8] {
+        self.public.as_slice()
+    }
+
+    fn privkey(&self) -> &[u8] {
+        &self.private
+    }
+
+    fn dh(&self, pubkey: &[u8], out: &mut [u8]) -> Result<(), snow::Error> {
+        let key = D::Key::from_slice(&self.private[..D::Key::len()]);
+        let pubkey = D::Pubkey::from_slice(&pubkey[..D::Pubkey::len()]);
+        let output = D::dh(&key, &pubkey, self.elligator).map_err(|_| snow::Error::Dh)?;
+        out[..D::Output::len()].copy_from_slice(output.as_slice());
+        Ok(())
+    }
+}
+
+/// A [`Cipher`] as a snow [`Cipher`](snow::types::Cipher).
+pub struct SnowCipher<C: Cipher> {
+    key: C::Key,
+}
+
+impl<C: Cipher> Default for SnowCipher<C> {
+    fn default() -> Self {
+        SnowCipher { key: C::Key::new() }
+    }
+}
+
+impl<C> snow::types::Cipher for SnowCipher<C>
+where
+    C: Cipher,
+    C::Key: Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        C::name()
+    }
+
+    fn set(&mut self, key: &[u8]) {
+        self.key = C::Key::from_slice(&key[..C::key_len()]);
+    }
+
+    fn encrypt(&self, nonce: u64, authtext: &[u8], plaintext: &[u8], out: &mut [u8]) -> usize {
+        let len = plaintext.len() + C::tag_len();
+        C::encrypt(&self.key, nonce, authtext, plaintext, &mut out[..len]);
+        len
+    }
+
+    fn decrypt(
+        &self,
+        nonce: u64,
+        authtext: &[u8],
+        ciphertext: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, snow::Error> {
+        let len = ciphertext
+            .len()
+            .checked_sub(C::tag_len())
+            .ok_or(snow::Error::Decrypt)?;
+        C::decrypt(&self.key, nonce, authtext, ciphertext, &mut out[..len])
+            .map_err(|_| snow::Error::Decrypt)?;
+        Ok(len)
+    }
+
+    fn rekey(&mut self) {
+        self.key = C::rekey(&self.key);
+    }
+}
+
+/// A [`Hash`] as a snow [`Hash`](snow::types::Hash).
+#[derive(Default)]
+pub struct SnowHash<H: Hash> {
+    hash: H,
+}
+
+impl<H> snow::types::Hash for SnowHash<H>
+where
+    H: Hash + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        H::name()
+    }
+
+    fn block_len(&self) -> usize {
+        H::block_len()
+    }
+
+    fn hash_len(&self) -> usize {
+        H::hash_len()
+    }
+
+    fn reset(&mut self) {
+        self.hash.reset();
+    }
+
+    fn input(&mut self, data: &[u8]) {
+        self.hash.input(data);
+    }
+
+    fn result(&mut self, out: &mut [u8]) {
+        out[..H::hash_len()].copy_from_slice(self.hash.result().as_slice());
+    }
+}
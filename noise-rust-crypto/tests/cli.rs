@@ -0,0 +1,110 @@
+// Smoke tests of the `noise` command line tool, with the `cli` feature, e.g.,
+// `cargo test --features cli --test cli`.
+
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+fn noise(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_noise"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("noise-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn genkey_pubkey_fingerprint() {
+    let dir = temp_dir("keys");
+    let key = dir.join("key");
+    let key = key.to_str().unwrap();
+    let public = format!("{}.pub", key);
+
+    assert!(stdout(noise(&["genkey", key])).is_empty());
+    let public_text = fs::read_to_string(&public).unwrap();
+    assert_eq!(stdout(noise(&["pubkey", key])), public_text);
+
+    // The fingerprints of the private key are those of its public key.
+    let fingerprint = stdout(noise(&["fingerprint", &public]));
+    assert_eq!(stdout(noise(&["fingerprint", key])), fingerprint);
+    let lines: Vec<&str> = fingerprint.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].len(), 52);
+
+    // Without a file, both keys are printed.
+    let printed = stdout(noise(&["genkey"]));
+    assert!(printed.len() > public_text.len());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn errors() {
+    let usage = noise(&["frobnicate"]);
+    assert_eq!(usage.status.code(), Some(2));
+    assert!(String::from_utf8(usage.stderr)
+        .unwrap()
+        .starts_with("Usage:"));
+
+    let missing = noise(&["fingerprint", "/nonexistent/key"]);
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(String::from_utf8(missing.stderr)
+        .unwrap()
+        .starts_with("noise: "));
+}
+
+#[test]
+fn connect_listen() {
+    let protocol = "Noise_NN_25519_ChaChaPoly_BLAKE2s";
+    let addr = {
+        let l = TcpListener::bind("127.0.0.1:0").unwrap();
+        l.local_addr().unwrap().to_string()
+    };
+    let mut listen = Command::new(env!("CARGO_BIN_EXE_noise"))
+        .args(["listen", &addr, protocol])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Retry until the listener is up.
+    let connected = (0..50).any(|_| {
+        let mut connect = Command::new(env!("CARGO_BIN_EXE_noise"))
+            .args(["connect", &addr, protocol])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let _ = connect.stdin.take().unwrap().write_all(b"hello");
+        let success = connect.wait().unwrap().success();
+        if !success {
+            sleep(Duration::from_millis(100));
+        }
+        success
+    });
+    if !connected {
+        listen.kill().unwrap();
+    }
+    let output = listen.wait_with_output().unwrap();
+    assert!(connected, "could not connect to the listener");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello");
+}
@@ -0,0 +1,49 @@
+// Smoke tests for `wasm32-unknown-unknown`, with the `wasm` feature, e.g.,
+// `wasm-pack test --headless --firefox -- --features wasm`.
+
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use noise_protocol::patterns::noise_nn;
+use noise_protocol::{HandshakeState, HandshakeStateBuilder, U8Array, DH};
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+type HS = HandshakeState<X25519, ChaCha20Poly1305, Blake2s>;
+
+#[wasm_bindgen_test]
+fn genkey() {
+    let a = X25519::genkey(false);
+    let b = X25519::genkey(false);
+    assert_ne!(a.public, b.public);
+    assert_eq!(X25519::pubkey(&a.private), a.public);
+}
+
+#[wasm_bindgen_test]
+fn elligator_handshake() {
+    let e = X25519::genkey(true);
+    assert_ne!(e.private.as_slice(), [0u8; 32]);
+
+    let new = |initiator: bool| -> HS {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_nn())
+            .set_is_initiator(initiator)
+            .set_is_elligator_encoded(true)
+            .set_prologue(b"wasm");
+        b.build_handshake_state()
+    };
+    let mut i = new(true);
+    let mut r = new(false);
+
+    let mut msg = [0u8; 128];
+    let mut out = [0u8; 128];
+    let len = i.write_message_to(b"hello", &mut msg).unwrap();
+    let n = r.read_message_to(&msg[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"hello");
+    let len = r.write_message_to(b"world", &mut msg).unwrap();
+    let n = i.read_message_to(&msg[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"world");
+    assert!(i.completed() && r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+}
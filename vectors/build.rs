@@ -10,6 +10,7 @@ use std::path::Path;
 fn gen<O: Write>(mut out: O) -> ::std::io::Result<()> {
     let mut dhs = HashMap::new();
     dhs.insert("25519", vec!["crypto::X25519"]);
+    dhs.insert("448", vec!["crypto::X448"]);
 
     let mut ciphers = HashMap::new();
     ciphers.insert(
@@ -0,0 +1,80 @@
+// Ascon-128a and Deoxys-II-256, whose crates are optional.
+
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::generic_aead::aead::generic_array::GenericArray;
+use noise_rust_crypto::generic_aead::aead::{AeadInPlace, KeyInit};
+use noise_rust_crypto::{Ascon128a, Blake2s, DeoxysII256, X25519};
+
+fn nn<C: Cipher>() {
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(&[]);
+    let mut i = ib.build_handshake_state::<C, Blake2s>();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_nn())
+        .set_is_initiator(false)
+        .set_prologue(&[]);
+    let mut r = rb.build_handshake_state::<C, Blake2s>();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"hello").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"hello");
+}
+
+#[test]
+fn ascon128a() {
+    assert_eq!(Ascon128a::name(), "Ascon128a");
+    assert_eq!(Ascon128a::key_len(), 16);
+
+    // Ascon-128a with key 00..0f and nonce 00000000_00000000 || n.
+    let key: Vec<u8> = (0..16).collect();
+    let mut c = CipherState::<Ascon128a>::new(&key, 0);
+    let mut out = [0u8; 21];
+    c.encrypt_ad(b"ad", b"hello", &mut out);
+    assert_eq!(
+        hex::encode(out),
+        "32b6fe9febdd2460bbb685e7e96bbf296cb422a193"
+    );
+    c.encrypt_ad(b"ad", b"hello", &mut out);
+    assert_eq!(
+        hex::encode(out),
+        "00fd32ccc600d1d443fcfa5227d8a7bda541767f3e"
+    );
+
+    let mut c = CipherState::<Ascon128a>::new(&key, 1);
+    let mut plaintext = [0u8; 5];
+    c.decrypt_ad(b"ad", &out, &mut plaintext).unwrap();
+    assert_eq!(&plaintext, b"hello");
+
+    nn::<Ascon128a>();
+}
+
+#[test]
+fn deoxys_ii_256() {
+    assert_eq!(DeoxysII256::name(), "DeoxysII256");
+
+    let key = [7u8; 32];
+    let mut c = CipherState::<DeoxysII256>::new(&key, 0);
+    let first = c.encrypt_vec(b"hello");
+    let mut out = [0u8; 21];
+    c.encrypt_ad(b"ad", b"hello", &mut out);
+
+    let mut nonce = [0u8; 15];
+    nonce[7..].copy_from_slice(&1u64.to_be_bytes());
+    let mut expected = *b"hello";
+    let tag = deoxys::DeoxysII256::new_from_slice(&key)
+        .unwrap()
+        .encrypt_in_place_detached(GenericArray::from_slice(&nonce), b"ad", &mut expected)
+        .unwrap();
+    assert_eq!(out[..5], expected);
+    assert_eq!(out[5..], tag[..]);
+
+    let mut c = CipherState::<DeoxysII256>::new(&key, 0);
+    assert_eq!(c.decrypt_vec(&first).unwrap(), b"hello");
+    out[0] ^= 1;
+    assert!(c.decrypt_ad(b"ad", &out, &mut [0u8; 5]).is_err());
+
+    nn::<DeoxysII256>();
+}
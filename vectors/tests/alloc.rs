@@ -0,0 +1,55 @@
+// Handshake messages written and read in caller buffers do not allocate,
+// checked with a counting global allocator.
+
+mod common;
+
+use common::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// The only test of this file, so that no other thread allocates meanwhile.
+#[test]
+fn caller_buffers_do_not_allocate() {
+    let (mut i, mut r) = xx_pair();
+    let mut message = [0u8; 1024];
+    let mut payload = [0u8; 1024];
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    while !i.completed() {
+        let (send, recv) = if i.is_write_turn() {
+            (&mut i, &mut r)
+        } else {
+            (&mut r, &mut i)
+        };
+        let len = send.write_message_to(b"payload", &mut message).unwrap();
+        let n = recv.read_message_to(&message[..len], &mut payload).unwrap();
+        assert_eq!(&payload[..n], b"payload");
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+    assert!(r.completed());
+
+    // Unless the last sent message is kept.
+    let (mut i, _) = xx_pair();
+    i.set_keep_last_sent(true);
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    i.write_message_to(b"", &mut message).unwrap();
+    assert!(ALLOCATIONS.load(Ordering::SeqCst) > before);
+}
@@ -0,0 +1,133 @@
+// The `arbitrary` impls for structure-aware fuzzing, and handshakes built
+// from them.
+
+use arbitrary::{Arbitrary, Unstructured};
+use noise_protocol::negotiation::HandshakeMessage;
+use noise_protocol::patterns::*;
+use noise_protocol::registry::{HandshakeParams, ProtocolName};
+use noise_rust_crypto::elligator::arbitrary_representative;
+
+/// Deterministic stand-in for fuzzer input.
+fn input(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1d ^ seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn arbitrary_tokens_and_patterns() {
+    let data = input(0, 4096);
+    let mut u = Unstructured::new(&data);
+
+    let mut seen = [false; 10];
+    for _ in 0..256 {
+        let i = match Token::arbitrary(&mut u).unwrap() {
+            Token::E => 0,
+            Token::S => 1,
+            Token::EE => 2,
+            Token::ES => 3,
+            Token::SE => 4,
+            Token::SS => 5,
+            Token::PSK => 6,
+            Token::E1 => 7,
+            Token::EKEM1 => 8,
+            Token::SIG => 9,
+        };
+        seen[i] = true;
+    }
+    assert_eq!(seen, [true; 10]);
+
+    let (mut hybrid, mut signed) = (false, false);
+    for _ in 0..1024 {
+        let name = HandshakePattern::arbitrary(&mut u).unwrap().get_name();
+        hybrid |= name.ends_with("hfs");
+        signed |= name.ends_with("sig");
+    }
+    assert!(hybrid && signed);
+}
+
+#[test]
+fn arbitrary_messages_and_representatives() {
+    let data = input(1, 1 << 16);
+    let mut u = Unstructured::new(&data);
+
+    for _ in 0..16 {
+        let m = HandshakeMessage::arbitrary(&mut u).unwrap();
+        let mut encoded = Vec::new();
+        m.encode(&mut encoded);
+        assert_eq!(HandshakeMessage::decode(&encoded), Some((m, encoded.len())));
+    }
+
+    let data = input(2, 4096);
+    let mut u = Unstructured::new(&data);
+    let representatives: Vec<[u8; 32]> = (0..64)
+        .map(|_| arbitrary_representative(&mut u).unwrap())
+        .collect();
+    assert!(representatives.contains(&[0; 32]));
+    assert!(representatives.iter().any(|r| r[..4] != r[4..8]));
+}
+
+#[test]
+fn handshakes_from_unstructured() {
+    let registry = noise_rust_crypto::registry::registry();
+    let mut completed = 0;
+    for seed in 0..64 {
+        let data = input(seed, 8192);
+        let mut u = Unstructured::new(&data);
+        let name = ProtocolName::arbitrary(&mut u).unwrap();
+        let protocol_name = name.to_string();
+        assert_eq!(ProtocolName::parse(&protocol_name), Ok(name));
+        // Only the predefined patterns without pre-message ephemeral keys
+        // can be created by name.
+        if by_name(name.pattern).is_none()
+            || name.pattern.contains("fallback")
+            || !registry.contains(name.dh, name.cipher, name.hash)
+        {
+            continue;
+        }
+
+        let is_elligator_encoded = name.dh == "25519" && bool::arbitrary(&mut u).unwrap();
+        let prologue = <&[u8]>::arbitrary(&mut u).unwrap();
+        let psks: Vec<[u8; 32]> = (0..name.pattern.matches("psk").count())
+            .map(|_| u.arbitrary().unwrap())
+            .collect();
+        let (i_private, i_public) = registry.generate_keypair(name.dh, false).unwrap();
+        let (r_private, r_public) = registry.generate_keypair(name.dh, false).unwrap();
+        let params = |is_initiator| HandshakeParams {
+            is_initiator,
+            is_elligator_encoded,
+            prologue,
+            s: Some(if is_initiator { &i_private } else { &r_private }),
+            rs: Some(if is_initiator { &r_public } else { &i_public }),
+            psks: &psks,
+        };
+        let mut i = registry
+            .new_handshake(&protocol_name, &params(true))
+            .unwrap();
+        let mut r = registry
+            .new_handshake(&protocol_name, &params(false))
+            .unwrap();
+
+        while !i.completed() {
+            let (send, recv) = if i.is_write_turn() {
+                (&mut i, &mut r)
+            } else {
+                (&mut r, &mut i)
+            };
+            let payload = <&[u8]>::arbitrary(&mut u).unwrap();
+            let payload = &payload[..payload.len().min(1024)];
+            let m = send.write_message_vec(payload).unwrap();
+            assert_eq!(recv.read_message_vec(&m).unwrap(), payload);
+        }
+        assert!(r.completed());
+        assert_eq!(i.get_hash(), r.get_hash());
+        completed += 1;
+    }
+    assert!(completed > 0);
+}
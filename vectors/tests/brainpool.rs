@@ -0,0 +1,101 @@
+// The Brainpool curves P256r1 and P384r1.
+
+use noise_protocol::registry::HandshakeParams;
+use noise_protocol::*;
+use noise_rust_crypto::{BrainpoolP256r1, BrainpoolP384r1};
+
+fn check<D: DH>(a: &str, a_public: &str, b: &str, b_public: &str, shared: &str) {
+    let a = D::Key::from_slice(&hex::decode(a).unwrap());
+    let b = D::Key::from_slice(&hex::decode(b).unwrap());
+    assert_eq!(hex::encode(D::pubkey(&a).as_slice()), a_public);
+    assert_eq!(hex::encode(D::pubkey(&b).as_slice()), b_public);
+    let b_public = D::pubkey(&b);
+    let a_public = D::pubkey(&a);
+    assert_eq!(
+        hex::encode(D::dh(&a, &b_public, false).unwrap().as_slice()),
+        shared
+    );
+    assert_eq!(
+        hex::encode(D::dh(&b, &a_public, false).unwrap().as_slice()),
+        shared
+    );
+
+    // Private keys must be in [1, n), public keys on the curve.
+    assert!(D::dh(&D::Key::new(), &b_public, false).is_err());
+    assert!(D::dh(&D::Key::new_with(0xff), &b_public, false).is_err());
+    // 0 is not the x-coordinate of a point on either curve.
+    assert!(D::dh(&a, &D::Pubkey::new(), false).is_err());
+    assert!(D::dh(&a, &D::Pubkey::new_with(0xff), false).is_err());
+    assert!(D::dh(&a, &b_public, true).is_err());
+
+    let k = D::genkey(false);
+    let other = D::genkey(false);
+    assert_eq!(
+        D::dh(&k.private, &other.public, false).unwrap().as_slice(),
+        D::dh(&other.private, &k.public, false).unwrap().as_slice()
+    );
+}
+
+#[test]
+fn brainpool_p256r1() {
+    assert_eq!(BrainpoolP256r1::name(), "BrainpoolP256r1");
+    check::<BrainpoolP256r1>(
+        "0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20",
+        "4e366cf3c8a982e423831d6715e722acf03cab8452e3c64d1e3b038caf87fc48",
+        "404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f",
+        "a1c8c261c3e79cef4f005756c25daa882f686dd1cc1ccd71d806dcacb73c9e82",
+        "6eebb3299846160d496554a0448939789f72c8dbe19603b9bdb467fee5929d43",
+    );
+}
+
+#[test]
+fn brainpool_p384r1() {
+    assert_eq!(BrainpoolP384r1::name(), "BrainpoolP384r1");
+    check::<BrainpoolP384r1>(
+        "0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20\
+         2122232425262728292a2b2c2d2e2f30",
+        "78083585d7bc642b9899c0547604dac754db56d69ba822c40d79ba4a33e61d64\
+         86869b012305179fd4b2cac97e66cffa",
+        "404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f\
+         606162636465666768696a6b6c6d6e6f",
+        "206fc7a0569eee44d85f58e12c42ad9ee0fa82832e2edf0fb13023f032718a43\
+         f98c5fcefcfe75756e128b1e1baefc18",
+        "49397e1b69e5f701983ef0cf2be36f8c8c51d0edf0cd2b61e7ef8dd579dc3e99\
+         173175d16f15aad237bdf4e31357ced9",
+    );
+}
+
+#[test]
+fn brainpool_handshake() {
+    let registry = noise_rust_crypto::registry::registry();
+    for (name, dh) in [
+        ("Noise_XX_BrainpoolP256r1_AESGCM_SHA256", "BrainpoolP256r1"),
+        ("Noise_XX_BrainpoolP384r1_AESGCM_SHA512", "BrainpoolP384r1"),
+    ] {
+        let (i_private, _) = registry.generate_keypair(dh, false).unwrap();
+        let (r_private, _) = registry.generate_keypair(dh, false).unwrap();
+        let params = |is_initiator, s| HandshakeParams {
+            is_initiator,
+            is_elligator_encoded: false,
+            prologue: b"brainpool",
+            s: Some(s),
+            rs: None,
+            psks: &[],
+        };
+        let mut i = registry
+            .new_handshake(name, &params(true, &i_private))
+            .unwrap();
+        let mut r = registry
+            .new_handshake(name, &params(false, &r_private))
+            .unwrap();
+
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).unwrap();
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).unwrap();
+        let m = i.write_message_vec(b"hello").unwrap();
+        assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+        assert!(i.completed() && r.completed());
+        assert_eq!(i.get_hash(), r.get_hash());
+    }
+}
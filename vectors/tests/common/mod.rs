@@ -0,0 +1,67 @@
+// Handshake pairs shared by the tests.
+
+#![allow(dead_code)]
+
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+pub type HS = HandshakeState<X25519, ChaCha20Poly1305, Blake2s>;
+
+/// Initiator and responder builders of `pattern`, to be completed with keys.
+pub fn builders<'a>(
+    pattern: HandshakePattern,
+    prologue: &'a [u8],
+) -> (
+    HandshakeStateBuilder<'a, X25519>,
+    HandshakeStateBuilder<'a, X25519>,
+) {
+    let mut ib = HandshakeStateBuilder::new();
+    ib.set_pattern(pattern)
+        .set_is_initiator(true)
+        .set_prologue(prologue);
+    let mut rb = HandshakeStateBuilder::new();
+    rb.set_pattern(pattern)
+        .set_is_initiator(false)
+        .set_prologue(prologue);
+    (ib, rb)
+}
+
+/// An initiator and a responder of `pattern`, both with a static key.
+pub fn pair(pattern: HandshakePattern) -> (HS, HS) {
+    let (mut ib, mut rb) = builders(pattern, b"test");
+    ib.set_s(X25519::genkey(false));
+    rb.set_s(X25519::genkey(false));
+    (ib.build_handshake_state(), rb.build_handshake_state())
+}
+
+pub fn xx_pair() -> (HS, HS) {
+    pair(noise_xx())
+}
+
+/// An initiator and a responder of a one-way pattern, the initiator knowing
+/// the static key of the responder.
+pub fn file_pair(pattern: HandshakePattern) -> (HS, HS) {
+    let rs = X25519::genkey(false);
+    let (mut ib, mut rb) = builders(pattern, b"file");
+    ib.set_rs(rs.public);
+    rb.set_s(rs);
+    (ib.build_handshake_state(), rb.build_handshake_state())
+}
+
+/// Run a handshake to completion, each message carrying a payload.
+pub fn run_handshake<D: DH, C: Cipher, H: Hash, K: Kem, S: Signature>(
+    i: &mut HandshakeState<D, C, H, K, S>,
+    r: &mut HandshakeState<D, C, H, K, S>,
+) {
+    while !i.completed() {
+        let (send, recv) = if i.is_write_turn() {
+            (&mut *i, &mut *r)
+        } else {
+            (&mut *r, &mut *i)
+        };
+        let m = send.write_message_vec(b"payload").unwrap();
+        assert_eq!(recv.read_message_vec(&m).unwrap(), b"payload");
+    }
+    assert!(r.completed());
+}
@@ -0,0 +1,31 @@
+use noise_protocol::patterns::noise_nn;
+use noise_protocol::{HandshakeState, U8Array, DH};
+use noise_rust_crypto::entropy::{set_entropy_source, EntropySource};
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+// Not random, to check that it is used.
+fn fill(dest: &mut [u8]) {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    dest.fill(0x42);
+}
+
+static SOURCE: &dyn EntropySource = &fill;
+
+#[test]
+fn custom_entropy_source() {
+    set_entropy_source(&SOURCE);
+
+    let k = X25519::genkey(false);
+    assert_eq!(k.private.as_slice(), &[0x42; 32]);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+
+    // Ephemeral keys too.
+    let mut i: HandshakeState<X25519, ChaCha20Poly1305, Blake2s> =
+        HandshakeState::new(noise_nn(), true, false, b"", None, None, None, None);
+    let m = i.write_message_vec(b"").unwrap();
+    assert_eq!(m, k.public);
+    assert_eq!(CALLS.load(Ordering::Relaxed), 2);
+}
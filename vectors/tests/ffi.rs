@@ -0,0 +1,206 @@
+use noise_ffi::*;
+use std::ffi::CString;
+use std::ptr;
+
+unsafe fn handshake_new(
+    protocol_name: &str,
+    params: &NoiseHandshakeParams,
+    state: &mut *mut NoiseState,
+) -> i32 {
+    let protocol_name = CString::new(protocol_name).unwrap();
+    noise_handshake_new(protocol_name.as_ptr(), params, state)
+}
+
+unsafe fn new(protocol_name: &str, params: &NoiseHandshakeParams) -> *mut NoiseState {
+    let mut state = ptr::null_mut();
+    assert_eq!(handshake_new(protocol_name, params, &mut state), NOISE_OK);
+    state
+}
+
+fn params(is_initiator: bool) -> NoiseHandshakeParams {
+    NoiseHandshakeParams {
+        is_initiator,
+        is_elligator_encoded: false,
+        prologue: b"prologue".as_ptr(),
+        prologue_len: 8,
+        s: ptr::null(),
+        s_len: 0,
+        rs: ptr::null(),
+        rs_len: 0,
+        psks: ptr::null(),
+        psks_len: 0,
+    }
+}
+
+// Pass a message from `from` to `to`, with `write` and `read` being
+// write/read or encrypt/decrypt.
+type Op = unsafe extern "C" fn(*mut NoiseState, *const u8, usize, *mut u8, *mut usize) -> i32;
+
+unsafe fn transfer(
+    write: Op,
+    read: Op,
+    from: *mut NoiseState,
+    to: *mut NoiseState,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut buf = [0u8; 1024];
+    let mut len = buf.len();
+    assert_eq!(
+        write(
+            from,
+            payload.as_ptr(),
+            payload.len(),
+            buf.as_mut_ptr(),
+            &mut len
+        ),
+        NOISE_OK
+    );
+    let mut out = [0u8; 1024];
+    let mut out_len = out.len();
+    assert_eq!(
+        read(to, buf.as_ptr(), len, out.as_mut_ptr(), &mut out_len),
+        NOISE_OK
+    );
+    out[..out_len].to_vec()
+}
+
+#[test]
+fn ffi_xx() {
+    unsafe {
+        let name = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+        let dh = CString::new("25519").unwrap();
+        let mut keys = [[0u8; 32]; 4];
+        for k in keys.chunks_mut(2) {
+            let (mut private_len, mut public_len) = (32, 32);
+            assert_eq!(
+                noise_generate_keypair(
+                    dh.as_ptr(),
+                    false,
+                    k[0].as_mut_ptr(),
+                    &mut private_len,
+                    k[1].as_mut_ptr(),
+                    &mut public_len,
+                ),
+                NOISE_OK
+            );
+        }
+
+        let mut i_params = params(true);
+        i_params.s = keys[0].as_ptr();
+        i_params.s_len = 32;
+        let mut r_params = params(false);
+        r_params.s = keys[2].as_ptr();
+        r_params.s_len = 32;
+        let i = new(name, &i_params);
+        let r = new(name, &r_params);
+
+        // Not our turn.
+        let mut buf = [0u8; 128];
+        let mut len = buf.len();
+        assert_eq!(
+            noise_write_message(r, ptr::null(), 0, buf.as_mut_ptr(), &mut len),
+            NOISE_ERROR_INVALID_STATE
+        );
+        // Too small, the state is unchanged.
+        let mut len = 4;
+        assert_eq!(
+            noise_write_message(i, ptr::null(), 0, buf.as_mut_ptr(), &mut len),
+            NOISE_ERROR_BUFFER_TOO_SMALL
+        );
+        assert_eq!(len, 32);
+
+        assert_eq!(
+            transfer(noise_write_message, noise_read_message, i, r, b"a"),
+            b"a"
+        );
+        assert_eq!(
+            transfer(noise_write_message, noise_read_message, r, i, b"b"),
+            b"b"
+        );
+        assert_eq!(noise_split(i), NOISE_ERROR_INVALID_STATE);
+        assert_eq!(
+            transfer(noise_write_message, noise_read_message, i, r, b"c"),
+            b"c"
+        );
+        assert!(noise_handshake_completed(i));
+        assert!(noise_handshake_completed(r));
+
+        let mut rs = [0u8; 32];
+        let mut rs_len = rs.len();
+        assert_eq!(
+            noise_handshake_remote_static(r, rs.as_mut_ptr(), &mut rs_len),
+            NOISE_OK
+        );
+        assert_eq!(rs, keys[1]);
+        let mut h = [[0u8; 32]; 2];
+        for (s, h) in [i, r].into_iter().zip(&mut h) {
+            let mut len = 32;
+            assert_eq!(noise_handshake_hash(s, h.as_mut_ptr(), &mut len), NOISE_OK);
+        }
+        assert_eq!(h[0], h[1]);
+
+        assert_eq!(noise_split(i), NOISE_OK);
+        assert_eq!(noise_split(r), NOISE_OK);
+        assert_eq!(
+            transfer(noise_session_encrypt, noise_session_decrypt, i, r, b"hello"),
+            b"hello"
+        );
+        assert_eq!(
+            transfer(noise_session_encrypt, noise_session_decrypt, r, i, b"world"),
+            b"world"
+        );
+
+        // Tampered.
+        let mut c = [0u8; 32];
+        let mut c_len = c.len();
+        assert_eq!(
+            noise_session_encrypt(i, b"x".as_ptr(), 1, c.as_mut_ptr(), &mut c_len),
+            NOISE_OK
+        );
+        c[0] ^= 1;
+        let mut p_len = c.len();
+        assert_eq!(
+            noise_session_decrypt(r, c.as_ptr(), c_len, c.as_mut_ptr(), &mut p_len),
+            NOISE_ERROR_DECRYPTION
+        );
+
+        noise_free(i);
+        noise_free(r);
+        noise_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn ffi_errors() {
+    unsafe {
+        let mut state = ptr::null_mut();
+        for (name, code) in [
+            (
+                "Noise_XX_448_ChaChaPoly_BLAKE2s",
+                NOISE_ERROR_UNKNOWN_PROTOCOL,
+            ),
+            (
+                "Noise_ZZ_25519_ChaChaPoly_BLAKE2s",
+                NOISE_ERROR_UNKNOWN_PROTOCOL,
+            ),
+            ("Noise_XX", NOISE_ERROR_UNKNOWN_PROTOCOL),
+            ("Noise_NK_25519_ChaChaPoly_BLAKE2s", NOISE_ERROR_INVALID_KEY),
+        ] {
+            assert_eq!(handshake_new(name, &params(true), &mut state), code);
+        }
+        assert_eq!(
+            noise_handshake_new(ptr::null(), &params(true), &mut state),
+            NOISE_ERROR_INVALID_ARGUMENT
+        );
+        assert!(state.is_null());
+
+        let short_psk = [0u8; 16];
+        let mut p = params(true);
+        p.psks = short_psk.as_ptr();
+        p.psks_len = short_psk.len();
+        assert_eq!(
+            handshake_new("Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s", &p, &mut state),
+            NOISE_ERROR_INVALID_ARGUMENT
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use noise_protocol::fingerprint::*;
+use noise_protocol::DH;
+use noise_rust_crypto::X25519;
+
+#[test]
+fn zbase32() {
+    assert_eq!(ZBase32(&[0xf0, 0xbf, 0xc7]).to_string(), "6n9hq");
+    assert_eq!(ZBase32(&[0xd4, 0x7a, 0x04]).to_string(), "4t7ye");
+
+    let k = X25519::genkey(false).public;
+    let s = ZBase32(&k).to_string();
+    assert_eq!(s.len(), 52);
+    assert_eq!(parse_zbase32::<[u8; 32]>(&s), Some(k));
+    assert_eq!(parse_zbase32::<[u8; 32]>(&s.to_uppercase()), Some(k));
+    assert_eq!(parse_zbase32::<[u8; 32]>(&s[1..]), None);
+    assert_eq!(parse_zbase32::<[u8; 32]>(&s.replace(&s[..1], "l")), None);
+}
+
+#[test]
+fn short_fingerprint() {
+    let k = X25519::genkey(false).public;
+    let f = ShortFingerprint::of(&k);
+    let s = f.to_string();
+    assert_eq!(s.len(), 8 * 4 + 7 + 5);
+    assert_eq!(s.parse::<ShortFingerprint>(), Ok(f));
+    assert_eq!(s.to_uppercase().parse::<ShortFingerprint>(), Ok(f));
+    assert!(s.parse::<ShortFingerprint>().unwrap().matches(&k));
+    assert!(!f.matches(&X25519::genkey(false).public));
+
+    // A typo in the key part is caught by the checksum.
+    let typo = if s.starts_with('0') { "1" } else { "0" }.to_string() + &s[1..];
+    assert_eq!(
+        typo.parse::<ShortFingerprint>(),
+        Err(ParseFingerprintError::Checksum)
+    );
+    assert_eq!(
+        s[5..].parse::<ShortFingerprint>(),
+        Err(ParseFingerprintError::Malformed)
+    );
+}
@@ -0,0 +1,103 @@
+// Kuznyechik-MGM and Streebog, whose crates are optional.
+
+use noise_protocol::registry::HandshakeParams;
+use noise_protocol::*;
+use noise_rust_crypto::{KuznyechikMgm, Streebog256, Streebog512};
+
+#[test]
+fn streebog() {
+    // GOST R 34.11-2012, example M1, and the empty message.
+    let m1 = b"012345678901234567890123456789012345678901234567890123456789012";
+    assert_eq!(Streebog256::name(), "Streebog256");
+    assert_eq!(
+        hex::encode(Streebog256::hash(m1).as_slice()),
+        "9d151eefd8590b89daa6ba6cb74af9275dd051026bb149a452fd84e5e57b5500"
+    );
+    assert_eq!(
+        hex::encode(Streebog256::hash(b"").as_slice()),
+        "3f539a213e97c802cc229d474c6aa32a825a360b2a933a949fd925208d9ce1bb"
+    );
+    assert_eq!(Streebog512::name(), "Streebog512");
+    assert_eq!(
+        hex::encode(Streebog512::hash(m1).as_slice()),
+        "1b54d01a4af5b9d5cc3d86d68d285462b19abc2475222f35c085122be4ba1ffa\
+         00ad30f8767b3a82384c6574f024c311e2a481332b08ef7f41797891c1646f48"
+    );
+    assert_eq!(
+        hex::encode(Streebog512::hash(b"").as_slice()),
+        "8e945da209aa869f0455928529bcae4679e9873ab707b55315f56ceb98bef0a7\
+         362f715528356ee83cda5f2aac4c6ad2ba3a715c1bcd81cb8e9f90bf4c1c1a8a"
+    );
+}
+
+#[test]
+fn kuznyechik_mgm() {
+    assert_eq!(KuznyechikMgm::name(), "KuznyechikMGM");
+
+    // Kuznyechik-MGM with key 00..1f and nonce 00000000_00000000 || n.
+    let key: Vec<u8> = (0..32).collect();
+    let mut c = CipherState::<KuznyechikMgm>::new(&key, 0);
+    let mut out = [0u8; 21];
+    c.encrypt_ad(b"ad", b"hello", &mut out);
+    assert_eq!(
+        hex::encode(out),
+        "73b006b00c3f98e912ee769bb7e8196a499b8afc1e"
+    );
+    c.encrypt_ad(b"ad", b"hello", &mut out);
+    assert_eq!(
+        hex::encode(out),
+        "5a8c730c56f761c54f61d003773642cba46ac7f057"
+    );
+
+    let mut c = CipherState::<KuznyechikMgm>::new(&key, 1);
+    let mut plaintext = [0u8; 5];
+    c.decrypt_ad(b"ad", &out, &mut plaintext).unwrap();
+    assert_eq!(&plaintext, b"hello");
+    out[0] ^= 1;
+    assert!(c.decrypt_ad(b"ad", &out, &mut plaintext).is_err());
+
+    // Rekey encrypts 32 zero bytes with nonce 2 ^ 64 - 1.
+    let rekeyed =
+        hex::decode("93f56c4fa0611c6b04e16d65b414879f01925ed272f9d297d34d82faab1c4907").unwrap();
+    let mut c = CipherState::<KuznyechikMgm>::new(&key, 0);
+    c.rekey();
+    assert_eq!(
+        c.encrypt_vec(b"hello"),
+        CipherState::<KuznyechikMgm>::new(&rekeyed, 0).encrypt_vec(b"hello")
+    );
+}
+
+#[test]
+fn gost_handshake() {
+    let registry = noise_rust_crypto::registry::registry();
+    for name in [
+        "Noise_XX_25519_KuznyechikMGM_Streebog256",
+        "Noise_XX_25519_KuznyechikMGM_Streebog512",
+    ] {
+        let (i_private, _) = registry.generate_keypair("25519", false).unwrap();
+        let (r_private, _) = registry.generate_keypair("25519", false).unwrap();
+        let params = |is_initiator, s| HandshakeParams {
+            is_initiator,
+            is_elligator_encoded: false,
+            prologue: b"gost",
+            s: Some(s),
+            rs: None,
+            psks: &[],
+        };
+        let mut i = registry
+            .new_handshake(name, &params(true, &i_private))
+            .unwrap();
+        let mut r = registry
+            .new_handshake(name, &params(false, &r_private))
+            .unwrap();
+
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).unwrap();
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).unwrap();
+        let m = i.write_message_vec(b"hello").unwrap();
+        assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+        assert!(i.completed() && r.completed());
+        assert_eq!(i.get_hash(), r.get_hash());
+    }
+}
@@ -0,0 +1,836 @@
+// Handshake behavior beyond the test vectors: errors, retransmission,
+// state export, key checks and 0-RTT.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+#[test]
+fn ephemeral_reuse_detected() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let reused = Arc::new(AtomicUsize::new(0));
+    let counter = reused.clone();
+    let detector = Arc::new(EphemeralReuseDetector::new(
+        16,
+        move |r: &EphemeralReuse| {
+            assert_eq!(r.origin, EphemeralOrigin::Remote);
+            counter.fetch_add(1, Ordering::SeqCst);
+        },
+    ));
+
+    let e = X25519::genkey(false);
+    for fixed_e in [true, true, false] {
+        let (mut i, mut r) = xx_pair();
+        if fixed_e {
+            let mut b = HandshakeStateBuilder::<X25519>::new();
+            b.set_pattern(noise_xx())
+                .set_is_initiator(true)
+                .set_prologue(b"test")
+                .set_s(X25519::genkey(false))
+                .set_e(e.clone());
+            i = b.build_handshake_state();
+        }
+        r.set_ephemeral_observer(detector.clone());
+        run_handshake(&mut i, &mut r);
+    }
+
+    assert_eq!(reused.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn resend_last_message() {
+    let (mut i, _) = xx_pair();
+    i.write_message_vec(b"").unwrap();
+    // Not kept by default.
+    assert_eq!(i.last_sent_message(), None);
+
+    let (mut i, mut r) = xx_pair();
+    i.set_keep_last_sent(true);
+    assert_eq!(i.last_sent_message(), None);
+
+    let m = i.write_message_vec(b"").unwrap();
+    assert_eq!(i.last_sent_message(), Some(&m[..]));
+    assert_eq!(i.resend().unwrap(), m);
+
+    // The first copy is lost, the retransmission gets through.
+    r.read_message_vec(&i.resend().unwrap()).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    assert!(i.completed() && r.completed());
+}
+
+#[test]
+fn half_rtt_payload_protection() {
+    let (mut i, mut r) = xx_pair();
+    assert!(!i.get_next_payload_protection().encrypted);
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+
+    // The responder's first message is ready for application data.
+    let p = r.get_next_payload_protection();
+    assert!(p.encrypted && p.forward_secret && p.sender_authenticated);
+    assert!(!p.recipient_authenticated);
+    let m = r.write_message_vec(b"response").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"response");
+
+    let p = noise_ik().payload_protection(0);
+    assert!(p.encrypted && p.sender_authenticated && p.recipient_authenticated);
+    assert!(!p.forward_secret);
+}
+
+#[test]
+fn replayed_first_message_rejected() {
+    use std::sync::Arc;
+
+    let rs = X25519::genkey(false);
+    let cache = Arc::new(MemoryReplayCache::new(16));
+    let responder = || {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(false)
+            .set_prologue(&[])
+            .set_s(rs.clone());
+        let mut r: HS = b.build_handshake_state();
+        r.set_replay_cache(cache.clone());
+        r
+    };
+
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_ik())
+        .set_is_initiator(true)
+        .set_prologue(&[])
+        .set_s(X25519::genkey(false))
+        .set_rs(rs.public);
+    let mut i: HS = b.build_handshake_state();
+    let m = i.write_message_vec(b"early data").unwrap();
+
+    assert_eq!(responder().read_message_vec(&m).unwrap(), b"early data");
+    let err = responder().read_message_vec(&m).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Replay);
+}
+
+#[test]
+fn structured_errors() {
+    type CS = CipherState<ChaCha20Poly1305>;
+
+    let mut a = CS::from_key(&[7; 32]).unwrap();
+    let mut b = CS::from_key(&[7; 32]).unwrap();
+    let mut c = a.encrypt_vec(b"hello");
+    let last = c.len() - 1;
+    c[last] ^= 1;
+    let e = b.decrypt_vec(&c).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Decryption);
+    assert_eq!(e.to_string(), "Decryption failed");
+    assert_eq!(
+        b.decrypt_vec(&[0; 15]).unwrap_err().kind(),
+        ErrorKind::TooShort
+    );
+
+    // A replayed nonce is rejected before decryption.
+    let c = a.encrypt_vec(b"once");
+    let mut filter: ReplayFilter = ReplayFilter::new();
+    let mut out = [0u8; 4];
+    filter.decrypt(&b, 1, &[], &c, &mut out).unwrap();
+    let e = filter.decrypt(&b, 1, &[], &c, &mut out).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Replay);
+
+    // Errors of the primitives are propagated by the handshake.
+    let (mut i, mut r) = xx_pair();
+    let mut m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    m = r.write_message_vec(b"").unwrap();
+    m[40] ^= 1;
+    let e = i.read_message_vec(&m).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Decryption);
+}
+
+#[test]
+fn wrong_turn_is_an_error() {
+    let (mut i, mut r) = xx_pair();
+    assert!(i.is_my_turn());
+    assert!(!r.is_my_turn());
+
+    let mut buf = [0u8; 128];
+    let e = r.write_message_to(b"", &mut buf).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WrongTurn);
+    assert_eq!(
+        r.write_message_vec(b"").unwrap_err().kind(),
+        ErrorKind::WrongTurn
+    );
+    assert_eq!(
+        i.read_message_vec(&[0; 32]).unwrap_err().kind(),
+        ErrorKind::WrongTurn
+    );
+
+    run_handshake(&mut i, &mut r);
+    assert!(!i.is_my_turn() && !r.is_my_turn());
+    for hs in [&mut i, &mut r] {
+        let e = hs.write_message(b"", &mut []).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::HandshakeComplete);
+        let e = hs.read_message_to(&[0; 32], &mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::HandshakeComplete);
+    }
+}
+
+#[test]
+fn low_order_dh_output_rejected() {
+    let responder = |allow| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_nn())
+            .set_is_initiator(false)
+            .set_prologue(b"")
+            .set_allow_low_order(allow);
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2s>()
+    };
+
+    // The identity as the initiator ephemeral key.
+    let m = [0u8; 32];
+    let mut r = responder(false);
+    r.read_message_vec(&m).unwrap();
+    let e = r.write_message_vec(b"").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidPublicKey);
+
+    let mut r = responder(true);
+    r.read_message_vec(&m).unwrap();
+    r.write_message_vec(b"").unwrap();
+    assert!(r.completed());
+}
+
+#[test]
+fn builder_psk() {
+    let build = |initiator: bool, psk: &[u8]| -> HS {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_xx_psk3())
+            .set_is_initiator(initiator)
+            .set_prologue(b"")
+            .set_s(X25519::genkey(false))
+            .push_psk(psk);
+        b.build_handshake_state()
+    };
+
+    let (mut i, mut r) = (build(true, &[1; 32]), build(false, &[1; 32]));
+    run_handshake(&mut i, &mut r);
+
+    let (mut i, mut r) = (build(true, &[1; 32]), build(false, &[2; 32]));
+    let m = i.write_message_vec(&[]).unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(&[]).unwrap();
+    i.read_message_vec(&m).unwrap();
+    let m = i.write_message_vec(&[]).unwrap();
+    assert_eq!(
+        r.read_message_vec(&m).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+}
+
+#[test]
+fn builder_checks_keys() {
+    let build =
+        |pattern: HandshakePattern, initiator: bool, s: bool, rs: bool| -> Result<HS, Error> {
+            let mut b = HandshakeStateBuilder::<X25519>::new();
+            b.set_pattern(pattern)
+                .set_is_initiator(initiator)
+                .set_prologue(b"");
+            if s {
+                b.set_s(X25519::genkey(false));
+            }
+            if rs {
+                b.set_rs(X25519::genkey(false).public);
+            }
+            b.try_build_handshake_state()
+        };
+    let missing = |r: Result<HS, Error>| r.err().map(|e| e.kind()) == Some(ErrorKind::MissingKey);
+
+    assert!(build(noise_nn(), true, false, false).is_ok());
+    assert!(missing(build(noise_xx(), true, false, false)));
+    assert!(missing(build(noise_xx(), false, false, false)));
+    // The initiator of NK only needs the responder's key.
+    assert!(build(noise_nk(), true, false, true).is_ok());
+    assert!(missing(build(noise_nk(), true, false, false)));
+    assert!(missing(build(noise_nk(), false, false, false)));
+    assert!(missing(build(noise_ik(), true, true, false)));
+    assert!(build(noise_ik(), true, true, true).is_ok());
+    assert!(build(noise_ik(), false, true, false).is_ok());
+
+    // An unset pattern, role or prologue is an error too, not a panic.
+    let parameter = |b: HandshakeStateBuilder<X25519>| {
+        let r: Result<HS, Error> = b.try_build_handshake_state();
+        r.err().map(|e| e.kind()) == Some(ErrorKind::MissingParameter)
+    };
+    let mut b = HandshakeStateBuilder::new();
+    b.set_is_initiator(true).set_prologue(b"");
+    assert!(parameter(b));
+    let mut b = HandshakeStateBuilder::new();
+    b.set_pattern(noise_nn()).set_prologue(b"");
+    assert!(parameter(b));
+    let mut b = HandshakeStateBuilder::new();
+    b.set_pattern(noise_nn()).set_is_initiator(true);
+    assert!(parameter(b));
+}
+
+#[test]
+fn build_initiator_and_responder() {
+    let alice = X25519::genkey(false);
+    let bob = X25519::genkey(false);
+
+    let mut b = HandshakeStateBuilder::new();
+    b.pattern(noise_ik())
+        .prologue(b"builder")
+        .local_static(alice.clone())
+        .remote_static(bob.public)
+        .enable_elligator();
+    let mut i: HS = b.build_initiator().unwrap();
+    let mut b = HandshakeStateBuilder::new();
+    b.pattern(noise_ik())
+        .prologue(b"builder")
+        .local_static(bob)
+        .enable_elligator();
+    let mut r: HS = b.build_responder().unwrap();
+    assert!(i.get_is_initiator() && !r.get_is_initiator());
+
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_hash(), r.get_hash());
+    assert_eq!(r.get_rs().unwrap(), alice.public);
+
+    // The initiator of IK needs the key of the responder.
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.pattern(noise_ik()).prologue(b"").local_static(alice);
+    let e = b.build_initiator::<ChaCha20Poly1305, Blake2s, NoKem, NoSignature>();
+    assert_eq!(e.err().unwrap().kind(), ErrorKind::MissingKey);
+}
+
+#[test]
+fn noise_pipes_fallback() {
+    let alice_s = X25519::genkey(false);
+    let bob_s = X25519::genkey(false);
+    let bob_old_s = X25519::genkey(false);
+
+    // Alice tries `IK` with an old static key of Bob.
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_ik())
+        .set_is_initiator(true)
+        .set_prologue(b"pipes")
+        .set_s(alice_s.clone())
+        .set_rs(bob_old_s.public);
+    let mut alice: HS = ib.build_handshake_state();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_ik())
+        .set_is_initiator(false)
+        .set_prologue(b"pipes")
+        .set_s(bob_s.clone());
+    let mut bob: HS = rb.build_handshake_state();
+
+    let m = alice.write_message_vec(b"0-rtt").unwrap();
+    assert_eq!(
+        bob.read_message_vec(&m).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+
+    // Bob falls back to `XXfallback`, as the initiator.
+    let mut bob = bob.fallback(noise_xx_fallback()).unwrap();
+    assert!(bob.get_is_initiator());
+    let m = bob.write_message_vec(b"fallback").unwrap();
+
+    // Alice can not read it as `IK`, so she falls back too.
+    assert!(alice.clone().read_message_vec(&m).is_err());
+    let mut alice = alice.fallback(noise_xx_fallback()).unwrap();
+    assert!(!alice.get_is_initiator());
+    assert_eq!(alice.read_message_vec(&m).unwrap(), b"fallback");
+    let m = alice.write_message_vec(b"done").unwrap();
+    assert_eq!(bob.read_message_vec(&m).unwrap(), b"done");
+
+    assert!(alice.completed() && bob.completed());
+    assert_eq!(alice.get_hash(), bob.get_hash());
+    assert_eq!(alice.get_rs().unwrap(), bob_s.public);
+    assert_eq!(bob.get_rs().unwrap(), alice_s.public);
+
+    // A responder that has not read any ephemeral key can not fall back.
+    let (_, r) = xx_pair();
+    assert_eq!(
+        r.fallback(noise_xx_fallback()).err().unwrap().kind(),
+        ErrorKind::MissingKey
+    );
+}
+
+#[test]
+fn noise_pipes_fallback_after_restore() {
+    let alice_s = X25519::genkey(false);
+    let bob_s = X25519::genkey(false);
+    let bob_old_s = X25519::genkey(false);
+    let ik_pair = || {
+        let (mut ib, mut rb) = builders(noise_ik(), b"pipes");
+        ib.set_s(alice_s.clone()).set_rs(bob_old_s.public);
+        rb.set_s(bob_s.clone());
+        let alice: HS = ib.build_handshake_state();
+        let bob: HS = rb.build_handshake_state();
+        (alice, bob)
+    };
+    // Both fall back to `XXfallback`, which mixes in the prologue again.
+    let fall_back = |alice: HS, bob: HS| {
+        let mut bob = bob.fallback(noise_xx_fallback()).unwrap();
+        let mut alice = alice.fallback(noise_xx_fallback()).unwrap();
+        let m = bob.write_message_vec(b"fallback").unwrap();
+        assert_eq!(alice.read_message_vec(&m).unwrap(), b"fallback");
+        let m = alice.write_message_vec(b"done").unwrap();
+        assert_eq!(bob.read_message_vec(&m).unwrap(), b"done");
+        assert!(alice.completed() && bob.completed());
+        assert_eq!(alice.get_hash(), bob.get_hash());
+    };
+
+    // Alice precomputed her `IK` message.
+    let (alice, mut bob) = ik_pair();
+    let pre = alice.precompute_first_message(b"0-rtt").unwrap();
+    let pre = PrecomputedMessage::from_bytes(&pre.to_bytes()).unwrap();
+    assert!(bob.read_message_vec(pre.message()).is_err());
+    fall_back(pre.resume(Some(alice_s.clone())).unwrap(), bob);
+
+    // Bob saved his state after failing to read it.
+    let (mut alice, mut bob) = ik_pair();
+    let m = alice.write_message_vec(b"0-rtt").unwrap();
+    assert!(bob.read_message_vec(&m).is_err());
+    let bob = HS::import_state(&bob.export_state(), Some(bob_s.clone())).unwrap();
+    fall_back(alice, bob);
+}
+
+#[test]
+fn messages_in_caller_buffers() {
+    let (mut i, mut r) = xx_pair();
+    let mut buf = [0u8; 256];
+    let mut payload = [0u8; 256];
+
+    assert_eq!(
+        i.write_message_to(b"hello", &mut buf[..36])
+            .unwrap_err()
+            .kind(),
+        ErrorKind::BufferTooSmall
+    );
+    let len = i.write_message_to(b"hello", &mut buf).unwrap();
+    assert_eq!(len, 32 + 5);
+
+    assert_eq!(
+        r.clone()
+            .read_message_to(&buf[..31], &mut payload)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::TooShort
+    );
+    assert_eq!(
+        r.clone()
+            .read_message_to(&buf[..len], &mut payload[..4])
+            .unwrap_err()
+            .kind(),
+        ErrorKind::BufferTooSmall
+    );
+    assert_eq!(r.read_message_to(&buf[..len], &mut payload).unwrap(), 5);
+    assert_eq!(&payload[..5], b"hello");
+
+    let len = r.write_message_to(b"", &mut buf).unwrap();
+    assert_eq!(i.read_message_to(&buf[..len], &mut payload).unwrap(), 0);
+    let len = i.write_message_to(b"!", &mut buf).unwrap();
+    assert_eq!(r.read_message_to(&buf[..len], &mut payload).unwrap(), 1);
+    assert!(i.completed() && r.completed());
+}
+
+#[test]
+fn export_import_state() {
+    let rs = X25519::genkey(false);
+    let (mut ib, mut rb) = builders(noise_xx(), b"test");
+    ib.set_s(X25519::genkey(false));
+    rb.set_s(rs.clone());
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+
+    let m = i.write_message_vec(b"hello").unwrap();
+    assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+
+    // Suspend and resume the responder.
+    let state = r.export_state();
+    drop(r);
+    let mut r = HS::import_state(&state, Some(rs.clone())).unwrap();
+    let m = r.write_message_vec(b"world").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"world");
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    assert!(i.completed() && r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+
+    // Malformed, other version, or wrong static key.
+    assert!(HS::import_state(&state[..state.len() - 1], Some(rs.clone())).is_err());
+    let mut longer = state.to_vec();
+    longer.push(0);
+    assert!(HS::import_state(&longer, Some(rs.clone())).is_err());
+    let mut other = state.to_vec();
+    other[0] = 1;
+    assert!(HS::import_state(&other, Some(rs)).is_err());
+    assert!(HS::import_state(&state, Some(X25519::genkey(false))).is_err());
+}
+
+#[test]
+fn psk_provider() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let server = X25519::genkey(false);
+    let known = X25519::genkey(false);
+    let mut psks = HashMap::new();
+    psks.insert(known.public, [9u8; 32]);
+    let provider = Arc::new(move |position: usize, rs: Option<&[u8; 32]>| {
+        assert_eq!(position, 2);
+        psks.get(rs?).copied()
+    });
+
+    let handshake = |client: &DhKeyPair<_, _>| {
+        let (mut ib, mut rb) = builders(noise_ik_psk2(), b"");
+        ib.set_s(client.clone())
+            .set_rs(server.public)
+            .push_psk(&[9; 32]);
+        rb.set_s(server.clone()).set_psk_provider(provider.clone());
+        let mut i: HS = ib.build_handshake_state();
+        let mut r: HS = rb.build_handshake_state();
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).unwrap();
+        r.write_message_vec(b"")
+            .map(|m| i.read_message_vec(&m).unwrap())
+            .map_err(|e| e.kind())
+    };
+
+    assert!(handshake(&known).is_ok());
+    assert_eq!(handshake(&X25519::genkey(false)), Err(ErrorKind::NeedPSK));
+}
+
+#[test]
+fn remote_static_verifier() {
+    use std::sync::Arc;
+
+    let allowed = X25519::genkey(false);
+    let allowed_public = allowed.public;
+    let verifier = Arc::new(move |rs: &[u8]| rs == allowed_public);
+
+    let handshake = |client: DhKeyPair<_, _>| {
+        let (_, mut r) = xx_pair();
+        r.set_remote_static_verifier(verifier.clone());
+        let mut ib = HandshakeStateBuilder::<X25519>::new();
+        ib.set_pattern(noise_xx())
+            .set_is_initiator(true)
+            .set_prologue(b"test")
+            .set_s(client);
+        let mut i: HS = ib.build_handshake_state();
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).unwrap();
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).unwrap();
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).map(|_| r).map_err(|e| e.kind())
+    };
+
+    let r = handshake(allowed.clone()).unwrap();
+    assert_eq!(r.get_remote_static(), Some(&allowed.public[..]));
+    assert_eq!(
+        handshake(X25519::genkey(false)).err(),
+        Some(ErrorKind::AuthenticationRejected)
+    );
+}
+
+#[test]
+fn static_key_mismatch() {
+    use noise_protocol::util::ct_eq_pubkey;
+
+    assert!(ct_eq_pubkey(&[1, 2, 3], &[1, 2, 3]));
+    assert!(!ct_eq_pubkey(&[1, 2, 3], &[1, 2, 4]));
+    assert!(!ct_eq_pubkey(&[1, 2, 3], &[1, 2]));
+
+    let handshake = |expected: [u8; 32]| {
+        let (mut i, _) = xx_pair();
+        let mut rb = HandshakeStateBuilder::<X25519>::new();
+        rb.set_pattern(noise_xx())
+            .set_is_initiator(false)
+            .set_prologue(b"test")
+            .set_s(X25519::genkey(false))
+            .set_rs(expected);
+        let mut r: HS = rb.build_handshake_state();
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).unwrap();
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).unwrap();
+        let m = i.write_message_vec(b"").unwrap();
+        r.read_message_vec(&m).map_err(|e| e.kind())
+    };
+
+    assert_eq!(handshake([0; 32]), Err(ErrorKind::StaticKeyMismatch));
+
+    // The expected key.
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    let s = X25519::genkey(false);
+    ib.set_pattern(noise_xx())
+        .set_is_initiator(true)
+        .set_prologue(b"test")
+        .set_s(s.clone());
+    let mut i: HS = ib.build_handshake_state();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_xx())
+        .set_is_initiator(false)
+        .set_prologue(b"test")
+        .set_s(X25519::genkey(false))
+        .set_rs(s.public);
+    let mut r: HS = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(r.get_remote_static(), Some(&s.public[..]));
+}
+
+#[test]
+fn strict_pubkey_validation() {
+    assert_eq!(
+        X25519::validate_pubkey(&X25519::genkey(false).public),
+        Ok(())
+    );
+    let mut one = [0u8; 32];
+    one[0] = 1;
+    assert_eq!(
+        X25519::validate_pubkey(&one),
+        Err(noise_rust_crypto::PubkeyError::SmallOrder)
+    );
+    // p = 2^255 - 19, and a key with the top bit set.
+    let mut p = [0xffu8; 32];
+    p[0] = 0xed;
+    p[31] = 0x7f;
+    assert_eq!(
+        X25519::validate_pubkey(&p),
+        Err(noise_rust_crypto::PubkeyError::NonCanonical)
+    );
+    let mut top = X25519::genkey(false).public;
+    top[31] |= 0x80;
+    assert_eq!(
+        X25519::validate_pubkey(&top),
+        Err(noise_rust_crypto::PubkeyError::NonCanonical)
+    );
+
+    let responder = |strict| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_nn())
+            .set_is_initiator(false)
+            .set_prologue(b"")
+            .set_allow_low_order(true)
+            .set_strict_pubkey_validation(strict);
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2s>()
+    };
+
+    // A small order ephemeral key is rejected when read, even though
+    // all-zero DH outputs are allowed.
+    let mut r = responder(true);
+    let e = r.read_message_vec(&one).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidPublicKey);
+    responder(false).read_message_vec(&one).unwrap();
+
+    // The setting is saved with the state.
+    let state = responder(true).export_state();
+    let mut r = HS::import_state(&state, None).unwrap();
+    let e = r.read_message_vec(&one).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidPublicKey);
+
+    // Valid keys pass.
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(b"");
+    let mut i = ib.build_handshake_state::<ChaCha20Poly1305, Blake2s>();
+    let mut r = responder(true);
+    run_handshake(&mut i, &mut r);
+}
+
+#[test]
+fn tampered_handshake_messages() {
+    // Deterministic, unlike the fuzz targets.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut rand = move |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    let key = |b: u8| DhKeyPair::from_private::<X25519>(U8Array::from_slice(&[b; 32]));
+    let build = |pattern: HandshakePattern, initiator: bool| {
+        let (s, rs) = if initiator { (1, 2) } else { (2, 1) };
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(pattern)
+            .set_is_initiator(initiator)
+            .set_prologue(b"")
+            .set_s(key(s))
+            .set_rs(key(rs).public);
+        for _ in 0..4 {
+            b.push_psk(&[0; 32]);
+        }
+        b.try_build_handshake_state().ok()
+    };
+
+    for pattern in patterns::predefined() {
+        let (Some(mut i), Some(mut r)): (Option<HS>, Option<HS>) =
+            (build(pattern, true), build(pattern, false))
+        else {
+            continue;
+        };
+        for n in 0..pattern.get_message_patterns_len() {
+            let (send, recv) = if n % 2 == 0 {
+                (&mut i, &mut r)
+            } else {
+                (&mut r, &mut i)
+            };
+            let e = send.clone().write_message(b"payload", &mut [0; 1]);
+            assert_eq!(e.unwrap_err().kind(), ErrorKind::BufferTooSmall);
+            let message = send.write_message_vec(b"payload").unwrap();
+            let overhead = recv.get_next_message_overhead();
+
+            let mut tampered = Vec::new();
+            for _ in 0..8 {
+                tampered.push(message[..rand(message.len())].to_vec());
+                let mut flipped = message.clone();
+                flipped[rand(message.len())] ^= 1 << rand(8);
+                tampered.push(flipped);
+            }
+            for m in tampered {
+                let mut copy = recv.clone();
+                match copy.read_message_vec(&m) {
+                    Ok(_) => assert!(!pattern.payload_protection(n).encrypted),
+                    Err(e) => {
+                        assert!(matches!(
+                            e.kind(),
+                            ErrorKind::TooShort
+                                | ErrorKind::Decryption
+                                | ErrorKind::InvalidPublicKey
+                                | ErrorKind::StaticKeyMismatch
+                        ));
+                        // The state is unchanged, the genuine message can
+                        // still be read.
+                        assert_eq!(copy.read_message_vec(&message).unwrap(), b"payload");
+                    }
+                }
+                let mut out = vec![0; m.len().saturating_sub(overhead) + 1];
+                let kind = recv.clone().read_message(&m, &mut out).unwrap_err().kind();
+                assert!(matches!(
+                    kind,
+                    ErrorKind::TooShort | ErrorKind::BufferTooSmall
+                ));
+            }
+            assert_eq!(recv.read_message_vec(&message).unwrap(), b"payload");
+        }
+    }
+
+    // No static key, where one is needed.
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_xx())
+        .set_is_initiator(true)
+        .set_prologue(b"test");
+    let mut i: HS = ib.build_handshake_state();
+    let (_, mut r) = xx_pair();
+    r.read_message_vec(&i.write_message_vec(b"").unwrap())
+        .unwrap();
+    i.read_message_vec(&r.write_message_vec(b"").unwrap())
+        .unwrap();
+    let h = i.get_hash().to_vec();
+    let e = i.write_message_vec(b"").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::MissingKey);
+    // The failed message is not half-written.
+    assert_eq!(i.get_hash(), &h[..]);
+    assert!(i.is_write_turn() && !i.completed());
+    let e = i.write_message_vec(b"").unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::MissingKey);
+}
+
+#[test]
+fn ephemeral_pool() {
+    use std::sync::Arc;
+
+    let pool = Arc::new(EphemeralPool::<X25519>::new(true, 3));
+    assert_eq!(pool.refill(2), 2);
+    assert_eq!(pool.refill(5), 1);
+    assert_eq!(pool.len(), 3);
+
+    let build = |is_initiator| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_xx())
+            .set_is_initiator(is_initiator)
+            .set_is_elligator_encoded(true)
+            .set_prologue(b"")
+            .set_s(X25519::genkey(false))
+            .set_ephemeral_pool(pool.clone());
+        b.build_handshake_state()
+    };
+    let mut i: HS = build(true);
+    let mut r: HS = build(false);
+    assert_eq!(pool.len(), 1);
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_hash(), r.get_hash());
+
+    // The responder of a one-way pattern sends no ephemeral key.
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_n())
+        .set_is_initiator(false)
+        .set_is_elligator_encoded(true)
+        .set_prologue(b"")
+        .set_s(X25519::genkey(false))
+        .set_ephemeral_pool(pool.clone());
+    let _: HS = b.build_handshake_state();
+    assert_eq!(pool.len(), 1);
+    assert!(pool.take().is_some());
+    assert!(pool.is_empty());
+}
+
+#[test]
+fn tai64n_zero_rtt() {
+    use noise_protocol::tai64n::*;
+
+    let is = X25519::genkey(false);
+    let rs = X25519::genkey(false);
+    let build = |is_initiator: bool| {
+        let (s, remote) = if is_initiator { (&is, &rs) } else { (&rs, &is) };
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_kk_psk0())
+            .set_is_initiator(is_initiator)
+            .set_prologue(b"")
+            .set_s(s.clone())
+            .set_rs(remote.public)
+            .push_psk(&[1; 32]);
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2s>()
+    };
+
+    let t = Tai64N::from_unix(1_700_000_000, 5);
+    assert_eq!(hex::encode(t.to_bytes()), "400000006553f10a00000005");
+    assert_eq!(Tai64N::from_bytes(&t.to_bytes()).unwrap(), t);
+    assert!(Tai64N::from_unix(1_700_000_000, 6) > t);
+
+    let mut window = TimestampWindow::new(120);
+    let msg = write_timestamped(&mut build(true), t, b"0-rtt").unwrap();
+    let mut r = build(false);
+    let now = Tai64N::from_unix(1_700_000_030, 0);
+    assert_eq!(
+        read_timestamped(&mut r, &msg, &mut window, now).unwrap(),
+        b"0-rtt"
+    );
+    assert_eq!(window.newest(), Some(t));
+    assert!(r.write_message_vec(b"").is_ok());
+
+    // Replayed.
+    assert_eq!(
+        read_timestamped(&mut build(false), &msg, &mut window, now)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Replay
+    );
+    // Too old.
+    let msg =
+        write_timestamped(&mut build(true), Tai64N::from_unix(1_699_999_000, 0), b"").unwrap();
+    let mut fresh = TimestampWindow::new(120);
+    assert_eq!(
+        read_timestamped(&mut build(false), &msg, &mut fresh, now)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Replay
+    );
+    assert_eq!(fresh.newest(), None);
+}
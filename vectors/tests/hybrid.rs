@@ -0,0 +1,67 @@
+// Hybrid forward secrecy handshakes with ML-KEM-768.
+
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, MlKem768, X25519};
+
+type HS = HandshakeState<X25519, ChaCha20Poly1305, Blake2s, MlKem768>;
+
+fn run_handshake(i: &mut HS, r: &mut HS) {
+    let mut init_send = true;
+    while !i.completed() {
+        let (send, recv) = if init_send {
+            (&mut *i, &mut *r)
+        } else {
+            (&mut *r, &mut *i)
+        };
+        let m = send.write_message_vec(b"payload").unwrap();
+        assert_eq!(recv.read_message_vec(&m).unwrap(), b"payload");
+        init_send = !init_send;
+    }
+    assert!(r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+}
+
+#[test]
+fn xx_hfs() {
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_xx_hfs())
+        .set_is_initiator(true)
+        .set_prologue(b"")
+        .set_s(X25519::genkey(false));
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_xx_hfs())
+        .set_is_initiator(false)
+        .set_prologue(b"")
+        .set_s(X25519::genkey(false));
+    let mut i: HS = ib.build_hybrid_handshake_state();
+    let mut r: HS = rb.build_hybrid_handshake_state();
+
+    // e, e1 in the clear.
+    assert_eq!(i.get_next_message_overhead(), 32 + 1184);
+    run_handshake(&mut i, &mut r);
+
+    let (mut i_send, _) = i.get_ciphers();
+    let (_, mut r_recv) = r.get_ciphers();
+    let c = i_send.encrypt_vec(b"hello");
+    assert_eq!(r_recv.decrypt_vec(&c).unwrap(), b"hello");
+}
+
+#[test]
+fn ik_hfs() {
+    let rs = X25519::genkey(false);
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_ik_hfs())
+        .set_is_initiator(true)
+        .set_prologue(b"")
+        .set_s(X25519::genkey(false))
+        .set_rs(rs.public);
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_ik_hfs())
+        .set_is_initiator(false)
+        .set_prologue(b"")
+        .set_s(rs);
+    let mut i: HS = ib.build_hybrid_handshake_state();
+    let mut r: HS = rb.build_hybrid_handshake_state();
+    run_handshake(&mut i, &mut r);
+}
@@ -0,0 +1,338 @@
+// Framing and I/O adapters: codecs, sockets, streams, demultiplexing and
+// file encryption.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::X25519;
+
+#[test]
+fn tokio_codec() {
+    use bytes::BytesMut;
+    use noise_protocol::codec::{NoiseCodec, MAX_PAYLOAD_LEN};
+    use tokio_util::codec::{Decoder, Encoder};
+
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut ic = NoiseCodec::from_handshake(&i);
+    let mut rc = NoiseCodec::from_handshake(&r);
+
+    let mut buf = BytesMut::new();
+    ic.encode(&b"hello"[..], &mut buf).unwrap();
+    ic.encode(&b""[..], &mut buf).unwrap();
+    let mut partial = buf.split_to(3);
+    assert!(rc.decode(&mut partial).unwrap().is_none());
+    partial.unsplit(buf);
+    assert_eq!(&rc.decode(&mut partial).unwrap().unwrap()[..], b"hello");
+    assert_eq!(&rc.decode(&mut partial).unwrap().unwrap()[..], b"");
+    assert!(rc.decode(&mut partial).unwrap().is_none());
+
+    assert!(ic
+        .encode(&vec![0u8; MAX_PAYLOAD_LEN + 1][..], &mut BytesMut::new())
+        .is_err());
+
+    let mut buf = BytesMut::new();
+    ic.encode(&b"tampered"[..], &mut buf).unwrap();
+    buf[5] ^= 1;
+    assert_eq!(
+        rc.decode(&mut buf).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn udp_demux() {
+    use noise_protocol::demux::{Demux, Packet, Route};
+    use std::time::Duration;
+
+    let (i, r) = xx_pair();
+    let mut client = Demux::new(Duration::from_secs(60));
+    let mut server = Demux::<HS>::new(Duration::from_secs(60));
+    let ci = client.insert(i, None);
+
+    // -> e
+    let msg = client.get_mut(ci).unwrap().write_message_vec(&[]).unwrap();
+    let datagram = Packet::HandshakeInit {
+        sender: ci,
+        message: &msg,
+    }
+    .to_vec();
+    let (sender, msg) = match server.route(&datagram) {
+        Some(Route::New { sender, message }) => (sender, message.to_vec()),
+        _ => panic!(),
+    };
+    let mut r = r;
+    r.read_message_vec(&msg).unwrap();
+    // <- e, ee, s, es
+    let msg = r.write_message_vec(&[]).unwrap();
+    let si = server.insert(r, Some(sender));
+    assert_eq!(server.remote_index(si), Some(ci));
+    let datagram = Packet::HandshakeResponse {
+        sender: si,
+        receiver: ci,
+        message: &msg,
+    }
+    .to_vec();
+    match client.route(&datagram) {
+        Some(Route::Session {
+            index,
+            sender: Some(sender),
+            session,
+            message,
+        }) => {
+            assert_eq!(index, ci);
+            session.read_message_vec(message).unwrap();
+            client.set_remote_index(ci, sender);
+        }
+        _ => panic!(),
+    }
+    // -> s, se
+    let msg = client.get_mut(ci).unwrap().write_message_vec(&[]).unwrap();
+    let datagram = Packet::Transport {
+        receiver: client.remote_index(ci).unwrap(),
+        message: &msg,
+    }
+    .to_vec();
+    match server.route(&datagram) {
+        Some(Route::Session {
+            index,
+            sender: None,
+            session,
+            message,
+        }) => {
+            assert_eq!(index, si);
+            session.read_message_vec(message).unwrap();
+            assert!(session.completed());
+        }
+        _ => panic!(),
+    }
+
+    // Unknown index and malformed datagrams.
+    let datagram = Packet::Transport {
+        receiver: si.wrapping_add(1),
+        message: &[],
+    }
+    .to_vec();
+    assert!(server.route(&datagram).is_none());
+    assert!(server.route(&[3, 0]).is_none());
+
+    assert!(server.expire().is_empty());
+    let mut expiring = Demux::new(Duration::ZERO);
+    let index = expiring.insert((), None);
+    std::thread::sleep(Duration::from_millis(1));
+    assert_eq!(expiring.expire(), [(index, ())]);
+    assert!(expiring.is_empty());
+}
+
+#[test]
+fn async_noise_stream() {
+    use noise_protocol::stream::NoiseStream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let (i, r) = xx_pair();
+    let (a, b) = tokio::io::duplex(1024);
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    rt.block_on(async move {
+        let responder = tokio::spawn(async move {
+            let mut s = NoiseStream::handshake_tokio(b, r).await.unwrap();
+            let mut buf = vec![0u8; 100_000];
+            s.read_exact(&mut buf).await.unwrap();
+            s.write_all(&buf[..5]).await.unwrap();
+            s.shutdown().await.unwrap();
+            buf
+        });
+        let mut s = NoiseStream::handshake_tokio(a, i).await.unwrap();
+        // Larger than one message.
+        let data: Vec<u8> = (0..100_000u32).map(|x| x as u8).collect();
+        s.write_all(&data).await.unwrap();
+        s.flush().await.unwrap();
+
+        let mut reply = Vec::new();
+        s.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, &data[..5]);
+        assert_eq!(responder.await.unwrap(), data);
+    });
+}
+
+#[test]
+fn blocking_noise_socket() {
+    use noise_protocol::socket::NoiseSocket;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    let (i, r) = xx_pair();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let responder = std::thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut s = NoiseSocket::handshake(tcp, r).unwrap();
+        let mut buf = vec![0u8; 100_000];
+        s.read_exact(&mut buf).unwrap();
+        s.write_all(&buf[..5]).unwrap();
+        (buf, s.transport().get_hash().to_vec())
+    });
+
+    let mut s = NoiseSocket::handshake(TcpStream::connect(addr).unwrap(), i).unwrap();
+    // Larger than one message.
+    let data: Vec<u8> = (0..100_000u32).map(|x| x as u8).collect();
+    s.write_all(&data).unwrap();
+    let mut reply = [0u8; 5];
+    s.read_exact(&mut reply).unwrap();
+    assert_eq!(reply, data[..5]);
+
+    let (received, hash) = responder.join().unwrap();
+    assert_eq!(received, data);
+    assert_eq!(hash, s.transport().get_hash());
+    // The responder has closed the connection.
+    assert_eq!(s.read(&mut reply).unwrap(), 0);
+}
+
+#[test]
+fn noise_socket_negotiation() {
+    use noise_protocol::negotiation::*;
+
+    let alice_s = X25519::genkey(false);
+    let bob_s = X25519::genkey(false);
+    let bob_old_s = X25519::genkey(false);
+
+    // Alice sends an `IK` message to an old static key of Bob.
+    let mut alice_neg = Negotiation::new(b"IK");
+    let prologue = alice_neg.initial_prologue();
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_ik())
+        .set_is_initiator(true)
+        .set_prologue(&prologue)
+        .set_s(alice_s.clone())
+        .set_rs(bob_old_s.public);
+    let mut alice: HS = ib.build_handshake_state();
+    let m = alice.write_message_vec(&encode_body(b"0-rtt", 64)).unwrap();
+    alice_neg.set_initial_message(&m);
+    let mut wire = Vec::new();
+    HandshakeMessage::new(b"IK", &m).encode(&mut wire);
+
+    // Bob can not read it, and switches to `XXfallback`.
+    let (msg, len) = HandshakeMessage::decode(&wire).unwrap();
+    assert_eq!(len, wire.len());
+    assert!(HandshakeMessage::decode(&wire[..len - 1]).is_none());
+    let mut bob_neg = Negotiation::new(msg.negotiation_data);
+    bob_neg.set_initial_message(msg.noise_message);
+    let prologue = bob_neg.initial_prologue();
+    assert_eq!(prologue, alice_neg.initial_prologue());
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_ik())
+        .set_is_initiator(false)
+        .set_prologue(&prologue)
+        .set_s(bob_s.clone());
+    let mut bob: HS = rb.build_handshake_state();
+    assert!(bob.read_message_vec(msg.noise_message).is_err());
+    let mut bob = bob
+        .fallback_with_prologue(noise_xx_fallback(), &bob_neg.switch_prologue(b"XXfallback"))
+        .unwrap();
+    let m = bob.write_message_vec(&encode_body(b"", 0)).unwrap();
+    let mut wire = Vec::new();
+    HandshakeMessage::new(b"XXfallback", &m).encode(&mut wire);
+
+    let (msg, _) = HandshakeMessage::decode(&wire).unwrap();
+    assert_eq!(msg.negotiation_data, b"XXfallback");
+    let mut alice = alice
+        .fallback_with_prologue(
+            noise_xx_fallback(),
+            &alice_neg.switch_prologue(msg.negotiation_data),
+        )
+        .unwrap();
+    let body = alice.read_message_vec(msg.noise_message).unwrap();
+    assert_eq!(decode_body(&body).unwrap(), b"");
+    let m = alice.write_message_vec(&encode_body(b"hi", 0)).unwrap();
+    assert_eq!(
+        decode_body(&bob.read_message_vec(&m).unwrap()).unwrap(),
+        b"hi"
+    );
+    assert_eq!(alice.get_hash(), bob.get_hash());
+
+    // Transport messages.
+    let (mut c, _) = alice.get_ciphers();
+    let (mut d, _) = bob.get_ciphers();
+    let mut wire = Vec::new();
+    encode_transport_message(&c.encrypt_vec(&encode_body(b"hello", 100)), &mut wire);
+    assert_eq!(wire.len(), 2 + 100 + 16);
+    let (m, len) = decode_transport_message(&wire).unwrap();
+    assert_eq!(len, wire.len());
+    let payload = d.decrypt_vec(m).unwrap();
+    assert_eq!(decode_body(&payload).unwrap(), b"hello");
+    assert_eq!(
+        decode_body(&payload[..6]).unwrap_err().kind(),
+        ErrorKind::TooShort
+    );
+
+    // A retry binds the rejected attempt.
+    assert_ne!(
+        alice_neg.retry_prologue(b"XX"),
+        alice_neg.switch_prologue(b"XX")
+    );
+    assert_eq!(
+        alice_neg.retry_prologue(b"XX"),
+        bob_neg.retry_prologue(b"XX")
+    );
+}
+
+#[test]
+fn file_encryption() {
+    for len in [0, 100, 1024, 3000] {
+        let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let (i, r) = file_pair(noise_n());
+
+        let mut encrypted = Vec::new();
+        assert_eq!(
+            file::encrypt(i, 1024, &data[..], &mut encrypted).unwrap(),
+            len as u64
+        );
+        let mut decrypted = Vec::new();
+        assert_eq!(
+            file::decrypt(r, &encrypted[..], &mut decrypted).unwrap(),
+            len as u64
+        );
+        assert_eq!(decrypted, data);
+    }
+
+    // Not a one-way pattern.
+    let (i, _) = xx_pair();
+    assert_eq!(
+        file::FileEncryptor::new(i, 1024).err().unwrap().kind(),
+        ErrorKind::InvalidState
+    );
+}
+
+#[test]
+fn file_truncation_detected() {
+    let (i, r) = file_pair(noise_n());
+    let (mut enc, header) = file::FileEncryptor::new(i, 16).unwrap();
+    let c0 = enc.encrypt_chunk(&[1; 16], false);
+    let c1 = enc.encrypt_chunk(&[2; 16], false);
+    let c2 = enc.encrypt_chunk(&[3; 5], true);
+
+    let mut dec = file::FileDecryptor::new(r.clone(), &header).unwrap();
+    assert_eq!(dec.decrypt_chunk(&c0).unwrap(), [1; 16]);
+    assert_eq!(dec.decrypt_chunk(&c1).unwrap(), [2; 16]);
+    assert_eq!(dec.finish().unwrap_err().kind(), ErrorKind::TooShort);
+
+    let mut dec = file::FileDecryptor::new(r.clone(), &header).unwrap();
+    assert_eq!(
+        dec.decrypt_chunk(&c1).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+
+    let mut dec = file::FileDecryptor::new(r.clone(), &header).unwrap();
+    dec.decrypt_chunk(&c0).unwrap();
+    dec.decrypt_chunk(&c1).unwrap();
+    assert_eq!(dec.decrypt_chunk(&c2).unwrap(), [3; 5]);
+    dec.finish().unwrap();
+
+    let mut stream = header.clone();
+    stream.extend_from_slice(&c0);
+    stream.extend_from_slice(&c1);
+    assert!(file::decrypt(r, &stream[..], &mut Vec::new()).is_err());
+}
@@ -0,0 +1,142 @@
+// Hybrid patterns, with a KEM built from X25519.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+/// A KEM made of X25519, to test hybrid patterns without a post-quantum KEM.
+enum DhKem {}
+
+impl Kem for DhKem {
+    type Key = <X25519 as DH>::Key;
+    type Pubkey = [u8; 32];
+    type Ciphertext = [u8; 32];
+    type Output = <X25519 as DH>::Output;
+
+    fn name() -> &'static str {
+        "DHKEM"
+    }
+
+    fn genkey() -> (Self::Key, Self::Pubkey) {
+        let k = X25519::genkey(false);
+        (k.private, k.public)
+    }
+
+    fn encapsulate(pk: &Self::Pubkey) -> Result<(Self::Ciphertext, Self::Output), Error> {
+        let e = X25519::genkey(false);
+        Ok((e.public, X25519::dh(&e.private, pk, false)?))
+    }
+
+    fn decapsulate(k: &Self::Key, ct: &Self::Ciphertext) -> Result<Self::Output, Error> {
+        X25519::dh(k, ct, false)
+    }
+}
+
+#[test]
+fn hybrid_handshake() {
+    type HybridHs = HandshakeState<X25519, ChaCha20Poly1305, Blake2s, DhKem>;
+
+    let hybrid_pair = || {
+        let (ib, rb) = builders(noise_nn_hfs(), b"");
+        let i: HybridHs = ib.build_hybrid_handshake_state();
+        let r: HybridHs = rb.build_hybrid_handshake_state();
+        (i, r)
+    };
+
+    let (mut i, mut r) = hybrid_pair();
+    let mut ss = SymmetricState::<ChaCha20Poly1305, Blake2s>::new(
+        b"Noise_NNhfs_25519+DHKEM_ChaChaPoly_BLAKE2s",
+    );
+    ss.mix_hash(b"");
+    assert_eq!(i.get_hash(), ss.get_hash());
+
+    // -> e, e1
+    let m1 = i.write_message_vec(b"one").unwrap();
+    assert_eq!(m1.len(), 32 + 32 + 3);
+    assert_eq!(r.read_message_vec(&m1).unwrap(), b"one");
+    // <- e, ee, ekem1
+    assert_eq!(r.get_next_message_overhead(), 32 + 32 + 16 + 16);
+    let m2 = r.write_message_vec(b"two").unwrap();
+
+    // The KEM ciphertext is encrypted and mixed into the key.
+    let mut tampered = m2.clone();
+    tampered[40] ^= 1;
+    assert_eq!(
+        i.clone().read_message_vec(&tampered).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+    assert_eq!(i.read_message_vec(&m2).unwrap(), b"two");
+    assert_eq!(i.get_hash(), r.get_hash());
+
+    // Different ephemeral KEM keys give different transport keys.
+    let (mut i2, mut r2) = hybrid_pair();
+    run_handshake(&mut i2, &mut r2);
+    let c = i.get_ciphers().0.encrypt_vec(b"x");
+    assert!(r2.get_ciphers().0.decrypt_vec(&c).is_err());
+}
+
+#[test]
+fn hybrid_ekem1_without_e1() {
+    use Token::*;
+    type HybridHs = HandshakeState<X25519, ChaCha20Poly1305, Blake2s, DhKem>;
+
+    // A pattern with ekem1 but no e1, e.g., a peer's malformed custom one.
+    let no_e1 = HandshakePattern::new(&[], &[], &[&[E], &[E, EE, EKEM1]], "NNhfs");
+    let build = |initiator| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(no_e1)
+            .set_is_initiator(initiator)
+            .set_prologue(b"");
+        b.build_hybrid_handshake_state::<ChaCha20Poly1305, Blake2s, DhKem>()
+    };
+    let mut i: HybridHs = build(true);
+    let mut r: HybridHs = build(false);
+
+    // Encapsulating to an e1 never received fails.
+    let m1 = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m1).unwrap();
+    assert_eq!(
+        r.write_message_vec(b"").unwrap_err().kind(),
+        ErrorKind::MissingKey
+    );
+
+    // So does decapsulating an ekem1 from the network without an e1.
+    for n in 0..16u8 {
+        let mut m2 = X25519::genkey(false).public.to_vec();
+        m2.extend((0..32 + 16 + 16).map(|k: u8| k.wrapping_mul(31) ^ n));
+        assert_eq!(
+            i.clone().read_message_vec(&m2).unwrap_err().kind(),
+            ErrorKind::MissingKey
+        );
+    }
+}
+
+#[test]
+fn all_hybrid_patterns() {
+    for pattern in hybrid() {
+        let is = X25519::genkey(false);
+        let rs = X25519::genkey(false);
+        let (mut ib, mut rb) = builders(pattern, b"");
+        ib.set_s(is.clone());
+        rb.set_s(rs.clone());
+        if !pattern.get_pre_r().is_empty() {
+            ib.set_rs(rs.public);
+        }
+        if !pattern.get_pre_i().is_empty() {
+            rb.set_rs(is.public);
+        }
+        let mut i: HandshakeState<X25519, ChaCha20Poly1305, Blake2s, DhKem> =
+            ib.build_hybrid_handshake_state();
+        let mut r: HandshakeState<X25519, ChaCha20Poly1305, Blake2s, DhKem> =
+            rb.build_hybrid_handshake_state();
+        run_handshake(&mut i, &mut r);
+        assert!(r.completed(), "{}", pattern.get_name());
+        assert_eq!(i.get_hash(), r.get_hash());
+        assert!(i.get_rs().into_iter().all(|k| k == rs.public));
+        assert!(r.get_rs().into_iter().all(|k| k == is.public));
+    }
+    assert_eq!(hybrid().count(), 12);
+}
@@ -0,0 +1,74 @@
+// Transport keys logged by the `keylog` feature of noise-protocol.
+
+use noise_protocol::keylog::{KeyLogWriter, INITIATOR_LABEL, RESPONDER_LABEL};
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+type HS = HandshakeState<X25519, ChaCha20Poly1305, Blake2s>;
+
+/// A writer whose output can be read while the log owns it.
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn nn(initiator: bool) -> HS {
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_nn())
+        .set_is_initiator(initiator)
+        .set_prologue(b"");
+    b.build_handshake_state()
+}
+
+#[test]
+fn key_log_lines() {
+    let (mut i, mut r) = (nn(true), nn(false));
+    let out = Shared::default();
+    i.set_key_log(Arc::new(KeyLogWriter::new(out.clone())));
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+
+    // Nothing is logged before the ciphers are split, and the responder has
+    // no key log.
+    assert!(out.0.lock().unwrap().is_empty());
+    let hash = hex::encode(i.get_hash());
+    let checkpoint = TransportState::from_handshake(&i).extract();
+    let (send, recv) = (checkpoint.send.0, checkpoint.recv.0);
+    TransportState::from_handshake(&r);
+
+    let log = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert!(log.ends_with('\n'));
+    assert_eq!(
+        lines,
+        [
+            format!(
+                "{} {} {}",
+                INITIATOR_LABEL,
+                hash,
+                hex::encode(send.as_slice())
+            ),
+            format!(
+                "{} {} {}",
+                RESPONDER_LABEL,
+                hash,
+                hex::encode(recv.as_slice())
+            ),
+        ]
+    );
+    assert_eq!(lines[0].split(' ').nth(1).unwrap().len(), 64);
+    assert_eq!(lines[0].split(' ').nth(2).unwrap().len(), 64);
+}
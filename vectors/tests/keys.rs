@@ -0,0 +1,393 @@
+// Static and ephemeral keys: key rings, precomputation, DH caches, pairing,
+// Elligator and opaque keys.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, Kmac256, X25519};
+
+#[test]
+fn keyring_accepts_previous_key() {
+    let old = X25519::genkey(false);
+    let new = X25519::genkey(false);
+
+    let mut ring = KeyRing::<X25519>::new(old.clone());
+    ring.rotate(new.clone());
+
+    let responder = |s: DhKeyPair<_, _>| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(false)
+            .set_prologue(&[])
+            .set_s(s);
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2s>()
+    };
+
+    for (rs, slot) in [
+        (old.public, KeySlot::Previous),
+        (new.public, KeySlot::Current),
+    ] {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(true)
+            .set_prologue(&[])
+            .set_s(X25519::genkey(false))
+            .set_rs(rs);
+        let mut i: HS = b.build_handshake_state();
+        let m = i.write_message_vec(b"hello").unwrap();
+        let mut out = vec![0u8; 5];
+        let (_, matched) = ring.read_message(responder, &m, &mut out).unwrap();
+        assert_eq!(matched, slot);
+        assert_eq!(out, b"hello");
+    }
+
+    ring.retire_previous();
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_ik())
+        .set_is_initiator(true)
+        .set_prologue(&[])
+        .set_s(X25519::genkey(false))
+        .set_rs(old.public);
+    let mut i: HS = b.build_handshake_state();
+    let m = i.write_message_vec(b"hello").unwrap();
+    let mut out = vec![0u8; 5];
+    assert!(ring.read_message(responder, &m, &mut out).is_err());
+}
+
+#[test]
+fn precomputed_first_message() {
+    let s = X25519::genkey(false);
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_xx())
+        .set_is_initiator(true)
+        .set_prologue(b"test")
+        .set_s(s.clone());
+    let i: HS = b.build_handshake_state();
+    let (_, mut r) = xx_pair();
+
+    let pre = i.precompute_first_message(b"hello").unwrap();
+    let pre = PrecomputedMessage::from_bytes(&pre.to_bytes()).unwrap();
+
+    assert_eq!(r.read_message_vec(pre.message()).unwrap(), b"hello");
+    let mut i: HS = pre.resume(Some(s.clone())).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    assert!(i.completed() && r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+
+    // Wrong static key or protocol.
+    let wrong_s = X25519::genkey(false);
+    assert!(pre
+        .resume::<X25519, ChaCha20Poly1305, Blake2s>(Some(wrong_s))
+        .is_err());
+    assert!(pre
+        .resume::<X25519, ChaCha20Poly1305, Kmac256>(Some(s))
+        .is_err());
+}
+
+#[test]
+fn static_dh_cache() {
+    use std::sync::Arc;
+
+    let rs = X25519::genkey(false);
+    let is = X25519::genkey(false);
+    let cache = Arc::new(MemoryStaticDhCache::<X25519>::new(16));
+
+    for _ in 0..2 {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(true)
+            .set_prologue(&[])
+            .set_s(is.clone())
+            .set_rs(rs.public);
+        let mut i: HS = b.build_handshake_state();
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(false)
+            .set_prologue(&[])
+            .set_s(rs.clone());
+        let mut r: HS = b.build_handshake_state();
+        r.set_static_dh_cache(cache.clone());
+
+        run_handshake(&mut i, &mut r);
+        assert_eq!(i.get_hash(), r.get_hash());
+        assert!(cache.get(&rs.public, &is.public).is_some());
+    }
+
+    cache.invalidate_local(&rs.public);
+    assert!(cache.get(&rs.public, &is.public).is_none());
+}
+
+#[test]
+fn static_dh_cache_lru() {
+    let local = X25519::genkey(false).public;
+    let peers: Vec<_> = (0..3).map(|_| X25519::genkey(false)).collect();
+    let output = |i: usize| <X25519 as DH>::Output::from_slice(&[i as u8; 32]);
+    let cache = MemoryStaticDhCache::<X25519>::new(2);
+
+    cache.insert(&local, &peers[0].public, &output(0));
+    cache.insert(&local, &peers[1].public, &output(1));
+    // Use the first entry, so the second is evicted.
+    assert!(cache.get(&local, &peers[0].public).is_some());
+    cache.insert(&local, &peers[2].public, &output(2));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&local, &peers[1].public).is_none());
+    assert_eq!(
+        cache.get(&local, &peers[0].public).unwrap().as_slice(),
+        output(0).as_slice()
+    );
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn shared_static_key() {
+    let server = SharedStaticKey::<X25519>::new(X25519::genkey(false));
+    for _ in 0..2 {
+        let (mut ib, mut rb) = builders(noise_ik(), b"");
+        ib.set_s(X25519::genkey(false)).set_rs(*server.public());
+        rb.set_shared_s(server.clone());
+        let mut i: HS = ib.build_handshake_state();
+        let mut r: HS = rb.build_handshake_state();
+        run_handshake(&mut i, &mut r);
+        assert_eq!(i.get_hash(), r.get_hash());
+    }
+}
+
+#[test]
+fn precomputed_static_key() {
+    use std::sync::Arc;
+
+    let server_key = X25519::genkey(false);
+    let cache = Arc::new(MemoryStaticDhCache::<X25519>::new(16));
+    let server = PrecomputedStatic::<X25519>::from_private(server_key.private.clone())
+        .with_dh_cache(cache.clone());
+    assert_eq!(server.public(), &server_key.public);
+
+    let client = X25519::genkey(false);
+    for _ in 0..2 {
+        let (mut ib, mut rb) = builders(noise_kk(), b"");
+        ib.set_s(client.clone()).set_rs(*server.public());
+        rb.local_static_precomputed(&server).set_rs(client.public);
+        let mut i: HS = ib.build_handshake_state();
+        let mut r: HS = rb.build_handshake_state();
+        run_handshake(&mut i, &mut r);
+        assert_eq!(i.get_hash(), r.get_hash());
+        assert_eq!(cache.len(), 1);
+    }
+}
+
+#[test]
+fn pairing() {
+    use noise_protocol::pairing::*;
+
+    let mode = PairingMode::NumericComparison;
+    assert!(mode.needs_comparison());
+    let (mut ib, mut rb) = builders(mode.pattern(), b"pair");
+    ib.set_s(X25519::genkey(false));
+    rb.set_s(X25519::genkey(false));
+    let (mut i, mut r): (HS, HS) = (ib.build_handshake_state(), rb.build_handshake_state());
+    run_handshake(&mut i, &mut r);
+
+    let sas = short_auth_string(&i);
+    assert_eq!(sas, short_auth_string(&r));
+    assert!(sas.value() < 1_000_000);
+    assert_eq!(sas.to_string().len(), 6);
+
+    let it = confirmation_tag(&i);
+    let rt = confirmation_tag(&r);
+    assert!(verify_confirmation_tag(&r, it.as_slice()));
+    assert!(verify_confirmation_tag(&i, rt.as_slice()));
+    // A tag can not be reflected back to its sender.
+    assert!(!verify_confirmation_tag(&i, it.as_slice()));
+
+    let mode = PairingMode::OutOfBand;
+    let (ib, rb) = builders(mode.pattern(), b"pair");
+    let (mut i, mut r): (HS, HS) = (ib.build_handshake_state(), rb.build_handshake_state());
+    i.push_psk(&[1; 32]);
+    r.push_psk(&[2; 32]);
+    let m = i.write_message_vec(&[]).unwrap();
+    assert!(r.read_message_vec(&m).is_err());
+}
+
+// A deterministic RNG, for tests only.
+struct CounterRng(u64);
+
+impl rand_core::RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let x = (self.0 ^ (self.0 >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x ^ (x >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let x = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&x[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl rand_core::CryptoRng for CounterRng {}
+
+#[test]
+fn genkey_with_rng() {
+    assert_eq!(
+        X25519::genkey_with_rng(&mut CounterRng(1), false).public,
+        X25519::genkey_with_rng(&mut CounterRng(1), false).public
+    );
+    assert_ne!(
+        X25519::genkey_with_rng(&mut CounterRng(1), false).public,
+        X25519::genkey_with_rng(&mut CounterRng(2), false).public
+    );
+
+    let transcript = |seed| {
+        let mut rng = CounterRng(seed);
+        let mut i: HS = HandshakeState::new(noise_nn(), true, false, b"", None, None, None, None);
+        let mut r: HS = HandshakeState::new(noise_nn(), false, false, b"", None, None, None, None);
+        let mut m1 = vec![0u8; i.get_next_message_overhead()];
+        i.write_message_with_rng(&mut rng, &[], &mut m1).unwrap();
+        r.read_message_vec(&m1).unwrap();
+        let mut m2 = vec![0u8; r.get_next_message_overhead()];
+        r.write_message_with_rng(&mut rng, &[], &mut m2).unwrap();
+        i.read_message_vec(&m2).unwrap();
+        (m1, m2, i.get_hash().to_vec())
+    };
+    assert_eq!(transcript(7), transcript(7));
+    assert_ne!(transcript(7).0, transcript(8).0);
+}
+
+// X25519, without its own `genkey_with_rng`.
+enum OsRngOnly {}
+
+impl DH for OsRngOnly {
+    type Key = <X25519 as DH>::Key;
+    type Pubkey = <X25519 as DH>::Pubkey;
+    type Output = <X25519 as DH>::Output;
+
+    fn name() -> &'static str {
+        X25519::name()
+    }
+
+    fn genkey(elligator: bool) -> DhKeyPair<Self::Key, Self::Pubkey> {
+        X25519::genkey(elligator)
+    }
+
+    fn pubkey(k: &Self::Key) -> Self::Pubkey {
+        X25519::pubkey(k)
+    }
+
+    fn dh(k: &Self::Key, pk: &Self::Pubkey, elligator: bool) -> Result<Self::Output, Error> {
+        X25519::dh(k, pk, elligator)
+    }
+}
+
+#[test]
+fn genkey_with_rng_defaults_to_genkey() {
+    // Falls back to the OS RNG: the keys differ despite the same seed.
+    assert_ne!(
+        OsRngOnly::genkey_with_rng(&mut CounterRng(1), false).public,
+        OsRngOnly::genkey_with_rng(&mut CounterRng(1), false).public
+    );
+
+    let mut i = HandshakeState::<OsRngOnly, ChaCha20Poly1305, Blake2s>::new(
+        noise_nn(),
+        true,
+        false,
+        b"",
+        None,
+        None,
+        None,
+        None,
+    );
+    let mut m = vec![0u8; i.get_next_message_overhead()];
+    i.write_message_with_rng(&mut CounterRng(1), &[], &mut m)
+        .unwrap();
+}
+
+#[test]
+fn elligator_encode_decode() {
+    use noise_rust_crypto::elligator::{decode_representative, encode_pubkey};
+
+    let k = X25519::genkey(false);
+    for _ in 0..16 {
+        let other = X25519::genkey(true);
+        let pk = decode_representative(&other.public);
+        assert_eq!(
+            *X25519::dh(&k.private, &pk, false).unwrap(),
+            *X25519::dh(&k.private, &other.public, true).unwrap()
+        );
+        for tweak in 0..2 {
+            let r = encode_pubkey(&pk, tweak).unwrap();
+            assert_eq!(r[31] >> 7, tweak);
+            assert_eq!(decode_representative(&r), pk);
+        }
+    }
+}
+
+#[test]
+fn queued_psks_survive_export() {
+    let (mut ib, mut rb) = builders(noise_nn_psk0(), b"");
+    ib.push_psk(&[3; 32]);
+    rb.push_psk(&[3; 32]);
+    let i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+    let mut i = HS::import_state(&i.export_state(), None).unwrap();
+    run_handshake(&mut i, &mut r);
+}
+
+#[test]
+fn opaque_static_key() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Stands in for a key in a TPM, which only exposes DH operations.
+    struct Device {
+        key: DhKeyPair<<X25519 as DH>::Key, [u8; 32]>,
+        uses: AtomicUsize,
+    }
+
+    impl OpaqueDh<X25519> for Device {
+        fn pubkey(&self) -> [u8; 32] {
+            self.key.public
+        }
+
+        fn dh(
+            &self,
+            remote: &[u8; 32],
+            is_elligator_encoded: bool,
+        ) -> Result<<X25519 as DH>::Output, Error> {
+            self.uses.fetch_add(1, Ordering::Relaxed);
+            X25519::dh(&self.key.private, remote, is_elligator_encoded)
+        }
+    }
+
+    let device = Arc::new(Device {
+        key: X25519::genkey(false),
+        uses: AtomicUsize::new(0),
+    });
+    let (mut ib, mut rb) = builders(noise_xx(), b"");
+    ib.set_opaque_s(device.clone());
+    rb.set_s(X25519::genkey(false));
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_hash(), r.get_hash());
+    assert_eq!(r.get_rs(), Some(device.key.public));
+    // The `se` token of the last message.
+    assert_eq!(device.uses.load(Ordering::Relaxed), 1);
+}
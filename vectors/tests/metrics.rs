@@ -0,0 +1,131 @@
+// Metrics of the `metrics` feature of noise-protocol, through a debugging
+// recorder.
+
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use metrics_util::MetricKind;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+type HS = HandshakeState<X25519, ChaCha20Poly1305, Blake2s>;
+
+fn nn(initiator: bool) -> HS {
+    let mut b = HandshakeStateBuilder::<X25519>::new();
+    b.set_pattern(noise_nn())
+        .set_is_initiator(initiator)
+        .set_prologue(b"");
+    b.build_handshake_state()
+}
+
+#[test]
+fn handshake_counters() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    // One handshake completes. In another, the responder completes, but the
+    // initiator fails to decrypt its message.
+    let (mut i, mut r) = (nn(true), nn(false));
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"hello").unwrap();
+    i.read_message_vec(&m).unwrap();
+
+    let (mut i, mut r) = (nn(true), nn(false));
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let mut m = r.write_message_vec(b"hello").unwrap();
+    *m.last_mut().unwrap() ^= 1;
+    i.read_message_vec(&m).unwrap_err();
+
+    // With a key ring, the responder first fails with its current key, then
+    // succeeds with the previous one, all in one handshake.
+    let ik = |initiator: bool, s, rs: Option<<X25519 as DH>::Pubkey>| -> HS {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_ik())
+            .set_is_initiator(initiator)
+            .set_prologue(b"")
+            .set_s(s);
+        if let Some(rs) = rs {
+            b.set_rs(rs);
+        }
+        b.build_handshake_state()
+    };
+    let old = X25519::genkey(false);
+    let mut ring = KeyRing::<X25519>::new(old.clone());
+    ring.rotate(X25519::genkey(false));
+    let mut i = ik(true, X25519::genkey(false), Some(old.public));
+    let m = i.write_message_vec(b"").unwrap();
+    let (mut r, _) = ring
+        .read_message(|s| ik(false, s, None), &m, &mut [])
+        .unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+
+    // A fallback continues the same handshake.
+    let mut alice = ik(true, X25519::genkey(false), Some(old.public));
+    let m = alice.write_message_vec(b"").unwrap();
+    let mut bob = ik(false, X25519::genkey(false), None);
+    bob.read_message_vec(&m).unwrap_err();
+    let mut bob = bob.fallback(noise_xx_fallback()).unwrap();
+    let mut alice = alice.fallback(noise_xx_fallback()).unwrap();
+    alice
+        .read_message_vec(&bob.write_message_vec(b"").unwrap())
+        .unwrap();
+    bob.read_message_vec(&alice.write_message_vec(b"").unwrap())
+        .unwrap();
+    assert!(alice.completed() && bob.completed());
+
+    // Only transport payload bytes are counted, not the in-band rekey
+    // header.
+    let mut i = TransportState::from_handshake(&alice);
+    let mut r = TransportState::from_handshake(&bob);
+    let policy = RekeyPolicy {
+        in_band: true,
+        ..Default::default()
+    };
+    i.set_rekey_policy(policy);
+    r.set_rekey_policy(policy);
+    let mut m = [0u8; 64];
+    let mut out = [0u8; 64];
+    let len = i.send(b"hello", &mut m).unwrap();
+    assert_eq!(r.recv(&m[..len], &mut out).unwrap(), 5);
+
+    let counter = |name: &str, labels: &[(&str, &str)]| {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .find_map(|(key, _, _, value)| {
+                let (kind, key) = key.into_parts();
+                let matches = kind == MetricKind::Counter
+                    && key.name() == name
+                    && key
+                        .labels()
+                        .map(|l| (l.key(), l.value()))
+                        .eq(labels.iter().copied());
+                match value {
+                    DebugValue::Counter(n) if matches => Some(n),
+                    _ => None,
+                }
+            })
+    };
+    assert_eq!(counter("noise_handshakes_started_total", &[]), Some(7));
+    assert_eq!(counter("noise_handshakes_completed_total", &[]), Some(7));
+    assert_eq!(
+        counter("noise_handshakes_failed_total", &[("reason", "decryption")]),
+        Some(3)
+    );
+    assert_eq!(
+        counter("noise_handshakes_failed_total", &[("reason", "dh")]),
+        None
+    );
+    assert_eq!(
+        counter("noise_transport_bytes_total", &[("direction", "sent")]),
+        Some(5)
+    );
+    assert_eq!(
+        counter("noise_transport_bytes_total", &[("direction", "received")]),
+        Some(5)
+    );
+}
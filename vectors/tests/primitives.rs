@@ -0,0 +1,391 @@
+// Ciphers, hashes and the symmetric state beyond the default suite.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, KeccakDomain, Kmac256, X25519};
+
+#[test]
+fn kmac256() {
+    // NIST SP 800-185 KMAC256 sample #4.
+    enum Tagged {}
+    impl KeccakDomain for Tagged {
+        const CUSTOMIZATION: &'static [u8] = b"My Tagged Application";
+    }
+    let key: Vec<u8> = (0x40..0x60).collect();
+    let mac = Kmac256::<Tagged>::hmac(&key, &[0, 1, 2, 3]);
+    assert_eq!(
+        hex::encode(mac.as_slice()),
+        "20c570c31346f703c9ac36c61c03cb64c3970d0cfc787e9b79599d273a68d2f7\
+         f69d4cc3de9d104a351689f27cf6f5951f0103f33f4f24871024d9c27773a8dd"
+    );
+
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(&[]);
+    let mut i = ib.build_handshake_state::<ChaCha20Poly1305, Kmac256>();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_nn())
+        .set_is_initiator(false)
+        .set_prologue(&[]);
+    let mut r = rb.build_handshake_state::<ChaCha20Poly1305, Kmac256>();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    assert_eq!(i.get_hash(), r.get_hash());
+    assert_eq!(i.get_hash().len(), 64);
+}
+
+#[test]
+fn cascade_cipher() {
+    use noise_rust_crypto::ChaChaPolyAesGcm;
+
+    type CascadeHS = HandshakeState<X25519, ChaChaPolyAesGcm, Blake2s>;
+
+    let (mut ib, mut rb) = builders(noise_xx(), b"");
+    ib.set_s(X25519::genkey(false));
+    rb.set_s(X25519::genkey(false));
+    let mut i: CascadeHS = ib.build_handshake_state();
+    let mut r: CascadeHS = rb.build_handshake_state();
+
+    while !i.completed() {
+        let (send, recv) = if i.is_write_turn() {
+            (&mut i, &mut r)
+        } else {
+            (&mut r, &mut i)
+        };
+        let overhead = send.get_next_message_overhead();
+        let m = send.write_message_vec(b"payload").unwrap();
+        assert_eq!(m.len(), overhead + 7);
+        assert_eq!(recv.read_message_vec(&m).unwrap(), b"payload");
+    }
+
+    let (mut ic, _) = i.get_ciphers();
+    let (mut rc, _) = r.get_ciphers();
+    let mut c = ic.encrypt_vec(b"hello");
+    assert_eq!(c.len(), 5 + 32);
+    assert_eq!(rc.clone().decrypt_vec(&c).unwrap(), b"hello");
+    let len = c.len();
+    assert_eq!(rc.decrypt_in_place(&mut c, len).ok(), Some(5));
+    assert_eq!(&c[..5], b"hello");
+
+    ic.rekey();
+    rc.rekey();
+    let c = ic.encrypt_vec(b"again");
+    assert_eq!(rc.decrypt_vec(&c).unwrap(), b"again");
+}
+
+#[test]
+fn aes_hardware_acceleration() {
+    use noise_rust_crypto::{accel, Aes128Gcm, Aes256Gcm};
+
+    let accel = accel::detect();
+    let hw = Aes256Gcm::is_hardware_accelerated();
+    assert_eq!(hw, accel.aes && accel.clmul);
+    assert_eq!(Aes128Gcm::is_hardware_accelerated(), hw);
+}
+
+#[test]
+fn xchacha20poly1305() {
+    use noise_rust_crypto::XChaCha20Poly1305;
+
+    // Nonce 1 is the 24-byte nonce of 16 zero bytes and 1 in little endian.
+    let k = <XChaCha20Poly1305 as Cipher>::Key::from_slice(&(0..32).collect::<Vec<u8>>());
+    let mut c = [0u8; 5 + 16];
+    XChaCha20Poly1305::encrypt(&k, 1, b"ad", b"hello", &mut c);
+    assert_eq!(hex::encode(c), "5a0d3b48e3af15a9054914e0687affe8e54a44cfc8");
+    let mut p = [0u8; 5];
+    XChaCha20Poly1305::decrypt(&k, 1, b"ad", &c, &mut p).unwrap();
+    assert_eq!(&p, b"hello");
+    assert!(XChaCha20Poly1305::decrypt(&k, 2, b"ad", &c, &mut p).is_err());
+
+    type XChaChaHs = HandshakeState<X25519, XChaCha20Poly1305, Blake2s>;
+    let (ib, rb) = builders(noise_nn(), b"");
+    let mut i: XChaChaHs = ib.build_handshake_state();
+    let mut r: XChaChaHs = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+    let c = i.get_ciphers().0.encrypt_vec(b"hello");
+    assert_eq!(r.get_ciphers().0.decrypt_vec(&c).unwrap(), b"hello");
+}
+
+#[test]
+fn blake3() {
+    use noise_rust_crypto::Blake3;
+
+    assert_eq!(
+        hex::encode(Blake3::hash(b"").as_slice()),
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+    let mut h = Blake3::default();
+    h.input(b"ab");
+    h.input(b"c");
+    assert_eq!(
+        hex::encode(h.result().as_slice()),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+    // `result` resets the state.
+    assert_eq!(h.result().as_slice(), Blake3::hash(b"").as_slice());
+
+    type Blake3Hs = HandshakeState<X25519, ChaCha20Poly1305, Blake3>;
+    let (ib, rb) = builders(noise_nn(), b"");
+    let mut i: Blake3Hs = ib.build_handshake_state();
+    let mut r: Blake3Hs = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_hash(), r.get_hash());
+}
+
+#[test]
+fn sha3() {
+    use noise_rust_crypto::{Sha3_256, Sha3_512};
+
+    assert_eq!(
+        hex::encode(Sha3_256::hash(b"").as_slice()),
+        "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+    );
+    assert_eq!(
+        hex::encode(Sha3_512::hash(b"abc").as_slice()),
+        "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e\
+         10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"
+    );
+    // HMAC uses the rate as block length.
+    assert_eq!(
+        hex::encode(Sha3_256::hmac(b"key", b"data").as_slice()),
+        "b102a8999ec7667682d13c250db75c1fe88632bc807d915ecb7599375c2a393a"
+    );
+    assert_eq!(
+        hex::encode(Sha3_512::hmac(b"key", b"data").as_slice()),
+        "752bf49d54115aaa670ea62bdf79eb95e6df787938bec5fabdfc4745cf49f7fe\
+         11b7c2f73989ad2e568f06ced3a2d99536b05a121f43647b98ea43f818f38b33"
+    );
+}
+
+#[test]
+fn aes128gcm() {
+    use noise_rust_crypto::Aes128Gcm;
+
+    // Test case 2 of the GCM spec, with nonce 0.
+    let key = <Aes128Gcm as Cipher>::Key::new();
+    let mut out = [0u8; 32];
+    Aes128Gcm::encrypt(&key, 0, &[], &[0; 16], &mut out);
+    assert_eq!(
+        hex::encode(out),
+        "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf"
+    );
+
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(&[]);
+    let mut i = ib.build_handshake_state::<Aes128Gcm, Blake2s>();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_nn())
+        .set_is_initiator(false)
+        .set_prologue(&[]);
+    let mut r = rb.build_handshake_state::<Aes128Gcm, Blake2s>();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"hello").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"hello");
+
+    let (mut send, _) = i.get_ciphers();
+    let (mut recv, _) = r.get_ciphers();
+    send.rekey();
+    recv.rekey();
+    let c = send.encrypt_vec(b"world");
+    assert_eq!(recv.decrypt_vec(&c).unwrap(), b"world");
+}
+
+#[test]
+fn aes256gcmsiv() {
+    use noise_rust_crypto::Aes256GcmSiv;
+
+    assert_eq!(Aes256GcmSiv::name(), "AESGCMSIV");
+    let key = vec![7u8; 32];
+    let mut a = CipherState::<Aes256GcmSiv>::new(&key, 0);
+    let mut b = CipherState::<Aes256GcmSiv>::new(&key, 0);
+    let c = a.encrypt_vec(b"hello");
+    assert_eq!(b.decrypt_vec(&c).unwrap(), b"hello");
+    // Not AES-GCM.
+    let mut gcm = CipherState::<noise_rust_crypto::Aes256Gcm>::new(&key, 0);
+    assert_ne!(gcm.encrypt_vec(b"hello"), c);
+
+    let mut c = a.encrypt_vec(b"world");
+    c[0] ^= 1;
+    assert!(b.decrypt_vec(&c).is_err());
+
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(&[]);
+    let mut i = ib.build_handshake_state::<Aes256GcmSiv, Blake2s>();
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_nn())
+        .set_is_initiator(false)
+        .set_prologue(&[]);
+    let mut r = rb.build_handshake_state::<Aes256GcmSiv, Blake2s>();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"hello").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"hello");
+}
+
+noise_rust_crypto::impl_noise_cipher!(
+    /// AES-256-GCM through the generic adapter.
+    GenericAesGcm,
+    aes_gcm::Aes256Gcm,
+    "AESGCM",
+    32
+);
+
+#[test]
+fn generic_aead_cipher() {
+    use noise_rust_crypto::Aes256Gcm;
+
+    assert_eq!(GenericAesGcm::name(), "AESGCM");
+    let key = [7u8; 32];
+    let mut a = CipherState::<GenericAesGcm>::new(&key, 0);
+    let mut b = CipherState::<Aes256Gcm>::new(&key, 0);
+    for _ in 0..3 {
+        let c = a.encrypt_vec(b"hello");
+        assert_eq!(c, b.encrypt_vec(b"hello"));
+    }
+
+    let mut b = CipherState::<GenericAesGcm>::new(&key, 0);
+    let mut c = a.encrypt_vec(b"");
+    assert!(b.decrypt_vec(&c).is_err());
+    c = CipherState::<GenericAesGcm>::new(&key, 0).encrypt_vec(b"world");
+    assert_eq!(b.decrypt_vec(&c).unwrap(), b"world");
+}
+
+#[test]
+fn generic_digest_hash() {
+    use noise_rust_crypto::generic_hash::{DigestHash, HashName};
+    use noise_rust_crypto::Sha256;
+
+    enum Sha256Name {}
+
+    impl HashName for Sha256Name {
+        const NAME: &'static str = "SHA256";
+    }
+
+    type GenericSha256 = DigestHash<sha2::Sha256, Sha256Name>;
+
+    assert_eq!(GenericSha256::name(), "SHA256");
+    assert_eq!(GenericSha256::block_len(), 64);
+    assert_eq!(GenericSha256::hash_len(), 32);
+    assert_eq!(
+        GenericSha256::hash(b"abc").as_slice(),
+        Sha256::hash(b"abc").as_slice()
+    );
+    let mut h = GenericSha256::default();
+    h.input(b"a");
+    h.input(b"bc");
+    assert_eq!(h.result().as_slice(), Sha256::hash(b"abc").as_slice());
+    // Reset after the result.
+    assert_eq!(h.result().as_slice(), Sha256::hash(b"").as_slice());
+    assert_eq!(
+        GenericSha256::hmac(b"key", b"data").as_slice(),
+        Sha256::hmac(b"key", b"data").as_slice()
+    );
+
+    // Same handshake as with the hand-written wrapper.
+    let hs = |initiator: bool| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_nn())
+            .set_is_initiator(initiator)
+            .set_prologue(&[]);
+        b
+    };
+    let mut i = hs(true).build_handshake_state::<ChaCha20Poly1305, GenericSha256>();
+    let mut r = hs(false).build_handshake_state::<ChaCha20Poly1305, Sha256>();
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"hello").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"hello");
+    assert_eq!(i.get_hash(), r.get_hash());
+}
+
+#[test]
+fn symmetric_state() {
+    type SS = SymmetricState<ChaCha20Poly1305, Blake2s>;
+
+    // Replay the responder of `Noise_NN` by hand.
+    let e = X25519::genkey(false);
+    let mut ib = HandshakeStateBuilder::<X25519>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_prologue(b"prologue");
+    let mut i: HS = ib.build_handshake_state();
+    let m1 = i.write_message_vec(b"one").unwrap();
+
+    let mut ss = SS::new(b"Noise_NN_25519_ChaChaPoly_BLAKE2s");
+    ss.mix_hash(b"prologue");
+    let re = &m1[..32];
+    ss.mix_hash(re);
+    assert_eq!(ss.overhead(), 0);
+    let mut payload = [0u8; 3];
+    ss.decrypt_and_hash(&m1[32..], &mut payload).unwrap();
+    assert_eq!(&payload, b"one");
+
+    let mut m2 = e.public.to_vec();
+    ss.mix_hash(&e.public);
+    let re: [u8; 32] = re.try_into().unwrap();
+    ss.mix_key(X25519::dh(&e.private, &re, false).unwrap().as_slice());
+    let mut c = vec![0u8; 3 + ss.overhead()];
+    ss.encrypt_and_hash(b"two", &mut c);
+    m2.extend_from_slice(&c);
+
+    assert_eq!(i.read_message_vec(&m2).unwrap(), b"two");
+    assert_eq!(i.get_hash(), ss.get_hash());
+    let (mut ic, _) = i.get_ciphers();
+    let (mut rc, _) = ss.split();
+    assert_eq!(rc.decrypt_vec(&ic.encrypt_vec(b"three")).unwrap(), b"three");
+}
+
+#[test]
+fn symmetric_state_vec() {
+    type SS = SymmetricState<ChaCha20Poly1305, Blake2s>;
+
+    let mut a = SS::new(b"MyProtocol_v1");
+    let mut b = SS::new(b"MyProtocol_v1");
+    // Without a key, in the clear.
+    let m = a.encrypt_and_hash_vec(b"hello");
+    assert_eq!(m, b"hello");
+    assert_eq!(b.decrypt_and_hash_vec(&m).unwrap(), b"hello");
+
+    a.mix_key(b"secret");
+    b.mix_key(b"secret");
+    let m = a.encrypt_and_hash_vec(b"world");
+    assert_eq!(m.len(), 5 + 16);
+    assert_eq!(
+        b.decrypt_and_hash_vec(&m[..15]).unwrap_err().kind(),
+        ErrorKind::TooShort
+    );
+    let mut tampered = m.clone();
+    tampered[0] ^= 1;
+    assert!(b.decrypt_and_hash_vec(&tampered).is_err());
+    // Failures do not change the state.
+    assert_eq!(b.decrypt_and_hash_vec(&m).unwrap(), b"world");
+    assert_eq!(a.get_hash(), b.get_hash());
+}
+
+#[test]
+fn util_hkdf() {
+    use noise_protocol::util::hkdf;
+
+    let ck = [1u8; 32];
+    let (a, b) = Blake2s::hkdf(&ck, b"ikm");
+    let (_, _, c) = Blake2s::hkdf3(&ck, b"ikm");
+    let outputs = hkdf::<Blake2s>(&ck, b"ikm", 3);
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[0].as_slice(), a.as_slice());
+    assert_eq!(outputs[1].as_slice(), b.as_slice());
+    assert_eq!(outputs[2].as_slice(), c.as_slice());
+    let outputs = hkdf::<Blake2s>(&ck, b"ikm", 1);
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].as_slice(), a.as_slice());
+}
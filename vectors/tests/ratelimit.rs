@@ -0,0 +1,108 @@
+// Handshake rate limiting and client puzzles.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+#[test]
+fn handshake_rate_limit() {
+    use noise_protocol::demux::{Demux, Packet, Route};
+    use noise_protocol::ratelimit::RateLimiter;
+    use std::time::Duration;
+
+    let mut server = Demux::<HS>::new(Duration::from_secs(60));
+    let limiter = RateLimiter::with_limit(Duration::from_secs(60), 16, 2);
+    let datagram = Packet::HandshakeInit {
+        sender: 1,
+        message: &[0; 32],
+    }
+    .to_vec();
+
+    for _ in 0..2 {
+        let r = server.route_from(&datagram, &"a", false, &limiter);
+        assert!(matches!(r, Some(Route::New { sender: 1, .. })));
+    }
+    let r = server.route_from(&datagram, &"a", false, &limiter);
+    assert!(matches!(r, Some(Route::Challenge { sender: 1 })));
+    let r = server.route_from(&datagram, &"a", true, &limiter);
+    assert!(matches!(r, Some(Route::New { .. })));
+    let r = server.route_from(&datagram, &"b", false, &limiter);
+    assert!(matches!(r, Some(Route::New { .. })));
+}
+
+#[test]
+fn puzzle() {
+    use noise_protocol::puzzle::{bind_prologue, Challenge, Solution};
+
+    let challenge = Challenge::new(8, [7; 16]);
+    let challenge = Challenge::from_bytes(&challenge.to_bytes()).unwrap();
+    let solution = challenge.solve::<Blake2s>();
+    let solution = Solution::from_bytes(&solution.to_bytes()).unwrap();
+    assert!(challenge.verify::<Blake2s>(&solution));
+
+    // The responder's challenge must match the one the initiator solved.
+    for (responder_challenge, ok) in [(challenge, true), (Challenge::new(8, [9; 16]), false)] {
+        let ip = bind_prologue(b"app", &challenge, &solution);
+        let rp = bind_prologue(b"app", &responder_challenge, &solution);
+        let mut ib = HandshakeStateBuilder::<X25519>::new();
+        ib.set_pattern(noise_nn())
+            .set_is_initiator(true)
+            .set_prologue(&ip);
+        let mut rb = HandshakeStateBuilder::<X25519>::new();
+        rb.set_pattern(noise_nn())
+            .set_is_initiator(false)
+            .set_prologue(&rp);
+        let mut i: HS = ib.build_handshake_state();
+        let mut r: HS = rb.build_handshake_state();
+        let m = i.write_message_vec(&[]).unwrap();
+        r.read_message_vec(&m).unwrap();
+        let m = r.write_message_vec(&[]).unwrap();
+        assert_eq!(i.read_message_vec(&m).is_ok(), ok);
+    }
+}
+
+#[test]
+fn handshake_limiter() {
+    use noise_protocol::ratelimit::{HandshakeLimiter, HandshakeLimits};
+    use std::time::Duration;
+
+    let memory = HandshakeLimiter::handshake_memory::<X25519, ChaCha20Poly1305, Blake2s>();
+    let limiter = HandshakeLimiter::new(HandshakeLimits {
+        max_handshakes: 2,
+        max_memory: memory * 3,
+        timeout: Duration::from_millis(200),
+    });
+
+    let a = limiter.try_acquire(memory).unwrap();
+    let b = limiter.try_acquire(memory).unwrap();
+    assert!(limiter.try_acquire(memory).is_none());
+    assert_eq!(limiter.memory(), memory * 2);
+    drop(a);
+    assert!(limiter.try_acquire(memory * 3).is_none());
+    let c = limiter.try_acquire(memory).unwrap();
+
+    // Queued until a handshake finishes.
+    let l = limiter.clone();
+    let t = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        drop(c);
+        l
+    });
+    let d = limiter
+        .acquire_timeout(memory, Duration::from_secs(5))
+        .unwrap();
+    let limiter = t.join().unwrap();
+
+    // Or until stalled ones expire.
+    assert!(limiter
+        .acquire_timeout(memory, Duration::from_secs(5))
+        .is_some());
+    assert!(b.is_expired());
+    assert!(!d.is_expired());
+    drop(d);
+    assert_eq!(limiter.in_flight(), 0);
+    assert_eq!(limiter.memory(), 0);
+}
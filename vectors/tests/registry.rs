@@ -0,0 +1,162 @@
+// Handshakes created from protocol names, at run time and at compile time.
+
+mod common;
+
+use common::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, Kmac256, X25519};
+
+#[test]
+fn registry_handshake() {
+    use noise_protocol::registry::*;
+
+    let mut registry = Registry::new();
+    registry
+        .register::<X25519, ChaCha20Poly1305, Blake2s>()
+        .register::<X25519, ChaCha20Poly1305, Kmac256>();
+    assert!(registry.contains("25519", "ChaChaPoly", "KMAC256"));
+
+    let (s, _) = registry.generate_keypair("25519", false).unwrap();
+    let (rs_private, rs) = registry.generate_keypair("25519", false).unwrap();
+    let name = "Noise_IKpsk2_25519_ChaChaPoly_BLAKE2s";
+    let psks = [[7u8; 32]];
+    let mut i = registry
+        .new_handshake(
+            name,
+            &HandshakeParams {
+                is_initiator: true,
+                s: Some(&s),
+                rs: Some(&rs),
+                psks: &psks,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let mut r = registry
+        .new_handshake(
+            name,
+            &HandshakeParams {
+                s: Some(&rs_private),
+                psks: &psks,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+    let m = i.write_message_vec(b"hello").unwrap();
+    assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    assert!(i.completed() && r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+
+    let (mut i_send, _) = i.get_ciphers();
+    let (mut r_recv, _) = r.get_ciphers();
+    let c = i_send.encrypt_vec(b"transport");
+    assert_eq!(r_recv.decrypt_vec(&c).unwrap(), b"transport");
+
+    let err = |name, params: &HandshakeParams| registry.new_handshake(name, params).err();
+    let params = HandshakeParams::default();
+    assert_eq!(
+        err("Noise_NN_25519_AESGCM_BLAKE2s", &params),
+        Some(RegistryError::UnknownSuite)
+    );
+    assert_eq!(
+        err("Noise_ZZ_25519_ChaChaPoly_BLAKE2s", &params),
+        Some(RegistryError::UnknownPattern)
+    );
+    assert_eq!(
+        err("Noise_NN_25519_ChaChaPoly", &params),
+        Some(RegistryError::InvalidProtocolName)
+    );
+    assert_eq!(
+        err(
+            "Noise_NK_25519_ChaChaPoly_BLAKE2s",
+            &HandshakeParams {
+                is_initiator: true,
+                ..params
+            }
+        ),
+        Some(RegistryError::MissingKey)
+    );
+}
+
+#[test]
+fn resolve_compiled_in_suites() {
+    use noise_protocol::registry::{HandshakeParams, RegistryError};
+    use noise_rust_crypto::registry::{registry, resolve};
+
+    let registry = registry();
+    for (cipher, hash) in [
+        ("ChaChaPoly", "BLAKE2b"),
+        ("AESGCM", "SHA512"),
+        ("XChaChaPoly", "BLAKE3"),
+        ("AESGCMSIV", "SHA3-256"),
+    ] {
+        assert!(registry.contains("25519", cipher, hash), "{cipher} {hash}");
+    }
+
+    let initiator = HandshakeParams {
+        is_initiator: true,
+        ..Default::default()
+    };
+    let responder = HandshakeParams::default();
+    for name in [
+        "Noise_NN_25519_ChaChaPoly_BLAKE2s",
+        "Noise_NN_448_AESGCM_SHA256",
+        "Noise_NN_25519_ChaChaPoly+AESGCM_SHA512",
+    ] {
+        let mut i = match resolve(name, &initiator) {
+            Ok(i) => i,
+            // X448 is not available in every build.
+            Err(RegistryError::UnknownSuite) if name.contains("448") => continue,
+            Err(e) => panic!("{name}: {e}"),
+        };
+        let mut r = resolve(name, &responder).unwrap();
+        while !i.completed() {
+            let (send, recv) = if i.is_write_turn() {
+                (&mut i, &mut r)
+            } else {
+                (&mut r, &mut i)
+            };
+            let m = send.write_message_vec(b"").unwrap();
+            recv.read_message_vec(&m).unwrap();
+        }
+        assert_eq!(i.get_hash(), r.get_hash());
+    }
+
+    assert_eq!(
+        resolve("Noise_NN_25519_Rot13_BLAKE2s", &initiator).err(),
+        Some(RegistryError::UnknownSuite)
+    );
+}
+
+noise_rust_crypto::protocol!(mod xx = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s");
+
+#[test]
+fn protocol_macro() {
+    let name = format!(
+        "Noise_{}_{}_{}_{}",
+        xx::PATTERN.get_name(),
+        <xx::Dh as DH>::name(),
+        <xx::Cipher as Cipher>::name(),
+        <xx::Hash as Hash>::name()
+    );
+    assert_eq!(name, xx::NAME);
+    let mut ib = HandshakeStateBuilder::<xx::Dh>::new();
+    ib.set_pattern(xx::PATTERN)
+        .set_is_initiator(true)
+        .set_prologue(b"")
+        .set_s(xx::Dh::genkey(false));
+    let mut rb = HandshakeStateBuilder::<xx::Dh>::new();
+    rb.set_pattern(xx::PATTERN)
+        .set_is_initiator(false)
+        .set_prologue(b"")
+        .set_s(xx::Dh::genkey(false));
+    let mut i: xx::HandshakeState = ib.build_handshake_state();
+    let mut r: xx::HandshakeState = rb.build_handshake_state();
+    i.push_psk(&[7; 32]);
+    r.push_psk(&[7; 32]);
+    run_handshake(&mut i, &mut r);
+    let _: xx::TransportState = TransportState::from_handshake(&i);
+}
@@ -0,0 +1,194 @@
+use noise_protocol::patterns::{noise_ix, noise_kk, noise_nk, noise_nn};
+use noise_protocol::{HandshakeState, HandshakeStateBuilder, ObfuscationConfig, DH};
+use noise_rust_crypto::rfc9380::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+fn le(s: &str) -> [u8; 32] {
+    let mut b: [u8; 32] = hex::decode(s).unwrap().try_into().unwrap();
+    b.reverse();
+    b
+}
+
+// curve25519_XMD:SHA-512_ELL2_NU_, RFC 9380 appendix J.7.2: u[0] and Q.x.
+#[test]
+fn map_to_curve_vectors() {
+    let vectors = [
+        (
+            "608d892b641f0328523802a6603427c26e55e6f27e71a91a478148d45b5093cd",
+            "51125222da5e763d97f3c10fcc92ea6860b9ccbbd2eb1285728f566721c1e65b",
+        ),
+        (
+            "46f5b22494bfeaa7f232cc8d054be68561af50230234d7d1d63d1d9abeca8da5",
+            "7d56d1e08cb0ccb92baf069c18c49bb5a0dcd927eff8dcf75ca921ef7f3e6eeb",
+        ),
+    ];
+    for (u, x) in vectors {
+        assert_eq!(map_to_curve(&le(u)), le(x));
+        // Bit 255 is ignored.
+        let mut u = le(u);
+        u[31] |= 0x80;
+        assert_eq!(map_to_curve(&u), le(x));
+    }
+}
+
+#[test]
+fn representatives() {
+    let x = le("51125222da5e763d97f3c10fcc92ea6860b9ccbbd2eb1285728f566721c1e65b");
+    for tweak in 0..8 {
+        let r = representative(&x, tweak).unwrap();
+        assert_eq!(map_to_curve(&r), x);
+        assert_eq!(r[31] >> 7, tweak >> 2);
+    }
+
+    let k = X25519Rfc9380::genkey(true);
+    let mut high_bits = 0;
+    for _ in 0..64 {
+        let other = X25519Rfc9380::genkey(true);
+        high_bits |= 1 << (other.public[31] >> 7);
+        assert_eq!(
+            *X25519Rfc9380::dh(&k.private, &other.public, true).unwrap(),
+            *X25519Rfc9380::dh(&other.private, &X25519Rfc9380::pubkey(&k.private), false).unwrap(),
+        );
+    }
+    assert_eq!(high_bits, 3);
+}
+
+#[test]
+fn elligator_handshake() {
+    type HS = HandshakeState<X25519Rfc9380, ChaCha20Poly1305, Blake2s>;
+
+    let mut ib = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    ib.set_pattern(noise_nn())
+        .set_is_initiator(true)
+        .set_is_elligator_encoded(true)
+        .set_prologue(b"");
+    let mut rb = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    rb.set_pattern(noise_nn())
+        .set_is_initiator(false)
+        .set_is_elligator_encoded(true)
+        .set_prologue(b"");
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+
+    let m = i.write_message_vec(b"hello").unwrap();
+    assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+    let m = r.write_message_vec(b"world").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"world");
+    assert_eq!(i.get_hash(), r.get_hash());
+}
+
+#[test]
+fn elligator_static_keys() {
+    type HS = HandshakeState<X25519Rfc9380, ChaCha20Poly1305, Blake2s>;
+
+    let is = X25519Rfc9380::genkey(true);
+    let rs = X25519Rfc9380::genkey(true);
+
+    // `IX` sends the static key of the initiator in the clear.
+    let mut ib = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    ib.set_pattern(noise_ix())
+        .set_is_initiator(true)
+        .set_is_elligator_encoded(true)
+        .set_is_static_elligator_encoded(true)
+        .set_prologue(b"")
+        .set_s(is.clone());
+    let mut rb = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    rb.set_pattern(noise_ix())
+        .set_is_initiator(false)
+        .set_is_elligator_encoded(true)
+        .set_is_static_elligator_encoded(true)
+        .set_prologue(b"")
+        .set_s(rs.clone());
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+
+    let m = i.write_message_vec(b"").unwrap();
+    assert_eq!(m[32..64], is.public);
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    assert_eq!(i.get_hash(), r.get_hash());
+    assert_eq!(i.get_rs().unwrap(), rs.public);
+    assert_eq!(r.get_rs().unwrap(), is.public);
+
+    // `SS`, with representatives known ahead of time.
+    let is_public = is.public;
+    let mut ib = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    ib.set_pattern(noise_kk())
+        .set_is_initiator(true)
+        .set_is_static_elligator_encoded(true)
+        .set_prologue(b"")
+        .set_s(is)
+        .set_rs(rs.public);
+    let mut rb = HandshakeStateBuilder::<X25519Rfc9380>::new();
+    rb.set_pattern(noise_kk())
+        .set_is_initiator(false)
+        .set_is_static_elligator_encoded(true)
+        .set_prologue(b"")
+        .set_s(rs)
+        .set_rs(is_public);
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+    let m = i.write_message_vec(b"hello").unwrap();
+    assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+    let m = r.write_message_vec(b"world").unwrap();
+    assert_eq!(i.read_message_vec(&m).unwrap(), b"world");
+}
+
+// Not random, but good enough to vary padding lengths.
+fn fill_random(out: &mut [u8]) {
+    static STATE: AtomicU64 = AtomicU64::new(0x9e37_79b9_7f4a_7c15);
+    for b in out {
+        let x = STATE.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        *b = (x.wrapping_mul(0xbf58_476d_1ce4_e5b9) >> 56) as u8;
+    }
+}
+
+#[test]
+fn obfuscated_handshake() {
+    type HS = HandshakeState<X25519Rfc9380, ChaCha20Poly1305, Blake2s>;
+
+    let config = ObfuscationConfig {
+        min_padding: 16,
+        max_padding: 256,
+        fill_random,
+    };
+    let rs = X25519Rfc9380::genkey(false);
+    let mut lens = Vec::new();
+
+    for _ in 0..8 {
+        let mut ib = HandshakeStateBuilder::<X25519Rfc9380>::new();
+        ib.set_pattern(noise_nk())
+            .set_is_initiator(true)
+            .set_prologue(b"")
+            .set_rs(rs.public);
+        let mut rb = HandshakeStateBuilder::<X25519Rfc9380>::new();
+        rb.set_pattern(noise_nk())
+            .set_is_initiator(false)
+            .set_prologue(b"")
+            .set_s(rs.clone());
+        let mut i: HS = ib.build_handshake_state();
+        let mut r: HS = rb.build_handshake_state();
+        i.set_obfuscation(config);
+        r.set_obfuscation(config);
+
+        let overhead = i.get_next_message_overhead();
+        let m = i.write_message_vec(b"hello").unwrap();
+        assert_eq!(m.len(), 5 + overhead);
+        // e, payload, padding, padding length and tag.
+        assert!((32 + 5 + 16 + 2 + 16..=32 + 5 + 256 + 2 + 16).contains(&m.len()));
+        lens.push(m.len());
+        assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+
+        let m = r.write_message_vec(b"world").unwrap();
+        let mut out = [0u8; 1024];
+        let len = i.read_message_to(&m, &mut out).unwrap();
+        assert_eq!(&out[..len], b"world");
+        assert_eq!(i.get_hash(), r.get_hash());
+    }
+
+    lens.sort_unstable();
+    lens.dedup();
+    assert!(lens.len() > 1);
+}
@@ -0,0 +1,170 @@
+// Ed25519 keys and signature patterns.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+#[test]
+fn ed25519_to_x25519() {
+    use noise_rust_crypto::convert::{ed25519_to_x25519_private, ed25519_to_x25519_public};
+
+    // From libsodium's test/default/ed25519_convert.
+    let seed =
+        hex::decode("421151a459faeade3d247115f94aedae42318124095afabe4d1451a559faedee").unwrap();
+    let ed_pk =
+        hex::decode("b5076a8474a832daee4dd5b4040983b6623b5f344aca57d4d6ee4baf3f259e6e").unwrap();
+    let private = ed25519_to_x25519_private(seed[..].try_into().unwrap());
+    let public = ed25519_to_x25519_public(ed_pk[..].try_into().unwrap()).unwrap();
+    assert_eq!(
+        hex::encode(&private[..]),
+        "8052030376d47112be7f73ed7a019293dd12ad910b654455798b4667d73de166"
+    );
+    assert_eq!(
+        hex::encode(public),
+        "f1814f0e8ff1043d8a44d25babff3cedcae6c22c3edaa48f857ae70de2baae50"
+    );
+    assert_eq!(X25519::pubkey(&private), public);
+
+    let mut identity = [0u8; 32];
+    identity[0] = 1;
+    assert_eq!(ed25519_to_x25519_public(&identity), None);
+
+    let (mut ib, mut rb) = builders(noise_ik(), b"");
+    ib.set_s(X25519::genkey(false)).set_rs(public);
+    rb.set_s(DhKeyPair::from_private::<X25519>(private));
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_hash(), r.get_hash());
+}
+
+#[test]
+fn ed25519_rfc8032() {
+    use noise_rust_crypto::Ed25519;
+
+    // Test 2 of RFC 8032, section 7.1.
+    let k =
+        hex::decode("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb").unwrap();
+    let k = <Ed25519 as Signature>::Key::from_slice(&k);
+    let pk = Ed25519::pubkey(&k);
+    assert_eq!(
+        hex::encode(pk),
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c"
+    );
+    let sig = Ed25519::sign(&k, &[0x72]);
+    assert_eq!(
+        hex::encode(sig),
+        "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+         085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00"
+    );
+    assert!(Ed25519::verify(&pk, &[0x72], &sig).is_ok());
+    assert_eq!(
+        Ed25519::verify(&pk, &[0x73], &sig).unwrap_err().kind(),
+        ErrorKind::Signature
+    );
+}
+
+#[test]
+fn signature_patterns() {
+    use noise_rust_crypto::Ed25519;
+    use std::sync::Arc;
+
+    type SigHs = HandshakeState<X25519, ChaCha20Poly1305, Blake2s, NoKem, Ed25519>;
+
+    let ik = Ed25519::genkey();
+    let rk = Ed25519::genkey();
+
+    // Also built with the checks of `try_build_handshake_state`, the
+    // signing keys set afterwards.
+    let (ib, rb) = builders(noise_xx_sig(), b"");
+    let mut i: SigHs = ib.try_build_handshake_state().unwrap();
+    let mut r: SigHs = rb.try_build_handshake_state().unwrap();
+    i.set_signing_key(ik.clone());
+    r.set_signing_key(rk.clone());
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_remote_signing_key(), Some(&Ed25519::pubkey(&rk)));
+
+    for pattern in patterns::signed() {
+        let (ib, rb) = builders(pattern, b"");
+        let mut i: SigHs = ib.build_signed_handshake_state(ik.clone());
+        let mut r: SigHs = rb.build_signed_handshake_state(rk.clone());
+        run_handshake(&mut i, &mut r);
+        assert_eq!(i.get_hash(), r.get_hash());
+        let signs = |initiator: bool| {
+            (0..pattern.get_message_patterns_len())
+                .filter(|m| (m % 2 == 0) == initiator)
+                .any(|m| {
+                    pattern
+                        .get_message_pattern(m)
+                        .iter()
+                        .any(|t| matches!(t, Token::SIG))
+                })
+        };
+        assert_eq!(
+            i.get_remote_signing_key(),
+            signs(false).then(|| Ed25519::pubkey(&rk)).as_ref()
+        );
+        assert_eq!(
+            r.get_remote_signing_key(),
+            signs(true).then(|| Ed25519::pubkey(&ik)).as_ref()
+        );
+    }
+
+    // The signing key is checked by its own verifier, not by the one of
+    // static DH keys.
+    let nx_sig = |verifier: Arc<dyn RemoteStaticVerifier>| {
+        let (mut ib, rb) = builders(noise_nx_sig(), b"");
+        ib.set_remote_static_verifier(Arc::new(|_: &[u8]| false))
+            .set_remote_signing_key_verifier(verifier);
+        let mut i: SigHs = ib.build_signed_handshake_state(ik.clone());
+        let mut r: SigHs = rb.build_signed_handshake_state(rk.clone());
+        r.read_message_vec(&i.write_message_vec(b"").unwrap())
+            .unwrap();
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).map_err(|e| e.kind())
+    };
+    let rk_public = Ed25519::pubkey(&rk);
+    assert!(nx_sig(Arc::new(move |k: &[u8]| k == rk_public)).is_ok());
+    assert_eq!(
+        nx_sig(Arc::new(|_: &[u8]| false)),
+        Err(ErrorKind::AuthenticationRejected)
+    );
+
+    // Signs another message than the handshake hash.
+    enum Forger {}
+
+    impl Signature for Forger {
+        type Key = <Ed25519 as Signature>::Key;
+        type Pubkey = [u8; 32];
+        type Sig = [u8; 64];
+
+        fn name() -> &'static str {
+            "Ed25519"
+        }
+
+        fn pubkey(k: &Self::Key) -> Self::Pubkey {
+            Ed25519::pubkey(k)
+        }
+
+        fn sign(k: &Self::Key, _: &[u8]) -> Self::Sig {
+            Ed25519::sign(k, b"forged")
+        }
+
+        fn verify(pk: &Self::Pubkey, msg: &[u8], sig: &Self::Sig) -> Result<(), Error> {
+            Ed25519::verify(pk, msg, sig)
+        }
+    }
+
+    let (ib, rb) = builders(noise_nx_sig(), b"");
+    let mut i: SigHs = ib.build_signed_handshake_state(ik);
+    let mut r: HandshakeState<X25519, ChaCha20Poly1305, Blake2s, NoKem, Forger> =
+        rb.build_signed_handshake_state(rk);
+    r.read_message_vec(&i.write_message_vec(b"").unwrap())
+        .unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    let e = i.read_message_vec(&m).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Signature);
+}
@@ -0,0 +1,230 @@
+// Handshakes with snow: through the resolver of noise_rust_crypto::snow_compat,
+// and between snow and this crate for every pattern and suite both support.
+
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::snow_compat::SnowResolver;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+use proptest::prelude::*;
+
+fn snow_builder(name: &str, elligator: bool) -> snow::Builder<'_> {
+    snow::Builder::with_resolver(
+        name.parse().unwrap(),
+        Box::new(SnowResolver::new(elligator)),
+    )
+}
+
+#[test]
+fn snow_resolver_elligator_xx() {
+    let name = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+    let keypair = snow_builder(name, true).generate_keypair().unwrap();
+    assert_eq!(keypair.private.len(), 64);
+    assert_eq!(keypair.public[..], keypair.private[32..]);
+    let mut i = snow_builder(name, true)
+        .local_private_key(&keypair.private)
+        .prologue(b"snow")
+        .build_initiator()
+        .unwrap();
+
+    let mut rb = HandshakeStateBuilder::<X25519>::new();
+    rb.set_pattern(noise_xx())
+        .set_is_initiator(false)
+        .set_prologue(b"snow")
+        .set_is_elligator_encoded(true)
+        .set_is_static_elligator_encoded(true)
+        .set_s(X25519::genkey(true));
+    let mut r: HandshakeState<X25519, ChaCha20Poly1305, Blake2s> = rb.build_handshake_state();
+
+    let mut buf = [0u8; 1024];
+    let len = i.write_message(b"hello", &mut buf).unwrap();
+    assert_eq!(r.read_message_vec(&buf[..len]).unwrap(), b"hello");
+    let msg = r.write_message_vec(b"").unwrap();
+    i.read_message(&msg, &mut buf).unwrap();
+    let len = i.write_message(b"", &mut buf).unwrap();
+    r.read_message_vec(&buf[..len]).unwrap();
+
+    assert!(i.is_handshake_finished() && r.completed());
+    assert_eq!(r.get_rs().unwrap()[..], keypair.public[..]);
+    assert_eq!(i.get_handshake_hash(), r.get_hash());
+
+    let mut i = i.into_transport_mode().unwrap();
+    let mut r = TransportState::from_handshake(&r);
+    let mut out = [0u8; 1024];
+    let len = i.write_message(b"ping", &mut buf).unwrap();
+    let n = r.recv(&buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"ping");
+    let len = r.send(b"pong", &mut buf).unwrap();
+    let n = i.read_message(&buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"pong");
+}
+
+#[test]
+fn snow_resolver_plain_matches_default() {
+    let name = "Noise_NN_25519_AESGCM_SHA256";
+    let mut i = snow_builder(name, false).build_initiator().unwrap();
+    let mut r = snow::Builder::new(name.parse().unwrap())
+        .build_responder()
+        .unwrap();
+
+    let mut buf = [0u8; 1024];
+    let mut out = [0u8; 1024];
+    let len = i.write_message(b"", &mut buf).unwrap();
+    r.read_message(&buf[..len], &mut out).unwrap();
+    let len = r.write_message(b"hi", &mut buf).unwrap();
+    let n = i.read_message(&buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"hi");
+    assert_eq!(i.get_handshake_hash(), r.get_handshake_hash());
+
+    let mut i = i.into_transport_mode().unwrap();
+    let mut r = r.into_transport_mode().unwrap();
+    i.rekey_outgoing();
+    r.rekey_incoming();
+    let len = i.write_message(b"rekeyed", &mut buf).unwrap();
+    let n = r.read_message(&buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"rekeyed");
+}
+
+/// The predefined patterns that snow supports too, without fallback.
+fn shared_patterns() -> Vec<&'static str> {
+    predefined()
+        .map(|p| p.get_name())
+        .filter(|name| !name.contains("fallback"))
+        .filter(|name| {
+            format!("Noise_{}_25519_ChaChaPoly_BLAKE2s", name)
+                .parse::<snow::params::NoiseParams>()
+                .is_ok()
+        })
+        .collect()
+}
+
+/// Whether each side needs its static key and the remote static key.
+fn needs_keys(pattern: &HandshakePattern, initiator: bool) -> (bool, bool) {
+    let (local_pre, remote_pre) = if initiator {
+        (pattern.get_pre_i(), pattern.get_pre_r())
+    } else {
+        (pattern.get_pre_r(), pattern.get_pre_i())
+    };
+    let is_s = |t: &Token| matches!(t, Token::S);
+    let sends_s = (0..pattern.get_message_patterns_len())
+        .filter(|i| (i % 2 == 0) == initiator)
+        .any(|i| pattern.get_message_pattern(i).iter().any(is_s));
+    (
+        local_pre.iter().any(is_s) || sends_s,
+        remote_pre.iter().any(is_s),
+    )
+}
+
+/// The locations of the `psk` modifiers of a pattern name, in the order the
+/// tokens are used.
+fn psk_locations(pattern: &str) -> Vec<u8> {
+    let mut locations: Vec<u8> = pattern
+        .split("psk")
+        .skip(1)
+        .map(|rest| rest[..1].parse().unwrap())
+        .collect();
+    locations.sort_unstable();
+    locations
+}
+
+proptest! {
+    #[test]
+    fn snow_interop(
+        pattern in prop::sample::select(shared_patterns()),
+        cipher in prop::sample::select(vec!["ChaChaPoly", "AESGCM"]),
+        hash in prop::sample::select(vec!["SHA256", "SHA512", "BLAKE2s", "BLAKE2b"]),
+        snow_is_initiator in any::<bool>(),
+        prologue in prop::collection::vec(any::<u8>(), 0..64),
+        psk in any::<[u8; 32]>(),
+        payloads in prop::collection::vec(
+            prop::collection::vec(any::<u8>(), 0..512),
+            1..8,
+        ),
+    ) {
+        let name = format!("Noise_{}_25519_{}_{}", pattern, cipher, hash);
+        let hp = by_name(pattern).unwrap();
+        let i_keys = snow::Builder::new(name.parse().unwrap()).generate_keypair().unwrap();
+        let r_keys = snow::Builder::new(name.parse().unwrap()).generate_keypair().unwrap();
+        let (snow_keys, our_keys) = if snow_is_initiator {
+            (&i_keys, &r_keys)
+        } else {
+            (&r_keys, &i_keys)
+        };
+        let psks: Vec<[u8; 32]> = psk_locations(pattern)
+            .iter()
+            .map(|&l| {
+                let mut psk = psk;
+                psk[0] ^= l;
+                psk
+            })
+            .collect();
+
+        let (needs_s, needs_rs) = needs_keys(&hp, snow_is_initiator);
+        let mut snow = snow::Builder::new(name.parse().unwrap()).prologue(&prologue);
+        if needs_s {
+            snow = snow.local_private_key(&snow_keys.private);
+        }
+        if needs_rs {
+            snow = snow.remote_public_key(&our_keys.public);
+        }
+        for (&l, psk) in psk_locations(pattern).iter().zip(&psks) {
+            snow = snow.psk(l, psk);
+        }
+        let mut snow = if snow_is_initiator {
+            snow.build_initiator().unwrap()
+        } else {
+            snow.build_responder().unwrap()
+        };
+
+        let (needs_s, needs_rs) = needs_keys(&hp, !snow_is_initiator);
+        let params = registry::HandshakeParams {
+            is_initiator: !snow_is_initiator,
+            is_elligator_encoded: false,
+            prologue: &prologue,
+            s: Some(&our_keys.private[..]).filter(|_| needs_s),
+            rs: Some(&snow_keys.public[..]).filter(|_| needs_rs),
+            psks: &psks,
+        };
+        let mut ours = noise_rust_crypto::registry::resolve(&name, &params).unwrap();
+
+        let mut buf = vec![0u8; 65535];
+        for (n, payload) in payloads.iter().cycle().enumerate() {
+            if ours.completed() {
+                break;
+            }
+            let received = if (n % 2 == 0) == snow_is_initiator {
+                let len = snow.write_message(payload, &mut buf).unwrap();
+                ours.read_message_vec(&buf[..len]).unwrap()
+            } else {
+                let msg = ours.write_message_vec(payload).unwrap();
+                let len = snow.read_message(&msg, &mut buf).unwrap();
+                buf[..len].to_vec()
+            };
+            prop_assert_eq!(&received, payload);
+        }
+        prop_assert!(snow.is_handshake_finished());
+        prop_assert_eq!(snow.get_handshake_hash(), ours.get_hash());
+
+        let (i_to_r, r_to_i) = ours.get_ciphers();
+        let (mut our_send, mut our_recv) = if snow_is_initiator {
+            (r_to_i, i_to_r)
+        } else {
+            (i_to_r, r_to_i)
+        };
+        let mut snow = snow.into_transport_mode().unwrap();
+        for (n, payload) in payloads.iter().enumerate() {
+            let snow_sends = if hp.is_one_way() {
+                snow_is_initiator
+            } else {
+                n % 2 == 0
+            };
+            if snow_sends {
+                let len = snow.write_message(payload, &mut buf).unwrap();
+                prop_assert_eq!(&our_recv.decrypt_vec(&buf[..len]).unwrap(), payload);
+            } else {
+                let msg = our_send.encrypt_vec(payload);
+                let len = snow.read_message(&msg, &mut buf).unwrap();
+                prop_assert_eq!(&buf[..len], &payload[..]);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use noise_protocol::patterns::*;
+use noise_protocol::rand_core::{CryptoRng, Error, RngCore};
+use noise_protocol::testvectors::{generate, parse, to_json, VectorError, VectorRunner};
+use noise_protocol::CipherState;
+use noise_rust_crypto::{Aes256Gcm, Blake2b, Blake2s, ChaCha20Poly1305, Sha256, Sha512, X25519};
+
+// A deterministic RNG: the ChaCha20 keystream of a fixed key.
+struct TestRng(CipherState<ChaCha20Poly1305>);
+
+impl TestRng {
+    fn new() -> Self {
+        TestRng(CipherState::new(&[0x17; 32], 0))
+    }
+}
+
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut b = [0u8; 4];
+        self.fill_bytes(&mut b);
+        u32::from_le_bytes(b)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut b = [0u8; 8];
+        self.fill_bytes(&mut b);
+        u64::from_le_bytes(b)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let c = self.0.encrypt_vec(&vec![0; dest.len()]);
+        dest.copy_from_slice(&c[..dest.len()]);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for TestRng {}
+
+fn runner() -> VectorRunner {
+    let mut runner = VectorRunner::new();
+    runner
+        .register::<X25519, ChaCha20Poly1305, Sha256>()
+        .register::<X25519, ChaCha20Poly1305, Sha512>()
+        .register::<X25519, ChaCha20Poly1305, Blake2s>()
+        .register::<X25519, ChaCha20Poly1305, Blake2b>()
+        .register::<X25519, Aes256Gcm, Sha256>()
+        .register::<X25519, Aes256Gcm, Sha512>()
+        .register::<X25519, Aes256Gcm, Blake2s>()
+        .register::<X25519, Aes256Gcm, Blake2b>();
+    runner
+}
+
+#[test]
+fn runner_verifies_cacophony() {
+    let runner = runner();
+    let mut verified = 0;
+    for v in parse(include_str!("vectors/cacophony.txt")).unwrap() {
+        match runner.verify(&v) {
+            Ok(()) => verified += 1,
+            Err(VectorError::Unsupported) => {}
+            Err(e) => panic!("{}: {}", v.protocol_name, e),
+        }
+    }
+    assert!(verified > 0);
+
+    // A tampered vector.
+    let mut v = parse(include_str!("vectors/cacophony.txt"))
+        .unwrap()
+        .remove(0);
+    v.messages[0].ciphertext[0] ^= 1;
+    assert!(matches!(runner.verify(&v), Err(VectorError::Mismatch(0))));
+}
+
+fn elligator_patterns() -> Vec<HandshakePattern> {
+    let mut patterns: Vec<_> = predefined().filter(|p| p.get_name().len() <= 2).collect();
+    patterns.extend([noise_nn_psk0(), noise_ik_psk2(), noise_xx_psk3()]);
+    patterns
+}
+
+#[test]
+fn elligator_vectors() {
+    let gen = || {
+        let mut rng = TestRng::new();
+        let vectors: Vec<_> = elligator_patterns()
+            .into_iter()
+            .map(|p| generate::<X25519, ChaCha20Poly1305, Blake2s, _>(p, true, 2, &mut rng))
+            .collect();
+        to_json(&vectors)
+    };
+    let json = gen();
+    // Deterministic, so that a file of vectors can be regenerated.
+    assert_eq!(json, gen());
+
+    let runner = runner();
+    for v in parse(&json).unwrap() {
+        assert!(v.elligator_encoded);
+        assert!(v.init_ephemeral_public.is_some());
+        runner.verify(&v).unwrap();
+    }
+}
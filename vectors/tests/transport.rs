@@ -0,0 +1,760 @@
+// Transport and cipher states: splitting, rekeying, padding and exporters.
+
+mod common;
+
+use common::*;
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::{Blake2s, ChaCha20Poly1305, X25519};
+
+#[test]
+fn session_id_matches() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(i.get_session_id(), r.get_session_id());
+    assert_ne!(i.get_session_id().as_bytes()[..], i.get_hash()[..16]);
+    assert_eq!(i.get_session_id().to_string().len(), 2 * SESSION_ID_LEN);
+
+    // The transport states have it too, also after a restore.
+    let it = TransportState::from_handshake(&i);
+    let rt = TransportState::from_handshake(&r);
+    assert_eq!(it.session_id(), i.get_session_id());
+    assert_eq!(rt.session_id(), it.session_id());
+    let rt = TransportState::restore(rt.extract());
+    assert_eq!(rt.session_id(), it.session_id());
+}
+
+#[test]
+fn cipher_states_from_external_keys() {
+    type CS = CipherState<ChaCha20Poly1305>;
+
+    assert!(CS::from_key(&[0; 31]).is_none());
+    let mut a = CS::from_key(&[7; 32]).unwrap();
+    let mut b = CS::from_key(&[7; 32]).unwrap();
+    assert_eq!(b.decrypt_vec(&a.encrypt_vec(b"hi")).unwrap(), b"hi");
+
+    let (mut i_send, mut i_recv) = CS::pair_from_secret::<Blake2s>(&[1; 32], b"example v1");
+    let (mut r_recv, mut r_send) = CS::pair_from_secret::<Blake2s>(&[1; 32], b"example v1");
+    assert_eq!(
+        r_recv.decrypt_vec(&i_send.encrypt_vec(b"ping")).unwrap(),
+        b"ping"
+    );
+    assert_eq!(
+        i_recv.decrypt_vec(&r_send.encrypt_vec(b"pong")).unwrap(),
+        b"pong"
+    );
+
+    // Different labels give different keys.
+    let (mut other, _) = CS::pair_from_secret::<Blake2s>(&[1; 32], b"example v2");
+    assert!(r_recv.decrypt_vec(&other.encrypt_vec(b"ping")).is_err());
+}
+
+#[test]
+fn vectored_encryption() {
+    fn check<C: Cipher>() {
+        let key = vec![7u8; C::key_len()];
+        let mut a = CipherState::<C>::new(&key, 0);
+        let mut b = CipherState::<C>::new(&key, 0);
+        for bufs in [&[][..], &[&b""[..]], &[b"hello", b" ", b"world", b"", b"!"]] {
+            let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+            let mut out = vec![0u8; len + C::tag_len()];
+            a.encrypt_ad_vectored(b"ad", bufs, &mut out);
+            let mut expected = vec![0u8; len + C::tag_len()];
+            b.encrypt_ad(b"ad", &bufs.concat(), &mut expected);
+            assert_eq!(out, expected);
+        }
+        assert_eq!(a.get_next_n(), 3);
+    }
+
+    check::<ChaCha20Poly1305>();
+    check::<noise_rust_crypto::Aes256Gcm>();
+    check::<noise_rust_crypto::Aes128Gcm>();
+    // The default implementation.
+    check::<noise_rust_crypto::XChaCha20Poly1305>();
+}
+
+#[test]
+fn transport_state() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+    assert!(it.is_initiator() && !rt.is_initiator());
+    assert_eq!(it.get_hash(), i.get_hash());
+    assert_eq!(it.get_hash(), rt.get_hash());
+
+    let mut buf = [0u8; 64];
+    let mut out = [0u8; 64];
+    assert_eq!(
+        it.send(b"hello", &mut buf[..20]).unwrap_err().kind(),
+        ErrorKind::BufferTooSmall
+    );
+    let len = it.send(b"hello", &mut buf).unwrap();
+    assert_eq!(len, 21);
+    assert_eq!(
+        rt.recv(&buf[..15], &mut out).unwrap_err().kind(),
+        ErrorKind::TooShort
+    );
+    assert_eq!(rt.recv(&buf[..len], &mut out).unwrap(), 5);
+    assert_eq!(&out[..5], b"hello");
+    assert_eq!(
+        rt.recv(&buf[..len], &mut out).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+
+    let len = rt.send(b"world", &mut buf).unwrap();
+    assert_eq!(it.recv(&buf[..len], &mut out).unwrap(), 5);
+    assert_eq!((it.sending_nonce(), it.receiving_nonce()), (1, 1));
+
+    it.rekey_outgoing();
+    let len = it.send(b"rekeyed", &mut buf).unwrap();
+    assert!(rt.clone().recv(&buf[..len], &mut out).is_err());
+    rt.rekey_incoming();
+    assert_eq!(rt.recv(&buf[..len], &mut out).unwrap(), 7);
+}
+
+#[test]
+fn rekey_matches_spec() {
+    let key = [7u8; 32];
+    let mut c = CipherState::<ChaCha20Poly1305>::new(&key, 5);
+    c.rekey();
+    let (new_key, n) = c.clone().extract();
+    assert_eq!(n, 5);
+
+    let mut expected = [0u8; 48];
+    ChaCha20Poly1305::encrypt(
+        &U8Array::from_slice(&key),
+        u64::MAX,
+        &[],
+        &[0; 32],
+        &mut expected,
+    );
+    assert_eq!(new_key.as_slice(), &expected[..32]);
+
+    // The peer gets the same key.
+    let mut peer = CipherState::<ChaCha20Poly1305>::new(&key, 5);
+    peer.rekey();
+    let ct = c.encrypt_vec(b"hello");
+    assert_eq!(peer.decrypt_vec(&ct).unwrap(), b"hello");
+}
+
+#[test]
+fn nonce_exhaustion() {
+    let key = [1u8; 32];
+    let mut c = CipherState::<ChaCha20Poly1305>::new(&key, u64::MAX - 2);
+    let mut d = c.clone();
+    assert_eq!(c.remaining(), 2);
+
+    let mut out = [0u8; 16];
+    c.try_encrypt(b"", &mut out).unwrap();
+    assert_eq!(d.decrypt_vec(&out).unwrap(), b"");
+    let ct = c.encrypt_vec(b"");
+    assert!(c.is_exhausted());
+    assert_eq!(c.remaining(), 0);
+    assert_eq!(
+        c.try_encrypt(b"", &mut out).unwrap_err().kind(),
+        ErrorKind::NonceExhausted
+    );
+    assert!(std::panic::catch_unwind(move || c.encrypt_vec(b"")).is_err());
+
+    assert_eq!(d.decrypt_vec(&ct).unwrap(), b"");
+    assert!(d.is_exhausted());
+    assert!(d.decrypt_vec(&ct).is_err());
+}
+
+#[test]
+fn out_of_order_transport_messages() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let (mut send, _) = i.get_ciphers();
+    let (recv, _) = r.get_ciphers();
+
+    // Datagrams carry their nonces.
+    let packets: Vec<(u64, Vec<u8>)> = (0..5u8)
+        .map(|p| (send.get_next_n(), send.encrypt_vec(&[p])))
+        .collect();
+
+    let mut filter = ReplayFilter::<1>::new();
+    assert_eq!(ReplayFilter::<1>::window(), 64);
+    let mut out = [0u8; 1];
+    for &k in &[3, 0, 4, 1] {
+        let (n, ref ct) = packets[k];
+        filter.decrypt(&recv, n, &[], ct, &mut out).unwrap();
+        assert_eq!(out[0], k as u8);
+    }
+    // Replays are rejected.
+    let (n, ref ct) = packets[3];
+    assert!(filter.decrypt(&recv, n, &[], ct, &mut out).is_err());
+    // Forgeries do not mark the nonce as received.
+    let (n, ref ct) = packets[2];
+    let mut forged = ct.clone();
+    forged[0] ^= 1;
+    assert!(filter.decrypt(&recv, n, &[], &forged, &mut out).is_err());
+    filter.decrypt(&recv, n, &[], ct, &mut out).unwrap();
+    // The receiving nonce of the cipher state is untouched.
+    assert_eq!(recv.get_next_n(), 0);
+
+    // Nonces that fall out of the window are rejected.
+    assert!(filter.accept(100));
+    assert!(!filter.check(4) && !filter.check(36));
+    assert!(filter.check(37) && filter.accept(37));
+    assert!(!filter.accept(37) && !filter.accept(100));
+    assert!(filter.accept(99) && filter.accept(1000));
+    assert!(!filter.check(u64::MAX));
+}
+
+#[test]
+fn checkpoint_transport_state() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+
+    let mut buf = [0u8; 64];
+    let mut out = [0u8; 64];
+    let len = it.send(b"hello", &mut buf).unwrap();
+    rt.recv(&buf[..len], &mut out).unwrap();
+
+    // Checkpoint the initiator and restore it.
+    it.set_max_message_len(1000);
+    let checkpoint = it.extract();
+    assert_eq!((checkpoint.send.1, checkpoint.recv.1), (1, 0));
+    let mut it = TransportState::restore(checkpoint);
+    assert_eq!(it.get_hash(), rt.get_hash());
+    assert!(it.is_initiator() && !it.is_one_way());
+    assert_eq!(it.max_message_len(), 1000);
+
+    let len = it.send(b"world", &mut buf).unwrap();
+    assert_eq!(rt.recv(&buf[..len], &mut out).unwrap(), 5);
+    assert_eq!(&out[..5], b"world");
+    let len = rt.send(b"again", &mut buf).unwrap();
+    assert_eq!(it.recv(&buf[..len], &mut out).unwrap(), 5);
+
+    // And a single cipher state.
+    let (send, _) = rt.into_ciphers();
+    let (key, n) = send.extract();
+    let mut send = CipherState::<ChaCha20Poly1305>::restore(key, n);
+    assert_eq!(send.get_next_n(), 1);
+    let c = send.encrypt_vec(b"more");
+    let (_, mut recv) = it.into_ciphers();
+    assert_eq!(recv.decrypt_vec(&c).unwrap(), b"more");
+}
+
+#[test]
+fn split_one_way() {
+    let server = X25519::genkey(false);
+    let (mut ib, mut rb) = builders(noise_n(), b"");
+    ib.set_rs(server.public);
+    rb.set_s(server);
+    let mut i: HS = ib.build_handshake_state();
+    let mut r: HS = rb.build_handshake_state();
+    run_handshake(&mut i, &mut r);
+
+    let mut it = i.split_one_way().unwrap();
+    let mut rt = r.split_one_way().unwrap();
+    assert!(it.is_one_way() && rt.is_one_way());
+
+    let mut msg = [0u8; 21];
+    let mut out = [0u8; 5];
+    assert_eq!(it.send(b"hello", &mut msg).ok(), Some(21));
+    assert_eq!(rt.recv(&msg, &mut out).ok(), Some(5));
+    assert_eq!(&out, b"hello");
+
+    assert_eq!(
+        rt.send(b"hello", &mut msg).unwrap_err().kind(),
+        ErrorKind::PatternViolation
+    );
+    assert_eq!(
+        it.recv(&msg, &mut out).unwrap_err().kind(),
+        ErrorKind::PatternViolation
+    );
+
+    // Still one-way after a restore.
+    let mut it = TransportState::restore(it.extract());
+    let mut rt = TransportState::restore(rt.extract());
+    assert!(it.is_one_way() && rt.is_one_way());
+    assert_eq!(it.send(b"again", &mut msg).ok(), Some(21));
+    assert_eq!(rt.recv(&msg, &mut out).ok(), Some(5));
+    assert_eq!(
+        rt.send(b"hello", &mut msg).unwrap_err().kind(),
+        ErrorKind::PatternViolation
+    );
+    assert_eq!(
+        it.recv(&msg, &mut out).unwrap_err().kind(),
+        ErrorKind::PatternViolation
+    );
+
+    // Not a one-way pattern.
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    assert_eq!(
+        i.split_one_way().err().map(|e| e.kind()),
+        Some(ErrorKind::PatternViolation)
+    );
+}
+
+#[test]
+fn export_key() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+
+    let k = i.export_key(b"signature", 42);
+    assert_eq!(k.len(), 42);
+    assert_eq!(k, r.export_key(b"signature", 42));
+    assert_ne!(k, i.export_key(b"certificate", 42));
+    assert_ne!(k[..32], i.export_key(b"signature", 32)[..]);
+    // Unrelated to the transport keys.
+    let (c1, _) = i.get_ciphers();
+    assert_ne!(k[..32], c1.extract().0[..]);
+
+    let t = TransportState::from_handshake(&r);
+    assert_eq!(t.export_key(b"signature", 42).unwrap(), k);
+
+    let restored = TransportState::restore(t.clone().extract());
+    assert_eq!(restored.export_key(b"signature", 42).unwrap(), k);
+
+    // Unless the exporter secret is not saved.
+    let mut checkpoint = t.extract();
+    checkpoint.exporter = None;
+    let restored = TransportState::restore(checkpoint);
+    assert_eq!(
+        restored.export_key(b"signature", 42).unwrap_err().kind(),
+        ErrorKind::MissingKey
+    );
+}
+
+#[test]
+fn resumption_ticket() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let client = TransportState::from_handshake(&i);
+    let server = TransportState::from_handshake(&r);
+
+    let ticket_key = TicketKey::new([7; 32]);
+    let issued = server.issue_resumption_ticket(&ticket_key).unwrap();
+    let ticket = client
+        .accept_resumption_ticket(issued.blob().to_vec())
+        .unwrap();
+    assert_eq!(ticket.psk(), issued.psk());
+
+    // A fresh `NNpsk0` handshake, with 0-RTT data in the first message.
+    let resume = |blob: &[u8], key: &TicketKey| -> Result<Vec<u8>, Error> {
+        let mut ib = HandshakeStateBuilder::<X25519>::new();
+        ib.set_pattern(noise_nn_psk0())
+            .set_is_initiator(true)
+            .set_prologue(b"");
+        let mut i: HS = ib.build_handshake_state();
+        i.push_psk(ticket.psk());
+        let m = i.write_message_vec(b"early data").unwrap();
+
+        let psk = key.open::<ChaCha20Poly1305, Blake2s>(blob)?;
+        let mut rb = HandshakeStateBuilder::<X25519>::new();
+        rb.set_pattern(noise_nn_psk0())
+            .set_is_initiator(false)
+            .set_prologue(b"");
+        let mut r: HS = rb.build_handshake_state();
+        r.push_psk(&*psk);
+        let early = r.read_message_vec(&m)?;
+        let m = r.write_message_vec(b"").unwrap();
+        i.read_message_vec(&m).unwrap();
+        assert!(i.completed() && r.completed());
+        Ok(early)
+    };
+    assert_eq!(resume(ticket.blob(), &ticket_key).unwrap(), b"early data");
+
+    // Another ticket key, or a tampered ticket.
+    let err = resume(ticket.blob(), &TicketKey::new([8; 32])).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Decryption);
+    let mut blob = ticket.blob().to_vec();
+    blob[0] ^= 1;
+    assert_eq!(
+        resume(&blob, &ticket_key).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+    assert_eq!(
+        resume(&blob[1..], &ticket_key).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+
+    let mut checkpoint = server.clone().extract();
+    checkpoint.exporter = None;
+    let restored = TransportState::restore(checkpoint);
+    assert_eq!(
+        restored
+            .issue_resumption_ticket(&ticket_key)
+            .err()
+            .unwrap()
+            .kind(),
+        ErrorKind::MissingKey
+    );
+}
+
+#[test]
+fn rekey_policy() {
+    use std::time::Duration;
+
+    let transports = || {
+        let (mut i, mut r) = xx_pair();
+        run_handshake(&mut i, &mut r);
+        (
+            TransportState::from_handshake(&i),
+            TransportState::from_handshake(&r),
+        )
+    };
+    let transfer = |from: &mut TransportState<_, _>, to: &mut TransportState<_, _>, n: usize| {
+        for k in 0..n {
+            let payload = vec![k as u8; k];
+            let mut msg = vec![0; payload.len() + from.overhead()];
+            from.send(&payload, &mut msg).unwrap();
+            let mut out = vec![0; msg.len()];
+            let len = to.recv(&msg, &mut out)?;
+            assert_eq!(out[..len], payload[..]);
+        }
+        Ok::<_, Error>(())
+    };
+
+    // Both sides count.
+    let policy = RekeyPolicy {
+        messages: 3,
+        ..Default::default()
+    };
+    let (mut i, mut r) = transports();
+    i.set_rekey_policy(policy);
+    r.set_rekey_policy(policy);
+    transfer(&mut i, &mut r, 10).unwrap();
+    transfer(&mut r, &mut i, 10).unwrap();
+    assert_eq!(i.overhead(), 16);
+    // A peer without the policy fails after the first rekey.
+    let (mut i, mut unaware) = transports();
+    i.set_rekey_policy(policy);
+    transfer(&mut i, &mut unaware, 3).unwrap();
+    assert!(transfer(&mut i, &mut unaware, 1).is_err());
+    // The policy and its counts survive a checkpoint.
+    let (mut i, mut r) = transports();
+    i.set_rekey_policy(policy);
+    r.set_rekey_policy(policy);
+    transfer(&mut i, &mut r, 2).unwrap();
+    let mut i = TransportState::restore(i.extract());
+    transfer(&mut i, &mut r, 10).unwrap();
+
+    let policy = RekeyPolicy {
+        bytes: 20,
+        ..Default::default()
+    };
+    let (mut i, mut r) = transports();
+    i.set_rekey_policy(policy);
+    r.set_rekey_policy(policy);
+    transfer(&mut i, &mut r, 20).unwrap();
+
+    // In-band, the receiver follows the flags of the sender.
+    for policy in [
+        RekeyPolicy {
+            messages: 2,
+            in_band: true,
+            ..Default::default()
+        },
+        RekeyPolicy {
+            time: Some(Duration::ZERO),
+            in_band: true,
+            ..Default::default()
+        },
+    ] {
+        let (mut i, mut r) = transports();
+        i.set_rekey_policy(policy);
+        r.set_rekey_policy(RekeyPolicy {
+            in_band: true,
+            ..Default::default()
+        });
+        assert_eq!(i.overhead(), 17);
+        transfer(&mut i, &mut r, 10).unwrap();
+        transfer(&mut r, &mut i, 10).unwrap();
+        assert_eq!(i.sending_nonce(), 10);
+    }
+}
+
+#[test]
+#[should_panic(expected = "a rekey time needs in_band")]
+fn rekey_time_needs_in_band() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    TransportState::from_handshake(&i).set_rekey_policy(RekeyPolicy {
+        time: Some(std::time::Duration::from_secs(60)),
+        ..Default::default()
+    });
+}
+
+#[test]
+fn padded_transport() {
+    use noise_protocol::padding::*;
+
+    assert_eq!(BlockPadding(64).padded_len(2), 64);
+    assert_eq!(BlockPadding(64).padded_len(128), 128);
+    assert_eq!(BlockPadding(0).padded_len(5), 5);
+    for (len, padded) in [(2, 2), (9, 10), (100, 104), (1000, 1024), (1025, 1088)] {
+        assert_eq!(Padme.padded_len(len), padded);
+    }
+    let random = RandomPadding {
+        max: 10,
+        fill_random: |b| b.fill(0xff),
+    };
+    assert!((5..=15).contains(&random.padded_len(5)));
+
+    let mut buf = [0u8; 100];
+    let len = pad(&BlockPadding(16), b"hello", &mut buf).unwrap();
+    assert_eq!(len, 16);
+    assert_eq!(unpad(&buf[..len]).unwrap(), b"hello");
+    // Capped to the buffer.
+    assert_eq!(pad(&BlockPadding(256), b"hello", &mut buf).unwrap(), 100);
+    assert_eq!(
+        pad(&Padme, b"hello", &mut buf[..6]).unwrap_err().kind(),
+        ErrorKind::BufferTooSmall
+    );
+    // A header longer than the plaintext.
+    buf[..2].copy_from_slice(&200u16.to_be_bytes());
+    assert_eq!(unpad(&buf).unwrap_err().kind(), ErrorKind::TooShort);
+
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut i = PaddedTransport::new(TransportState::from_handshake(&i), BlockPadding(256));
+    let mut r = PaddedTransport::new(TransportState::from_handshake(&r), Padme);
+    assert_eq!(i.overhead(), 18);
+    for payload in [&b""[..], b"a", &[7; 200]] {
+        let mut msg = [0u8; 1024];
+        let len = i.send(payload, &mut msg).unwrap();
+        // All the same length.
+        assert_eq!(len, 256 + 16);
+        let mut out = [0u8; 1024];
+        let n = r.recv(&msg[..len], &mut out).unwrap();
+        assert_eq!(&out[..n], payload);
+    }
+    let mut msg = [0u8; 1024];
+    let len = r.send(&[1; 100], &mut msg).unwrap();
+    assert_eq!(len, 104 + 16);
+    let mut out = [0u8; 50];
+    assert_eq!(
+        i.recv(&msg[..len], &mut out).unwrap_err().kind(),
+        ErrorKind::BufferTooSmall
+    );
+}
+
+#[test]
+fn send_large_payload() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+
+    let payload: Vec<u8> = (0..200_000u32).map(|n| n as u8).collect();
+    let mut messages = Vec::new();
+    it.send_large(&payload, |m| messages.push(m.to_vec()))
+        .unwrap();
+    assert_eq!(messages.len(), 4);
+    assert!(messages.iter().all(|m| m.len() <= 65535));
+
+    let mut out = Vec::new();
+    let (last, chunks) = messages.split_last().unwrap();
+    for m in chunks {
+        assert!(!rt.recv_large(m, &mut out).unwrap());
+    }
+    assert!(rt.recv_large(last, &mut out).unwrap());
+    assert_eq!(out, payload);
+
+    // An empty payload is one message.
+    let mut messages = Vec::new();
+    it.send_large(b"", |m| messages.push(m.to_vec())).unwrap();
+    assert_eq!(messages.len(), 1);
+    out.clear();
+    assert!(rt.recv_large(&messages[0], &mut out).unwrap());
+    assert!(out.is_empty());
+
+    // A plain message is not a chunk, and leaves `out` alone.
+    let mut buf = [0u8; 64];
+    let len = it.send(b"", &mut buf).unwrap();
+    out.extend_from_slice(b"kept");
+    assert_eq!(
+        rt.recv_large(&buf[..len], &mut out).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+    assert_eq!(out, b"kept");
+
+    // Each chunk holds at least one byte of the payload, so a maximum of
+    // one byte of payload is too small.
+    it.set_max_message_len(it.overhead() + 1);
+    let mut messages = Vec::new();
+    assert_eq!(
+        it.send_large(b"ab", |m| messages.push(m.to_vec()))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::BufferTooSmall
+    );
+    assert!(messages.is_empty());
+    it.set_max_message_len(it.overhead() + 2);
+    it.send_large(b"ab", |m| messages.push(m.to_vec())).unwrap();
+    assert_eq!(messages.len(), 2);
+    out.clear();
+    assert!(!rt.recv_large(&messages[0], &mut out).unwrap());
+    assert!(rt.recv_large(&messages[1], &mut out).unwrap());
+    assert_eq!(out, b"ab");
+}
+
+#[test]
+fn stream_encryptor() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+    let mut buf = [0u8; 64];
+    let mut out = [0u8; 64];
+    let len = it.send(b"hello", &mut buf).unwrap();
+    assert_eq!(rt.recv(&buf[..len], &mut out).unwrap(), 5);
+
+    let (mut ienc, mut idec) = it.into_stream_encryptor(16).unwrap();
+    let (mut renc, mut rdec) = rt.into_stream_encryptor(16).unwrap();
+    let data: Vec<u8> = (0..40).collect();
+    let chunks: Vec<Vec<u8>> = data
+        .chunks(16)
+        .enumerate()
+        .map(|(n, chunk)| ienc.encrypt_chunk(chunk, n == 2))
+        .collect();
+    assert!(ienc.is_finished());
+    assert_eq!(chunks[0].len(), rdec.encrypted_chunk_len());
+
+    // Out of order.
+    assert_eq!(
+        rdec.decrypt_chunk(&chunks[1]).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+    let mut decrypted = Vec::new();
+    for chunk in &chunks {
+        decrypted.extend(rdec.decrypt_chunk(chunk).unwrap());
+    }
+    rdec.finish().unwrap();
+    assert_eq!(decrypted, data);
+
+    // Truncated.
+    let chunk = renc.encrypt_chunk(&data[..16], false);
+    assert_eq!(idec.decrypt_chunk(&chunk).unwrap(), &data[..16]);
+    assert_eq!(idec.finish().unwrap_err().kind(), ErrorKind::TooShort);
+
+    let (mut i, mut r) = file_pair(noise_n());
+    run_handshake(&mut i, &mut r);
+    let t = i.split_one_way().unwrap();
+    assert_eq!(
+        t.into_stream_encryptor(16).err().unwrap().kind(),
+        ErrorKind::PatternViolation
+    );
+}
+
+#[test]
+fn cipherstate_set_nonce() {
+    let key = [3u8; 32];
+    let mut send = CipherState::<ChaCha20Poly1305>::new(&key, 0);
+    let mut recv = CipherState::<ChaCha20Poly1305>::new(&key, 0);
+    let _lost = send.encrypt_vec(b"lost");
+    let _lost = send.encrypt_vec(b"lost too");
+    let truncated = send.get_next_n() as u8;
+    let msg = send.encrypt_vec(b"hello");
+
+    assert!(recv.decrypt_vec(&msg).is_err());
+    recv.set_nonce(u64::from(truncated));
+    assert_eq!(recv.get_next_n(), 2);
+    assert_eq!(recv.decrypt_vec(&msg).unwrap(), b"hello");
+    assert_eq!(recv.get_next_n(), 3);
+
+    recv.set_nonce(u64::MAX);
+    assert!(recv.is_exhausted());
+}
+
+#[test]
+fn transport_associated_data() {
+    let (mut i, mut r) = xx_pair();
+    run_handshake(&mut i, &mut r);
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+
+    let mut buf = [0u8; 64];
+    let mut out = [0u8; 64];
+    let len = it.send_with_ad(b"route 7", b"hello", &mut buf).unwrap();
+    assert_eq!(len, 5 + it.overhead());
+    assert_eq!(
+        rt.recv_with_ad(b"route 8", &buf[..len], &mut out)
+            .unwrap_err()
+            .kind(),
+        ErrorKind::Decryption
+    );
+    assert_eq!(
+        rt.recv(&buf[..len], &mut out).unwrap_err().kind(),
+        ErrorKind::Decryption
+    );
+    let n = rt.recv_with_ad(b"route 7", &buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"hello");
+
+    // Empty associated data is the same as none.
+    let len = rt.send_with_ad(b"", b"back", &mut buf).unwrap();
+    let n = it.recv(&buf[..len], &mut out).unwrap();
+    assert_eq!(&out[..n], b"back");
+}
+
+#[test]
+fn max_message_len() {
+    let pattern = noise_xx();
+    assert_eq!(HS::max_payload_len(&pattern, 0), 65535 - 32);
+    assert_eq!(HS::max_payload_len(&pattern, 1), 65535 - 32 - 48 - 16);
+    assert_eq!(HS::max_payload_len(&pattern, 2), 65535 - 48 - 16);
+
+    // The longest payloads fit exactly, one more byte does not.
+    let (mut i, mut r) = xx_pair();
+    for n in 0..3 {
+        let (send, recv) = if n % 2 == 0 {
+            (&mut i, &mut r)
+        } else {
+            (&mut r, &mut i)
+        };
+        let max = HS::max_payload_len(&pattern, n);
+        let err = send
+            .clone()
+            .write_message_vec(&vec![0; max + 1])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MessageTooLong);
+        let msg = send.write_message_vec(&vec![0; max]).unwrap();
+        assert_eq!(msg.len(), 65535);
+        assert_eq!(recv.read_message_vec(&msg).unwrap().len(), max);
+    }
+
+    // A lower maximum is enforced in both directions, and by the transport.
+    let build = |initiator: bool, max: usize| {
+        let mut b = HandshakeStateBuilder::<X25519>::new();
+        b.set_pattern(noise_nn())
+            .set_is_initiator(initiator)
+            .set_prologue(b"")
+            .set_max_message_len(max);
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2s>()
+    };
+    let mut i = build(true, 65535);
+    let mut r = build(false, 100);
+    let msg = i.clone().write_message_vec(&[0; 69]).unwrap();
+    let err = r.clone().read_message_vec(&msg).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MessageTooLong);
+    let msg = i.write_message_vec(&[0; 68]).unwrap();
+    r.read_message_vec(&msg).unwrap();
+    // The maximum is saved with the state.
+    let mut r = HS::import_state(&r.export_state(), None).unwrap();
+    assert_eq!(r.get_max_message_len(), 100);
+    let err = r.clone().write_message_vec(&[0; 53]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MessageTooLong);
+    let msg = r.write_message_vec(&[0; 52]).unwrap();
+    i.read_message_vec(&msg).unwrap();
+
+    let mut it = TransportState::from_handshake(&i);
+    let mut rt = TransportState::from_handshake(&r);
+    assert_eq!(rt.max_message_len(), 100);
+    assert_eq!(rt.max_payload_len(), 84);
+    let mut out = [0u8; 200];
+    let err = rt.send(&[0; 85], &mut out).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MessageTooLong);
+    let len = it.send(&[0; 85], &mut out).unwrap();
+    let err = rt.recv(&out[..len], &mut [0; 200]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MessageTooLong);
+    rt.set_max_message_len(101);
+    assert_eq!(rt.recv(&out[..len], &mut [0; 200]).unwrap(), 85);
+}
@@ -0,0 +1,140 @@
+// X448, whose crate is optional, and its Elligator representatives.
+
+use noise_protocol::patterns::*;
+use noise_protocol::*;
+use noise_rust_crypto::elligator448::{decode_representative, encode_pubkey};
+use noise_rust_crypto::{Blake2b, ChaCha20Poly1305, X448};
+
+fn h56(s: &str) -> [u8; 56] {
+    hex::decode(s).unwrap().try_into().unwrap()
+}
+
+#[test]
+fn x448_rfc7748() {
+    // RFC 7748, section 6.2.
+    let alice = U8Array::from_slice(
+        &hex::decode(
+            "9a8f4925d1519f5775cf46b04b5800d4ee9ee8bae8bc5565d498c28d\
+         d9c9baf574a9419744897391006382a6f127ab1d9ac2d8c0a598726b",
+        )
+        .unwrap(),
+    );
+    let bob = U8Array::from_slice(
+        &hex::decode(
+            "1c306a7ac2a0e2e0990b294470cba339e6453772b075811d8fad0d1d\
+         6927c120bb5ee8972b0d3e21374c9c921b09d1b0366f10b65173992d",
+        )
+        .unwrap(),
+    );
+    let alice_public = h56("9b08f7cc31b7e3e67d22d5aea121074a273bd2b83de09c63faa73d2c\
+         22c5d9bbc836647241d953d40c5b12da88120d53177f80e532c41fa0");
+    let bob_public = h56("3eb7a829b0cd20f5bcfc0b599b6feccf6da4627107bdb0d4f345b430\
+         27d8b972fc3e34fb4232a13ca706dcb57aec3dae07bdc1c67bf33609");
+    assert_eq!(X448::pubkey(&alice), alice_public);
+    assert_eq!(X448::pubkey(&bob), bob_public);
+
+    let shared = "07fff4181ac6cc95ec1c16a94a0f74d12da232ce40a77552281d282b\
+                  b60c0b56fd2464c335543936521c24403085d59a449a5037514a879d";
+    assert_eq!(
+        hex::encode(X448::dh(&alice, &bob_public, false).unwrap().as_slice()),
+        shared
+    );
+    assert_eq!(
+        hex::encode(X448::dh(&bob, &alice_public, false).unwrap().as_slice()),
+        shared
+    );
+
+    // Section 5.2, the first test vector.
+    let k = U8Array::from_slice(
+        &hex::decode(
+            "3d262fddf9ec8e88495266fea19a34d28882acef045104d0d1aae121\
+         700a779c984c24f8cdd78fbff44943eba368f54b29259a4f1c600ad3",
+        )
+        .unwrap(),
+    );
+    let u = h56("06fce640fa3487bfda5f6cf2d5263f8aad88334cbd07437f020f08f9\
+         814dc031ddbdc38c19c6da2583fa5429db94ada18aa7a7fb4ef8a086");
+    assert_eq!(
+        hex::encode(X448::dh(&k, &u, false).unwrap().as_slice()),
+        "ce3e4ff95a60dc6697da1db1d85e6afbdf79b50a2412d7546d5f239f\
+         e14fbaadeb445fc66a01b0779d98223961111e21766282f73dd96b6f"
+    );
+
+    // Low order public keys are rejected.
+    assert!(X448::dh(&k, &[0; 56], false).is_err());
+}
+
+#[test]
+fn x448_elligator_encode_decode() {
+    // The representatives of Alice's public key from RFC 7748.
+    let alice_public = h56("9b08f7cc31b7e3e67d22d5aea121074a273bd2b83de09c63faa73d2c\
+         22c5d9bbc836647241d953d40c5b12da88120d53177f80e532c41fa0");
+    let r = encode_pubkey(&alice_public, 0).unwrap();
+    assert_eq!(
+        hex::encode(r),
+        "74b81965fc39a227a9aa8e6a800a67c29cd557d208a8d72270ef014f\
+         0a85fc03b61856ea0b1a44177fe7afee49820732bd2cd9280431f7cd"
+    );
+    assert_eq!(decode_representative(&r), alice_public);
+    let r = encode_pubkey(&alice_public, 1).unwrap();
+    assert_eq!(
+        hex::encode(r),
+        "8b47e69a03c65dd8565571957ff5983d632aa82df75728dd8f10feb0\
+         f47a03fc49e7a915f4e5bbe880185011b67df8cd42d326d7fbce0832"
+    );
+    assert_eq!(decode_representative(&r), alice_public);
+
+    // Every string is a representative.
+    let r: Vec<u8> = (0..56).collect();
+    assert_eq!(
+        hex::encode(decode_representative(&r.try_into().unwrap())),
+        "fef33986c35964f3dafbe3b5bbd359e412e1d49c2c93c135f6c703ac\
+         cc83cd7726fb9f6ac6ed25bab0b0526b9da37af1c1f11b629560204c"
+    );
+
+    let k = X448::genkey(false);
+    for _ in 0..16 {
+        let other = X448::genkey(true);
+        let pk = decode_representative(&other.public);
+        assert_eq!(
+            X448::dh(&k.private, &pk, false).unwrap().as_slice(),
+            X448::dh(&k.private, &other.public, true)
+                .unwrap()
+                .as_slice()
+        );
+        assert_eq!(
+            X448::dh(&other.private, &X448::pubkey(&k.private), false)
+                .unwrap()
+                .as_slice(),
+            X448::dh(&k.private, &other.public, true)
+                .unwrap()
+                .as_slice()
+        );
+        for tweak in 0..2 {
+            let r = encode_pubkey(&pk, tweak).unwrap();
+            assert_eq!(decode_representative(&r), pk);
+        }
+    }
+}
+
+#[test]
+fn x448_elligator_handshake() {
+    let build = |is_initiator| {
+        let mut b = HandshakeStateBuilder::<X448>::new();
+        b.set_pattern(noise_xx())
+            .set_is_initiator(is_initiator)
+            .set_is_elligator_encoded(true)
+            .set_prologue(b"")
+            .set_s(X448::genkey(false));
+        b.build_handshake_state::<ChaCha20Poly1305, Blake2b>()
+    };
+    let (mut i, mut r) = (build(true), build(false));
+    let m = i.write_message_vec(b"").unwrap();
+    r.read_message_vec(&m).unwrap();
+    let m = r.write_message_vec(b"").unwrap();
+    i.read_message_vec(&m).unwrap();
+    let m = i.write_message_vec(b"hello").unwrap();
+    assert_eq!(r.read_message_vec(&m).unwrap(), b"hello");
+    assert!(i.completed() && r.completed());
+    assert_eq!(i.get_hash(), r.get_hash());
+}